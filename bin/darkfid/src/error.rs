@@ -40,6 +40,8 @@ pub enum RpcError {
     // State-related errors,
     NotSynced = -32120,
     UnknownSlot = -32121,
+    UnknownTx = -32122,
+    UnknownNullifier = -32123,
 
     // Parsing errors
     ParseError = -32190,
@@ -66,6 +68,8 @@ fn to_tuple(e: RpcError) -> (i64, String) {
         // State-related errors
         RpcError::NotSynced => "Blockchain is not synced",
         RpcError::UnknownSlot => "Did not find slot",
+        RpcError::UnknownTx => "Did not find transaction",
+        RpcError::UnknownNullifier => "Did not find nullifier",
         // Parsing errors
         RpcError::ParseError => "Parse error",
         // Contract-related errors
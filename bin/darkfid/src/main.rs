@@ -26,17 +26,17 @@ use structopt_toml::{serde::Deserialize, structopt::StructOpt, StructOptToml};
 use url::Url;
 
 use darkfi::{
-    async_daemonize, cli_desc,
+    async_daemonize,
+    blockchain::{export_snapshot, import_snapshot_headers},
+    cli_desc,
     consensus::{
-        constants::{
-            MAINNET_BOOTSTRAP_TIMESTAMP, MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP,
-            MAINNET_INITIAL_DISTRIBUTION, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
-            TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION,
+        proto::{
+            ProposalInventory, ProtocolProposal, ProtocolSync, ProtocolSyncConsensus, ProtocolTx,
+            TxInventory,
         },
-        proto::{ProtocolProposal, ProtocolSync, ProtocolSyncConsensus, ProtocolTx},
         task::{block_sync_task, proposal_task},
         validator::ValidatorStatePtr,
-        ValidatorState,
+        BlockProposal, ChainParams, ValidatorState,
     },
     net,
     net::P2pPtr,
@@ -48,7 +48,8 @@ use darkfi::{
         },
         server::{listen_and_serve, RequestHandler},
     },
-    util::path::expand_path,
+    tx::Transaction,
+    util::{path::expand_path, time::Timestamp},
     wallet::{walletdb::init_wallet, WalletPtr},
     Error, Result,
 };
@@ -68,9 +69,65 @@ struct Args {
     config: Option<String>,
 
     #[structopt(long, default_value = "testnet")]
-    /// Chain to use (testnet, mainnet)
+    /// Chain to use (testnet, mainnet, devnet)
     chain: String,
 
+    #[structopt(long)]
+    /// Override the chain network magic bytes (hex-encoded)
+    network_magic: Option<String>,
+
+    #[structopt(long)]
+    /// Override the chain genesis timestamp
+    genesis_timestamp: Option<i64>,
+
+    #[structopt(long)]
+    /// Override the chain bootstrap timestamp
+    bootstrap_timestamp: Option<i64>,
+
+    #[structopt(long)]
+    /// Override the chain slot time in seconds
+    slot_time: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain finalization sync period duration in seconds
+    final_sync_duration: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain number of slots in one epoch
+    epoch_length: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain cap of transactions included in a block
+    txs_cap: Option<usize>,
+
+    #[structopt(long)]
+    /// Override the chain gas limit of a transaction
+    tx_gas_limit: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain minimum fee a transaction must prove paying
+    min_tx_fee: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain block leader reward
+    reward: Option<u64>,
+
+    #[structopt(long)]
+    /// Override the chain slots after which the block leader reward halves
+    reward_halving_interval: Option<u64>,
+
+    #[structopt(long)]
+    /// File to append the slot leaders count history to
+    leader_history_log: Option<String>,
+
+    #[structopt(long)]
+    /// File to append the consensus controller output history to
+    f_history_log: Option<String>,
+
+    #[structopt(long)]
+    /// File to append the slot lottery history to
+    lottery_history_log: Option<String>,
+
     #[structopt(long)]
     /// Participate in consensus
     consensus: bool,
@@ -91,6 +148,22 @@ struct Args {
     /// Path to blockchain database
     database: String,
 
+    #[structopt(long)]
+    /// Export the blockchain to a snapshot file and exit
+    export_snapshot: Option<String>,
+
+    #[structopt(long)]
+    /// Import the blockchain from a snapshot file and exit
+    import_snapshot: Option<String>,
+
+    #[structopt(long)]
+    /// Only verify block headers and leader proofs when importing a snapshot, trusting its contract states
+    import_headers_only: bool,
+
+    #[structopt(long)]
+    /// Roll back the given number of blocks, reverting their contract state changes, and exit
+    rollback: Option<u64>,
+
     #[structopt(long, default_value = "tcp://127.0.0.1:8340")]
     /// JSON-RPC listen URL
     rpc_listen: Url,
@@ -127,6 +200,26 @@ struct Args {
     /// Prefered transports of outbound connections for the consensus protocol (repeatable flag)
     consensus_p2p_transports: Vec<String>,
 
+    #[structopt(long)]
+    /// File used to persist known hosts of the consensus protocol
+    consensus_p2p_hosts_file: Option<String>,
+
+    #[structopt(long)]
+    /// File used to persist outbound anchor peers of the consensus protocol
+    consensus_p2p_anchors_file: Option<String>,
+
+    #[structopt(long)]
+    /// File used to persist banned hosts of the consensus protocol
+    consensus_p2p_banlist_file: Option<String>,
+
+    #[structopt(long)]
+    /// File holding the node's static key for noise transports of the consensus protocol
+    consensus_p2p_node_key_file: Option<String>,
+
+    #[structopt(long)]
+    /// Hex-encoded static key of a consensus peer accepted over noise transports (repeatable flag)
+    consensus_p2p_trusted_peer_key: Vec<String>,
+
     #[structopt(long)]
     /// P2P accept addresses for the syncing protocol (repeatable flag)
     sync_p2p_accept: Vec<Url>,
@@ -151,6 +244,18 @@ struct Args {
     /// Prefered transports of outbound connections for the syncing protocol (repeatable flag)
     sync_p2p_transports: Vec<String>,
 
+    #[structopt(long)]
+    /// File used to persist known hosts of the syncing protocol
+    sync_p2p_hosts_file: Option<String>,
+
+    #[structopt(long)]
+    /// File used to persist outbound anchor peers of the syncing protocol
+    sync_p2p_anchors_file: Option<String>,
+
+    #[structopt(long)]
+    /// File used to persist banned hosts of the syncing protocol
+    sync_p2p_banlist_file: Option<String>,
+
     #[structopt(long)]
     /// Enable localnet hosts
     localnet: bool,
@@ -210,21 +315,42 @@ impl RequestHandler for Darkfid {
             Some("clock") => return self.misc_clock(req.id, params).await,
             Some("get_info") => return self.misc_get_info(req.id, params).await,
             Some("get_consensus_info") => return self.misc_get_consensus_info(req.id, params).await,
+            Some("get_banlist") => return self.misc_get_banlist(req.id, params).await,
+            Some("ban") => return self.misc_ban(req.id, params).await,
+            Some("unban") => return self.misc_unban(req.id, params).await,
 
             // ==================
             // Blockchain methods
             // ==================
             Some("blockchain.get_slot") => return self.blockchain_get_slot(req.id, params).await,
             Some("blockchain.get_tx") => return self.blockchain_get_tx(req.id, params).await,
+            Some("blockchain.get_tx_location") => {
+                return self.blockchain_get_tx_location(req.id, params).await
+            }
+            Some("blockchain.get_tx_receipt") => {
+                return self.blockchain_get_tx_receipt(req.id, params).await
+            }
+            Some("blockchain.get_tx_proof") => {
+                return self.blockchain_get_tx_proof(req.id, params).await
+            }
+            Some("blockchain.lookup_nullifier") => {
+                return self.blockchain_lookup_nullifier(req.id, params).await
+            }
             Some("blockchain.last_known_slot") => {
                 return self.blockchain_last_known_slot(req.id, params).await
             }
+            Some("blockchain.min_tx_fee") => {
+                return self.blockchain_min_tx_fee(req.id, params).await
+            }
             Some("blockchain.subscribe_blocks") => {
                 return self.blockchain_subscribe_blocks(req.id, params).await
             }
             Some("blockchain.subscribe_err_txs") => {
                 return self.blockchain_subscribe_err_txs(req.id, params).await
             }
+            Some("blockchain.subscribe_events") => {
+                return self.blockchain_subscribe_events(req.id, params).await
+            }
             Some("blockchain.lookup_zkas") => {
                 return self.blockchain_lookup_zkas(req.id, params).await
             }
@@ -265,6 +391,74 @@ impl Darkfid {
     }
 }
 
+/// Build the chain parameters of the configured chain, applying configured overrides.
+fn chain_params(args: &Args) -> Result<ChainParams> {
+    let mut params = match ChainParams::from_chain(&args.chain) {
+        Ok(params) => params,
+        Err(e) => {
+            error!("Unsupported chain `{}`", args.chain);
+            return Err(e)
+        }
+    };
+
+    if let Some(magic) = &args.network_magic {
+        let Ok(magic) = u32::from_str_radix(magic, 16) else {
+            error!("Invalid network magic `{}`", magic);
+            return Err(Error::ConfigInvalid)
+        };
+        params.network_magic = magic.to_be_bytes();
+    }
+    if let Some(ts) = args.genesis_timestamp {
+        params.genesis_ts = Timestamp(ts);
+    }
+    if let Some(ts) = args.bootstrap_timestamp {
+        params.bootstrap_ts = Timestamp(ts);
+    }
+    if let Some(slot_time) = args.slot_time {
+        params.slot_time = slot_time;
+    }
+    if let Some(final_sync_dur) = args.final_sync_duration {
+        params.final_sync_dur = final_sync_dur;
+    }
+    if let Some(epoch_length) = args.epoch_length {
+        params.epoch_length = epoch_length;
+    }
+    if let Some(txs_cap) = args.txs_cap {
+        params.txs_cap = txs_cap;
+    }
+    if let Some(tx_gas_limit) = args.tx_gas_limit {
+        params.tx_gas_limit = tx_gas_limit;
+    }
+    if let Some(min_tx_fee) = args.min_tx_fee {
+        params.min_tx_fee = min_tx_fee;
+    }
+    if let Some(reward) = args.reward {
+        params.reward = reward;
+    }
+    if let Some(interval) = args.reward_halving_interval {
+        params.reward_halving_interval = interval;
+    }
+    if let Some(path) = &args.leader_history_log {
+        params.leader_history_log = Some(expand_path(path)?);
+    }
+    if let Some(path) = &args.f_history_log {
+        params.f_history_log = Some(expand_path(path)?);
+    }
+    if let Some(path) = &args.lottery_history_log {
+        params.lottery_history_log = Some(expand_path(path)?);
+    }
+
+    if params.slot_time == 0 ||
+        params.epoch_length == 0 ||
+        params.final_sync_dur >= params.slot_time
+    {
+        error!("Chain slot time, epoch length and finalization sync duration are misconfigured");
+        return Err(Error::ConfigInvalid)
+    }
+
+    Ok(params)
+}
+
 async_daemonize!(realmain);
 async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     if args.consensus && args.clock_sync {
@@ -304,25 +498,8 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         Path::new(expand_path(&args.database)?.to_str().unwrap()).join(args.chain.clone());
     let sled_db = sled::open(&db_path)?;
 
-    // Initialize validator state
-    let (bootstrap_ts, genesis_ts, genesis_data, initial_distribution) = match args.chain.as_str() {
-        "mainnet" => (
-            *MAINNET_BOOTSTRAP_TIMESTAMP,
-            *MAINNET_GENESIS_TIMESTAMP,
-            *MAINNET_GENESIS_HASH_BYTES,
-            *MAINNET_INITIAL_DISTRIBUTION,
-        ),
-        "testnet" => (
-            *TESTNET_BOOTSTRAP_TIMESTAMP,
-            *TESTNET_GENESIS_TIMESTAMP,
-            *TESTNET_GENESIS_HASH_BYTES,
-            *TESTNET_INITIAL_DISTRIBUTION,
-        ),
-        x => {
-            error!("Unsupported chain `{}`", x);
-            return Err(Error::UnsupportedChain)
-        }
-    };
+    // Initialize chain parameters
+    let params = chain_params(&args)?;
     // Parse faucet addresses
     let mut faucet_pubkeys = vec![];
 
@@ -343,10 +520,7 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     // Initialize validator state
     let state = ValidatorState::new(
         &sled_db,
-        bootstrap_ts,
-        genesis_ts,
-        genesis_data,
-        initial_distribution,
+        params,
         wallet.clone(),
         faucet_pubkeys,
         args.consensus,
@@ -354,6 +528,35 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     )
     .await?;
 
+    // Snapshot export and import, and rollbacks are done offline
+    if let Some(path) = args.export_snapshot {
+        let path = expand_path(&path)?;
+        info!("Exporting blockchain to {:?}", path);
+        export_snapshot(&state.read().await.blockchain, &path)?;
+        info!("Blockchain exported successfully");
+        return Ok(())
+    }
+
+    if let Some(path) = args.import_snapshot {
+        let path = expand_path(&path)?;
+        info!("Importing blockchain from {:?}", path);
+        if args.import_headers_only {
+            let state = state.read().await;
+            import_snapshot_headers(&state.blockchain, &state.consensus.params, &path)?;
+        } else {
+            state.write().await.import_snapshot(&path).await?;
+        }
+        info!("Blockchain imported successfully");
+        return Ok(())
+    }
+
+    if let Some(n) = args.rollback {
+        info!("Rolling back {} blocks", n);
+        let removed = state.write().await.rollback(n).await?;
+        info!("Rolled back {} blocks successfully", removed.len());
+        return Ok(())
+    }
+
     let sync_p2p = {
         info!("Registering block sync P2P protocols...");
         let sync_network_settings = net::Settings {
@@ -365,6 +568,9 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
             outbound_transports: net::settings::get_outbound_transports(args.sync_p2p_transports),
             localnet: args.localnet,
             channel_log: args.channel_log,
+            hosts_file: args.sync_p2p_hosts_file,
+            anchors_file: args.sync_p2p_anchors_file,
+            banlist_file: args.sync_p2p_banlist_file,
             ..Default::default()
         };
 
@@ -391,6 +597,20 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
             })
             .await;
 
+        let tx_inventory = TxInventory::new(state.clone());
+        registry
+            .register_with_requirements(
+                net::SESSION_ALL,
+                net::ProtocolInventory::<Transaction>::REQUIREMENTS,
+                move |channel, p2p| {
+                    let store = tx_inventory.clone();
+                    async move {
+                        net::ProtocolInventory::<Transaction>::init(channel, p2p, store).await
+                    }
+                },
+            )
+            .await;
+
         Some(p2p)
     };
 
@@ -411,6 +631,11 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
                 ),
                 localnet: args.localnet,
                 channel_log: args.channel_log,
+                hosts_file: args.consensus_p2p_hosts_file,
+                anchors_file: args.consensus_p2p_anchors_file,
+                banlist_file: args.consensus_p2p_banlist_file,
+                node_key_file: args.consensus_p2p_node_key_file,
+                trusted_peer_keys: args.consensus_p2p_trusted_peer_key,
                 ..Default::default()
             };
             let p2p = net::P2p::new(consensus_network_settings).await;
@@ -424,6 +649,20 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
                 })
                 .await;
 
+            let proposal_inventory = ProposalInventory::new(state.clone());
+            registry
+                .register_with_requirements(
+                    net::SESSION_ALL,
+                    net::ProtocolInventory::<BlockProposal>::REQUIREMENTS,
+                    move |channel, p2p| {
+                        let store = proposal_inventory.clone();
+                        async move {
+                            net::ProtocolInventory::<BlockProposal>::init(channel, p2p, store).await
+                        }
+                    },
+                )
+                .await;
+
             let _state = state.clone();
             registry
                 .register(net::SESSION_ALL, move |channel, p2p| {
@@ -483,7 +722,13 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
 
         info!("Starting consensus protocol task");
         let _ex = ex.clone();
-        ex.spawn(proposal_task(consensus_p2p.unwrap(), sync_p2p.unwrap(), state, _ex)).detach();
+        ex.spawn(proposal_task(
+            consensus_p2p.clone().unwrap(),
+            sync_p2p.clone().unwrap(),
+            state,
+            _ex,
+        ))
+        .detach();
     } else {
         info!("Not starting consensus P2P network");
     }
@@ -493,6 +738,14 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     print!("\r");
     info!("Caught termination signal, cleaning up and exiting...");
 
+    info!("Stopping P2P networks...");
+    if let Some(p2p) = consensus_p2p {
+        p2p.stop().await;
+    }
+    if let Some(p2p) = sync_p2p {
+        p2p.stop().await;
+    }
+
     info!("Flushing sled database...");
     let flushed_bytes = sled_db.flush_async().await?;
     info!("Flushed {} bytes", flushed_bytes);
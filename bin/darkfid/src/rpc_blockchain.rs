@@ -16,12 +16,15 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use darkfi_sdk::crypto::ContractId;
+use std::str::FromStr;
+
+use darkfi_sdk::crypto::{ContractId, Nullifier};
 use darkfi_serial::{deserialize, serialize};
 use log::{debug, error};
 use serde_json::{json, Value};
 
 use darkfi::{
+    consensus::TxInclusionProof,
     rpc::jsonrpc::{
         ErrorCode::{InternalError, InvalidParams, ParseError},
         JsonError, JsonResponse, JsonResult, JsonSubscriber,
@@ -108,6 +111,166 @@ impl Darkfid {
         JsonResponse::new(json!(serialize(tx)), id).into()
     }
 
+    // RPCAPI:
+    // Queries the blockchain database for the location of a finalized transaction.
+    // Returns the slot and hash of the block including it, its index in the block,
+    // and the number of blocks confirming it, the including block counted.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_tx_location", "params": ["TxHash"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"slot": 42, "block": "BlockHash", "index": 0, "confirmations": 3}, "id": 1}
+    pub async fn blockchain_get_tx_location(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let Ok(tx_hash) = blake3::Hash::from_hex(params[0].as_str().unwrap()) else {
+            return JsonError::new(ParseError, None, id).into()
+        };
+
+        let blockchain = { self.validator_state.read().await.blockchain.clone() };
+        let location = match blockchain.get_tx_locations(&[tx_hash]) {
+            Ok(v) => v[0],
+            Err(e) => {
+                error!("[RPC] blockchain.get_tx_location: Failed fetching tx location: {}", e);
+                return JsonError::new(InternalError, None, id).into()
+            }
+        };
+
+        let Some(location) = location else {
+            return server_error(RpcError::UnknownTx, id, None)
+        };
+
+        let result = json!({
+            "slot": location.slot,
+            "block": location.block.to_hex().as_str(),
+            "index": location.index,
+            "confirmations": blockchain.confirmations(location.slot),
+        });
+
+        JsonResponse::new(result, id).into()
+    }
+
+    // RPCAPI:
+    // Queries the blockchain database for the receipt of a finalized transaction.
+    // Returns the gas spent by the transaction and the events emitted by its
+    // contract calls, each one as a base58 encoded serialized `ContractEvent`.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_tx_receipt", "params": ["TxHash"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"gas_used": 1234, "events": ["ABCD...", ...]}, "id": 1}
+    pub async fn blockchain_get_tx_receipt(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let Ok(tx_hash) = blake3::Hash::from_hex(params[0].as_str().unwrap()) else {
+            return JsonError::new(ParseError, None, id).into()
+        };
+
+        let blockchain = { self.validator_state.read().await.blockchain.clone() };
+        let receipt = match blockchain.get_tx_receipts(&[tx_hash]) {
+            Ok(mut v) => v.remove(0),
+            Err(e) => {
+                error!("[RPC] blockchain.get_tx_receipt: Failed fetching tx receipt: {}", e);
+                return JsonError::new(InternalError, None, id).into()
+            }
+        };
+
+        let Some(receipt) = receipt else {
+            return server_error(RpcError::UnknownTx, id, None)
+        };
+
+        let events: Vec<String> =
+            receipt.events.iter().map(|x| bs58::encode(&serialize(x)).into_string()).collect();
+
+        let result = json!({
+            "gas_used": receipt.gas_used,
+            "events": events,
+        });
+
+        JsonResponse::new(result, id).into()
+    }
+
+    // RPCAPI:
+    // Queries the blockchain database for the merkle inclusion proof of a
+    // finalized transaction, verifiable against the header of the block including it.
+    // Returns the block hash and the serialized proof upon success.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.get_tx_proof", "params": ["TxHash"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": {"block": "BlockHash", "proof": [...]}, "id": 1}
+    pub async fn blockchain_get_tx_proof(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let Ok(tx_hash) = blake3::Hash::from_hex(params[0].as_str().unwrap()) else {
+            return JsonError::new(ParseError, None, id).into()
+        };
+
+        let blockchain = { self.validator_state.read().await.blockchain.clone() };
+        let location = match blockchain.get_tx_locations(&[tx_hash]) {
+            Ok(v) => v[0],
+            Err(e) => {
+                error!("[RPC] blockchain.get_tx_proof: Failed fetching tx location: {}", e);
+                return JsonError::new(InternalError, None, id).into()
+            }
+        };
+
+        let Some(location) = location else {
+            return server_error(RpcError::UnknownTx, id, None)
+        };
+
+        let block = match blockchain.blocks.get(&[location.block], true) {
+            Ok(mut v) => v.remove(0).unwrap(),
+            Err(e) => {
+                error!("[RPC] blockchain.get_tx_proof: Failed fetching block: {}", e);
+                return JsonError::new(InternalError, None, id).into()
+            }
+        };
+
+        let Some(proof) = TxInclusionProof::new(&block.txs, location.index as usize) else {
+            error!("[RPC] blockchain.get_tx_proof: Transaction index out of block bounds");
+            return JsonError::new(InternalError, None, id).into()
+        };
+
+        let result = json!({
+            "block": location.block.to_hex().as_str(),
+            "proof": serialize(&proof),
+        });
+
+        JsonResponse::new(result, id).into()
+    }
+
+    // RPCAPI:
+    // Queries the blockchain database for the finalized transaction revealing
+    // the given nullifier. Returns the transaction hash upon success.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.lookup_nullifier", "params": ["Nullifier"], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": "TxHash", "id": 1}
+    pub async fn blockchain_lookup_nullifier(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let Ok(nullifier) = Nullifier::from_str(params[0].as_str().unwrap()) else {
+            return JsonError::new(ParseError, None, id).into()
+        };
+
+        let blockchain = { self.validator_state.read().await.blockchain.clone() };
+        let tx_hash = match blockchain.get_nullifier_txs(&[nullifier]) {
+            Ok(v) => v[0],
+            Err(e) => {
+                error!("[RPC] blockchain.lookup_nullifier: Failed fetching nullifier: {}", e);
+                return JsonError::new(InternalError, None, id).into()
+            }
+        };
+
+        let Some(tx_hash) = tx_hash else {
+            return server_error(RpcError::UnknownNullifier, id, None)
+        };
+
+        JsonResponse::new(json!(tx_hash.to_hex().as_str()), id).into()
+    }
+
     // RPCAPI:
     // Queries the blockchain database to find the last known slot
     //
@@ -126,6 +289,21 @@ impl Darkfid {
         JsonResponse::new(json!(last_slot.0), id).into()
     }
 
+    // RPCAPI:
+    // Returns the minimum fee a transaction must prove paying on the chain
+    // this node runs. Transactions don't need a fee call if it is 0.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.min_tx_fee", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": 1000, "id": 1}
+    pub async fn blockchain_min_tx_fee(&self, id: Value, params: &[Value]) -> JsonResult {
+        if !params.is_empty() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let min_tx_fee = self.validator_state.read().await.consensus.params.min_tx_fee;
+        JsonResponse::new(json!(min_tx_fee), id).into()
+    }
+
     // RPCAPI:
     // Initializes a subscription to new incoming blocks.
     // Once a subscription is established, `darkfid` will send JSON-RPC notifications of
@@ -162,6 +340,24 @@ impl Darkfid {
         JsonSubscriber::new(err_txs_subscriber).into()
     }
 
+    // RPCAPI:
+    // Initializes a subscription to contract events emitted by finalized transactions.
+    // Once a subscription is established, `darkfid` will send JSON-RPC notifications
+    // of each new event to the subscriber, along with the hash of its transaction.
+    //
+    // --> {"jsonrpc": "2.0", "method": "blockchain.subscribe_events", "params": [], "id": 1}
+    // <-- {"jsonrpc": "2.0", "method": "blockchain.subscribe_events", "params": [`tx_hash`, `event`]}
+    pub async fn blockchain_subscribe_events(&self, id: Value, params: &[Value]) -> JsonResult {
+        if !params.is_empty() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let events_subscriber =
+            self.validator_state.read().await.subscribers.get("events").unwrap().clone();
+
+        JsonSubscriber::new(events_subscriber).into()
+    }
+
     // RPCAPI:
     // Performs a lookup of zkas bincodes for a given contract ID and returns all of
     // them, including their namespace.
@@ -211,3 +407,114 @@ impl Darkfid {
         JsonResponse::new(json!(ret), id).into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use darkfi::{
+        blockchain::{StateDiff, TxReceipt},
+        consensus::{BlockInfo, ChainParams, ValidatorState},
+        rpc::jsonrpc::ErrorCode,
+        tx::Transaction,
+        wallet::walletdb::WalletDb,
+        Result,
+    };
+    use darkfi_sdk::{
+        crypto::MONEY_CONTRACT_ID,
+        event::{ContractEvent, NULLIFIER_EVENT_TOPIC},
+        pasta::pallas,
+        tx::ContractCall,
+    };
+
+    use super::*;
+
+    fn error_code(result: JsonResult) -> Value {
+        match result {
+            JsonResult::Error(e) => e.error.code,
+            _ => panic!("Expected an error response"),
+        }
+    }
+
+    fn response(result: JsonResult) -> Value {
+        match result {
+            JsonResult::Response(r) => r.result,
+            _ => panic!("Expected a response"),
+        }
+    }
+
+    #[test]
+    fn tx_index_rpcs() -> Result<()> {
+        async_std::task::block_on(async {
+            let sled_db = sled::Config::new().temporary(true).open()?;
+            let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+            let state = ValidatorState::new(
+                &sled_db,
+                ChainParams::testnet(),
+                wallet.clone(),
+                vec![],
+                false,
+                false,
+            )
+            .await?;
+            let darkfid = Darkfid::new(state.clone(), None, None, wallet).await;
+
+            // A finalized block with a transaction revealing a nullifier
+            let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data: vec![0x00] }];
+            let tx = Transaction { calls, proofs: vec![], signatures: vec![] };
+            let tx_hash = blake3::hash(&serialize(&tx));
+            let nullifier = Nullifier::from(pallas::Base::from(42));
+            let event = ContractEvent {
+                contract_id: *MONEY_CONTRACT_ID,
+                call_idx: 0,
+                topics: vec![vec![0x00], NULLIFIER_EVENT_TOPIC.to_vec(), serialize(&nullifier)],
+                data: vec![],
+            };
+
+            let blockchain = state.read().await.blockchain.clone();
+            let mut block = BlockInfo::default();
+            block.header.previous = blockchain.last()?.1;
+            block.header.slot = 1;
+            block.txs = vec![tx];
+            let blockhash = blockchain.add(&[block])?[0];
+            blockchain.add_state_diffs(&[blockhash], &[StateDiff::default()])?;
+            blockchain
+                .add_tx_receipts(&[tx_hash], &[TxReceipt { gas_used: 0, events: vec![event] }])?;
+
+            let result = darkfid
+                .blockchain_get_tx_location(json!(1), &[json!(tx_hash.to_hex().as_str())])
+                .await;
+            assert_eq!(
+                response(result),
+                json!({
+                    "slot": 1,
+                    "block": blockhash.to_hex().as_str(),
+                    "index": 0,
+                    "confirmations": 1,
+                })
+            );
+
+            let result = darkfid
+                .blockchain_lookup_nullifier(json!(1), &[json!(nullifier.to_string())])
+                .await;
+            assert_eq!(response(result), json!(tx_hash.to_hex().as_str()));
+
+            // Unknown items and malformed params
+            let unknown = blake3::hash(b"unknown");
+            let result = darkfid
+                .blockchain_get_tx_location(json!(1), &[json!(unknown.to_hex().as_str())])
+                .await;
+            assert_eq!(error_code(result), json!(RpcError::UnknownTx as i64));
+
+            let unknown = Nullifier::from(pallas::Base::from(43));
+            let result =
+                darkfid.blockchain_lookup_nullifier(json!(1), &[json!(unknown.to_string())]).await;
+            assert_eq!(error_code(result), json!(RpcError::UnknownNullifier as i64));
+
+            let result = darkfid.blockchain_get_tx_location(json!(1), &[json!("foo")]).await;
+            assert_eq!(error_code(result), json!(ErrorCode::ParseError.code()));
+            let result = darkfid.blockchain_lookup_nullifier(json!(1), &[]).await;
+            assert_eq!(error_code(result), json!(ErrorCode::InvalidParams.code()));
+
+            Ok(())
+        })
+    }
+}
@@ -19,7 +19,7 @@
 use serde_json::{json, Value};
 
 use darkfi::{
-    rpc::jsonrpc::{JsonResponse, JsonResult},
+    rpc::jsonrpc::{ErrorCode::InvalidParams, JsonError, JsonResponse, JsonResult},
     util::time::Timestamp,
 };
 
@@ -69,4 +69,65 @@ impl Darkfid {
         };
         JsonResponse::new(resp, id).into()
     }
+
+    // RPCAPI:
+    // Returns the active bans of the sync and consensus P2P networks,
+    // along with the UNIX timestamp each ban expires at.
+    //
+    // --> {"jsonrpc": "2.0", "method": "get_banlist", "params": [], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": {"sync": [{"host": "1.2.3.4", "expiry": 1680000000}], "consensus": []}, "id": 42}
+    pub async fn misc_get_banlist(&self, id: Value, _params: &[Value]) -> JsonResult {
+        let sync = match &self.sync_p2p {
+            Some(p2p) => p2p.get_banlist_info().await,
+            None => json!([]),
+        };
+        let consensus = match &self.consensus_p2p {
+            Some(p2p) => p2p.get_banlist_info().await,
+            None => json!([]),
+        };
+        JsonResponse::new(json!({"sync": sync, "consensus": consensus}), id).into()
+    }
+
+    // RPCAPI:
+    // Bans a host on the sync and consensus P2P networks for given amount
+    // of seconds, disconnecting any of its open channels.
+    // Returns `true` on success.
+    //
+    // --> {"jsonrpc": "2.0", "method": "ban", "params": ["1.2.3.4", 86400], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 42}
+    pub async fn misc_ban(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 2 || !params[0].is_string() || !params[1].is_u64() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let host = params[0].as_str().unwrap();
+        let duration = params[1].as_u64().unwrap();
+
+        for p2p in [&self.sync_p2p, &self.consensus_p2p].into_iter().flatten() {
+            p2p.ban_host(host, duration).await;
+        }
+
+        JsonResponse::new(json!(true), id).into()
+    }
+
+    // RPCAPI:
+    // Lifts the ban of a host on the sync and consensus P2P networks.
+    // Returns `true` if the host was banned on any of them.
+    //
+    // --> {"jsonrpc": "2.0", "method": "unban", "params": ["1.2.3.4"], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 42}
+    pub async fn misc_unban(&self, id: Value, params: &[Value]) -> JsonResult {
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(InvalidParams, None, id).into()
+        }
+
+        let host = params[0].as_str().unwrap();
+
+        let mut unbanned = false;
+        for p2p in [&self.sync_p2p, &self.consensus_p2p].into_iter().flatten() {
+            unbanned |= p2p.unban_host(host).await;
+        }
+
+        JsonResponse::new(json!(unbanned), id).into()
+    }
 }
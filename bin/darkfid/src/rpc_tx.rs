@@ -31,11 +31,11 @@ use crate::{server_error, RpcError};
 impl Darkfid {
     // RPCAPI:
     // Simulate a network state transition with the given transaction.
-    // Returns `true` if the transaction is valid, otherwise, a corresponding
-    // error.
+    // Returns the gas spent by the transaction if it is valid, otherwise,
+    // a corresponding error.
     //
     // --> {"jsonrpc": "2.0", "method": "tx.simulate", "params": ["base58encodedTX"], "id": 1}
-    // <-- {"jsonrpc": "2.0", "result": true, "id": 1}
+    // <-- {"jsonrpc": "2.0", "result": 1234, "id": 1}
     pub async fn tx_simulate(&self, id: Value, params: &[Value]) -> JsonResult {
         if params.len() != 1 || !params[0].is_string() {
             return JsonError::new(InvalidParams, None, id).into()
@@ -64,20 +64,15 @@ impl Darkfid {
         };
 
         // Simulate state transition
-        match self.validator_state.read().await.verify_transactions(&[tx], false).await {
-            Ok(erroneous_txs) => {
-                if !erroneous_txs.is_empty() {
-                    error!("[RPC] tx.simulate: invalid transaction provided");
-                    return server_error(RpcError::TxSimulationFail, id, None)
-                }
-            }
+        let gas_used = match self.validator_state.read().await.simulate_transaction(&tx).await {
+            Ok(v) => v,
             Err(e) => {
                 error!("[RPC] tx.simulate: Failed to validate state transition: {}", e);
                 return server_error(RpcError::TxSimulationFail, id, None)
             }
         };
 
-        JsonResponse::new(json!(true), id).into()
+        JsonResponse::new(json!(gas_used), id).into()
     }
 
     // RPCAPI:
@@ -118,9 +113,16 @@ impl Darkfid {
         if self.consensus_p2p.is_some() {
             // Consider we're participating in consensus here?
             // The append_tx function performs a state transition check.
-            if !self.validator_state.write().await.append_tx(tx.clone()).await {
-                error!("[RPC] tx.broadcast: Failed to append transaction to mempool");
-                return server_error(RpcError::TxBroadcastFail, id, None)
+            match self.validator_state.write().await.append_tx(tx.clone()).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    error!("[RPC] tx.broadcast: Failed to append transaction to mempool");
+                    return server_error(RpcError::TxBroadcastFail, id, None)
+                }
+                Err(e) => {
+                    error!("[RPC] tx.broadcast: Failed to validate state transition: {}", e);
+                    return server_error(RpcError::TxSimulationFail, id, None)
+                }
             }
         } else {
             // We'll perform the state transition check here.
@@ -17,23 +17,33 @@
  */
 
 use std::{
-    env::set_current_dir,
     fs::{read, read_dir, read_to_string, File},
     io::{ErrorKind, Write},
-    path::{Path, PathBuf},
+    path::Path,
     str::FromStr,
 };
 
-use rand::{rngs::OsRng, RngCore};
-
+use anyhow::{anyhow, Result};
 use darkfi::{
-    crypto::keypair::SecretKey,
-    node::{MemoryState, State},
-    runtime::vm_runtime::Runtime,
+    blockchain::{Blockchain, BlockchainOverlay},
+    consensus::ChainParams,
+    runtime::vm_runtime::{ContractSection, Runtime},
+    tx::Transaction,
     util::cli::{fg_green, fg_red},
     zkas::ZkBinary,
-    Error, Result,
 };
+use darkfi_deployooor_contract::{
+    client::{deploy_v1::DeployCallBuilder, lock_v1::LockCallBuilder},
+    DeployFunction,
+};
+use darkfi_sdk::{
+    crypto::{ContractId, Keypair, SecretKey, DEPLOYOOOR_CONTRACT_ID},
+    ContractCall,
+};
+use darkfi_serial::Encodable;
+use rand::rngs::OsRng;
+
+use super::Drk;
 
 const CIRCUIT_DIR_NAME: &str = "proof";
 const CONTRACT_FILE_NAME: &str = "contract.wasm";
@@ -43,24 +53,58 @@ const DEPLOY_KEY_NAME: &str = "deploy.key";
 /// This key allows to update the wasm code and the zk circuits on chain
 /// by creating a signature. When deployed, the contract can be accessed
 /// by requesting the public counterpart of this secret key.
-pub fn create_deploy_key(mut rng: impl RngCore, path: &Path) -> Result<SecretKey> {
-    let secret = SecretKey::random(&mut rng);
+fn create_deploy_key(path: &Path) -> Result<SecretKey> {
+    let secret = SecretKey::random(&mut OsRng);
     let mut file = File::create(path)?;
-    file.write_all(bs58::encode(&secret.to_bytes()).into_string().as_bytes())?;
+    file.write_all(secret.to_string().as_bytes())?;
     Ok(secret)
 }
 
-/// Reads a deploy key from a file on the filesystem and returns it.
-fn read_deploy_key(s: &Path) -> core::result::Result<SecretKey, std::io::Error> {
-    eprintln!("Trying to read deploy key from file: {:?}", s);
-    let contents = read_to_string(s)?;
-    let secret = SecretKey::from_str(&contents).unwrap();
-    Ok(secret)
+/// Reads the deploy key of a contract directory, creating a new one
+/// if it doesn't exist yet.
+fn read_deploy_key(path: &Path) -> Result<Keypair> {
+    let key_path = path.join(DEPLOY_KEY_NAME);
+    eprintln!("Trying to read deploy key from file: {:?}", key_path);
+
+    let secret = match read_to_string(&key_path) {
+        Ok(v) => SecretKey::from_str(v.trim())?,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            // We didn't find a deploy key, generate a new one.
+            eprintln!("Did not find an existing key, creating a new one.");
+            let secret = create_deploy_key(&key_path)?;
+            eprintln!("Created new deploy key in {:?}", key_path);
+            secret
+        }
+        Err(e) => return Err(anyhow!("Failed to read deploy key: {}", e)),
+    };
+
+    Ok(Keypair::new(secret))
 }
 
-/// Creates necessary data to deploy a given smart contract on the network.
-/// For consistency, we point this function to a directory where our smart
-/// contract and the compiled circuits are contained. This is going to give
+/// Encode and sign a deployooor contract call transaction, paying its fee
+/// with a coin of the drk wallet.
+async fn create_tx(
+    drk: &Drk,
+    function: DeployFunction,
+    params: &impl Encodable,
+    deploy_keypair: &Keypair,
+) -> Result<Transaction> {
+    let mut data = vec![function as u8];
+    params.encode(&mut data)?;
+    let calls = vec![ContractCall { contract_id: *DEPLOYOOOR_CONTRACT_ID, data }];
+    let proofs = vec![vec![]];
+    let mut tx = Transaction { calls, proofs, signatures: vec![] };
+    let fee = drk.prepend_fee_call(&mut tx, &[]).await?;
+    let sigs = tx.create_sigs(&mut OsRng, &[deploy_keypair.secret])?;
+    tx.signatures = vec![sigs];
+    drk.sign_fee_call(&mut tx, fee).await?;
+
+    Ok(tx)
+}
+
+/// Creates a transaction deploying, or upgrading, a given smart contract on the
+/// network. For consistency, we point this function to a directory where our
+/// smart contract and the compiled circuits are contained. This is going to give
 /// us a uniform approach to scm and gives a generic layout of the source:
 /// ```text
 /// smart-contract
@@ -68,114 +112,90 @@ fn read_deploy_key(s: &Path) -> core::result::Result<SecretKey, std::io::Error>
 /// ├── deploy.key
 /// ├── Makefile
 /// ├── proof
-/// │   ├── circuit0.zk
-/// │   ├── circuit0.zk.bin
-/// │   ├── circuit1.zk
-/// │   └── circuit1.zk.bin
+/// │   ├── circuit0.zk
+/// │   ├── circuit0.zk.bin
+/// │   ├── circuit1.zk
+/// │   └── circuit1.zk.bin
 /// ├── contract.wasm
 /// ├── src
-/// │   └── lib.rs
+/// │   └── lib.rs
 /// └── tests
 /// ```
-//pub fn create_deploy_data(path: &Path) -> Result<ContractDeploy> {
-pub fn create_deploy_data(path: &Path) -> Result<()> {
-    // Try to chdir into the contract directory
-    if let Err(e) = set_current_dir(path) {
-        eprintln!("Failed to chdir into {:?}", path);
-        return Err(e.into())
-    }
-
-    let deploy_key: SecretKey;
-
-    let deploy_key = match read_deploy_key(&PathBuf::from(DEPLOY_KEY_NAME)) {
-        Ok(v) => deploy_key = v,
-        Err(e) => {
-            if e.kind() == ErrorKind::NotFound {
-                // We didn't find a deploy key, generate a new one.
-                eprintln!("Did not find an existing key, creating a new one.");
-                match create_deploy_key(&mut OsRng, &PathBuf::from(DEPLOY_KEY_NAME)) {
-                    Ok(v) => {
-                        eprintln!("Created new deploy key in \"{}\".", DEPLOY_KEY_NAME);
-                        deploy_key = v;
-                    }
-                    Err(e) => {
-                        eprintln!("Failed to create new deploy key");
-                        return Err(e)
-                    }
-                }
-            }
-            eprintln!("Failed to read deploy key");
-            return Err(e.into())
-        }
-    };
-
-    // Search for ZK circuits in the directory. If none are found, we'll bail.
+/// The given payload gets passed to the contract's initialization function.
+pub async fn deploy_contract(
+    drk: &Drk,
+    path: &Path,
+    payload: Vec<u8>,
+) -> Result<(ContractId, Transaction)> {
+    let deploy_keypair = read_deploy_key(path)?;
+
+    // Search for ZK circuits in the directory. Contracts may have none.
     // The logic searches for `.zk.bin` files created by zkas.
-    eprintln!("Searching for compiled ZK circuits in \"{}\" ...", CIRCUIT_DIR_NAME);
+    let circuit_dir = path.join(CIRCUIT_DIR_NAME);
     let mut circuits = vec![];
-    for i in read_dir(CIRCUIT_DIR_NAME)? {
-        if let Err(e) = i {
-            eprintln!("Error iterating over \"{}\" directory", CIRCUIT_DIR_NAME);
-            return Err(e.into())
-        }
-
-        let f = i.unwrap();
-        let fname = f.file_name();
-        let fname = fname.to_str().unwrap();
+    if circuit_dir.is_dir() {
+        eprintln!("Searching for compiled ZK circuits in {:?} ...", circuit_dir);
+        for entry in read_dir(&circuit_dir)? {
+            let path = entry?.path();
+            if !path.to_string_lossy().ends_with(".zk.bin") {
+                continue
+            }
 
-        if fname.ends_with(".zk.bin") {
             // Validate that the files can be properly decoded
-            eprintln!("{} {}", fg_green("Found:"), f.path().display());
-            let buf = read(f.path())?;
+            eprintln!("{} {}", fg_green("Found:"), path.display());
+            let buf = read(&path)?;
             if let Err(e) = ZkBinary::decode(&buf) {
-                eprintln!("{} Failed to decode zkas bincode in {:?}", fg_red("Error:"), f.path());
-                return Err(e)
+                eprintln!("{} Failed to decode zkas bincode in {:?}", fg_red("Error:"), path);
+                return Err(e.into())
             }
 
-            circuits.push(buf.clone());
+            circuits.push(buf);
         }
     }
 
-    if circuits.is_empty() {
-        return Err(Error::Custom("Found no valid ZK circuits".to_string()))
-    }
-
-    /* FIXME
     // Validate wasm binary. We inspect the bincode and try to load it into
-    // the wasm runtime. If loaded, we then look for the `ENTRYPOINT` function
-    // which we hardcode into our sdk and runtime and is the canonical way to
-    // run wasm binaries on chain.
-    eprintln!("Inspecting wasm binary in \"{}\"", CONTRACT_FILE_NAME);
-    let wasm_bytes = read(CONTRACT_FILE_NAME)?;
-    eprintln!("Initializing moch wasm runtime to check validity");
-    let runtime = match Runtime::new(&wasm_bytes, MemoryState::new(State::dummy()?)) {
-        Ok(v) => {
-            eprintln!("Found {} wasm binary", fg_green("valid"));
-            v
-        }
-        Err(e) => {
-            eprintln!("Failed to initialize wasm runtime");
-            return Err(e)
+    // a mock wasm runtime. If loaded, we then look for the `__initialize` and
+    // `__entrypoint` functions which we hardcode into our sdk and runtime and
+    // are the canonical way to run wasm binaries on chain.
+    let wasm_path = path.join(CONTRACT_FILE_NAME);
+    eprintln!("Inspecting wasm binary in {:?}", wasm_path);
+    let wasm_bincode = read(&wasm_path)?;
+
+    eprintln!("Initializing mock wasm runtime to check validity");
+    let params = ChainParams::testnet();
+    let sled_db = sled::Config::new().temporary(true).open()?;
+    let blockchain = Blockchain::new(&sled_db, params.genesis_ts, params.genesis_data)?;
+    let runtime = Runtime::new(
+        &wasm_bincode,
+        BlockchainOverlay::new(&blockchain)?,
+        ContractId::derive_public(deploy_keypair.public),
+        params.tx_gas_limit,
+    )?;
+    for section in [ContractSection::Deploy, ContractSection::Exec] {
+        if runtime.instance.exports.get_function(section.name()).is_err() {
+            eprintln!("{} Could not find {} function", fg_red("Error:"), section.name());
+            return Err(anyhow!("Invalid wasm binary"))
         }
-    };
-
-    eprintln!("Looking for entrypoint function inside the wasm");
-    let cs = ContractSection::Exec;
-    if let Err(e) = runtime.instance.exports.get_function(cs.name()) {
-        eprintln!("{} Could not find entrypoint function", fg_red("Error:"));
-        return Err(e.into())
     }
+    eprintln!("Found {} wasm binary", fg_green("valid"));
 
-    // TODO: Create a ZK proof enforcing the deploy key relations with their public
-    // counterparts (public key and contract address)
-    let mut total_bytes = 0;
-    total_bytes += wasm_bytes.len();
-    for circuit in circuits {
-        total_bytes += circuit.len();
-    }
-    */
+    eprintln!("Building transaction parameters");
+    let builder =
+        DeployCallBuilder { deploy_keypair, wasm_bincode, zkas_bincodes: circuits, ix: payload };
+    let debris = builder.build()?;
+
+    let tx = create_tx(drk, DeployFunction::DeployV1, &debris.params, &deploy_keypair).await?;
+    Ok((ContractId::derive_public(deploy_keypair.public), tx))
+}
+
+/// Creates a transaction locking the smart contract of given directory, so it
+/// can't be upgraded anymore.
+pub async fn lock_contract(drk: &Drk, path: &Path) -> Result<(ContractId, Transaction)> {
+    let deploy_keypair = read_deploy_key(path)?;
+
+    eprintln!("Building transaction parameters");
+    let debris = LockCallBuilder { deploy_keypair }.build()?;
 
-    // TODO: Return the data back to the main function, and work further in creating
-    // a transaction and broadcasting it.
-    Ok(())
+    let tx = create_tx(drk, DeployFunction::LockV1, &debris.params, &deploy_keypair).await?;
+    Ok((ContractId::derive_public(deploy_keypair.public), tx))
 }
@@ -17,7 +17,9 @@
  */
 
 use std::{
+    fs::read,
     io::{stdin, Read},
+    path::PathBuf,
     process::exit,
     str::FromStr,
     time::Instant,
@@ -53,6 +55,12 @@ mod rpc_airdrop;
 /// Payment methods
 mod rpc_transfer;
 
+/// Fee methods
+mod rpc_fee;
+
+/// Staking methods
+mod rpc_stake;
+
 /// Swap methods
 mod rpc_swap;
 use rpc_swap::PartialSwapData;
@@ -86,6 +94,9 @@ mod wallet_token;
 /// Wallet functionality related to transactions history
 mod wallet_txs_history;
 
+/// Contract deployment methods
+mod deploy_contract;
+
 #[derive(Parser)]
 #[command(about = cli_desc!())]
 struct Args {
@@ -153,6 +164,12 @@ enum Subcmd {
         coin: String,
     },
 
+    /// Stake a native token coin into a consensus lead coin
+    Stake {
+        /// base58-encoded coin to stake
+        coin: String,
+    },
+
     /// Airdrop some tokens
     Airdrop {
         /// Faucet JSON-RPC endpoint
@@ -229,6 +246,21 @@ enum Subcmd {
     /// Token functionalities
     #[command(subcommand)]
     Token(TokenSubcmd),
+
+    /// Deploy or upgrade the smart contract of given directory
+    Deploy {
+        /// Contract directory, holding contract.wasm, proof/ and deploy.key
+        path: PathBuf,
+
+        /// File holding the payload passed to the contract initialization
+        payload: Option<PathBuf>,
+    },
+
+    /// Lock the smart contract of given directory, forbidding further upgrades
+    Lock {
+        /// Contract directory, holding deploy.key
+        path: PathBuf,
+    },
 }
 
 #[derive(Subcommand)]
@@ -714,6 +746,24 @@ async fn main() -> Result<()> {
             Ok(())
         }
 
+        Subcmd::Stake { coin } => {
+            let bytes: [u8; 32] = bs58::decode(&coin).into_vec()?.try_into().unwrap();
+
+            let elem: pallas::Base = match pallas::Base::from_repr(bytes).into() {
+                Some(v) => v,
+                None => return Err(anyhow!("Invalid coin")),
+            };
+
+            let coin = Coin::from(elem);
+            let drk = Drk::new(args.endpoint).await?;
+            let tx =
+                drk.stake(&coin).await.with_context(|| "Failed to create stake transaction")?;
+
+            println!("{}", bs58::encode(&serialize(&tx)).into_string());
+
+            Ok(())
+        }
+
         Subcmd::Airdrop { faucet_endpoint, amount, address } => {
             let amount = f64::from_str(&amount).with_context(|| "Invalid amount")?;
             let drk = Drk::new(args.endpoint).await?;
@@ -1123,11 +1173,12 @@ async fn main() -> Result<()> {
 
                 let drk = Drk::new(args.endpoint).await?;
 
-                let is_valid =
+                let gas_used =
                     drk.simulate_tx(&tx).await.with_context(|| "Failed to simulate tx")?;
 
                 println!("Transaction ID: {}", tx.hash());
-                println!("State: {}", if is_valid { "valid" } else { "invalid" });
+                println!("State: valid");
+                println!("Gas used: {}", gas_used);
 
                 Ok(())
             }
@@ -1310,5 +1361,34 @@ async fn main() -> Result<()> {
                 Ok(())
             }
         },
+
+        Subcmd::Deploy { path, payload } => {
+            let payload = match payload {
+                Some(v) => read(v).with_context(|| "Failed to read payload file")?,
+                None => vec![],
+            };
+
+            let drk = Drk::new(args.endpoint).await?;
+            let (contract_id, tx) = deploy_contract::deploy_contract(&drk, &path, payload)
+                .await
+                .with_context(|| "Failed to create contract deployment transaction")?;
+
+            eprintln!("Contract ID: {}", contract_id);
+            println!("{}", bs58::encode(&serialize(&tx)).into_string());
+
+            Ok(())
+        }
+
+        Subcmd::Lock { path } => {
+            let drk = Drk::new(args.endpoint).await?;
+            let (contract_id, tx) = deploy_contract::lock_contract(&drk, &path)
+                .await
+                .with_context(|| "Failed to create contract lock transaction")?;
+
+            eprintln!("Contract ID: {}", contract_id);
+            println!("{}", bs58::encode(&serialize(&tx)).into_string());
+
+            Ok(())
+        }
     }
 }
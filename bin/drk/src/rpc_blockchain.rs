@@ -176,14 +176,14 @@ impl Drk {
         Ok(txid)
     }
 
-    /// Simulate the transaction with the state machine
-    pub async fn simulate_tx(&self, tx: &Transaction) -> Result<bool> {
+    /// Simulate the transaction with the state machine, returning the gas it spends
+    pub async fn simulate_tx(&self, tx: &Transaction) -> Result<u64> {
         let params = json!([bs58::encode(&serialize(tx)).into_string()]);
         let req = JsonRequest::new("tx.simulate", params);
         let rep = self.rpc_client.request(req).await?;
 
-        let is_valid = serde_json::from_value(rep)?;
-        Ok(is_valid)
+        let gas_used = serde_json::from_value(rep)?;
+        Ok(gas_used)
     }
 
     /// Queries darkfid for a block with given slot
@@ -87,8 +87,10 @@ impl Drk {
         let calls = vec![ContractCall { contract_id: *DAO_CONTRACT_ID, data }];
         let proofs = vec![proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let sigs = tx.create_sigs(&mut OsRng, &[dao.secret_key])?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -240,8 +242,10 @@ impl Drk {
         let calls = vec![ContractCall { contract_id: *DAO_CONTRACT_ID, data }];
         let proofs = vec![proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let sigs = tx.create_sigs(&mut OsRng, &[signature_secret])?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -373,8 +377,10 @@ impl Drk {
         let calls = vec![ContractCall { contract_id: *DAO_CONTRACT_ID, data }];
         let proofs = vec![proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let sigs = tx.create_sigs(&mut OsRng, &input_secrets)?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -581,9 +587,11 @@ impl Drk {
             signatures: vec![],
         };
 
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let xfer_sigs = tx.create_sigs(&mut OsRng, &xfer_signature_secrets)?;
         let exec_sigs = tx.create_sigs(&mut OsRng, &[exec_signature_secret])?;
         tx.signatures = vec![xfer_sigs, exec_sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -0,0 +1,119 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Result};
+use darkfi::{
+    rpc::jsonrpc::JsonRequest,
+    tx::Transaction,
+    zk::{proof::ProvingKey, vm::ZkCircuit, vm_stack::empty_witnesses},
+    zkas::ZkBinary,
+};
+use darkfi_money_contract::{
+    client::fee_v1::{FeeCallBuilder, FeeCallDebris},
+    MONEY_CONTRACT_ZKAS_FEE_NS_V1,
+};
+use darkfi_sdk::{
+    crypto::{contract_id::MONEY_CONTRACT_ID, Coin, Keypair, DARK_TOKEN_ID},
+    pasta::pallas,
+};
+use serde_json::json;
+
+use super::Drk;
+
+impl Drk {
+    /// Query darkfid for the minimum fee a transaction must prove paying.
+    pub async fn min_tx_fee(&self) -> Result<u64> {
+        let req = JsonRequest::new("blockchain.min_tx_fee", json!([]));
+        let rep = self.rpc_client.request(req).await?;
+        Ok(serde_json::from_value(rep)?)
+    }
+
+    /// Prepend a `Money::FeeV1` call paying the chain minimum fee to the given
+    /// unsigned transaction, spending a native token coin that is not in
+    /// `spent_coins`. Returns `None` if the chain requires no fee.
+    /// The returned debris must be passed to [`Drk::sign_fee_call`] once the
+    /// signatures of the other calls are created.
+    pub async fn prepend_fee_call(
+        &self,
+        tx: &mut Transaction,
+        spent_coins: &[Coin],
+    ) -> Result<Option<FeeCallDebris>> {
+        let fee = self.min_tx_fee().await?;
+        if fee == 0 {
+            return Ok(None)
+        }
+
+        // Fee coins must be native token coins not owned by any protocol
+        let owncoins = self.get_coins(false).await?;
+        let Some((coin, _)) = owncoins.into_iter().find(|(x, _)| {
+            x.note.token_id == *DARK_TOKEN_ID &&
+                x.note.spend_hook == pallas::Base::zero() &&
+                x.note.user_data == pallas::Base::zero() &&
+                x.note.value >= fee &&
+                !spent_coins.contains(&x.coin)
+        }) else {
+            return Err(anyhow!("Did not find a native token coin to pay the fee with"))
+        };
+
+        let tree = self.get_money_tree().await?;
+        let secrets = self.get_money_secrets().await?;
+        let keypair = Keypair::new(secrets[0]);
+
+        let zkas_bins = self.lookup_zkas(&MONEY_CONTRACT_ID).await?;
+        let Some(fee_zkbin) = zkas_bins.iter().find(|x| x.0 == MONEY_CONTRACT_ZKAS_FEE_NS_V1) else {
+            return Err(anyhow!("Fee circuit not found"))
+        };
+
+        let k = 13;
+        let fee_zkbin = ZkBinary::decode(&fee_zkbin.1)?;
+        let fee_circuit = ZkCircuit::new(empty_witnesses(&fee_zkbin), fee_zkbin.clone());
+
+        eprintln!("Creating Fee circuit proving key");
+        let fee_builder = FeeCallBuilder {
+            coin,
+            fee,
+            min_fee: fee,
+            change_public: keypair.public,
+            tree,
+            fee_zkbin,
+            fee_pk: ProvingKey::build(k, &fee_circuit),
+        };
+
+        eprintln!("Building fee call parameters");
+        let debris = fee_builder.build()?;
+
+        debris.prepend_call(tx)?;
+
+        Ok(Some(debris))
+    }
+
+    /// Sign the fee call prepended by [`Drk::prepend_fee_call`], placing its
+    /// signatures first, and mark its coin as spent in the wallet.
+    pub async fn sign_fee_call(
+        &self,
+        tx: &mut Transaction,
+        debris: Option<FeeCallDebris>,
+    ) -> Result<()> {
+        let Some(debris) = debris else { return Ok(()) };
+
+        debris.prepend_sigs(tx)?;
+        self.mark_spent_coin(&debris.spent_coin.coin).await?;
+
+        Ok(())
+    }
+}
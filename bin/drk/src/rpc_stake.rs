@@ -0,0 +1,125 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use anyhow::{anyhow, Result};
+use darkfi::{
+    consensus::lead_coin::LeadCoinSecrets,
+    rpc::jsonrpc::JsonRequest,
+    tx::Transaction,
+    zk::{proof::ProvingKey, vm::ZkCircuit, vm_stack::empty_witnesses},
+    zkas::ZkBinary,
+};
+use darkfi_money_contract::{
+    client::stake_v1::StakeCallBuilder, MoneyFunction, MONEY_CONTRACT_ZKAS_BURN_NS_V1,
+    MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1,
+};
+use darkfi_sdk::{
+    crypto::{contract_id::MONEY_CONTRACT_ID, Coin},
+    tx::ContractCall,
+};
+use darkfi_serial::Encodable;
+use rand::rngs::OsRng;
+use serde_json::json;
+
+use super::Drk;
+
+impl Drk {
+    /// Create a transaction staking the given native token coin into a
+    /// consensus lead coin, and place the lead coin into the wallet so
+    /// darkfid competes with it. Returns the transaction object on success.
+    pub async fn stake(&self, coin: &Coin) -> Result<Transaction> {
+        eprintln!("Fetching OwnCoins");
+        let owncoins = self.get_coins(false).await?;
+        let Some((owncoin, _)) = owncoins.into_iter().find(|(x, _)| x.coin == *coin) else {
+            return Err(anyhow!("Did not find unspent coin: {:?}", coin))
+        };
+
+        // We'll also need our Merkle tree
+        let tree = self.get_money_tree().await?;
+
+        // The lead coin is created in the last known slot
+        let req = JsonRequest::new("blockchain.last_known_slot", json!([]));
+        let rep = self.rpc_client.request(req).await?;
+        let slot: u64 = serde_json::from_value(rep)?;
+
+        let contract_id = *MONEY_CONTRACT_ID;
+
+        // Now we need to do a lookup for the zkas proof bincodes, and create
+        // the circuit objects and proving keys so we can build the transaction.
+        // We also do this through the RPC.
+        let zkas_bins = self.lookup_zkas(&contract_id).await?;
+
+        let Some(burn_zkbin) = zkas_bins.iter().find(|x| x.0 == MONEY_CONTRACT_ZKAS_BURN_NS_V1)
+        else {
+            return Err(anyhow!("Burn circuit not found"))
+        };
+
+        let Some(lead_mint_zkbin) =
+            zkas_bins.iter().find(|x| x.0 == MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1)
+        else {
+            return Err(anyhow!("Lead mint circuit not found"))
+        };
+
+        let burn_zkbin = ZkBinary::decode(&burn_zkbin.1)?;
+        let lead_mint_zkbin = ZkBinary::decode(&lead_mint_zkbin.1)?;
+
+        let k = 13;
+        let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
+        let lead_mint_circuit =
+            ZkCircuit::new(empty_witnesses(&lead_mint_zkbin), lead_mint_zkbin.clone());
+
+        // Lead coin secret key, committed to in its own Merkle tree
+        let secrets = LeadCoinSecrets::generate(1);
+
+        eprintln!("Creating Burn and Lead Mint circuit proving keys");
+        let stake_builder = StakeCallBuilder {
+            coin: owncoin,
+            tree,
+            slot,
+            sk: secrets.secret_keys[0].inner(),
+            sk_root: secrets.merkle_roots[0],
+            sk_pos: 0,
+            sk_merkle_path: secrets.merkle_paths[0].clone(),
+            burn_zkbin,
+            burn_pk: ProvingKey::build(k, &burn_circuit),
+            lead_mint_zkbin,
+            lead_mint_pk: ProvingKey::build(k, &lead_mint_circuit),
+        };
+
+        eprintln!("Building transaction parameters");
+        let debris = stake_builder.build()?;
+
+        // Encode and sign the transaction
+        let mut data = vec![MoneyFunction::StakeV1 as u8];
+        debris.params.encode(&mut data)?;
+        let calls = vec![ContractCall { contract_id, data }];
+        let proofs = vec![debris.proofs];
+        let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[*coin]).await?;
+        let sigs = tx.create_sigs(&mut OsRng, &[debris.signature_secret])?;
+        tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
+
+        // We need to mark the staked coin as spent, and keep the lead coin
+        // so consensus picks it up once the stake is in the contract state.
+        self.mark_spent_coin(coin).await?;
+        self.put_staked_coin(&debris.staked_coin.lead_coin()).await?;
+
+        Ok(tx)
+    }
+}
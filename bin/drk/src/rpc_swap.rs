@@ -261,9 +261,12 @@ impl Drk {
             proofs: vec![full_proofs],
             signatures: vec![],
         };
+        // The joining party pays the fee
+        let fee = self.prepend_fee_call(&mut tx, &[burn_coin.coin]).await?;
         eprintln!("Signing swap transaction");
         let sigs = tx.create_sigs(&mut OsRng, &[debris.signature_secret])?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -287,16 +290,18 @@ impl Drk {
         }
 
         if let Some(tx) = full {
-            // We're inspecting a full transaction
-            if tx.calls.len() != 1 {
+            // We're inspecting a full transaction. The swap call comes last,
+            // after the fee call if the transaction pays one.
+            if tx.calls.is_empty() || tx.calls.len() > 2 {
                 eprintln!(
-                    "Found {} contract calls in the transaction, there should be 1",
+                    "Found {} contract calls in the transaction, there should be 1 or 2",
                     tx.calls.len()
                 );
                 return Err(anyhow!("Inspection failed"))
             }
 
-            let params: MoneyTransferParamsV1 = deserialize(&tx.calls[0].data[1..])?;
+            let swap_idx = tx.calls.len() - 1;
+            let params: MoneyTransferParamsV1 = deserialize(&tx.calls[swap_idx].data[1..])?;
             eprintln!("Parameters:\n{:#?}", params);
 
             if params.inputs.len() != 2 {
@@ -427,7 +432,9 @@ impl Drk {
     pub async fn sign_swap(&self, tx: &mut Transaction) -> Result<()> {
         // We need our secret keys to try and decrypt the note
         let secret_keys = self.get_money_secrets().await?;
-        let params: MoneyTransferParamsV1 = deserialize(&tx.calls[0].data[1..])?;
+        // The swap call comes after the fee call, if any
+        let swap_idx = tx.calls.len() - 1;
+        let params: MoneyTransferParamsV1 = deserialize(&tx.calls[swap_idx].data[1..])?;
 
         // Our output should be outputs[0] so we try to decrypt that.
         let encrypted_note = &params.outputs[0].note;
@@ -451,7 +458,7 @@ impl Drk {
 
         eprintln!("Signing swap transaction");
         let sigs = tx.create_sigs(&mut OsRng, &[skey])?;
-        tx.signatures[0].insert(0, sigs[0]);
+        tx.signatures[swap_idx].insert(0, sigs[0]);
 
         Ok(())
     }
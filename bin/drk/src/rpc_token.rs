@@ -99,8 +99,10 @@ impl Drk {
         let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data }];
         let proofs = vec![debris.proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let sigs = tx.create_sigs(&mut OsRng, &[mint_authority.secret])?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -148,8 +150,10 @@ impl Drk {
         let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data }];
         let proofs = vec![debris.proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let fee = self.prepend_fee_call(&mut tx, &[]).await?;
         let sigs = tx.create_sigs(&mut OsRng, &[mint_authority.secret])?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         Ok(tx)
     }
@@ -31,7 +31,7 @@ use darkfi_money_contract::{
 use darkfi_sdk::{
     crypto::{
         contract_id::{DAO_CONTRACT_ID, MONEY_CONTRACT_ID},
-        Keypair, PublicKey, TokenId,
+        Coin, Keypair, PublicKey, TokenId,
     },
     pasta::pallas,
     tx::ContractCall,
@@ -153,8 +153,11 @@ impl Drk {
         let calls = vec![ContractCall { contract_id, data }];
         let proofs = vec![debris.proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+        let spent_coins: Vec<Coin> = debris.spent_coins.iter().map(|x| x.coin).collect();
+        let fee = self.prepend_fee_call(&mut tx, &spent_coins).await?;
         let sigs = tx.create_sigs(&mut OsRng, &debris.signature_secrets)?;
         tx.signatures = vec![sigs];
+        self.sign_fee_call(&mut tx, fee).await?;
 
         // We need to mark the coins we've spent in our wallet
         for spent_coin in debris.spent_coins {
@@ -18,7 +18,15 @@
 use std::collections::HashMap;
 
 use anyhow::{anyhow, Result};
-use darkfi::{rpc::jsonrpc::JsonRequest, tx::Transaction, wallet::walletdb::QueryType};
+use darkfi::{
+    consensus::{
+        constants::{CONSENSUS_COIN_COL, CONSENSUS_COIN_COL_STAKED, CONSENSUS_COIN_TABLE},
+        lead_coin::LeadCoin,
+    },
+    rpc::jsonrpc::JsonRequest,
+    tx::Transaction,
+    wallet::walletdb::QueryType,
+};
 use darkfi_money_contract::{
     client::{
         MoneyNote, OwnCoin, MONEY_ALIASES_COL_ALIAS, MONEY_ALIASES_COL_TOKEN_ID,
@@ -32,13 +40,16 @@ use darkfi_money_contract::{
         MONEY_KEYS_COL_SECRET, MONEY_KEYS_TABLE, MONEY_TOKENS_COL_IS_FROZEN,
         MONEY_TOKENS_COL_TOKEN_ID, MONEY_TOKENS_TABLE, MONEY_TREE_COL_TREE, MONEY_TREE_TABLE,
     },
-    model::{MoneyFreezeParamsV1, MoneyMintParamsV1, MoneyTransferParamsV1, Output},
+    model::{
+        MoneyFeeParamsV1, MoneyFreezeParamsV1, MoneyMintParamsV1, MoneyStakeParamsV1,
+        MoneyTransferParamsV1, MoneyUnstakeParamsV1,
+    },
     MoneyFunction,
 };
 use darkfi_sdk::{
     crypto::{
-        poseidon_hash, Coin, Keypair, MerkleNode, MerkleTree, Nullifier, PublicKey, SecretKey,
-        TokenId, MONEY_CONTRACT_ID,
+        note::AeadEncryptedNote, poseidon_hash, Coin, Keypair, MerkleNode, MerkleTree, Nullifier,
+        PublicKey, SecretKey, TokenId, MONEY_CONTRACT_ID,
     },
     incrementalmerkletree,
     incrementalmerkletree::Tree,
@@ -375,6 +386,27 @@ impl Drk {
         Ok(())
     }
 
+    /// Place a staked lead coin into the wallet, so darkfid can compete with it
+    /// in the leader election. Requires darkfid to run with participation enabled.
+    pub async fn put_staked_coin(&self, coin: &LeadCoin) -> Result<()> {
+        let query = format!(
+            "INSERT INTO {} ({}, {}) VALUES (?1, ?2);",
+            CONSENSUS_COIN_TABLE, CONSENSUS_COIN_COL_STAKED, CONSENSUS_COIN_COL
+        );
+
+        let coin = serialize(coin);
+        let params = json!([query, QueryType::Blob as u8, coin, QueryType::Blob as u8, coin]);
+
+        let req = JsonRequest::new("wallet.exec_sql", params);
+        let rep = self.rpc_client.request(req).await?;
+
+        if rep != true {
+            return Err(anyhow!("[put_staked_coin] Got unexpected reply from darkfid: {}", rep))
+        }
+
+        Ok(())
+    }
+
     /// Marks all coins in the wallet as spent, if their nullifier is in the given set
     pub async fn mark_spent_coins(&self, nullifiers: &[Nullifier]) -> Result<()> {
         if nullifiers.is_empty() {
@@ -486,7 +518,7 @@ impl Drk {
         let cid = *MONEY_CONTRACT_ID;
 
         let mut nullifiers: Vec<Nullifier> = vec![];
-        let mut outputs: Vec<Output> = vec![];
+        let mut outputs: Vec<(Coin, AeadEncryptedNote)> = vec![];
         let mut freezes: Vec<TokenId> = vec![];
 
         for (i, call) in tx.calls.iter().enumerate() {
@@ -499,7 +531,7 @@ impl Drk {
                 }
 
                 for output in params.outputs {
-                    outputs.push(output);
+                    outputs.push((output.coin, output.note));
                 }
 
                 continue
@@ -514,7 +546,7 @@ impl Drk {
                 }
 
                 for output in params.outputs {
-                    outputs.push(output);
+                    outputs.push((output.coin, output.note));
                 }
 
                 continue
@@ -524,7 +556,7 @@ impl Drk {
                 eprintln!("Found Money::MintV1 in call {}", i);
                 let params: MoneyMintParamsV1 = deserialize(&call.data[1..])?;
 
-                outputs.push(params.output);
+                outputs.push((params.output.coin, params.output.note));
 
                 continue
             }
@@ -537,6 +569,34 @@ impl Drk {
                 let token_id = TokenId::from(poseidon_hash([mint_x, mint_y]));
 
                 freezes.push(token_id);
+
+                continue
+            }
+
+            if call.contract_id == cid && call.data[0] == MoneyFunction::FeeV1 as u8 {
+                eprintln!("Found Money::FeeV1 in call {}", i);
+                let params: MoneyFeeParamsV1 = deserialize(&call.data[1..])?;
+
+                nullifiers.push(params.nullifier);
+                outputs.push((params.coin, params.note));
+
+                continue
+            }
+
+            if call.contract_id == cid && call.data[0] == MoneyFunction::StakeV1 as u8 {
+                eprintln!("Found Money::StakeV1 in call {}", i);
+                let params: MoneyStakeParamsV1 = deserialize(&call.data[1..])?;
+
+                nullifiers.push(params.input.nullifier);
+
+                continue
+            }
+
+            if call.contract_id == cid && call.data[0] == MoneyFunction::UnstakeV1 as u8 {
+                eprintln!("Found Money::UnstakeV1 in call {}", i);
+                let params: MoneyUnstakeParamsV1 = deserialize(&call.data[1..])?;
+
+                outputs.push((params.output.coin, params.output.note));
             }
         }
 
@@ -546,15 +606,13 @@ impl Drk {
 
         let mut owncoins = vec![];
 
-        for output in outputs {
-            let coin = output.coin;
-
+        for (coin, note) in outputs {
             // Append the new coin to the Merkle tree. Every coin has to be added.
             tree.append(&MerkleNode::from(coin.inner()));
 
             // Attempt to decrypt the note
             for secret in secrets.iter().chain(dao_secrets.iter()) {
-                if let Ok(note) = output.note.decrypt::<MoneyNote>(secret) {
+                if let Ok(note) = note.decrypt::<MoneyNote>(secret) {
                     eprintln!("Successfully decrypted a Money Note");
                     eprintln!("Witnessing coin in Merkle tree");
                     let leaf_position = tree.witness().unwrap();
@@ -25,6 +25,7 @@ pub enum RpcError {
     TimeLimitReached = -32108,
     ParseError = -32109,
     InternalError = -32110,
+    NoFeeCoin = -32111,
 }
 
 fn to_tuple(e: RpcError) -> (i64, String) {
@@ -33,6 +34,7 @@ fn to_tuple(e: RpcError) -> (i64, String) {
         RpcError::TimeLimitReached => "Timeout not expired, try again later",
         RpcError::ParseError => "Parse error",
         RpcError::InternalError => "Internal error",
+        RpcError::NoFeeCoin => "Faucet has no coin to pay the transaction fee with",
     };
 
     (e as i64, msg.to_string())
@@ -37,18 +37,25 @@ use darkfi::{
 };
 use darkfi_money_contract::{
     client::{
-        transfer_v1::TransferCallBuilder, MONEY_KEYS_COL_IS_DEFAULT, MONEY_KEYS_COL_PUBLIC,
+        fee_v1::{FeeCallBuilder, FeeCallDebris},
+        transfer_v1::TransferCallBuilder,
+        MoneyNote, OwnCoin, MONEY_KEYS_COL_IS_DEFAULT, MONEY_KEYS_COL_PUBLIC,
         MONEY_KEYS_COL_SECRET, MONEY_KEYS_TABLE, MONEY_TREE_COL_TREE, MONEY_TREE_TABLE,
     },
+    model::{
+        MoneyFeeParamsV1, MoneyMintParamsV1, MoneyStakeParamsV1, MoneyTransferParamsV1,
+        MoneyUnstakeParamsV1,
+    },
+    MoneyFunction,
     MoneyFunction::TransferV1 as MoneyTransfer,
-    MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_MINT_NS_V1,
+    MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_FEE_NS_V1, MONEY_CONTRACT_ZKAS_MINT_NS_V1,
 };
 use darkfi_sdk::{
     crypto::{
-        constants::MERKLE_DEPTH, contract_id::MONEY_CONTRACT_ID, Keypair, MerkleNode, PublicKey,
-        DARK_TOKEN_ID,
+        constants::MERKLE_DEPTH, contract_id::MONEY_CONTRACT_ID, note::AeadEncryptedNote,
+        poseidon_hash, Coin, Keypair, MerkleNode, MerkleTree, Nullifier, PublicKey, DARK_TOKEN_ID,
     },
-    incrementalmerkletree::bridgetree::BridgeTree,
+    incrementalmerkletree::{bridgetree::BridgeTree, Tree},
     pasta::{group::ff::PrimeField, pallas},
     tx::ContractCall,
 };
@@ -65,14 +72,9 @@ use url::Url;
 use darkfi::{
     async_daemonize, cli_desc,
     consensus::{
-        constants::{
-            MAINNET_BOOTSTRAP_TIMESTAMP, MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP,
-            MAINNET_INITIAL_DISTRIBUTION, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
-            TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION,
-        },
-        proto::{ProtocolSync, ProtocolTx},
+        proto::{ProtocolSync, ProtocolTx, TxInventory},
         task::block_sync_task,
-        ValidatorState, ValidatorStatePtr,
+        ChainParams, ValidatorState, ValidatorStatePtr,
     },
     net,
     net::P2pPtr,
@@ -107,7 +109,7 @@ struct Args {
     config: Option<String>,
 
     #[structopt(long, default_value = "testnet")]
-    /// Chain to use (testnet, mainnet)
+    /// Chain to use (testnet, mainnet, devnet)
     chain: String,
 
     #[structopt(long, default_value = "~/.config/darkfi/faucetd_wallet.db")]
@@ -181,6 +183,18 @@ struct Args {
 
 type ProvingKeyMap = Arc<RwLock<HashMap<[u8; 32], Vec<(String, ProvingKey, ZkBinary)>>>>;
 
+/// Native token coins owned by the faucet, used to pay transaction fees.
+/// They are found by scanning the finalized blocks of the synced blockchain,
+/// so the faucet must be funded with a transfer to its address.
+struct FeeCoins {
+    /// Merkle tree of all the coins in the money contract
+    tree: MerkleTree,
+    /// Slot of the last scanned block
+    last_scanned_slot: u64,
+    /// Unspent coins owned by the faucet
+    coins: Vec<OwnCoin>,
+}
+
 pub struct Faucetd {
     synced: Mutex<bool>, // AtomicBool is weird in Arc
     sync_p2p: P2pPtr,
@@ -192,6 +206,7 @@ pub struct Faucetd {
     airdrop_limit: u64,
     airdrop_map: Arc<Mutex<HashMap<[u8; 32], i64>>>,
     proving_keys: ProvingKeyMap,
+    fee_coins: Mutex<FeeCoins>,
 }
 
 #[async_trait]
@@ -244,8 +259,14 @@ impl Faucetd {
             return Err(Error::ZkasBincodeNotFound);
         };
 
+        let Some(fee_zkbytes) = db_handle.get(serialize(&MONEY_CONTRACT_ZKAS_FEE_NS_V1))? else {
+            error!("{} zkas bincode not found in sled database", MONEY_CONTRACT_ZKAS_FEE_NS_V1);
+            return Err(Error::ZkasBincodeNotFound);
+        };
+
         let (mint_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&mint_zkbytes)?;
         let (burn_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&burn_zkbytes)?;
+        let (fee_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&fee_zkbytes)?;
 
         let k = 13;
         let mint_zkbin = ZkBinary::decode(&mint_zkbin)?;
@@ -254,15 +275,21 @@ impl Faucetd {
         let burn_zkbin = ZkBinary::decode(&burn_zkbin)?;
         let burn_circuit = ZkCircuit::new(empty_witnesses(&burn_zkbin), burn_zkbin.clone());
 
+        let fee_zkbin = ZkBinary::decode(&fee_zkbin)?;
+        let fee_circuit = ZkCircuit::new(empty_witnesses(&fee_zkbin), fee_zkbin.clone());
+
         info!("Creating mint circuit proving key");
         let mint_provingkey = ProvingKey::build(k, &mint_circuit);
         info!("Creating burn circuit proving key");
         let burn_provingkey = ProvingKey::build(k, &burn_circuit);
+        info!("Creating fee circuit proving key");
+        let fee_provingkey = ProvingKey::build(k, &fee_circuit);
 
         {
             let provingkeys = vec![
                 (MONEY_CONTRACT_ZKAS_MINT_NS_V1.to_string(), mint_provingkey, mint_zkbin),
                 (MONEY_CONTRACT_ZKAS_BURN_NS_V1.to_string(), burn_provingkey, burn_zkbin),
+                (MONEY_CONTRACT_ZKAS_FEE_NS_V1.to_string(), fee_provingkey, fee_zkbin),
             ];
 
             let mut proving_keys_w = proving_keys.write().await;
@@ -284,6 +311,11 @@ impl Faucetd {
             airdrop_limit: limit,
             airdrop_map: Arc::new(Mutex::new(HashMap::new())),
             proving_keys,
+            fee_coins: Mutex::new(FeeCoins {
+                tree: MerkleTree::new(100),
+                last_scanned_slot: 0,
+                coins: vec![],
+            }),
         };
 
         Ok(faucetd)
@@ -362,6 +394,138 @@ impl Faucetd {
         Ok(keypair)
     }
 
+    /// Scan the finalized blocks after the last scanned slot for coins sent
+    /// to the faucet, and drop the faucet coins spent in them.
+    async fn scan_fee_coins(&self, fee_coins: &mut FeeCoins) -> Result<()> {
+        let blockchain = { self.validator_state.read().await.blockchain.clone() };
+        let cid = *MONEY_CONTRACT_ID;
+
+        loop {
+            let blocks = blockchain.get_blocks_after(fee_coins.last_scanned_slot, 100)?;
+            let Some(last) = blocks.last() else { break };
+            fee_coins.last_scanned_slot = last.header.slot;
+
+            for tx in blocks.iter().flat_map(|x| x.txs.iter()) {
+                let mut nullifiers: Vec<Nullifier> = vec![];
+                let mut outputs: Vec<(Coin, AeadEncryptedNote)> = vec![];
+
+                // Coins are appended to the Merkle tree in call order, the same
+                // way the money contract does.
+                for call in tx.calls.iter().filter(|x| x.contract_id == cid) {
+                    match MoneyFunction::try_from(call.data[0])? {
+                        MoneyFunction::TransferV1 | MoneyFunction::OtcSwapV1 => {
+                            let params: MoneyTransferParamsV1 = deserialize(&call.data[1..])?;
+                            nullifiers.extend(params.inputs.iter().map(|x| x.nullifier));
+                            outputs.extend(params.outputs.into_iter().map(|x| (x.coin, x.note)));
+                        }
+                        MoneyFunction::MintV1 => {
+                            let params: MoneyMintParamsV1 = deserialize(&call.data[1..])?;
+                            outputs.push((params.output.coin, params.output.note));
+                        }
+                        MoneyFunction::FeeV1 => {
+                            let params: MoneyFeeParamsV1 = deserialize(&call.data[1..])?;
+                            nullifiers.push(params.nullifier);
+                            outputs.push((params.coin, params.note));
+                        }
+                        MoneyFunction::StakeV1 => {
+                            let params: MoneyStakeParamsV1 = deserialize(&call.data[1..])?;
+                            nullifiers.push(params.input.nullifier);
+                        }
+                        MoneyFunction::UnstakeV1 => {
+                            let params: MoneyUnstakeParamsV1 = deserialize(&call.data[1..])?;
+                            outputs.push((params.output.coin, params.output.note));
+                        }
+                        _ => {}
+                    }
+                }
+
+                for (coin, note) in outputs {
+                    fee_coins.tree.append(&MerkleNode::from(coin.inner()));
+
+                    let Ok(note) = note.decrypt::<MoneyNote>(&self.keypair.secret) else {
+                        continue
+                    };
+
+                    info!("Found faucet coin {:?} of value {}", coin, note.value);
+                    let secret = self.keypair.secret;
+                    fee_coins.coins.push(OwnCoin {
+                        coin,
+                        nullifier: Nullifier::from(poseidon_hash([secret.inner(), note.serial])),
+                        note,
+                        secret,
+                        leaf_position: fee_coins.tree.witness().unwrap(),
+                    });
+                }
+
+                fee_coins.coins.retain(|x| !nullifiers.contains(&x.nullifier));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Build a `Money::FeeV1` call paying the given fee with a native token
+    /// coin of the faucet. The used coin is taken out of the faucet coins.
+    async fn build_fee_call(&self, fee: u64) -> std::result::Result<FeeCallDebris, RpcError> {
+        let mut fee_coins = self.fee_coins.lock().await;
+        if let Err(e) = self.scan_fee_coins(&mut fee_coins).await {
+            error!("Failed scanning the blockchain for faucet coins: {}", e);
+            return Err(RpcError::InternalError)
+        }
+
+        let Some(index) = fee_coins.coins.iter().position(|x| {
+            x.note.token_id == *DARK_TOKEN_ID &&
+                x.note.spend_hook == pallas::Base::zero() &&
+                x.note.user_data == pallas::Base::zero() &&
+                x.note.value >= fee
+        }) else {
+            error!("No faucet coin found to pay a fee of {}", fee);
+            return Err(RpcError::NoFeeCoin)
+        };
+
+        let (fee_zkbin, fee_pk) = {
+            let proving_keys_r = self.proving_keys.read().await;
+            let Some(fee_data) = proving_keys_r
+                .get(&MONEY_CONTRACT_ID.to_bytes())
+                .and_then(|x| x.iter().find(|x| x.0 == MONEY_CONTRACT_ZKAS_FEE_NS_V1))
+            else {
+                error!("{} proof data not found in vector", MONEY_CONTRACT_ZKAS_FEE_NS_V1);
+                return Err(RpcError::InternalError)
+            };
+
+            (fee_data.2.clone(), fee_data.1.clone())
+        };
+
+        let builder = FeeCallBuilder {
+            coin: fee_coins.coins[index].clone(),
+            fee,
+            min_fee: fee,
+            change_public: self.keypair.public,
+            tree: fee_coins.tree.clone(),
+            fee_zkbin,
+            fee_pk,
+        };
+
+        match builder.build() {
+            Ok(v) => {
+                fee_coins.coins.remove(index);
+                Ok(v)
+            }
+            Err(e) => {
+                error!("Failed to build fee call params: {}", e);
+                Err(RpcError::InternalError)
+            }
+        }
+    }
+
+    /// Put the coin of a fee call that didn't make it to the network back
+    /// into the faucet coins.
+    async fn restore_fee_coin(&self, fee_debris: Option<FeeCallDebris>) {
+        if let Some(fee_debris) = fee_debris {
+            self.fee_coins.lock().await.coins.push(fee_debris.spent_coin);
+        }
+    }
+
     // RPCAPI:
     // Processes a native token airdrop request and airdrops requested amount to address.
     // Returns the transaction ID upon success.
@@ -469,20 +633,42 @@ impl Faucetd {
         let calls = vec![ContractCall { contract_id: cid, data }];
         let proofs = vec![debris.proofs];
         let mut tx = Transaction { calls, proofs, signatures: vec![] };
+
+        // Pay the chain minimum fee with a faucet coin. The fee call has to come
+        // first, and be in place before any signature is created, since they sign
+        // all calls.
+        let min_tx_fee = self.validator_state.read().await.consensus.params.min_tx_fee;
+        let fee_debris = if min_tx_fee > 0 {
+            let fee_debris = match self.build_fee_call(min_tx_fee).await {
+                Ok(v) => v,
+                Err(e) => return server_error(e, id),
+            };
+
+            fee_debris.prepend_call(&mut tx).unwrap();
+            Some(fee_debris)
+        } else {
+            None
+        };
+
         let sigs = tx.create_sigs(&mut OsRng, &debris.signature_secrets).unwrap();
         tx.signatures = vec![sigs];
+        if let Some(fee_debris) = &fee_debris {
+            fee_debris.prepend_sigs(&mut tx).unwrap();
+        }
 
         // Safety check to see if the transaction is actually valid.
         if let Err(e) =
             self.validator_state.read().await.verify_transactions(&[tx.clone()], false).await
         {
             error!("airdrop(): Failed to verify transaction before broadcasting: {}", e);
+            self.restore_fee_coin(fee_debris).await;
             return JsonError::new(InternalError, None, id).into()
         }
 
         // Broadcast transaction to the network.
         if let Err(e) = self.sync_p2p.broadcast(tx.clone()).await {
             error!("airdrop(): Failed broadcasting transaction: {}", e);
+            self.restore_fee_coin(fee_debris).await;
             return JsonError::new(InternalError, None, id).into()
         };
 
@@ -560,23 +746,12 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         Path::new(expand_path(&args.database)?.to_str().unwrap()).join(args.chain.clone());
     let sled_db = sled::open(&db_path)?;
 
-    // Initialize validator state
-    let (bootstrap_ts, genesis_ts, genesis_data, initial_distribution) = match args.chain.as_str() {
-        "mainnet" => (
-            *MAINNET_BOOTSTRAP_TIMESTAMP,
-            *MAINNET_GENESIS_TIMESTAMP,
-            *MAINNET_GENESIS_HASH_BYTES,
-            *MAINNET_INITIAL_DISTRIBUTION,
-        ),
-        "testnet" => (
-            *TESTNET_BOOTSTRAP_TIMESTAMP,
-            *TESTNET_GENESIS_TIMESTAMP,
-            *TESTNET_GENESIS_HASH_BYTES,
-            *TESTNET_INITIAL_DISTRIBUTION,
-        ),
-        x => {
-            error!("Unsupported chain `{}`", x);
-            return Err(Error::UnsupportedChain)
+    // Initialize chain parameters
+    let params = match ChainParams::from_chain(&args.chain) {
+        Ok(params) => params,
+        Err(e) => {
+            error!("Unsupported chain `{}`", args.chain);
+            return Err(e)
         }
     };
 
@@ -594,18 +769,8 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
     }
 
     // Initialize validator state
-    let state = ValidatorState::new(
-        &sled_db,
-        bootstrap_ts,
-        genesis_ts,
-        genesis_data,
-        initial_distribution,
-        wallet.clone(),
-        faucet_pubkeys,
-        false,
-        false,
-    )
-    .await?;
+    let state =
+        ValidatorState::new(&sled_db, params, wallet.clone(), faucet_pubkeys, false, false).await?;
 
     // P2P network. The faucet doesn't participate in consensus, so we only
     // build the sync protocol.
@@ -641,6 +806,18 @@ async fn realmain(args: Args, ex: Arc<smol::Executor<'_>>) -> Result<()> {
         })
         .await;
 
+    let tx_inventory = TxInventory::new(state.clone());
+    registry
+        .register_with_requirements(
+            net::SESSION_ALL,
+            net::ProtocolInventory::<Transaction>::REQUIREMENTS,
+            move |channel, p2p| {
+                let store = tx_inventory.clone();
+                async move { net::ProtocolInventory::<Transaction>::init(channel, p2p, store).await }
+            },
+        )
+        .await;
+
     let airdrop_timeout = args.airdrop_timeout;
     let airdrop_limit = decode_base10(&args.airdrop_limit, 8, true)?;
 
@@ -103,4 +103,8 @@ impl net::Message for Privmsg {
     fn name() -> &'static str {
         "privmsg"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
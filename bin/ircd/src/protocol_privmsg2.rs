@@ -366,12 +366,20 @@ impl net::Message for Event {
     fn name() -> &'static str {
         "event"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 impl net::Message for Inv {
     fn name() -> &'static str {
         "inv"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 impl net::Message for SyncEvent {
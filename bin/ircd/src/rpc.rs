@@ -46,6 +46,9 @@ impl RequestHandler for JsonRpcInterface {
         match req.method.as_str() {
             Some("ping") => self.pong(req.id, req.params).await,
             Some("get_info") => self.get_info(req.id, req.params).await,
+            Some("get_banlist") => self.get_banlist(req.id, req.params).await,
+            Some("ban") => self.ban(req.id, req.params).await,
+            Some("unban") => self.unban(req.id, req.params).await,
             Some(_) | None => JsonError::new(ErrorCode::MethodNotFound, None, req.id).into(),
         }
     }
@@ -68,4 +71,50 @@ impl JsonRpcInterface {
         let resp = self.p2p.get_info().await;
         JsonResponse::new(resp, id).into()
     }
+
+    // RPCAPI:
+    // Retrieves the active bans of the P2P network, along with the UNIX
+    // timestamp each ban expires at.
+    // --> {"jsonrpc": "2.0", "method": "get_banlist", "params": [], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": [{"host": "1.2.3.4", "expiry": 1680000000}], "id": 42}
+    async fn get_banlist(&self, id: Value, _params: Value) -> JsonResult {
+        let resp = self.p2p.get_banlist_info().await;
+        JsonResponse::new(resp, id).into()
+    }
+
+    // RPCAPI:
+    // Bans a host for given amount of seconds, disconnecting any of its
+    // open channels. Returns `true` on success.
+    // --> {"jsonrpc": "2.0", "method": "ban", "params": ["1.2.3.4", 86400], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 42}
+    async fn ban(&self, id: Value, params: Value) -> JsonResult {
+        let Some(params) = params.as_array() else {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        };
+        if params.len() != 2 || !params[0].is_string() || !params[1].is_u64() {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        let host = params[0].as_str().unwrap();
+        let duration = params[1].as_u64().unwrap();
+        self.p2p.ban_host(host, duration).await;
+        JsonResponse::new(json!(true), id).into()
+    }
+
+    // RPCAPI:
+    // Lifts the ban of a host. Returns `true` if the host was banned.
+    // --> {"jsonrpc": "2.0", "method": "unban", "params": ["1.2.3.4"], "id": 42}
+    // <-- {"jsonrpc": "2.0", "result": true, "id": 42}
+    async fn unban(&self, id: Value, params: Value) -> JsonResult {
+        let Some(params) = params.as_array() else {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        };
+        if params.len() != 1 || !params[0].is_string() {
+            return JsonError::new(ErrorCode::InvalidParams, None, id).into()
+        }
+
+        let host = params[0].as_str().unwrap();
+        let unbanned = self.p2p.unban_host(host).await;
+        JsonResponse::new(json!(unbanned), id).into()
+    }
 }
@@ -28,6 +28,10 @@ impl net::Message for DchatMsg {
     fn name() -> &'static str {
         "DchatMsg"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 #[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
@@ -43,6 +43,10 @@ impl net::Message for Debugmsg {
     fn name() -> &'static str {
         "debugmsg"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 pub struct SeenDebugmsgIds {
@@ -16,6 +16,8 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::ops::Bound;
+
 use darkfi_serial::{deserialize, serialize};
 
 use crate::{
@@ -31,7 +33,7 @@ const SLED_BLOCK_ORDER_TREE: &[u8] = b"_block_order";
 /// The `HeaderStore` is a `sled` tree storing all the blockchain's blocks' headers
 /// where the key is the headers' hash, and value is the serialized header.
 #[derive(Clone)]
-pub struct HeaderStore(sled::Tree);
+pub struct HeaderStore(pub(crate) sled::Tree);
 
 impl HeaderStore {
     /// Opens a new or existing `HeaderStore` on the given sled database.
@@ -112,12 +114,30 @@ impl HeaderStore {
 
         Ok(headers)
     }
+
+    /// Remove a slice of header hashes from the headerstore.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of hashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
 }
 
 /// The `BlockStore` is a `sled` tree storing all the blockchain's blocks
 /// where the key is the blocks' hash, and value is the serialized block.
 #[derive(Clone)]
-pub struct BlockStore(sled::Tree);
+pub struct BlockStore(pub(crate) sled::Tree);
 
 impl BlockStore {
     /// Opens a new or existing `BlockStore` on the given sled database.
@@ -201,13 +221,31 @@ impl BlockStore {
 
         Ok(blocks)
     }
+
+    /// Remove a slice of block hashes from the blockstore.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of hashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
 }
 
 /// The `BlockOrderStore` is a `sled` tree storing the order of the
 /// blockchain's slots, where the key is the slot uid, and the value is
 /// the blocks' hash. [`BlockStore`] can be queried with this hash.
 #[derive(Clone)]
-pub struct BlockOrderStore(sled::Tree);
+pub struct BlockOrderStore(pub(crate) sled::Tree);
 
 impl BlockOrderStore {
     /// Opens a new or existing `BlockOrderStore` on the given sled database.
@@ -309,6 +347,11 @@ impl BlockOrderStore {
         Ok(ret)
     }
 
+    /// Retrieve the number of blocks after given slot.
+    pub fn count_after(&self, slot: u64) -> usize {
+        self.0.range((Bound::Excluded(slot.to_be_bytes()), Bound::Unbounded)).count()
+    }
+
     /// Fetch the last blockhash in the tree, based on the `Ord`
     /// implementation for `Vec<u8>`. This should not be able to
     /// fail because we initialize the store with the genesis block.
@@ -332,4 +375,22 @@ impl BlockOrderStore {
     pub fn is_empty(&self) -> bool {
         self.0.len() == 0
     }
+
+    /// Remove a slice of slots from the blockorderstore.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, slots: &[u64]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(slots))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of slots from the store.
+    pub fn remove_batch(&self, slots: &[u64]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for slot in slots {
+            batch.remove(&slot.to_be_bytes());
+        }
+
+        batch
+    }
 }
@@ -20,12 +20,12 @@ use std::io::Cursor;
 
 use darkfi_sdk::crypto::ContractId;
 use darkfi_serial::{deserialize, serialize};
-use log::{debug, error};
+use log::{debug, error, info};
 
 use crate::{
     blockchain::SledDbOverlayPtr,
     runtime::vm_runtime::SMART_CONTRACT_ZKAS_DB_NAME,
-    zk::{VerifyingKey, ZkCircuit},
+    zk::{empty_witnesses, VerifyingKey, ZkCircuit},
     zkas::ZkBinary,
     Error, Result,
 };
@@ -70,6 +70,18 @@ impl WasmStoreOverlay {
         Ok(Self(overlay))
     }
 
+    /// Fetches the bincode for a given ContractId
+    /// Returns an error if the bincode is not found.
+    pub fn get(&self, contract_id: ContractId) -> Result<Vec<u8>> {
+        if let Some(bincode) =
+            self.0.lock().unwrap().get(SLED_BINCODE_TREE, &serialize(&contract_id))?
+        {
+            return Ok(bincode.to_vec())
+        }
+
+        Err(Error::WasmBincodeNotFound)
+    }
+
     /// Inserts or replaces the bincode for a given ContractId
     pub fn insert(&self, contract_id: ContractId, bincode: &[u8]) -> Result<()> {
         if let Err(e) =
@@ -103,6 +115,20 @@ impl ContractStateStore {
         Ok(Self(tree))
     }
 
+    /// Retrieve the names of all sled trees holding contract data: this
+    /// tree, the wasm bincodes tree and all contract state trees.
+    pub fn get_all_trees(&self) -> Result<Vec<Vec<u8>>> {
+        let mut ret = vec![SLED_CONTRACTS_TREE.to_vec(), SLED_BINCODE_TREE.to_vec()];
+
+        for record in self.0.iter() {
+            let (_, state_pointers) = record?;
+            let state_pointers: Vec<[u8; 32]> = deserialize(&state_pointers)?;
+            ret.extend(state_pointers.iter().map(|x| x.to_vec()));
+        }
+
+        Ok(ret)
+    }
+
     /// Do a lookup of an existing contract state. In order to succeed, the
     /// state must have been previously initialized with `init()`. If the
     /// state has been found, a handle to it will be returned. Otherwise, we
@@ -282,4 +308,67 @@ impl ContractStateStoreOverlay {
         lock.open_tree(&ptr)?;
         Ok(ptr)
     }
+
+    /// Abstraction function for fetching a `ZkBinary` and its respective `VerifyingKey`
+    /// from a contract's zkas overlay tree.
+    pub fn get_zkas(
+        &self,
+        contract_id: &ContractId,
+        zkas_ns: &str,
+    ) -> Result<(ZkBinary, VerifyingKey)> {
+        debug!(target: "blockchain::contractstoreoverlay", "Looking up \"{}:{}\" zkas circuit & vk", contract_id, zkas_ns);
+
+        let zkas_tree = self.lookup(contract_id, SMART_CONTRACT_ZKAS_DB_NAME)?;
+
+        let Some(zkas_bytes) = self.0.lock().unwrap().get(&zkas_tree, &serialize(&zkas_ns))? else {
+            return Err(Error::ZkasBincodeNotFound)
+        };
+
+        // If anything in this function panics, that means corrupted data managed
+        // to get into this sled tree. This should not be possible.
+        let (zkbin, vkbin): (Vec<u8>, Vec<u8>) = deserialize(&zkas_bytes).unwrap();
+
+        // The first vec is the compiled zkas binary
+        let zkbin = ZkBinary::decode(&zkbin).unwrap();
+
+        // The second one is the serialized VerifyingKey for it
+        let mut vk_buf = Cursor::new(vkbin);
+        let vk = VerifyingKey::read::<Cursor<Vec<u8>>, ZkCircuit>(&mut vk_buf).unwrap();
+
+        Ok((zkbin, vk))
+    }
+
+    /// Insert a zkas bincode and its `VerifyingKey` into a contract's zkas overlay
+    /// tree, initializing the tree if needed. If the same bincode already exists
+    /// under its namespace, nothing is written.
+    pub fn insert_zkas(&self, contract_id: &ContractId, zkas_bincode: &[u8]) -> Result<()> {
+        // Make sure that we're actually working on legitimate bincode.
+        let zkbin = ZkBinary::decode(zkas_bincode)?;
+
+        let zkas_tree = match self.lookup(contract_id, SMART_CONTRACT_ZKAS_DB_NAME) {
+            Ok(v) => v,
+            Err(_) => self.init(contract_id, SMART_CONTRACT_ZKAS_DB_NAME)?,
+        };
+
+        let key = serialize(&zkbin.namespace);
+        let mut lock = self.0.lock().unwrap();
+        if let Some(bytes) = lock.get(&zkas_tree, &key)? {
+            let (existing_zkbin, _): (Vec<u8>, Vec<u8>) = deserialize(&bytes)?;
+            if existing_zkbin == zkas_bincode {
+                debug!(target: "blockchain::contractstoreoverlay", "Existing zkas bincode is the same. Skipping.");
+                return Ok(())
+            }
+        }
+
+        info!(target: "blockchain::contractstoreoverlay", "Creating VerifyingKey for {} zkas circuit", zkbin.namespace);
+        let witnesses = empty_witnesses(&zkbin);
+        let circuit = ZkCircuit::new(witnesses, zkbin);
+        let vk = VerifyingKey::build(13, &circuit);
+        let mut vk_buf = vec![];
+        vk.write(&mut vk_buf)?;
+
+        lock.insert(&zkas_tree, &key, &serialize(&(zkas_bincode.to_vec(), vk_buf)))?;
+
+        Ok(())
+    }
 }
@@ -16,14 +16,19 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::sync::{Arc, Mutex};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 
-use log::debug;
+use log::{debug, info};
+use sled::{transaction::TransactionResult, Transactional};
 
+use darkfi_sdk::crypto::Nullifier;
 use darkfi_serial::serialize;
 
 use crate::{
-    consensus::{Block, BlockInfo, SlotCheckpoint},
+    consensus::{block::SyncHeader, Block, BlockInfo, SlotCheckpoint},
     tx::Transaction,
     util::time::Timestamp,
     Result,
@@ -36,7 +41,19 @@ pub mod slot_checkpoint_store;
 pub use slot_checkpoint_store::SlotCheckpointStore;
 
 pub mod tx_store;
-pub use tx_store::{PendingTxOrderStore, PendingTxStore, TxStore};
+pub use tx_store::{
+    proven_fee, PendingTxOrderStore, PendingTxStore, TxLocation, TxLocationStore, TxReceipt,
+    TxReceiptStore, TxStore,
+};
+
+pub mod nullifier_store;
+pub use nullifier_store::{revealed_nullifiers, NullifierStore};
+
+pub mod snapshot;
+pub use snapshot::{export_snapshot, import_snapshot_headers, SnapshotReader};
+
+pub mod state_diff;
+pub use state_diff::{StateChange, StateDiff, StateDiffStore, StateOverlay};
 
 pub mod contract_store;
 pub use contract_store::{
@@ -58,6 +75,14 @@ pub struct Blockchain {
     pub slot_checkpoints: SlotCheckpointStore,
     /// Transactions sled tree
     pub transactions: TxStore,
+    /// Transaction locations sled tree
+    pub tx_locations: TxLocationStore,
+    /// Transaction receipts sled tree
+    pub tx_receipts: TxReceiptStore,
+    /// Revealed nullifiers sled tree
+    pub nullifiers: NullifierStore,
+    /// Per-block contract state diffs sled tree
+    pub state_diffs: StateDiffStore,
     /// Pending transactions sled tree
     pub pending_txs: PendingTxStore,
     /// Pending transactions order sled tree
@@ -76,23 +101,39 @@ impl Blockchain {
         let order = BlockOrderStore::new(db, genesis_ts, genesis_data)?;
         let slot_checkpoints = SlotCheckpointStore::new(db)?;
         let transactions = TxStore::new(db)?;
+        let tx_locations = TxLocationStore::new(db)?;
+        let tx_receipts = TxReceiptStore::new(db)?;
+        let nullifiers = NullifierStore::new(db)?;
+        let state_diffs = StateDiffStore::new(db)?;
         let pending_txs = PendingTxStore::new(db)?;
         let pending_txs_order = PendingTxOrderStore::new(db)?;
         let contracts = ContractStateStore::new(db)?;
         let wasm_bincode = WasmStore::new(db)?;
 
-        Ok(Self {
+        let blockchain = Self {
             sled_db: db.clone(),
             headers,
             blocks,
             order,
             slot_checkpoints,
             transactions,
+            tx_locations,
+            tx_receipts,
+            nullifiers,
+            state_diffs,
             pending_txs,
             pending_txs_order,
             contracts,
             wasm_bincode,
-        })
+        };
+
+        // Databases created before the transaction indexes existed
+        // get indexed once.
+        if blockchain.tx_locations.is_empty() && !blockchain.transactions.is_empty() {
+            blockchain.reindex_txs()?;
+        }
+
+        Ok(blockchain)
     }
 
     /// Insert a given slice of [`BlockInfo`] into the blockchain database.
@@ -106,7 +147,7 @@ impl Blockchain {
         // TODO: Make db writes here completely atomic
         for block in blocks {
             // Store transactions
-            self.transactions.insert(&block.txs)?;
+            let tx_hashes = self.transactions.insert(&block.txs)?;
 
             // Store header
             self.headers.insert(&[block.header.clone()])?;
@@ -118,11 +159,70 @@ impl Blockchain {
 
             // Store block order
             self.order.insert(&[block.header.slot], &[blockhash[0]])?;
+
+            // Index transactions
+            self.index_txs(block, &blockhash[0], &tx_hashes)?;
         }
 
         Ok(ret)
     }
 
+    /// Index the location of the transactions of given block.
+    fn index_txs(
+        &self,
+        block: &BlockInfo,
+        blockhash: &blake3::Hash,
+        tx_hashes: &[blake3::Hash],
+    ) -> Result<()> {
+        let locations: Vec<TxLocation> = (0..tx_hashes.len())
+            .map(|index| TxLocation {
+                slot: block.header.slot,
+                block: *blockhash,
+                index: index as u32,
+            })
+            .collect();
+
+        self.tx_locations.insert(tx_hashes, &locations)
+    }
+
+    /// Index the nullifiers revealed by given transactions, as reported
+    /// by their receipts.
+    fn index_nullifiers(&self, tx_hashes: &[blake3::Hash], receipts: &[TxReceipt]) -> Result<()> {
+        let mut nullifiers = vec![];
+        for (tx_hash, receipt) in tx_hashes.iter().zip(receipts.iter()) {
+            for nullifier in revealed_nullifiers(receipt) {
+                nullifiers.push((nullifier, *tx_hash));
+            }
+        }
+
+        self.nullifiers.insert(&nullifiers)
+    }
+
+    /// Rebuild the transaction indexes from all stored blocks and receipts.
+    fn reindex_txs(&self) -> Result<()> {
+        info!(target: "blockchain", "Indexing stored transactions");
+        for (_, blockhash) in self.order.get_all()? {
+            let block = &self.get_blocks_by_hash(&[blockhash])?[0];
+            let tx_hashes: Vec<blake3::Hash> =
+                block.txs.iter().map(|tx| blake3::hash(&serialize(tx))).collect();
+            self.index_txs(block, &blockhash, &tx_hashes)?;
+
+            // Receipts are missing for blocks imported without execution
+            let mut hashes = vec![];
+            let mut receipts = vec![];
+            for (tx_hash, receipt) in tx_hashes.iter().zip(self.tx_receipts.get(&tx_hashes, false)?)
+            {
+                if let Some(receipt) = receipt {
+                    hashes.push(*tx_hash);
+                    receipts.push(receipt);
+                }
+            }
+            self.index_nullifiers(&hashes, &receipts)?;
+        }
+
+        Ok(())
+    }
+
     /// Check if the given [`BlockInfo`] is in the database and all trees.
     pub fn has_block(&self, block: &BlockInfo) -> Result<bool> {
         let blockhash = match self.order.get(&[block.header.slot], true) {
@@ -179,6 +279,52 @@ impl Blockchain {
         self.get_blocks_by_hash(&hashes)
     }
 
+    /// Retrieve the headers of n blocks after given start slot, along with
+    /// their block hashes.
+    pub fn get_sync_headers_after(&self, slot: u64, n: u64) -> Result<Vec<SyncHeader>> {
+        debug!(target: "blockchain", "get_sync_headers_after(): {} -> {}", slot, n);
+        let hashes = self.order.get_after(slot, n)?;
+        let blocks = self.blocks.get(&hashes, true)?;
+
+        let mut ret = Vec::with_capacity(hashes.len());
+        for block in blocks {
+            // Since we used strict get, its safe to unwrap here
+            let block = block.unwrap();
+            let headers = self.headers.get(&[block.header], true)?;
+            let header = headers[0].clone().unwrap();
+            ret.push(SyncHeader { header, block });
+        }
+
+        Ok(ret)
+    }
+
+    /// Retrieve the [`TxLocation`]s of given transaction hashes.
+    /// Does not fail if any of them are not found.
+    pub fn get_tx_locations(&self, tx_hashes: &[blake3::Hash]) -> Result<Vec<Option<TxLocation>>> {
+        debug!(target: "blockchain", "get_tx_locations(): {:?}", tx_hashes);
+        self.tx_locations.get(tx_hashes, false)
+    }
+
+    /// Retrieve the [`TxReceipt`]s of given transaction hashes.
+    /// Does not fail if any of them are not found.
+    pub fn get_tx_receipts(&self, tx_hashes: &[blake3::Hash]) -> Result<Vec<Option<TxReceipt>>> {
+        debug!(target: "blockchain", "get_tx_receipts(): {:?}", tx_hashes);
+        self.tx_receipts.get(tx_hashes, false)
+    }
+
+    /// Retrieve the hashes of the transactions revealing given nullifiers.
+    /// Does not fail if any of them are not found.
+    pub fn get_nullifier_txs(&self, nullifiers: &[Nullifier]) -> Result<Vec<Option<blake3::Hash>>> {
+        debug!(target: "blockchain", "get_nullifier_txs(): {:?}", nullifiers);
+        self.nullifiers.get(nullifiers, false)
+    }
+
+    /// Retrieve the number of confirmations of a block in given slot,
+    /// counting the block itself and all blocks after it.
+    pub fn confirmations(&self, slot: u64) -> usize {
+        self.order.count_after(slot) + 1
+    }
+
     /// Retrieve stored blocks count
     pub fn len(&self) -> usize {
         self.order.len()
@@ -238,6 +384,145 @@ impl Blockchain {
         Ok(!vec.is_empty())
     }
 
+    /// Insert the contract state diffs of given blockhashes.
+    pub fn add_state_diffs(&self, hashes: &[blake3::Hash], diffs: &[StateDiff]) -> Result<()> {
+        self.state_diffs.insert(hashes, diffs)
+    }
+
+    /// Insert the receipts of given transaction hashes, indexing the
+    /// nullifiers they report.
+    pub fn add_tx_receipts(
+        &self,
+        tx_hashes: &[blake3::Hash],
+        receipts: &[TxReceipt],
+    ) -> Result<()> {
+        self.tx_receipts.insert(tx_hashes, receipts)?;
+        self.index_nullifiers(tx_hashes, receipts)
+    }
+
+    /// Roll back the last `n` blocks, reverting their contract state diffs
+    /// and removing them along with their transactions, indexes and the slot
+    /// checkpoints from their first slot onwards. The genesis block is never
+    /// removed, and all diffs have to be present before anything is touched.
+    /// Returns the removed blocks, last first.
+    ///
+    /// All writes are applied in a single transaction. Trees created by the
+    /// removed blocks get dropped afterwards, as that can't be part of it.
+    pub fn rollback(&self, n: u64) -> Result<Vec<BlockInfo>> {
+        let mut hashes = vec![];
+        for (slot, hash) in self.order.get_all()?.into_iter().rev().take(n as usize) {
+            if slot == 0 {
+                break
+            }
+            hashes.push((slot, hash));
+        }
+
+        let Some((first_slot, _)) = hashes.last() else { return Ok(vec![]) };
+
+        let slots: Vec<u64> = hashes.iter().map(|x| x.0).collect();
+        let block_hashes: Vec<blake3::Hash> = hashes.iter().map(|x| x.1).collect();
+        let blocks = self.get_blocks_by_hash(&block_hashes)?;
+        let diffs = self.state_diffs.get(&block_hashes, true)?;
+
+        // Contract state trees, reverted last block first
+        let mut batches = BTreeMap::new();
+        let mut created_trees = vec![];
+        for ((slot, hash), diff) in hashes.iter().zip(diffs) {
+            info!(target: "blockchain", "Rolling back block {} of slot {}", hash, slot);
+            let diff = diff.unwrap();
+            diff.revert_batches(&mut batches);
+            created_trees.extend(diff.created_trees);
+        }
+
+        let mut trees = vec![];
+        let mut tree_batches = vec![];
+        for (tree, batch) in batches {
+            trees.push(self.sled_db.open_tree(tree)?);
+            tree_batches.push(batch);
+        }
+
+        // Blocks, transactions and their indexes
+        let mut header_hashes = vec![];
+        let mut tx_hashes = vec![];
+        for block in &blocks {
+            header_hashes.push(block.header.headerhash());
+            tx_hashes.extend(block.txs.iter().map(|tx| blake3::hash(&serialize(tx))));
+        }
+        let nullifiers: Vec<Nullifier> = self
+            .tx_receipts
+            .get(&tx_hashes, false)?
+            .iter()
+            .flatten()
+            .flat_map(revealed_nullifiers)
+            .collect();
+
+        trees.extend([
+            self.nullifiers.0.clone(),
+            self.tx_receipts.0.clone(),
+            self.tx_locations.0.clone(),
+            self.transactions.0.clone(),
+            self.order.0.clone(),
+            self.blocks.0.clone(),
+            self.headers.0.clone(),
+            self.state_diffs.0.clone(),
+            self.slot_checkpoints.0.clone(),
+        ]);
+        tree_batches.extend([
+            self.nullifiers.remove_batch(&nullifiers),
+            self.tx_receipts.remove_batch(&tx_hashes),
+            self.tx_locations.remove_batch(&tx_hashes),
+            self.transactions.remove_batch(&tx_hashes),
+            self.order.remove_batch(&slots),
+            self.blocks.remove_batch(&block_hashes),
+            self.headers.remove_batch(&header_hashes),
+            self.state_diffs.remove_batch(&block_hashes),
+            self.slot_checkpoints.remove_from_batch(*first_slot)?,
+        ]);
+
+        let ret: TransactionResult<()> = trees.as_slice().transaction(|trees| {
+            for (tree, batch) in trees.iter().zip(tree_batches.iter()) {
+                tree.apply_batch(batch)?;
+            }
+            Ok(())
+        });
+        ret?;
+
+        for tree in created_trees {
+            self.sled_db.drop_tree(tree)?;
+        }
+
+        self.sled_db.flush()?;
+        Ok(blocks)
+    }
+
+    /// Retrieve the value a contract state key had right after the block
+    /// of given slot was applied, by reverting the diffs of later blocks.
+    pub fn get_state_at(&self, slot: u64, tree: &[u8], key: &[u8]) -> Result<Option<Vec<u8>>> {
+        // A missing tree is read as empty, since opening it would create it
+        let mut value = if self.sled_db.tree_names().iter().any(|name| name == tree) {
+            self.sled_db.open_tree(tree)?.get(key)?.map(|x| x.to_vec())
+        } else {
+            None
+        };
+
+        let hashes: Vec<blake3::Hash> = self
+            .order
+            .get_all()?
+            .into_iter()
+            .rev()
+            .take_while(|x| x.0 > slot)
+            .map(|x| x.1)
+            .collect();
+        for diff in self.state_diffs.get(&hashes, true)? {
+            let diff = diff.unwrap();
+            if let Some(change) = diff.changes.iter().find(|x| x.tree == tree && x.key == key) {
+                value = change.old.clone();
+            }
+        }
+
+        Ok(value)
+    }
+
     /// Insert a given slice of pending transactions into the blockchain database.
     /// On success, the function returns the transaction hashes in the same order
     /// as the input transactions.
@@ -289,14 +574,14 @@ impl Blockchain {
 }
 
 /// Atomic pointer to sled db overlay.
-pub type SledDbOverlayPtr = Arc<Mutex<sled_overlay::SledDbOverlay>>;
+pub type SledDbOverlayPtr = Arc<Mutex<StateOverlay>>;
 
 /// Atomic pointer to blockchain overlay.
 pub type BlockchainOverlayPtr = Arc<Mutex<BlockchainOverlay>>;
 
 /// Overlay structure over a [`Blockchain`] instance.
 pub struct BlockchainOverlay {
-    /// Main [`sled_overlay::SledDbOverlay`] to the sled db connection
+    /// Main [`StateOverlay`] to the sled db connection
     pub overlay: SledDbOverlayPtr,
     /// Contract states overlay
     pub contracts: ContractStateStoreOverlay,
@@ -307,10 +592,152 @@ pub struct BlockchainOverlay {
 impl BlockchainOverlay {
     /// Instantiate a new `BlockchainOverlay` over the given [`Blockchain`] instance.
     pub fn new(blockchain: &Blockchain) -> Result<BlockchainOverlayPtr> {
-        let overlay = Arc::new(Mutex::new(sled_overlay::SledDbOverlay::new(&blockchain.sled_db)));
+        let overlay = Arc::new(Mutex::new(StateOverlay::new(&blockchain.sled_db)));
         let contracts = ContractStateStoreOverlay::new(overlay.clone())?;
         let wasm_bincode = WasmStoreOverlay::new(overlay.clone())?;
 
         Ok(Arc::new(Mutex::new(Self { overlay, contracts, wasm_bincode })))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{
+        crypto::{ContractId, MONEY_CONTRACT_ID},
+        event::{ContractEvent, NULLIFIER_EVENT_TOPIC},
+        pasta::pallas,
+        tx::ContractCall,
+    };
+
+    use super::*;
+    use crate::consensus::{TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP};
+
+    #[test]
+    fn rollback_reverts_state() -> Result<()> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        let (_, genesis) = blockchain.last()?;
+
+        let existing = sled_db.open_tree(b"existing")?;
+        existing.insert(b"changed", "old")?;
+        existing.insert(b"removed", "old")?;
+
+        // Apply a block changing an existing tree and creating a new one
+        let mut overlay = StateOverlay::new(&sled_db);
+        overlay.open_tree(b"existing")?;
+        overlay.insert(b"existing", b"changed", b"new")?;
+        overlay.insert(b"existing", b"added", b"new")?;
+        overlay.remove(b"existing", b"removed")?;
+        overlay.open_tree(b"created")?;
+        overlay.insert(b"created", b"key", b"new")?;
+        let diff = overlay.diff()?;
+        overlay.apply()?;
+        assert_eq!(diff.created_trees, vec![b"created".to_vec()]);
+
+        let mut block = BlockInfo::default();
+        block.header.previous = genesis;
+        block.header.slot = 1;
+        let hash = blockchain.add(&[block])?[0];
+        blockchain.add_state_diffs(&[hash], &[diff])?;
+        let mut checkpoint = SlotCheckpoint::genesis_slot_checkpoint();
+        checkpoint.slot = 1;
+        blockchain.add_slot_checkpoints(&[checkpoint])?;
+
+        assert_eq!(blockchain.get_state_at(0, b"existing", b"changed")?, Some(b"old".to_vec()));
+        assert_eq!(blockchain.get_state_at(1, b"existing", b"changed")?, Some(b"new".to_vec()));
+
+        assert_eq!(blockchain.rollback(1)?.len(), 1);
+        assert_eq!(blockchain.last()?, (0, genesis));
+        assert!(!blockchain.slot_checkpoints.contains(1)?);
+        assert!(!blockchain.state_diffs.contains(&hash)?);
+
+        assert_eq!(&*existing.get(b"changed")?.unwrap(), b"old");
+        assert_eq!(&*existing.get(b"removed")?.unwrap(), b"old");
+        assert!(!existing.contains_key(b"added")?);
+        assert!(!sled_db.tree_names().iter().any(|name| name == b"created"));
+
+        // Querying a missing tree doesn't create it
+        assert_eq!(blockchain.get_state_at(0, b"created", b"key")?, None);
+        assert!(!sled_db.tree_names().iter().any(|name| name == b"created"));
+
+        // The genesis block is never rolled back
+        assert!(blockchain.rollback(1)?.is_empty());
+        assert_eq!(blockchain.last()?, (0, genesis));
+
+        Ok(())
+    }
+
+    fn tx(tag: u8) -> Transaction {
+        let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data: vec![0x00, tag] }];
+        Transaction { calls, proofs: vec![], signatures: vec![] }
+    }
+
+    fn receipt(contract_id: ContractId, nullifier: &Nullifier) -> TxReceipt {
+        let event = ContractEvent {
+            contract_id,
+            call_idx: 0,
+            topics: vec![vec![0x00], NULLIFIER_EVENT_TOPIC.to_vec(), serialize(nullifier)],
+            data: vec![],
+        };
+        TxReceipt { gas_used: 0, events: vec![event] }
+    }
+
+    #[test]
+    fn tx_indexes() -> Result<()> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        let (_, genesis) = blockchain.last()?;
+
+        let mut block = BlockInfo::default();
+        block.header.previous = genesis;
+        block.header.slot = 1;
+        block.txs = vec![tx(0), tx(1)];
+        let hash = blockchain.add(&[block.clone()])?[0];
+        blockchain.add_state_diffs(&[hash], &[StateDiff::default()])?;
+
+        let tx_hashes: Vec<blake3::Hash> =
+            block.txs.iter().map(|tx| blake3::hash(&serialize(tx))).collect();
+        let unknown = blake3::hash(b"unknown");
+        assert_eq!(
+            blockchain.get_tx_locations(&[tx_hashes[1], unknown])?,
+            vec![Some(TxLocation { slot: 1, block: hash, index: 1 }), None]
+        );
+        assert_eq!(blockchain.confirmations(1), 1);
+        assert_eq!(blockchain.confirmations(0), 2);
+
+        // Nullifiers get indexed along with the receipts reporting them
+        let nullifiers =
+            [Nullifier::from(pallas::Base::from(1)), Nullifier::from(pallas::Base::from(2))];
+        assert_eq!(blockchain.get_nullifier_txs(&nullifiers)?, vec![None, None]);
+        let receipts = [
+            receipt(*MONEY_CONTRACT_ID, &nullifiers[0]),
+            receipt(ContractId::from(pallas::Base::from(42)), &nullifiers[1]),
+        ];
+        blockchain.add_tx_receipts(&tx_hashes, &receipts)?;
+        assert_eq!(blockchain.get_nullifier_txs(&nullifiers)?, vec![Some(tx_hashes[0]), None]);
+
+        // Databases without indexes get reindexed when opened
+        blockchain.tx_locations.0.clear()?;
+        blockchain.nullifiers.0.clear()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        assert_eq!(
+            blockchain.get_tx_locations(&tx_hashes)?,
+            vec![
+                Some(TxLocation { slot: 1, block: hash, index: 0 }),
+                Some(TxLocation { slot: 1, block: hash, index: 1 }),
+            ]
+        );
+        assert_eq!(blockchain.get_nullifier_txs(&nullifiers)?, vec![Some(tx_hashes[0]), None]);
+
+        // Rolling back the block drops its indexes
+        assert_eq!(blockchain.rollback(1)?.len(), 1);
+        assert_eq!(blockchain.get_tx_locations(&tx_hashes)?, vec![None, None]);
+        assert_eq!(blockchain.get_nullifier_txs(&nullifiers)?, vec![None, None]);
+        assert!(blockchain.nullifiers.is_empty());
+
+        Ok(())
+    }
+}
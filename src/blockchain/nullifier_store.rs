@@ -0,0 +1,195 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::{
+    crypto::{Nullifier, MONEY_CONTRACT_ID},
+    event::NULLIFIER_EVENT_TOPIC,
+};
+use darkfi_serial::deserialize;
+
+use super::TxReceipt;
+use crate::{Error, Result};
+
+const SLED_NULLIFIER_TREE: &[u8] = b"_nullifiers";
+
+/// Retrieve the nullifiers revealed by a transaction, as reported by the
+/// money contract events of its receipt.
+pub fn revealed_nullifiers(receipt: &TxReceipt) -> Vec<Nullifier> {
+    receipt
+        .events
+        .iter()
+        .filter(|event| {
+            event.contract_id == *MONEY_CONTRACT_ID &&
+                event.topics.len() == 3 &&
+                event.topics[1] == NULLIFIER_EVENT_TOPIC
+        })
+        .filter_map(|event| deserialize(&event.topics[2]).ok())
+        .collect()
+}
+
+/// The `NullifierStore` is a `sled` tree indexing the nullifiers revealed
+/// by the blockchain's transactions, where the key is the nullifier, and
+/// the value is the hash of the transaction revealing it.
+#[derive(Clone)]
+pub struct NullifierStore(pub(crate) sled::Tree);
+
+impl NullifierStore {
+    /// Opens a new or existing `NullifierStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_NULLIFIER_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a slice of nullifiers and the hashes of the transactions
+    /// revealing them into the store. With sled, the operation is done
+    /// as a batch.
+    pub fn insert(&self, nullifiers: &[(Nullifier, blake3::Hash)]) -> Result<()> {
+        let mut batch = sled::Batch::default();
+
+        for (nullifier, tx_hash) in nullifiers {
+            batch.insert(&nullifier.to_bytes(), tx_hash.as_bytes());
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Check if the store contains a given nullifier.
+    pub fn contains(&self, nullifier: &Nullifier) -> Result<bool> {
+        Ok(self.0.contains_key(nullifier.to_bytes())?)
+    }
+
+    /// Fetch the hashes of the transactions revealing given nullifiers.
+    /// The resulting vector contains `Option`, which is `Some` if the
+    /// nullifier was found in the store, and otherwise it is `None`, if it
+    /// has not. The second parameter is a boolean which tells the function
+    /// to fail in case at least one nullifier was not found.
+    pub fn get(&self, nullifiers: &[Nullifier], strict: bool) -> Result<Vec<Option<blake3::Hash>>> {
+        let mut ret = Vec::with_capacity(nullifiers.len());
+
+        for nullifier in nullifiers {
+            if let Some(found) = self.0.get(nullifier.to_bytes())? {
+                let hash_bytes: [u8; 32] = found.as_ref().try_into().unwrap();
+                ret.push(Some(hash_bytes.into()));
+            } else {
+                if strict {
+                    return Err(Error::NullifierNotFound(nullifier.to_string()))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Retrieve records count
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    /// Remove a slice of nullifiers from the store.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, nullifiers: &[Nullifier]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(nullifiers))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of nullifiers from the store.
+    pub fn remove_batch(&self, nullifiers: &[Nullifier]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for nullifier in nullifiers {
+            batch.remove(&nullifier.to_bytes());
+        }
+
+        batch
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{
+        crypto::{ContractId, DAO_CONTRACT_ID},
+        event::ContractEvent,
+        pasta::pallas,
+    };
+    use darkfi_serial::serialize;
+
+    use super::*;
+
+    fn nullifier_event(contract_id: ContractId, nullifier: &Nullifier) -> ContractEvent {
+        ContractEvent {
+            contract_id,
+            call_idx: 0,
+            topics: vec![vec![0x00], NULLIFIER_EVENT_TOPIC.to_vec(), serialize(nullifier)],
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn nullifiers_from_receipt() {
+        let nullifiers: Vec<Nullifier> =
+            (0..3).map(|i| Nullifier::from(pallas::Base::from(i))).collect();
+
+        let mut other_topic = nullifier_event(*MONEY_CONTRACT_ID, &nullifiers[2]);
+        other_topic.topics[1] = b"coin".to_vec();
+        let receipt = TxReceipt {
+            gas_used: 0,
+            events: vec![
+                nullifier_event(*MONEY_CONTRACT_ID, &nullifiers[0]),
+                // Only the money contract reports nullifiers
+                nullifier_event(*DAO_CONTRACT_ID, &nullifiers[2]),
+                other_topic,
+                nullifier_event(*MONEY_CONTRACT_ID, &nullifiers[1]),
+            ],
+        };
+
+        assert_eq!(revealed_nullifiers(&receipt), nullifiers[..2]);
+        assert!(revealed_nullifiers(&TxReceipt::default()).is_empty());
+    }
+
+    #[test]
+    fn nullifier_store() -> Result<()> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let store = NullifierStore::new(&sled_db)?;
+
+        let nullifiers: Vec<Nullifier> =
+            (0..3).map(|i| Nullifier::from(pallas::Base::from(i))).collect();
+        let tx_hashes = [blake3::hash(b"tx1"), blake3::hash(b"tx2")];
+        store.insert(&[(nullifiers[0], tx_hashes[0]), (nullifiers[1], tx_hashes[1])])?;
+        assert_eq!(store.len(), 2);
+        assert!(store.contains(&nullifiers[0])?);
+        assert!(!store.contains(&nullifiers[2])?);
+
+        assert_eq!(
+            store.get(&nullifiers, false)?,
+            vec![Some(tx_hashes[0]), Some(tx_hashes[1]), None]
+        );
+        assert!(matches!(store.get(&nullifiers, true), Err(Error::NullifierNotFound(_))));
+
+        store.remove(&nullifiers[..1])?;
+        assert!(!store.contains(&nullifiers[0])?);
+        assert_eq!(store.len(), 1);
+
+        Ok(())
+    }
+}
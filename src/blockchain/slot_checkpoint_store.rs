@@ -26,7 +26,7 @@ const SLED_SLOT_CHECKPOINT_TREE: &[u8] = b"_slot_checkpoints";
 /// blockchain's slots, where the key is the slot uid, and the value is
 /// is the serialized checkpoint.
 #[derive(Clone)]
-pub struct SlotCheckpointStore(sled::Tree);
+pub struct SlotCheckpointStore(pub(crate) sled::Tree);
 
 impl SlotCheckpointStore {
     /// Opens a new or existing `SlotCheckpointStore` on the given sled database.
@@ -132,6 +132,18 @@ impl SlotCheckpointStore {
         Ok(checkpoint)
     }
 
+    /// Build the batch removing all slot checkpoints from given slot onwards.
+    pub fn remove_from_batch(&self, slot: u64) -> Result<sled::Batch> {
+        let mut batch = sled::Batch::default();
+
+        for entry in self.0.range(slot.to_be_bytes()..) {
+            let (key, _) = entry?;
+            batch.remove(key);
+        }
+
+        Ok(batch)
+    }
+
     /// Retrieve records count
     pub fn len(&self) -> usize {
         self.0.len()
@@ -0,0 +1,406 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Portable snapshots of the canonical blockchain.
+//!
+//! A snapshot file holds, in order:
+//! ```plaintext
+//! magic: [u8; 4]
+//! version: u8
+//! slot checkpoints: VarInt count, then each SlotCheckpoint
+//! blocks: VarInt count, then each BlockInfo in chain order, genesis included
+//! contract trees: VarInt count, then for each tree its name, a VarInt
+//!                 entries count and each (key, value) entry
+//! ```
+//! Slot checkpoints come first, so blocks leader proofs can be verified
+//! as they are read.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::Path,
+};
+
+use darkfi_serial::{Decodable, Encodable, VarInt};
+use log::info;
+
+use super::Blockchain;
+use crate::{
+    consensus::{BlockInfo, ChainParams, LightClient, SlotCheckpoint},
+    Error, Result,
+};
+
+/// Snapshot file magic bytes
+const SNAPSHOT_MAGIC: [u8; 4] = *b"dfsn";
+/// Snapshot file format version
+const SNAPSHOT_VERSION: u8 = 2;
+/// Records read and imported at once
+pub const SNAPSHOT_BATCH: usize = 100;
+
+/// Contract tree entries, as (key, value) pairs
+pub type TreeEntries = Vec<(Vec<u8>, Vec<u8>)>;
+
+/// Write the canonical blockchain, its slot checkpoints and all contract
+/// state trees to a snapshot file.
+pub fn export_snapshot(blockchain: &Blockchain, path: &Path) -> Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    SNAPSHOT_MAGIC.encode(&mut writer)?;
+    SNAPSHOT_VERSION.encode(&mut writer)?;
+
+    let slot_checkpoints = blockchain.slot_checkpoints.get_all()?;
+    info!(target: "blockchain::snapshot", "Exporting {} slot checkpoints", slot_checkpoints.len());
+    VarInt(slot_checkpoints.len() as u64).encode(&mut writer)?;
+    for slot_checkpoint in slot_checkpoints {
+        slot_checkpoint.encode(&mut writer)?;
+    }
+
+    let order = blockchain.order.get_all()?;
+    info!(target: "blockchain::snapshot", "Exporting {} blocks", order.len());
+    VarInt(order.len() as u64).encode(&mut writer)?;
+    for (_, blockhash) in order {
+        blockchain.get_blocks_by_hash(&[blockhash])?[0].encode(&mut writer)?;
+    }
+
+    let trees = blockchain.contracts.get_all_trees()?;
+    info!(target: "blockchain::snapshot", "Exporting {} contract trees", trees.len());
+    VarInt(trees.len() as u64).encode(&mut writer)?;
+    for name in trees {
+        let tree = blockchain.sled_db.open_tree(&name)?;
+        name.encode(&mut writer)?;
+        VarInt(tree.len() as u64).encode(&mut writer)?;
+        for record in tree.iter() {
+            let (key, value) = record?;
+            key.to_vec().encode(&mut writer)?;
+            value.to_vec().encode(&mut writer)?;
+        }
+    }
+
+    writer.flush()?;
+    Ok(())
+}
+
+/// Import a snapshot file into an empty blockchain without executing its
+/// transactions. Blocks are verified like a [`LightClient`] does, so they
+/// must extend our genesis block and carry valid leader proofs for the
+/// snapshot slot checkpoints. These checkpoints come from the same file and
+/// contract states are taken as they are, so the snapshot still has to come
+/// from a trusted source. Only contract state trees get written. On error,
+/// the database is left half imported and has to be discarded.
+pub fn import_snapshot_headers(
+    blockchain: &Blockchain,
+    params: &ChainParams,
+    path: &Path,
+) -> Result<()> {
+    let mut reader = SnapshotReader::open(blockchain, path)?;
+    let mut light_client = LightClient::new(params.clone())?;
+
+    loop {
+        let slot_checkpoints = reader.read_slot_checkpoints(SNAPSHOT_BATCH)?;
+        if slot_checkpoints.is_empty() {
+            break
+        }
+        blockchain.add_slot_checkpoints(&slot_checkpoints)?;
+    }
+
+    loop {
+        let blocks = reader.read_blocks(SNAPSHOT_BATCH)?;
+        if blocks.is_empty() {
+            break
+        }
+
+        // The genesis block is already there
+        let blocks: Vec<BlockInfo> = blocks.into_iter().filter(|x| x.header.slot != 0).collect();
+        let slots: Vec<u64> = blocks.iter().map(|x| x.header.slot).collect();
+        let slot_checkpoints: Vec<SlotCheckpoint> =
+            blockchain.slot_checkpoints.get(&slots, false)?.into_iter().flatten().collect();
+        light_client.add_slot_checkpoints(&slot_checkpoints)?;
+
+        for block in &blocks {
+            if let Err(e) = light_client.follow(&block.header, &block.clone().into()) {
+                return Err(Error::SnapshotInvalid(format!(
+                    "block {} verification failed: {}",
+                    block.blockhash(),
+                    e
+                )))
+            }
+        }
+        blockchain.add(&blocks)?;
+    }
+
+    while let Some((name, entries)) = reader.read_tree()? {
+        // State trees are the ones pointed to by the contracts tree, which
+        // gets exported first, so the allowed ones are retrieved every time.
+        if !blockchain.contracts.get_all_trees()?.contains(&name) {
+            return Err(Error::SnapshotInvalid(format!(
+                "tree {} is not a contract state tree",
+                bs58::encode(&name).into_string()
+            )))
+        }
+
+        let tree = blockchain.sled_db.open_tree(&name)?;
+        tree.clear()?;
+
+        let mut batch = sled::Batch::default();
+        for (key, value) in entries {
+            batch.insert(key, value);
+        }
+        tree.apply_batch(batch)?;
+    }
+
+    blockchain.sled_db.flush()?;
+    info!(target: "blockchain::snapshot", "Imported {} blocks", blockchain.len());
+    Ok(())
+}
+
+/// Snapshot file sections, in file order
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Section {
+    SlotCheckpoints,
+    Blocks,
+    Trees,
+}
+
+/// Sequential reader over a snapshot file. Sections have to be read in
+/// file order, and blocks are checked to form a chain extending the
+/// genesis block of the blockchain the snapshot gets imported into.
+pub struct SnapshotReader {
+    reader: BufReader<File>,
+    section: Section,
+    /// Records left in the current section
+    remaining: u64,
+    /// Slot and hash of the last block read
+    last: Option<(u64, blake3::Hash)>,
+    /// Hash of the genesis block the snapshot has to start with
+    genesis: blake3::Hash,
+}
+
+impl SnapshotReader {
+    /// Open a snapshot file to import into given blockchain, which must
+    /// not hold anything but its genesis block.
+    pub fn open(blockchain: &Blockchain, path: &Path) -> Result<Self> {
+        if blockchain.len() > 1 {
+            return Err(Error::SnapshotInvalid("blockchain database is not empty".to_string()))
+        }
+        let genesis = blockchain.order.get(&[0], true)?[0].unwrap();
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let magic: [u8; 4] = Decodable::decode(&mut reader)?;
+        let version: u8 = Decodable::decode(&mut reader)?;
+        if magic != SNAPSHOT_MAGIC || version != SNAPSHOT_VERSION {
+            return Err(Error::SnapshotInvalid("unknown file format".to_string()))
+        }
+        let remaining = VarInt::decode(&mut reader)?.0;
+
+        Ok(Self { reader, section: Section::SlotCheckpoints, remaining, last: None, genesis })
+    }
+
+    /// Move on to given section, once the previous ones have been read.
+    fn enter(&mut self, section: Section) -> Result<()> {
+        if self.section > section {
+            return Err(Error::SnapshotInvalid("sections read out of order".to_string()))
+        }
+
+        while self.section < section {
+            if self.remaining != 0 {
+                return Err(Error::SnapshotInvalid("sections read out of order".to_string()))
+            }
+            self.section = if self.section == Section::SlotCheckpoints {
+                Section::Blocks
+            } else {
+                Section::Trees
+            };
+            self.remaining = VarInt::decode(&mut self.reader)?.0;
+        }
+
+        Ok(())
+    }
+
+    /// Read up to `n` blocks, returning an empty vector once all have been read.
+    pub fn read_blocks(&mut self, n: usize) -> Result<Vec<BlockInfo>> {
+        self.enter(Section::Blocks)?;
+
+        let mut ret = vec![];
+        while self.remaining > 0 && ret.len() < n {
+            let block: BlockInfo = Decodable::decode(&mut self.reader)?;
+            let blockhash = block.blockhash();
+
+            match self.last {
+                None if blockhash != self.genesis => {
+                    return Err(Error::SnapshotInvalid("genesis block mismatch".to_string()))
+                }
+                Some((slot, hash))
+                    if block.header.previous != hash || block.header.slot <= slot =>
+                {
+                    return Err(Error::SnapshotInvalid(format!(
+                        "block {} does not extend the chain",
+                        blockhash
+                    )))
+                }
+                _ => {}
+            }
+
+            self.last = Some((block.header.slot, blockhash));
+            self.remaining -= 1;
+            ret.push(block);
+        }
+
+        Ok(ret)
+    }
+
+    /// Read up to `n` slot checkpoints, returning an empty vector once all
+    /// have been read.
+    pub fn read_slot_checkpoints(&mut self, n: usize) -> Result<Vec<SlotCheckpoint>> {
+        self.enter(Section::SlotCheckpoints)?;
+
+        let mut ret = vec![];
+        while self.remaining > 0 && ret.len() < n {
+            ret.push(Decodable::decode(&mut self.reader)?);
+            self.remaining -= 1;
+        }
+
+        Ok(ret)
+    }
+
+    /// Read the next contract tree, returning `None` once all have been read.
+    pub fn read_tree(&mut self) -> Result<Option<(Vec<u8>, TreeEntries)>> {
+        self.enter(Section::Trees)?;
+        if self.remaining == 0 {
+            return Ok(None)
+        }
+
+        let name: Vec<u8> = Decodable::decode(&mut self.reader)?;
+        let len = VarInt::decode(&mut self.reader)?.0;
+        let mut entries = vec![];
+        for _ in 0..len {
+            let key: Vec<u8> = Decodable::decode(&mut self.reader)?;
+            let value: Vec<u8> = Decodable::decode(&mut self.reader)?;
+            entries.push((key, value));
+        }
+
+        self.remaining -= 1;
+        Ok(Some((name, entries)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{crypto::MONEY_CONTRACT_ID, pasta::pallas};
+
+    use super::*;
+    use crate::{blockchain::BlockchainOverlay, consensus::testing::TestChain};
+
+    fn new_blockchain(params: &ChainParams) -> Result<Blockchain> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        Blockchain::new(&sled_db, params.genesis_ts, params.genesis_data)
+    }
+
+    /// Blockchain holding given chain and a money contract state tree
+    fn source_blockchain(chain: &TestChain) -> Result<Blockchain> {
+        let blockchain = new_blockchain(&chain.params)?;
+        blockchain.add(&chain.blocks)?;
+        blockchain.add_slot_checkpoints(&chain.slot_checkpoints)?;
+
+        let overlay = BlockchainOverlay::new(&blockchain)?;
+        let lock = overlay.lock().unwrap();
+        let ptr = lock.contracts.init(&MONEY_CONTRACT_ID, "test")?;
+        let mut state = lock.overlay.lock().unwrap();
+        state.insert(&ptr, b"key", b"value")?;
+        state.apply()?;
+
+        Ok(blockchain)
+    }
+
+    #[test]
+    fn snapshot_roundtrip() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        chain.extend(1);
+        chain.extend(3);
+        let source = source_blockchain(&chain)?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("snapshot");
+        export_snapshot(&source, &path)?;
+
+        let target = new_blockchain(&chain.params)?;
+        import_snapshot_headers(&target, &chain.params, &path)?;
+
+        assert_eq!(target.last()?, source.last()?);
+        assert_eq!(target.len(), 3);
+        assert!(target.has_slot(3)?);
+        let tree = target.contracts.lookup(&target.sled_db, &MONEY_CONTRACT_ID, "test")?;
+        assert_eq!(&*tree.get(b"key")?.unwrap(), b"value");
+
+        // Snapshots only import into empty blockchains
+        assert!(import_snapshot_headers(&target, &chain.params, &path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_tampered_block() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        chain.extend(1);
+        chain.blocks[0].lead_info.coin_eta = pallas::Base::from(42);
+        let source = source_blockchain(&chain)?;
+
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("snapshot");
+        export_snapshot(&source, &path)?;
+
+        let target = new_blockchain(&chain.params)?;
+        assert!(matches!(
+            import_snapshot_headers(&target, &chain.params, &path),
+            Err(Error::SnapshotInvalid(_))
+        ));
+        assert_eq!(target.len(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_rejects_store_trees() -> Result<()> {
+        let params = ChainParams::devnet();
+        let source = new_blockchain(&params)?;
+
+        // Hand written snapshot overwriting the transactions store
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("snapshot");
+        let mut writer = BufWriter::new(File::create(&path)?);
+        SNAPSHOT_MAGIC.encode(&mut writer)?;
+        SNAPSHOT_VERSION.encode(&mut writer)?;
+        VarInt(0).encode(&mut writer)?;
+        VarInt(1).encode(&mut writer)?;
+        source.get_blocks_by_slot(&[0])?[0].encode(&mut writer)?;
+        VarInt(1).encode(&mut writer)?;
+        b"_transactions".to_vec().encode(&mut writer)?;
+        VarInt(1).encode(&mut writer)?;
+        b"key".to_vec().encode(&mut writer)?;
+        b"value".to_vec().encode(&mut writer)?;
+        writer.flush()?;
+        drop(writer);
+
+        let target = new_blockchain(&params)?;
+        assert!(matches!(
+            import_snapshot_headers(&target, &params, &path),
+            Err(Error::SnapshotInvalid(_))
+        ));
+        assert!(target.sled_db.open_tree(b"_transactions")?.get(b"key")?.is_none());
+
+        Ok(())
+    }
+}
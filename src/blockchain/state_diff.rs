@@ -0,0 +1,362 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::{Bound, Deref, DerefMut, RangeBounds},
+};
+
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
+
+use crate::{Error, Result};
+
+const SLED_STATE_DIFF_TREE: &[u8] = b"_state_diffs";
+
+/// A single contract state key changed by a block. `None` values mean
+/// the key was absent.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct StateChange {
+    /// Name of the sled tree holding the key
+    pub tree: Vec<u8>,
+    /// The changed key
+    pub key: Vec<u8>,
+    /// Value before the block was applied
+    pub old: Option<Vec<u8>>,
+    /// Value after the block was applied
+    pub new: Option<Vec<u8>>,
+}
+
+/// Contract state changes of a block, sorted by tree and key.
+#[derive(Debug, Clone, Default, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct StateDiff {
+    pub changes: Vec<StateChange>,
+    /// Names of the sled trees created by the block
+    pub created_trees: Vec<Vec<u8>>,
+}
+
+impl StateDiff {
+    /// Add the writes reverting the changes to the per-tree batches, so
+    /// every key gets restored to its value before the block was applied.
+    /// Diffs of several blocks have to be added last block first.
+    pub fn revert_batches(&self, batches: &mut BTreeMap<Vec<u8>, sled::Batch>) {
+        for change in &self.changes {
+            let batch = batches.entry(change.tree.clone()).or_default();
+            match &change.old {
+                Some(value) => batch.insert(change.key.as_slice(), value.as_slice()),
+                None => batch.remove(change.key.as_slice()),
+            };
+        }
+    }
+}
+
+/// [`sled_overlay::SledDbOverlay`] journaling the previous value of every
+/// key written through it, so the changes it applies can be recorded as
+/// a [`StateDiff`]. Reads and everything else go to the inner overlay.
+pub struct StateOverlay {
+    overlay: sled_overlay::SledDbOverlay,
+    /// The underlying sled database, used for ordered iteration
+    db: sled::Db,
+    /// Value of each written (tree, key) before the first write
+    journal: BTreeMap<(Vec<u8>, Vec<u8>), Option<Vec<u8>>>,
+    /// Trees opened through the overlay that didn't exist before
+    created: BTreeSet<Vec<u8>>,
+}
+
+impl StateOverlay {
+    pub fn new(db: &sled::Db) -> Self {
+        Self {
+            overlay: sled_overlay::SledDbOverlay::new(db),
+            db: db.clone(),
+            journal: BTreeMap::new(),
+            created: BTreeSet::new(),
+        }
+    }
+
+    /// Open a tree through the overlay, recording it if it gets created.
+    pub fn open_tree(&mut self, tree_key: &[u8]) -> Result<()> {
+        if !self.db.tree_names().iter().any(|name| name == tree_key) {
+            self.created.insert(tree_key.to_vec());
+        }
+        self.overlay.open_tree(tree_key)?;
+        Ok(())
+    }
+
+    /// Keep the value of a key before its first write.
+    fn record(&mut self, tree_key: &[u8], key: &[u8]) -> Result<()> {
+        let journal_key = (tree_key.to_vec(), key.to_vec());
+        if !self.journal.contains_key(&journal_key) {
+            let old = self.overlay.get(tree_key, key)?.map(|x| x.to_vec());
+            self.journal.insert(journal_key, old);
+        }
+
+        Ok(())
+    }
+
+    /// Insert a key to given tree, recording its previous value.
+    pub fn insert(&mut self, tree_key: &[u8], key: &[u8], value: &[u8]) -> Result<()> {
+        self.record(tree_key, key)?;
+        self.overlay.insert(tree_key, key, value)?;
+        Ok(())
+    }
+
+    /// Remove a key from given tree, recording its previous value.
+    pub fn remove(&mut self, tree_key: &[u8], key: &[u8]) -> Result<()> {
+        self.record(tree_key, key)?;
+        self.overlay.remove(tree_key, key)?;
+        Ok(())
+    }
+
+    /// Retrieve up to `limit` entries of given tree with keys in the range
+    /// from `start` (inclusive) to `end` (exclusive), ordered by key, or in
+    /// reverse order if `reverse` is set. Missing bounds leave the range open.
+    ///
+    /// The committed tree is merged with the keys written through the
+    /// overlay, which are found in the journal and take precedence.
+    pub fn range(
+        &self,
+        tree_key: &[u8],
+        start: Option<&[u8]>,
+        end: Option<&[u8]>,
+        reverse: bool,
+        limit: usize,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let start = match start {
+            Some(k) => Bound::Included(k.to_vec()),
+            None => Bound::Unbounded,
+        };
+        let end = match end {
+            Some(k) => Bound::Excluded(k.to_vec()),
+            None => Bound::Unbounded,
+        };
+
+        // Current values of the written keys in the range
+        let mut written = BTreeMap::new();
+        for ((tree, key), _) in &self.journal {
+            if tree != tree_key || !(start.as_ref(), end.as_ref()).contains(key) {
+                continue
+            }
+            written.insert(key.clone(), self.overlay.get(tree_key, key)?.map(|x| x.to_vec()));
+        }
+
+        // Every written key can shadow at most one committed entry, so this
+        // many committed entries are enough to fill the limit.
+        // Trees that are not committed yet are only read from the overlay,
+        // since opening them in the database would create them.
+        let mut entries = BTreeMap::new();
+        if self.db.tree_names().iter().any(|name| name == tree_key) {
+            let tree = self.db.open_tree(tree_key)?;
+            let committed = tree.range::<Vec<u8>, _>((start, end));
+            let committed: Box<dyn Iterator<Item = sled::Result<(sled::IVec, sled::IVec)>>> =
+                if reverse { Box::new(committed.rev()) } else { Box::new(committed) };
+
+            for entry in committed.take(limit.saturating_add(written.len())) {
+                let (key, value) = entry?;
+                if !written.contains_key(key.as_ref()) {
+                    entries.insert(key.to_vec(), value.to_vec());
+                }
+            }
+        }
+
+        for (key, value) in written {
+            if let Some(value) = value {
+                entries.insert(key, value);
+            }
+        }
+
+        let ret = if reverse {
+            entries.into_iter().rev().take(limit).collect()
+        } else {
+            entries.into_iter().take(limit).collect()
+        };
+
+        Ok(ret)
+    }
+
+    /// Build the diff of all keys written so far whose value changed.
+    pub fn diff(&self) -> Result<StateDiff> {
+        let mut changes = vec![];
+        for ((tree, key), old) in &self.journal {
+            let new = self.overlay.get(tree, key)?.map(|x| x.to_vec());
+            if *old != new {
+                changes.push(StateChange {
+                    tree: tree.clone(),
+                    key: key.clone(),
+                    old: old.clone(),
+                    new,
+                });
+            }
+        }
+
+        Ok(StateDiff { changes, created_trees: self.created.iter().cloned().collect() })
+    }
+}
+
+impl Deref for StateOverlay {
+    type Target = sled_overlay::SledDbOverlay;
+
+    fn deref(&self) -> &Self::Target {
+        &self.overlay
+    }
+}
+
+impl DerefMut for StateOverlay {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.overlay
+    }
+}
+
+/// The `StateDiffStore` is a `sled` tree storing the contract state diff
+/// of each block, where the key is the blockhash, and the value is the
+/// serialized [`StateDiff`].
+#[derive(Clone)]
+pub struct StateDiffStore(pub(crate) sled::Tree);
+
+impl StateDiffStore {
+    /// Opens a new or existing `StateDiffStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_STATE_DIFF_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a slice of blockhashes and their state diffs into the store.
+    /// With sled, the operation is done as a batch.
+    pub fn insert(&self, hashes: &[blake3::Hash], diffs: &[StateDiff]) -> Result<()> {
+        assert_eq!(hashes.len(), diffs.len());
+        let mut batch = sled::Batch::default();
+
+        for (hash, diff) in hashes.iter().zip(diffs.iter()) {
+            batch.insert(hash.as_bytes(), serialize(diff));
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Check if the store contains the state diff of a given blockhash.
+    pub fn contains(&self, hash: &blake3::Hash) -> Result<bool> {
+        Ok(self.0.contains_key(hash.as_bytes())?)
+    }
+
+    /// Fetch the state diffs of given blockhashes.
+    /// The resulting vector contains `Option`, which is `Some` if the diff
+    /// was found in the store, and otherwise it is `None`, if it has not.
+    /// The second parameter is a boolean which tells the function to fail in
+    /// case at least one diff was not found.
+    pub fn get(&self, hashes: &[blake3::Hash], strict: bool) -> Result<Vec<Option<StateDiff>>> {
+        let mut ret = Vec::with_capacity(hashes.len());
+
+        for hash in hashes {
+            if let Some(found) = self.0.get(hash.as_bytes())? {
+                ret.push(Some(deserialize(&found)?));
+            } else {
+                if strict {
+                    return Err(Error::StateDiffNotFound(hash.to_hex().as_str().to_string()))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Remove a slice of blockhashes from the store.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of blockhashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
+
+    /// Retrieve records count
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(key: &[u8], value: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        (key.to_vec(), value.to_vec())
+    }
+
+    #[test]
+    fn overlay_range_merge() -> Result<()> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let tree = db.open_tree(b"state")?;
+        for key in [b"a", b"b", b"c", b"d"] {
+            tree.insert(key, "committed")?;
+        }
+
+        let mut overlay = StateOverlay::new(&db);
+        overlay.insert(b"state", b"b", b"written")?;
+        overlay.insert(b"state", b"e", b"written")?;
+        overlay.remove(b"state", b"c")?;
+
+        let all = vec![
+            entry(b"a", b"committed"),
+            entry(b"b", b"written"),
+            entry(b"d", b"committed"),
+            entry(b"e", b"written"),
+        ];
+        assert_eq!(overlay.range(b"state", None, None, false, 10)?, all);
+
+        let reversed: Vec<_> = all.iter().rev().cloned().collect();
+        assert_eq!(overlay.range(b"state", None, None, true, 10)?, reversed);
+
+        // Deleted keys don't count towards the limit
+        assert_eq!(overlay.range(b"state", Some(b"b"), None, false, 2)?, all[1..3]);
+        assert_eq!(overlay.range(b"state", None, Some(b"e"), true, 2)?, reversed[1..3]);
+
+        // Start is inclusive and end is exclusive
+        assert_eq!(overlay.range(b"state", Some(b"b"), Some(b"d"), false, 10)?, all[1..2]);
+
+        // The committed tree is untouched
+        assert_eq!(&*tree.get(b"b")?.unwrap(), b"committed");
+        assert!(tree.contains_key(b"c")?);
+        assert!(!tree.contains_key(b"e")?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn overlay_range_missing_tree() -> Result<()> {
+        let db = sled::Config::new().temporary(true).open()?;
+        let overlay = StateOverlay::new(&db);
+
+        assert!(overlay.range(b"missing", None, None, false, 10)?.is_empty());
+        assert!(!db.tree_names().iter().any(|name| name == b"missing"));
+
+        Ok(())
+    }
+}
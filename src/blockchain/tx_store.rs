@@ -18,11 +18,17 @@
 
 use std::collections::HashMap;
 
-use darkfi_serial::{deserialize, serialize};
+use darkfi_sdk::{
+    crypto::MONEY_CONTRACT_ID,
+    event::{ContractEvent, FEE_EVENT_TOPIC},
+};
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
 
 use crate::{tx::Transaction, Error, Result};
 
 const SLED_TX_TREE: &[u8] = b"_transactions";
+const SLED_TX_LOCATION_TREE: &[u8] = b"_transaction_locations";
+const SLED_TX_RECEIPT_TREE: &[u8] = b"_transaction_receipts";
 const SLED_PENDING_TX_TREE: &[u8] = b"_pending_transactions";
 const SLED_PENDING_TX_ORDER_TREE: &[u8] = b"_pending_transactions_order";
 
@@ -30,7 +36,7 @@ const SLED_PENDING_TX_ORDER_TREE: &[u8] = b"_pending_transactions_order";
 /// transactions where the key is the transaction hash, and the value is
 /// the serialized transaction.
 #[derive(Clone)]
-pub struct TxStore(sled::Tree);
+pub struct TxStore(pub(crate) sled::Tree);
 
 impl TxStore {
     /// Opens a new or existing `TxStore` on the given sled database.
@@ -117,6 +123,212 @@ impl TxStore {
     pub fn is_empty(&self) -> bool {
         self.0.len() == 0
     }
+
+    /// Remove a slice of transaction hashes from the txstore.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of hashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
+}
+
+/// Position of a finalized transaction in the blockchain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TxLocation {
+    /// Slot of the block including the transaction
+    pub slot: u64,
+    /// Hash of the block including the transaction
+    pub block: blake3::Hash,
+    /// Index of the transaction in the block
+    pub index: u32,
+}
+
+/// The `TxLocationStore` is a `sled` tree indexing the blockchain's
+/// transactions, where the key is the transaction hash, and the value is
+/// the serialized [`TxLocation`] of the transaction.
+#[derive(Clone)]
+pub struct TxLocationStore(pub(crate) sled::Tree);
+
+impl TxLocationStore {
+    /// Opens a new or existing `TxLocationStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_TX_LOCATION_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a slice of transaction hashes and their [`TxLocation`] into
+    /// the store. With sled, the operation is done as a batch.
+    pub fn insert(&self, tx_hashes: &[blake3::Hash], locations: &[TxLocation]) -> Result<()> {
+        assert_eq!(tx_hashes.len(), locations.len());
+        let mut batch = sled::Batch::default();
+
+        for (tx_hash, location) in tx_hashes.iter().zip(locations.iter()) {
+            batch.insert(tx_hash.as_bytes(), serialize(location));
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Check if the store contains a given transaction hash.
+    pub fn contains(&self, tx_hash: &blake3::Hash) -> Result<bool> {
+        Ok(self.0.contains_key(tx_hash.as_bytes())?)
+    }
+
+    /// Fetch the locations of given tx hashes from the store.
+    /// The resulting vector contains `Option`, which is `Some` if the tx
+    /// was found in the store, and otherwise it is `None`, if it has not.
+    /// The second parameter is a boolean which tells the function to fail in
+    /// case at least one tx was not found.
+    pub fn get(&self, tx_hashes: &[blake3::Hash], strict: bool) -> Result<Vec<Option<TxLocation>>> {
+        let mut ret = Vec::with_capacity(tx_hashes.len());
+
+        for tx_hash in tx_hashes {
+            if let Some(found) = self.0.get(tx_hash.as_bytes())? {
+                let location = deserialize(&found)?;
+                ret.push(Some(location));
+            } else {
+                if strict {
+                    let s = tx_hash.to_hex().as_str().to_string();
+                    return Err(Error::TransactionNotFound(s))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Retrieve records count
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.len() == 0
+    }
+
+    /// Remove a slice of transaction hashes from the store.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of hashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
+}
+
+/// Outcome of applying a finalized transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TxReceipt {
+    /// Gas spent by the transaction
+    pub gas_used: u64,
+    /// Events emitted by the transaction contract calls, in order
+    pub events: Vec<ContractEvent>,
+}
+
+/// Retrieve the minimum fee a transaction proved paying, as reported by
+/// the money contract fee event of its receipt, if it has a fee call.
+pub fn proven_fee(receipt: &TxReceipt) -> Option<u64> {
+    receipt
+        .events
+        .iter()
+        .find(|event| {
+            event.contract_id == *MONEY_CONTRACT_ID &&
+                event.topics.len() == 2 &&
+                event.topics[1] == FEE_EVENT_TOPIC
+        })
+        .and_then(|event| deserialize(&event.data).ok())
+}
+
+/// The `TxReceiptStore` is a `sled` tree storing the receipts of the
+/// blockchain's transactions, where the key is the transaction hash, and
+/// the value is the serialized [`TxReceipt`] of the transaction.
+#[derive(Clone)]
+pub struct TxReceiptStore(pub(crate) sled::Tree);
+
+impl TxReceiptStore {
+    /// Opens a new or existing `TxReceiptStore` on the given sled database.
+    pub fn new(db: &sled::Db) -> Result<Self> {
+        let tree = db.open_tree(SLED_TX_RECEIPT_TREE)?;
+        Ok(Self(tree))
+    }
+
+    /// Insert a slice of transaction hashes and their [`TxReceipt`] into
+    /// the store. With sled, the operation is done as a batch.
+    pub fn insert(&self, tx_hashes: &[blake3::Hash], receipts: &[TxReceipt]) -> Result<()> {
+        assert_eq!(tx_hashes.len(), receipts.len());
+        let mut batch = sled::Batch::default();
+
+        for (tx_hash, receipt) in tx_hashes.iter().zip(receipts.iter()) {
+            batch.insert(tx_hash.as_bytes(), serialize(receipt));
+        }
+
+        self.0.apply_batch(batch)?;
+        Ok(())
+    }
+
+    /// Fetch the receipts of given tx hashes from the store.
+    /// The resulting vector contains `Option`, which is `Some` if the receipt
+    /// was found in the store, and otherwise it is `None`, if it has not.
+    /// The second parameter is a boolean which tells the function to fail in
+    /// case at least one receipt was not found.
+    pub fn get(&self, tx_hashes: &[blake3::Hash], strict: bool) -> Result<Vec<Option<TxReceipt>>> {
+        let mut ret = Vec::with_capacity(tx_hashes.len());
+
+        for tx_hash in tx_hashes {
+            if let Some(found) = self.0.get(tx_hash.as_bytes())? {
+                let receipt = deserialize(&found)?;
+                ret.push(Some(receipt));
+            } else {
+                if strict {
+                    let s = tx_hash.to_hex().as_str().to_string();
+                    return Err(Error::TransactionNotFound(s))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+
+    /// Remove a slice of transaction hashes from the store.
+    /// With sled, the operation is done as a batch.
+    pub fn remove(&self, hashes: &[blake3::Hash]) -> Result<()> {
+        self.0.apply_batch(self.remove_batch(hashes))?;
+        Ok(())
+    }
+
+    /// Build the batch removing a slice of hashes from the store.
+    pub fn remove_batch(&self, hashes: &[blake3::Hash]) -> sled::Batch {
+        let mut batch = sled::Batch::default();
+
+        for hash in hashes {
+            batch.remove(hash.as_bytes());
+        }
+
+        batch
+    }
 }
 
 /// The `PendingTxStore` is a `sled` tree storing all the node pending
@@ -158,6 +370,34 @@ impl PendingTxStore {
         Ok(self.0.contains_key(tx_hash.as_bytes())?)
     }
 
+    /// Fetch given tx hashes from the pending tx store.
+    /// The resulting vector contains `Option`, which is `Some` if the tx
+    /// was found in the pending tx store, and otherwise it is `None`, if it
+    /// has not. The second parameter is a boolean which tells the function
+    /// to fail in case at least one tx was not found.
+    pub fn get(
+        &self,
+        tx_hashes: &[blake3::Hash],
+        strict: bool,
+    ) -> Result<Vec<Option<Transaction>>> {
+        let mut ret = Vec::with_capacity(tx_hashes.len());
+
+        for tx_hash in tx_hashes {
+            if let Some(found) = self.0.get(tx_hash.as_bytes())? {
+                let tx = deserialize(&found)?;
+                ret.push(Some(tx));
+            } else {
+                if strict {
+                    let s = tx_hash.to_hex().as_str().to_string();
+                    return Err(Error::TransactionNotFound(s))
+                }
+                ret.push(None);
+            }
+        }
+
+        Ok(ret)
+    }
+
     /// Retrieve all transactions from the pending tx store in the form of
     /// a HashMap with key the transaction hash and value the transaction
     /// itself.
@@ -256,3 +496,35 @@ impl PendingTxOrderStore {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tx_location_store() -> Result<()> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let store = TxLocationStore::new(&sled_db)?;
+
+        let tx_hashes = [blake3::hash(b"tx1"), blake3::hash(b"tx2")];
+        let locations = [
+            TxLocation { slot: 1, block: blake3::hash(b"block1"), index: 0 },
+            TxLocation { slot: 2, block: blake3::hash(b"block2"), index: 3 },
+        ];
+        store.insert(&tx_hashes, &locations)?;
+        assert_eq!(store.len(), 2);
+
+        let unknown = blake3::hash(b"tx3");
+        assert_eq!(
+            store.get(&[tx_hashes[1], unknown, tx_hashes[0]], false)?,
+            vec![Some(locations[1]), None, Some(locations[0])]
+        );
+        assert!(matches!(store.get(&[unknown], true), Err(Error::TransactionNotFound(_))));
+
+        store.remove(&tx_hashes[..1])?;
+        assert!(!store.contains(&tx_hashes[0])?);
+        assert_eq!(store.get(&tx_hashes, false)?, vec![None, Some(locations[1])]);
+
+        Ok(())
+    }
+}
@@ -19,9 +19,9 @@
 use std::fmt;
 
 use darkfi_sdk::{
-    crypto::{constants::MERKLE_DEPTH, MerkleNode},
-    incrementalmerkletree::{bridgetree::BridgeTree, Tree},
-    pasta::pallas,
+    crypto::{constants::MERKLE_DEPTH, MerkleNode, MerkleTree},
+    incrementalmerkletree::{Altitude, Hashable, Tree},
+    pasta::{group::ff::PrimeField, pallas},
 };
 use darkfi_serial::{serialize, SerialDecodable, SerialEncodable};
 
@@ -62,16 +62,82 @@ impl Header {
 
     /// Generate the genesis block.
     pub fn genesis_header(genesis_ts: Timestamp, genesis_data: blake3::Hash) -> Self {
-        let tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100);
-        let root = tree.root(0).unwrap();
-
-        Self::new(genesis_data, 0, 0, genesis_ts, root)
+        Self::new(genesis_data, 0, 0, genesis_ts, Self::txs_root(&[]))
     }
 
     /// Calculate the header hash
     pub fn headerhash(&self) -> blake3::Hash {
         blake3::hash(&serialize(self))
     }
+
+    /// Calculate the merkle root of provided block transaction hashes
+    pub fn txs_root(txs: &[blake3::Hash]) -> MerkleNode {
+        let mut tree = MerkleTree::new(100);
+        for tx in txs {
+            tree.append(&tx_leaf(tx));
+        }
+
+        tree.root(0).unwrap()
+    }
+}
+
+/// Calculate the merkle tree leaf of a transaction hash.
+/// The hash is truncated to 31 bytes, so it always fits in a base field element.
+pub fn tx_leaf(tx: &blake3::Hash) -> MerkleNode {
+    let mut bytes = [0_u8; 32];
+    bytes[0..31].copy_from_slice(&tx.as_bytes()[0..31]);
+    MerkleNode::from(pallas::Base::from_repr(bytes).unwrap())
+}
+
+/// Merkle proof of a transaction inclusion in a block, verified against
+/// the block [`Header`] root.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct TxInclusionProof {
+    /// Position of the transaction in the block
+    pub position: u64,
+    /// Authentication path of the transaction leaf
+    pub path: Vec<MerkleNode>,
+}
+
+impl TxInclusionProof {
+    /// Generate the inclusion proof of the transaction at provided index
+    /// of the block transaction hashes. Returns `None` if index is out of bounds.
+    pub fn new(txs: &[blake3::Hash], index: usize) -> Option<Self> {
+        let mut tree = MerkleTree::new(100);
+        let mut position = None;
+        for (i, tx) in txs.iter().enumerate() {
+            tree.append(&tx_leaf(tx));
+            if i == index {
+                position = tree.witness();
+            }
+        }
+
+        let position = position?;
+        let root = tree.root(0).unwrap();
+        let path = tree.authentication_path(position, &root)?;
+
+        Some(Self { position: u64::from(position), path })
+    }
+
+    /// Calculate the merkle root this proof yields for provided transaction hash.
+    pub fn root(&self, tx: &blake3::Hash) -> MerkleNode {
+        let mut node = tx_leaf(tx);
+        for (level, sibling) in self.path.iter().enumerate() {
+            let altitude = Altitude::from(level as u8);
+            node = if (self.position >> level) & 1 == 0 {
+                MerkleNode::combine(altitude, &node, sibling)
+            } else {
+                MerkleNode::combine(altitude, sibling, &node)
+            };
+        }
+
+        node
+    }
+
+    /// Verify provided transaction hash is included in the block of given header.
+    pub fn verify(&self, header: &Header, tx: &blake3::Hash) -> bool {
+        self.path.len() == MERKLE_DEPTH as usize && self.root(tx) == header.root
+    }
 }
 
 impl Default for Header {
@@ -89,7 +155,7 @@ impl Default for Header {
 /// This struct represents a tuple of the form (`magic`, `header`, `counter`, `txs`, `lead_info`).
 /// The header and transactions are stored as hashes, serving as pointers to
 /// the actual data in the sled database.
-#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
 pub struct Block {
     /// Block magic bytes
     pub magic: [u8; 4],
@@ -105,6 +171,10 @@ impl net::Message for Block {
     fn name() -> &'static str {
         "block"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 impl Block {
@@ -151,6 +221,10 @@ impl net::Message for BlockOrder {
     fn name() -> &'static str {
         "blockorder"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Structure representing full block data.
@@ -190,6 +264,12 @@ impl BlockInfo {
         let block: Block = self.clone().into();
         block.blockhash()
     }
+
+    /// Calculate the merkle root of the block transactions
+    pub fn txs_root(&self) -> MerkleNode {
+        let txs: Vec<blake3::Hash> = self.txs.iter().map(|x| blake3::hash(&serialize(x))).collect();
+        Header::txs_root(&txs)
+    }
 }
 
 impl From<BlockInfo> for Block {
@@ -215,6 +295,80 @@ impl net::Message for BlockResponse {
     fn name() -> &'static str {
         "blockresponse"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
+}
+
+/// Header of a finalized block along with the block itself, holding its
+/// leader proof and transaction hashes, so the header chain can be verified
+/// before the transactions get downloaded.
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
+pub struct SyncHeader {
+    /// Block header data
+    pub header: Header,
+    /// Block this header belongs to
+    pub block: Block,
+}
+
+impl SyncHeader {
+    /// Hash of the block, which the header of the next block points to.
+    pub fn blockhash(&self) -> blake3::Hash {
+        self.block.blockhash()
+    }
+}
+
+/// Auxiliary structure used for header-first blockchain syncing.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct HeaderOrder {
+    /// Slot UID of the last known block
+    pub slot: u64,
+}
+
+impl net::Message for HeaderOrder {
+    fn name() -> &'static str {
+        "headerorder"
+    }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
+}
+
+/// Auxiliary structure used for header-first blockchain syncing.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct HeaderResponse {
+    /// Response headers, in chain order.
+    pub headers: Vec<SyncHeader>,
+}
+
+impl net::Message for HeaderResponse {
+    fn name() -> &'static str {
+        "headerresponse"
+    }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
+}
+
+/// Auxiliary structure used for header-first blockchain syncing.
+/// Answered with a [`BlockResponse`] holding the requested blocks.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct BodyOrder {
+    /// Hashes of the requested blocks
+    pub hashes: Vec<blake3::Hash>,
+}
+
+impl net::Message for BodyOrder {
+    fn name() -> &'static str {
+        "bodyorder"
+    }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// This struct represents a block proposal, used for consensus.
@@ -230,8 +384,8 @@ pub struct BlockProposal {
 
 impl BlockProposal {
     #[allow(clippy::too_many_arguments)]
-    pub fn new(header: Header, txs: Vec<Transaction>, lead_info: LeadInfo) -> Self {
-        let block = BlockInfo::new(header, txs, lead_info);
+    pub fn new(magic: [u8; 4], header: Header, txs: Vec<Transaction>, lead_info: LeadInfo) -> Self {
+        let block = BlockInfo { magic, ..BlockInfo::new(header, txs, lead_info) };
         let hash = block.blockhash();
         let header = block.header.headerhash();
         Self { hash, header, block }
@@ -265,6 +419,16 @@ impl net::Message for BlockProposal {
     fn name() -> &'static str {
         "proposal"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Consensus
+    }
+}
+
+impl net::InvItem for BlockProposal {
+    fn inv_hash(&self) -> net::InvHash {
+        *self.hash.as_bytes()
+    }
 }
 
 impl From<BlockProposal> for BlockInfo {
@@ -272,3 +436,27 @@ impl From<BlockProposal> for BlockInfo {
         block.block
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{Header, TxInclusionProof};
+
+    #[test]
+    fn tx_inclusion_proof() {
+        let txs: Vec<blake3::Hash> = (0..5_u8).map(|i| blake3::hash(&[i])).collect();
+        let header = Header { root: Header::txs_root(&txs), ..Header::default() };
+
+        for (index, tx) in txs.iter().enumerate() {
+            let proof = TxInclusionProof::new(&txs, index).unwrap();
+            assert!(proof.verify(&header, tx));
+            // Proof doesn't hold for other transactions or positions
+            assert!(!proof.verify(&header, &txs[(index + 1) % txs.len()]));
+            let moved = TxInclusionProof { position: proof.position + 1, ..proof };
+            assert!(!moved.verify(&header, tx));
+        }
+
+        assert!(TxInclusionProof::new(&txs, txs.len()).is_none());
+        let empty = Header { root: Header::txs_root(&[]), ..Header::default() };
+        assert_eq!(empty.root, Header::genesis_header(header.timestamp, header.previous).root);
+    }
+}
@@ -16,11 +16,72 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use super::ChainParams;
 use crate::{util::time::Timestamp, Result};
 use log::debug;
-use std::{thread, time::Duration};
+use std::{
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicI64, Ordering},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
 use url::Url;
 
+/// Source of the current time consensus logic runs on
+pub trait TimeSource: Debug + Send + Sync {
+    /// Current time
+    fn now(&self) -> Timestamp;
+}
+
+/// Atomic pointer to a time source
+pub type TimeSourcePtr = Arc<dyn TimeSource>;
+
+/// Time source reading the system wall-clock time
+#[derive(Debug, Default)]
+pub struct SystemTime;
+
+impl SystemTime {
+    pub fn new() -> TimeSourcePtr {
+        Arc::new(Self)
+    }
+}
+
+impl TimeSource for SystemTime {
+    fn now(&self) -> Timestamp {
+        Timestamp::current_time()
+    }
+}
+
+/// Time source only moving when explicitly told to, so consensus
+/// logic can run through slots without waiting for them.
+#[derive(Debug)]
+pub struct SimulatedTime(AtomicI64);
+
+impl SimulatedTime {
+    pub fn new(start: Timestamp) -> Arc<Self> {
+        Arc::new(Self(AtomicI64::new(start.0)))
+    }
+
+    /// Set the current time
+    pub fn set(&self, time: Timestamp) {
+        self.0.store(time.0, Ordering::SeqCst);
+    }
+
+    /// Move the current time forward by given seconds
+    pub fn advance(&self, secs: u64) {
+        self.0.fetch_add(secs as i64, Ordering::SeqCst);
+    }
+}
+
+impl TimeSource for SimulatedTime {
+    fn now(&self) -> Timestamp {
+        Timestamp(self.0.load(Ordering::SeqCst))
+    }
+}
+
 pub enum Ticks {
     GENESIS { e: u64, sl: u64 },  //genesis epoch
     NEWSLOT { e: u64, sl: u64 },  // new slot
@@ -42,6 +103,7 @@ pub struct Clock {
     pub e_len: u64,    // epoch length in slots
     pub peers: Vec<Url>,
     pub genesis_time: Timestamp,
+    pub time: TimeSourcePtr,
 }
 
 impl Clock {
@@ -60,6 +122,22 @@ impl Clock {
             e_len: e_len.unwrap_or(3),        // 24.2 minutes
             peers,
             genesis_time: gt,
+            time: SystemTime::new(),
+        }
+    }
+
+    /// Create a clock ticking slots and epochs of the given chain parameters,
+    /// counted from their genesis timestamp, reading time from the given source.
+    pub fn from_params(params: &ChainParams, time: TimeSourcePtr, peers: Vec<Url>) -> Self {
+        Self {
+            sl: BB_SL,
+            e: BB_E,
+            tick_len: params.slot_time,
+            sl_len: params.slot_time,
+            e_len: params.epoch_length,
+            peers,
+            genesis_time: params.genesis_ts,
+            time,
         }
     }
 
@@ -73,7 +151,7 @@ impl Clock {
 
     async fn time(&self) -> Result<Timestamp> {
         //TODO (fix) add more than ntp server to time, and take the avg
-        Ok(Timestamp::current_time())
+        Ok(self.time.now())
     }
 
     /// returns time since genesis in seconds.
@@ -173,7 +251,8 @@ impl Clock {
 
 #[cfg(test)]
 mod tests {
-    use super::{Clock, Ticks};
+    use super::{Clock, SimulatedTime, SystemTime, Ticks};
+    use crate::{consensus::ChainParams, util::time::Timestamp};
     use futures::executor::block_on;
     use std::{thread, time::Duration};
     #[test]
@@ -185,6 +264,42 @@ mod tests {
         assert!((1..2).contains(&ttg));
     }
 
+    #[test]
+    fn clock_from_params() {
+        let mut params = ChainParams::devnet();
+        params.genesis_ts = Timestamp(Timestamp::current_time().0 - 3 * params.slot_time as i64);
+        let mut clock = Clock::from_params(&params, SystemTime::new(), vec![]);
+        assert_eq!(clock.get_sl_len(), params.slot_time);
+        assert_eq!(clock.get_e_len(), params.epoch_length);
+        block_on(clock.sync()).unwrap();
+        assert_eq!(clock.e, 0);
+        assert_eq!(clock.sl, 3);
+    }
+
+    #[test]
+    fn clock_simulated_time() {
+        let params = ChainParams::devnet();
+        let time = SimulatedTime::new(params.genesis_ts);
+        let mut clock = Clock::from_params(&params, time.clone(), vec![]);
+
+        // Genesis slot, at its beginning
+        assert!(matches!(block_on(clock.ticks()), Ticks::GENESIS { e: 0, sl: 0 }));
+        time.advance(params.slot_time - 1);
+        assert!(matches!(block_on(clock.ticks()), Ticks::TOCKS));
+
+        // Genesis tick already moves the clock to the next slot
+        time.advance(params.slot_time + 1);
+        assert!(matches!(block_on(clock.ticks()), Ticks::NEWSLOT { e: 0, sl: 2 }));
+
+        // First slot of next epoch
+        time.advance(params.slot_time * (params.epoch_length - 2));
+        assert!(matches!(block_on(clock.ticks()), Ticks::NEWEPOCH { e: 1, sl: 0 }));
+
+        // Skipping slots gets the clock out of sync
+        time.advance(params.slot_time * 2);
+        assert!(matches!(block_on(clock.ticks()), Ticks::OUTOFSYNC));
+    }
+
     fn _clock_ticking() {
         let clock = Clock::new(Some(9), Some(9), Some(9), vec![]);
         //block th for 3 secs
@@ -1,6 +1,13 @@
 -- Wallet definitions for consensus lead coins.
 
--- The consensus lead coin we have and can use
-CREATE TABLE IF NOT EXISTS consensus_coin (
-	coin BLOB
+-- NOTE: Earlier wallets kept unstaked coins in a `consensus_coin` table.
+-- Those coins can not compete anymore, since they were never staked, but
+-- the table is left untouched so their secrets are not lost.
+
+-- Secrets of the lead coins we staked through the money contract, along
+-- with the coin each one currently competes with, derived from it after
+-- every slot it won
+CREATE TABLE IF NOT EXISTS consensus_lead_coin (
+	staked BLOB NOT NULL,
+	coin BLOB NOT NULL
 );
@@ -80,13 +80,13 @@ pub const BLOCK_MAGIC_BYTES: [u8; 4] = [0x11, 0x6d, 0x75, 0x1f];
 /// Block info magic bytes
 pub const BLOCK_INFO_MAGIC_BYTES: [u8; 4] = [0x90, 0x44, 0xf1, 0xf6];
 
-/// Number of slots in one epoch
+/// Number of slots in one epoch, on testnet
 pub const EPOCH_LENGTH: usize = 10;
 
-/// Slot time in seconds
+/// Slot time in seconds, on testnet
 pub const SLOT_TIME: u64 = 90;
 
-/// Finalization sync period duration (should be >=2/3 of slot time)
+/// Finalization sync period duration (should be >=2/3 of slot time), on testnet
 pub const FINAL_SYNC_DUR: u64 = 60;
 
 /// Max resync retries duration in epochs
@@ -95,10 +95,40 @@ pub const SYNC_RETRIES_DURATION: u64 = 2;
 /// Max resync retries
 pub const SYNC_MAX_RETRIES: u64 = 10;
 
-/// Transactions included in a block cap
+/// Headers requested at once during header-first blockchain sync
+pub const SYNC_HEADERS_BATCH: u64 = 500;
+
+/// Block bodies requested at once from a single peer during blockchain sync
+pub const SYNC_BODIES_BATCH: usize = 10;
+
+/// Peers asked for the header chain during blockchain sync
+pub const SYNC_HEADER_PEERS: usize = 3;
+
+/// Seconds a peer gets to answer a blockchain sync request
+pub const SYNC_REQUEST_TIMEOUT: u64 = 30;
+
+/// Failed blockchain sync requests after which a peer is no longer used
+pub const SYNC_PEER_MAX_FAILURES: u32 = 3;
+
+/// Ban score for peers sending invalid blockchain sync data
+pub const SYNC_INVALID_DATA_BAN_SCORE: u32 = 50;
+
+/// Transactions included in a block cap, on testnet
 pub const TXS_CAP: usize = 50;
 
-/// Block leader reward
+/// Total serialized size of pending transactions cap, in bytes
+pub const MEMPOOL_MAX_BYTES: usize = 32 * 1024 * 1024;
+
+/// Seconds a pending transaction is kept before getting evicted
+pub const MEMPOOL_MAX_AGE: u64 = 4 * 60 * 60;
+
+/// Gas a transaction can spend executing its contract calls, on testnet
+pub const TX_GAS_LIMIT: u64 = 400_000_000;
+
+/// Minimum fee a transaction must prove paying, on testnet
+pub const MIN_TX_FEE: u64 = 1_000;
+
+/// Block leader reward, on testnet
 pub const REWARD: u64 = 1;
 
 /// Leader proofs k for zk proof rows (rows=2^k)
@@ -112,18 +142,18 @@ pub const LOTTERY_HEAD_START: u64 = 1;
 pub const PRF_NULLIFIER_PREFIX: u64 = 0;
 pub const PI_COMMITMENT_X_INDEX: usize = 1;
 pub const PI_COMMITMENT_Y_INDEX: usize = 2;
+pub const PI_DERIVED_COMMITMENT_X_INDEX: usize = 3;
+pub const PI_DERIVED_COMMITMENT_Y_INDEX: usize = 4;
 pub const PI_COMMITMENT_ROOT: usize = 5;
 pub const PI_NULLIFIER_INDEX: usize = 7;
 pub const PI_MU_Y_INDEX: usize = 8;
 pub const PI_MU_RHO_INDEX: usize = 10;
 pub const PI_SIGMA1_INDEX: usize = 12;
 pub const PI_SIGMA2_INDEX: usize = 13;
+pub const PI_REWARD_INDEX: usize = 14;
 pub const GENESIS_TOTAL_STAKE: u64 = 1;
 
-pub const LEADER_HISTORY_LOG: &str = "/tmp/lead_history.log";
-pub const F_HISTORY_LOG: &str = "/tmp/f_history.log";
-pub const LOTTERY_HISTORY_LOG: &str = "/tmp/lottery_history.log";
-
 // Wallet SQL table constant names. These have to represent the SQL schema.
-pub const CONSENSUS_COIN_TABLE: &str = "consensus_coin";
+pub const CONSENSUS_COIN_TABLE: &str = "consensus_lead_coin";
 pub const CONSENSUS_COIN_COL: &str = "coin";
+pub const CONSENSUS_COIN_COL_STAKED: &str = "staked";
@@ -31,7 +31,6 @@ use halo2_proofs::{arithmetic::Field, circuit::Value};
 use log::info;
 use rand::rngs::OsRng;
 
-use super::constants::EPOCH_LENGTH;
 use crate::{
     consensus::{constants, utils::fbig2base, Float10, TransferStx, TxRcpt},
     zk::{
@@ -46,6 +45,7 @@ use crate::{
 use std::{
     fs::File,
     io::{prelude::*, BufWriter},
+    path::Path,
 };
 
 pub const MERKLE_DEPTH_LEAD_COIN: usize = 32;
@@ -180,12 +180,14 @@ impl LeadCoin {
         current_eta: pallas::Base,
         current_slot: pallas::Base,
         derived_blind: pallas::Scalar,
+        reward: u64,
     ) -> Vec<pallas::Base> {
         // pk
         let pk = self.pk();
         // coin 1-2 cm/commitment
         let c1_cm_coord = self.coin1_commitment.to_affine().coordinates().unwrap();
-        let c2_cm_coord = self.derived_commitment(derived_blind).to_affine().coordinates().unwrap();
+        let c2_cm_coord =
+            self.derived_commitment(derived_blind, reward).to_affine().coordinates().unwrap();
         // lottery seed
         let seed_msg =
             [pallas::Base::from(PREFIX_SEED), self.coin1_sk_root.inner(), self.nonce, ZERO];
@@ -212,6 +214,7 @@ impl LeadCoin {
             rho,
             sigma1,
             sigma2,
+            pallas::Base::from(reward),
         ];
         public_inputs
     }
@@ -250,6 +253,7 @@ impl LeadCoin {
         sigma2: pallas::Base,
         current_eta: pallas::Base,
         current_slot: pallas::Base,
+        lottery_history_log: Option<&Path>,
     ) -> bool {
         let y_seed =
             [pallas::Base::from(PREFIX_SEED), self.coin1_sk_root.inner(), self.nonce, ZERO];
@@ -263,11 +267,9 @@ impl LeadCoin {
         let headstart = Self::headstart();
         let target = sigma1 * value + sigma2 * value * value + headstart;
 
-        let y_t_str = format!("{:?},{:?}\n", y, target);
-        let f =
-            File::options().append(true).create(true).open(constants::LOTTERY_HISTORY_LOG).unwrap();
-
-        {
+        if let Some(path) = lottery_history_log {
+            let y_t_str = format!("{:?},{:?}\n", y, target);
+            let f = File::options().append(true).create(true).open(path).unwrap();
             let mut writer = BufWriter::new(f);
             let _ = writer.write(&y_t_str.into_bytes()).unwrap();
         }
@@ -288,11 +290,16 @@ impl LeadCoin {
         let commit_v = poseidon_hash(commit_msg);
         pedersen_commitment_base(commit_v, blind)
     }
-    /// calculated derived coin commitment
-    pub fn derived_commitment(&self, blind: pallas::Scalar) -> pallas::Point {
+    /// Hash of the coin commitment, its leaf in the coin commitments tree
+    pub fn commitment_hash(&self) -> pallas::Base {
+        let coords = self.coin1_commitment.to_affine().coordinates().unwrap();
+        poseidon_hash([*coords.x(), *coords.y()])
+    }
+    /// calculated derived coin commitment, rewarded with given reward
+    pub fn derived_commitment(&self, blind: pallas::Scalar, reward: u64) -> pallas::Point {
         let pk = self.pk();
         let rho = self.derived_rho();
-        Self::commitment(pk, pallas::Base::from(self.value + constants::REWARD), rho, blind)
+        Self::commitment(pk, pallas::Base::from(self.value + reward), rho, blind)
     }
 
     /// the new coin to be minted after the current coin is spent
@@ -301,10 +308,11 @@ impl LeadCoin {
         &self,
         coin_commitment_tree: &mut BridgeTree<MerkleNode, MERKLE_DEPTH>,
         derived_blind: pallas::Scalar,
+        reward: u64,
     ) -> LeadCoin {
         info!(target: "consensus::leadcoin", "derive_coin(): Deriving new coin!");
         let derived_c1_rho = self.derived_rho();
-        let derived_c1_cm = self.derived_commitment(derived_blind);
+        let derived_c1_cm = self.derived_commitment(derived_blind, reward);
         let derived_c1_cm_coord = derived_c1_cm.to_affine().coordinates().unwrap();
         let derived_c1_cm_msg = [*derived_c1_cm_coord.x(), *derived_c1_cm_coord.y()];
         let derived_c1_cm_base = poseidon_hash(derived_c1_cm_msg);
@@ -314,7 +322,7 @@ impl LeadCoin {
         let commitment_merkle_path =
             coin_commitment_tree.authentication_path(leaf_pos, &commitment_root).unwrap();
         LeadCoin {
-            value: self.value + constants::REWARD,
+            value: self.value + reward,
             slot: self.slot,
             nonce: derived_c1_rho,
             coin1_commitment: derived_c1_cm,
@@ -338,6 +346,7 @@ impl LeadCoin {
         slot: pallas::Base, //current slot index.
         pk: &ProvingKey,
         derived_blind: pallas::Scalar,
+        reward: u64,
     ) -> (Result<Proof>, Vec<pallas::Base>) {
         let (y_mu, rho_mu) = Self::election_seeds(eta, slot);
         let bincode = include_bytes!("../../proof/lead.zk.bin");
@@ -364,9 +373,10 @@ impl LeadCoin {
             Witness::Base(Value::known(sigma1)),
             Witness::Base(Value::known(sigma2)),
             Witness::Base(Value::known(headstart)),
+            Witness::Base(Value::known(pallas::Base::from(reward))),
         ];
         let circuit = ZkCircuit::new(witnesses, zkbin);
-        let public_inputs = self.public_inputs(sigma1, sigma2, eta, slot, derived_blind);
+        let public_inputs = self.public_inputs(sigma1, sigma2, eta, slot, derived_blind, reward);
         (Ok(Proof::create(pk, &[circuit], &public_inputs, &mut OsRng).unwrap()), public_inputs)
     }
 
@@ -383,6 +393,7 @@ impl LeadCoin {
         current_eta: pallas::Base,
         current_slot: pallas::Base,
         derived_blind: pallas::Scalar,
+        reward: u64,
     ) -> Result<TransferStx> {
         assert!(change_coin.value + transfered_coin.value == self.value && self.value > 0);
         let bincode = include_bytes!("../../proof/tx.zk.bin");
@@ -423,7 +434,7 @@ impl LeadCoin {
         let proof = Proof::create(
             pk,
             &[circuit],
-            &self.public_inputs(sigma1, sigma2, current_eta, current_slot, derived_blind),
+            &self.public_inputs(sigma1, sigma2, current_eta, current_slot, derived_blind, reward),
             &mut OsRng,
         )?;
         let cm3_msg_in = [
@@ -475,15 +486,15 @@ impl LeadCoinSecrets {
     /// ...
     /// sk[n] -> derive_function(sk[n-1]),
     /// ```
-    pub fn generate() -> Self {
-        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(EPOCH_LENGTH);
-        let mut sks = Vec::with_capacity(EPOCH_LENGTH);
-        let mut root_sks = Vec::with_capacity(EPOCH_LENGTH);
-        let mut path_sks = Vec::with_capacity(EPOCH_LENGTH);
+    pub fn generate(epoch_length: usize) -> Self {
+        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(epoch_length);
+        let mut sks = Vec::with_capacity(epoch_length);
+        let mut root_sks = Vec::with_capacity(epoch_length);
+        let mut path_sks = Vec::with_capacity(epoch_length);
 
         let mut prev_sk = SecretKey::from(pallas::Base::one());
 
-        for i in 0..EPOCH_LENGTH {
+        for i in 0..epoch_length {
             let secret = if i == 0 {
                 pedersen_commitment_u64(1, pallas::Scalar::random(&mut OsRng))
             } else {
@@ -17,12 +17,13 @@
  */
 
 use darkfi_sdk::{
-    crypto::{schnorr::Signature, Keypair, PublicKey},
+    crypto::{poseidon_hash, schnorr::Signature, Keypair, MerkleNode, PublicKey},
     pasta::pallas,
 };
 use darkfi_serial::{SerialDecodable, SerialEncodable};
 use log::error;
 
+use super::constants;
 use crate::{
     zk::proof::{Proof, VerifyingKey},
     Result,
@@ -84,6 +85,20 @@ impl LeadInfo {
     ) -> Self {
         Self { signature, public_key, public_inputs, coin_slot, coin_eta, proof, leaders }
     }
+
+    /// Merkle root of the lead coins tree the leader coin was proven in.
+    pub fn commitment_root(&self) -> MerkleNode {
+        MerkleNode::from(self.public_inputs[constants::PI_COMMITMENT_ROOT])
+    }
+
+    /// Hash of the commitment of the coin derived from the leader coin,
+    /// its leaf in the lead coins tree.
+    pub fn derived_commitment_hash(&self) -> pallas::Base {
+        poseidon_hash([
+            self.public_inputs[constants::PI_DERIVED_COMMITMENT_X_INDEX],
+            self.public_inputs[constants::PI_DERIVED_COMMITMENT_Y_INDEX],
+        ])
+    }
 }
 
 /// Wrapper over the Proof, for future additions.
@@ -0,0 +1,428 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::collections::HashMap;
+
+use darkfi_sdk::{crypto::schnorr::SchnorrPublic, pasta::pallas};
+use log::{debug, error, info};
+
+use super::{
+    block::TxInclusionProof, constants, lead_coin::LeadCoin, state::ConsensusState, Block,
+    ChainParams, Header, SlotCheckpoint,
+};
+use crate::{
+    blockchain::Blockchain,
+    zk::{proof::VerifyingKey, vm::ZkCircuit, vm_stack::empty_witnesses},
+    zkas::ZkBinary,
+    Error, Result,
+};
+
+/// Light client following the finalized blockchain through block headers,
+/// without retrieving or executing block transactions.
+///
+/// Each followed block is checked to extend the previous one, and its leader
+/// signature, proof and public inputs are verified against the chain parameters
+/// and the slot checkpoint of its slot. Slot checkpoints are retrieved from
+/// nodes, so they must be provided before following the blocks of their slot.
+///
+/// Checkpoint sigmas derive from the total stake, which headers don't
+/// commit to, so they can't be verified here. A node could serve sigmas
+/// letting its own leaders win any slot, so the client trusts its sources
+/// not to collude: checkpoints should be retrieved from several independent
+/// nodes and all of them provided, as any disagreement gets rejected.
+/// Likewise, the lead coin roots live in the money contract state, so leader
+/// proofs can't be checked to prove a staked coin. Full nodes reject the
+/// blocks of unstaked leaders when applying them.
+/// Transactions are then verified through their inclusion proofs against the
+/// followed headers, so wallets can confirm payments against untrusted nodes.
+#[derive(Clone)]
+pub struct LightClient {
+    /// Parameters of the followed chain
+    pub params: ChainParams,
+    /// Leader proof verifying key
+    lead_verifying_key: VerifyingKey,
+    /// Followed headers, by their block hash
+    headers: HashMap<blake3::Hash, Header>,
+    /// Hash of the last followed block
+    last: blake3::Hash,
+    /// Slot checkpoints of slots not followed yet
+    slot_checkpoints: HashMap<u64, SlotCheckpoint>,
+    /// Seen leader coins nullifiers
+    nullifiers: Vec<pallas::Base>,
+}
+
+impl LightClient {
+    /// Create a light client following the chain of provided parameters,
+    /// starting from its genesis block.
+    pub fn new(params: ChainParams) -> Result<Self> {
+        debug!(target: "consensus::light_client", "Generating leader proof verifying key with k: {}", constants::LEADER_PROOF_K);
+        let bincode = include_bytes!("../../proof/lead.zk.bin");
+        let zkbin = ZkBinary::decode(bincode)?;
+        let witnesses = empty_witnesses(&zkbin);
+        let circuit = ZkCircuit::new(witnesses, zkbin);
+        let lead_verifying_key = VerifyingKey::build(constants::LEADER_PROOF_K, &circuit);
+
+        let genesis_header = Header::genesis_header(params.genesis_ts, params.genesis_data);
+        let last = Block::genesis_block(params.genesis_ts, params.genesis_data).blockhash();
+        let headers = HashMap::from([(last, genesis_header)]);
+
+        Ok(Self {
+            params,
+            lead_verifying_key,
+            headers,
+            last,
+            slot_checkpoints: HashMap::new(),
+            nullifiers: vec![],
+        })
+    }
+
+    /// Create a light client following the chain of provided parameters,
+    /// starting from the last block of given blockchain. Stored blocks are
+    /// trusted, so only what later blocks get checked against is loaded.
+    pub fn from_blockchain(params: ChainParams, blockchain: &Blockchain) -> Result<Self> {
+        let mut client = Self::new(params)?;
+
+        // Genesis block is already followed
+        let order: Vec<(u64, blake3::Hash)> =
+            blockchain.order.get_all()?.into_iter().filter(|(slot, _)| *slot != 0).collect();
+        let hashes: Vec<blake3::Hash> = order.iter().map(|(_, hash)| *hash).collect();
+        let blocks = blockchain.blocks.get(&hashes, true)?;
+        let header_hashes: Vec<blake3::Hash> = blocks.iter().flatten().map(|x| x.header).collect();
+        let headers = blockchain.headers.get(&header_hashes, true)?;
+
+        // Since we used strict gets, its safe to unwrap here
+        for ((hash, block), header) in hashes.into_iter().zip(blocks).zip(headers) {
+            let lf = block.unwrap().lead_info;
+            if let Some(sn) = lf.public_inputs.get(constants::PI_NULLIFIER_INDEX) {
+                client.nullifiers.push(*sn);
+            }
+            client.headers.insert(hash, header.unwrap());
+            client.last = hash;
+        }
+
+        Ok(client)
+    }
+
+    /// Retrieve the slot and hash of the last followed block.
+    pub fn last(&self) -> (u64, blake3::Hash) {
+        (self.headers[&self.last].slot, self.last)
+    }
+
+    /// Retrieve the followed header of provided block hash.
+    pub fn get_header(&self, hash: &blake3::Hash) -> Option<&Header> {
+        self.headers.get(hash)
+    }
+
+    /// Store provided slot checkpoints, to verify the blocks of their slots.
+    /// Checkpoints of already followed slots are ignored. Fails if one of
+    /// them differs from the checkpoint already provided for its slot, in
+    /// which case the sources providing them disagree and none is stored.
+    pub fn add_slot_checkpoints(&mut self, slot_checkpoints: &[SlotCheckpoint]) -> Result<()> {
+        let (last_slot, _) = self.last();
+        let mut pending = self.slot_checkpoints.clone();
+        for slot_checkpoint in slot_checkpoints {
+            if slot_checkpoint.slot <= last_slot {
+                continue
+            }
+
+            if let Some(existing) = pending.get(&slot_checkpoint.slot) {
+                if existing != slot_checkpoint {
+                    error!(target: "consensus::light_client", "add_slot_checkpoints(): Conflicting checkpoints for slot {}", slot_checkpoint.slot);
+                    return Err(Error::SlotCheckpointMismatch(slot_checkpoint.slot))
+                }
+                continue
+            }
+
+            pending.insert(slot_checkpoint.slot, slot_checkpoint.clone());
+        }
+
+        self.slot_checkpoints = pending;
+        Ok(())
+    }
+
+    /// Verify provided block extends the followed chain and append it.
+    /// Returns the followed block hash.
+    pub fn follow(&mut self, header: &Header, block: &Block) -> Result<blake3::Hash> {
+        if block.magic != self.params.network_magic {
+            error!(target: "consensus::light_client", "follow(): Block belongs to a different network");
+            return Err(Error::ProposalNetworkMismatchError)
+        }
+
+        if block.header != header.headerhash() {
+            error!(target: "consensus::light_client", "follow(): Block header hash doesn't match header");
+            return Err(Error::ProposalHeadersMissmatchError)
+        }
+
+        let (last_slot, last_hash) = self.last();
+        if header.previous != last_hash || header.slot <= last_slot {
+            error!(target: "consensus::light_client", "follow(): Block doesn't extend last block {}", last_hash);
+            return Err(Error::BlockNotExtendingChain)
+        }
+
+        // Block must have been created during its slot
+        let slot_start = self.params.genesis_ts.0 + (header.slot * self.params.slot_time) as i64;
+        if header.epoch != header.slot / self.params.epoch_length ||
+            header.timestamp.0 < slot_start ||
+            header.timestamp.0 >= slot_start + self.params.slot_time as i64
+        {
+            error!(target: "consensus::light_client", "follow(): Block timestamp or epoch doesn't match slot {}", header.slot);
+            return Err(Error::BlockSlotMismatch)
+        }
+
+        if header.root != Header::txs_root(&block.txs) {
+            error!(target: "consensus::light_client", "follow(): Block transactions don't match header root");
+            return Err(Error::BlockTxsRootMismatch)
+        }
+
+        self.verify_leader(header, block)?;
+
+        let hash = block.blockhash();
+        info!(target: "consensus::light_client", "follow(): Following block {} of slot {}", hash, header.slot);
+        self.nullifiers.push(block.lead_info.public_inputs[constants::PI_NULLIFIER_INDEX]);
+        self.slot_checkpoints.retain(|slot, _| *slot > header.slot);
+        self.headers.insert(hash, header.clone());
+        self.last = hash;
+
+        Ok(hash)
+    }
+
+    /// Verify provided block leader signature, proof and public inputs.
+    fn verify_leader(&self, header: &Header, block: &Block) -> Result<()> {
+        let lf = &block.lead_info;
+
+        if !lf.public_key.verify(block.header.as_bytes(), &lf.signature) {
+            error!(target: "consensus::light_client", "verify_leader(): Leader {} signature could not be verified", lf.public_key);
+            return Err(Error::InvalidSignature)
+        }
+
+        if lf.public_inputs.len() <= constants::PI_REWARD_INDEX {
+            error!(target: "consensus::light_client", "verify_leader(): Leader public inputs are missing");
+            return Err(Error::InvalidPublicInputsError)
+        }
+
+        // Leader coin must not have been used in a followed block
+        let sn = lf.public_inputs[constants::PI_NULLIFIER_INDEX];
+        if self.nullifiers.contains(&sn) {
+            error!(target: "consensus::light_client", "verify_leader(): Leader coin nullifier exists");
+            return Err(Error::ProposalIsSpent)
+        }
+
+        // Leader lottery eta must come from a block of the followed chain
+        if !self.is_ancestor_eta(header.previous, lf.coin_eta) {
+            error!(target: "consensus::light_client", "verify_leader(): Leader eta doesn't match any followed block");
+            return Err(Error::ProposalDifferentCoinEtaError)
+        }
+
+        let Some(slot_checkpoint) = self.slot_checkpoints.get(&header.slot) else {
+            error!(target: "consensus::light_client", "verify_leader(): Missing slot checkpoint of slot {}", header.slot);
+            return Err(Error::SlotCheckpointNotFound(header.slot))
+        };
+
+        let (mu_y, mu_rho) = LeadCoin::election_seeds_u64(lf.coin_eta, header.slot);
+        let expected = [
+            (constants::PI_MU_Y_INDEX, mu_y),
+            (constants::PI_MU_RHO_INDEX, mu_rho),
+            (constants::PI_SIGMA1_INDEX, slot_checkpoint.sigma1),
+            (constants::PI_SIGMA2_INDEX, slot_checkpoint.sigma2),
+            (constants::PI_REWARD_INDEX, pallas::Base::from(self.params.reward(header.slot))),
+        ];
+        for (index, value) in expected {
+            if lf.public_inputs[index] != value {
+                error!(
+                    target: "consensus::light_client",
+                    "verify_leader(): Failed to verify public input {}: {:?}, proposed: {:?}",
+                    index, value, lf.public_inputs[index]
+                );
+                return Err(Error::ProposalPublicValuesMismatched)
+            }
+        }
+
+        if let Err(e) = lf.proof.verify(&self.lead_verifying_key, &lf.public_inputs) {
+            error!(target: "consensus::light_client", "verify_leader(): Error during leader proof verification: {}", e);
+            return Err(Error::LeaderProofVerification)
+        }
+
+        Ok(())
+    }
+
+    /// Check if provided eta is derived from given block hash or any of its followed ancestors.
+    fn is_ancestor_eta(&self, mut hash: blake3::Hash, eta: pallas::Base) -> bool {
+        while let Some(header) = self.headers.get(&hash) {
+            if ConsensusState::block_eta(&hash) == eta {
+                return true
+            }
+            // Genesis header doesn't point to a block
+            if header.slot == 0 {
+                break
+            }
+            hash = header.previous;
+        }
+
+        false
+    }
+
+    /// Verify provided transaction hash is included in given followed block.
+    pub fn verify_tx(
+        &self,
+        block: &blake3::Hash,
+        tx: &blake3::Hash,
+        proof: &TxInclusionProof,
+    ) -> Result<()> {
+        let Some(header) = self.headers.get(block) else {
+            return Err(Error::BlockNotFound(block.to_string()))
+        };
+
+        if !proof.verify(header, tx) {
+            error!(target: "consensus::light_client", "verify_tx(): Transaction {} inclusion proof is invalid", tx);
+            return Err(Error::TxNotIncluded(tx.to_string(), block.to_string()))
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::crypto::MerkleNode;
+
+    use super::*;
+    use crate::consensus::{testing::TestChain, BlockInfo};
+
+    fn follow(client: &mut LightClient, block: &BlockInfo) -> Result<blake3::Hash> {
+        client.follow(&block.header, &block.clone().into())
+    }
+
+    #[test]
+    fn follow_valid_chain() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        chain.extend(1);
+        chain.extend(2);
+        chain.extend(5);
+
+        let mut client = LightClient::new(chain.params.clone())?;
+        client.add_slot_checkpoints(&chain.slot_checkpoints)?;
+        for block in &chain.blocks {
+            follow(&mut client, block)?;
+        }
+        assert_eq!(client.last(), chain.last());
+        assert_eq!(client.get_header(&chain.blocks[1].blockhash()), Some(&chain.blocks[1].header));
+
+        // Checkpoints of followed slots are ignored, even conflicting ones
+        client.add_slot_checkpoints(&[TestChain::slot_checkpoint(2, pallas::Base::from(42))])?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn follow_rejects_invalid_headers() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        let block = chain.extend(1);
+        let mut client = LightClient::new(chain.params.clone())?;
+        client.add_slot_checkpoints(&chain.slot_checkpoints)?;
+
+        let mut bad = block.clone();
+        bad.magic = [0x00; 4];
+        assert!(matches!(follow(&mut client, &bad), Err(Error::ProposalNetworkMismatchError)));
+
+        let mut bad = Block::from(block.clone());
+        bad.header = blake3::hash(b"header");
+        assert!(matches!(
+            client.follow(&block.header, &bad),
+            Err(Error::ProposalHeadersMissmatchError)
+        ));
+
+        let mut bad = block.clone();
+        bad.header.previous = blake3::hash(b"previous");
+        assert!(matches!(follow(&mut client, &bad), Err(Error::BlockNotExtendingChain)));
+
+        let mut bad = block.clone();
+        bad.header.timestamp.0 += chain.params.slot_time as i64;
+        assert!(matches!(follow(&mut client, &bad), Err(Error::BlockSlotMismatch)));
+
+        let mut bad = block.clone();
+        bad.header.root = MerkleNode::from(pallas::Base::from(42));
+        assert!(matches!(follow(&mut client, &bad), Err(Error::BlockTxsRootMismatch)));
+
+        // Nothing got followed
+        assert_eq!(client.last().0, 0);
+        follow(&mut client, &block)?;
+        assert!(matches!(follow(&mut client, &block), Err(Error::BlockNotExtendingChain)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn follow_rejects_invalid_leaders() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        let mut client = LightClient::new(chain.params.clone())?;
+
+        let (_, genesis) = chain.last();
+        let eta = ConsensusState::block_eta(&genesis);
+        let coin = chain.winning_coin(1, eta);
+        let block = chain.block(genesis, 1, &coin, eta);
+        client.add_slot_checkpoints(&[TestChain::slot_checkpoint(1, eta)])?;
+        let previous = follow(&mut client, &block)?;
+
+        // Leader coins can only be used once
+        let bad = chain.block(previous, 2, &coin, eta);
+        assert!(matches!(follow(&mut client, &bad), Err(Error::ProposalIsSpent)));
+
+        // Eta must come from a followed block
+        let bad_eta = pallas::Base::from(42);
+        let bad_coin = chain.winning_coin(2, bad_eta);
+        let bad = chain.block(previous, 2, &bad_coin, bad_eta);
+        assert!(matches!(follow(&mut client, &bad), Err(Error::ProposalDifferentCoinEtaError)));
+
+        let eta = ConsensusState::block_eta(&previous);
+        let coin = chain.winning_coin(2, eta);
+        let block = chain.block(previous, 2, &coin, eta);
+
+        // Signature covers the header
+        let mut bad = block.clone();
+        bad.header.timestamp.0 += 1;
+        assert!(matches!(follow(&mut client, &bad), Err(Error::InvalidSignature)));
+
+        // Slot checkpoint must be provided, and match the proof sigmas
+        assert!(matches!(follow(&mut client, &block), Err(Error::SlotCheckpointNotFound(2))));
+        let mut bad_checkpoint = TestChain::slot_checkpoint(2, eta);
+        bad_checkpoint.sigma1 = pallas::Base::from(42);
+        client.add_slot_checkpoints(&[bad_checkpoint])?;
+        assert!(matches!(follow(&mut client, &block), Err(Error::ProposalPublicValuesMismatched)));
+
+        // Conflicting checkpoints are rejected as a whole
+        let checkpoints = [TestChain::slot_checkpoint(3, eta), TestChain::slot_checkpoint(2, eta)];
+        assert!(matches!(
+            client.add_slot_checkpoints(&checkpoints),
+            Err(Error::SlotCheckpointMismatch(2))
+        ));
+        client.add_slot_checkpoints(&checkpoints[..1])?;
+
+        // Proof must be valid for the block public inputs
+        let coin = chain.winning_coin(3, eta);
+        let block = chain.block(previous, 3, &coin, eta);
+        let mut bad = block.clone();
+        let other_coin = chain.winning_coin(3, eta);
+        bad.lead_info.proof = chain.block(previous, 3, &other_coin, eta).lead_info.proof;
+        assert!(matches!(follow(&mut client, &bad), Err(Error::LeaderProofVerification)));
+
+        assert_eq!(follow(&mut client, &block)?, block.blockhash());
+        assert_eq!(client.last(), (3, block.blockhash()));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,478 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use darkfi_sdk::crypto::Nullifier;
+use darkfi_serial::serialize;
+use log::{debug, info};
+
+use super::constants;
+use crate::{
+    blockchain::{proven_fee, revealed_nullifiers, Blockchain, TxReceipt},
+    tx::Transaction,
+    util::time::Timestamp,
+    Error, Result,
+};
+
+/// A pending transaction, along with the metadata used to order and evict it.
+#[derive(Debug, Clone)]
+pub struct MempoolEntry {
+    /// The pending transaction
+    pub tx: Transaction,
+    /// Transaction hash
+    pub hash: blake3::Hash,
+    /// Serialized transaction size in bytes
+    pub size: usize,
+    /// Arrival sequence number, lower means older
+    pub seq: u64,
+    /// Time the transaction entered the mempool
+    pub received: Timestamp,
+    /// Nullifiers revealed by the transaction
+    pub nullifiers: Vec<Nullifier>,
+    /// Minimum fee the transaction proved paying, zero if it has no fee call
+    pub fee: u64,
+}
+
+impl MempoolEntry {
+    fn new(tx: Transaction, receipt: &TxReceipt, seq: u64) -> Self {
+        let serialized = serialize(&tx);
+        let hash = blake3::hash(&serialized);
+        let nullifiers = revealed_nullifiers(receipt);
+        let fee = proven_fee(receipt).unwrap_or(0);
+        Self {
+            tx,
+            hash,
+            size: serialized.len(),
+            seq,
+            received: Timestamp::current_time(),
+            nullifiers,
+            fee,
+        }
+    }
+}
+
+/// Ordering policy of the mempool. Entries ordered first get proposed
+/// first and evicted last, and a transaction only replaces conflicting
+/// ones ordered after it.
+pub trait MempoolOrdering: Send + Sync {
+    fn cmp(&self, a: &MempoolEntry, b: &MempoolEntry) -> Ordering;
+}
+
+/// First come, first served ordering
+pub struct FifoOrdering;
+
+impl MempoolOrdering for FifoOrdering {
+    fn cmp(&self, a: &MempoolEntry, b: &MempoolEntry) -> Ordering {
+        a.seq.cmp(&b.seq)
+    }
+}
+
+/// Highest fee per byte first, falling back to first come, first served
+pub struct FeeRateOrdering;
+
+impl MempoolOrdering for FeeRateOrdering {
+    fn cmp(&self, a: &MempoolEntry, b: &MempoolEntry) -> Ordering {
+        // Compare a.fee / a.size against b.fee / b.size without dividing
+        let a_rate = a.fee as u128 * b.size as u128;
+        let b_rate = b.fee as u128 * a.size as u128;
+        b_rate.cmp(&a_rate).then(a.seq.cmp(&b.seq))
+    }
+}
+
+/// Mempool limits and ordering policy
+pub struct MempoolConfig {
+    /// Total serialized size of pending transactions cap
+    pub max_bytes: usize,
+    /// Seconds a pending transaction is kept before getting evicted
+    pub max_age: u64,
+    /// Ordering policy
+    pub ordering: Box<dyn MempoolOrdering>,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes: constants::MEMPOOL_MAX_BYTES,
+            max_age: constants::MEMPOOL_MAX_AGE,
+            ordering: Box::new(FeeRateOrdering),
+        }
+    }
+}
+
+/// Pool of verified transactions waiting to be included in a block.
+/// Entries are kept in memory and persisted in the blockchain pending
+/// txs stores, so they can be revalidated after a restart.
+pub struct Mempool {
+    /// Canonical blockchain, holding the pending txs stores
+    blockchain: Blockchain,
+    /// Limits and ordering policy
+    config: MempoolConfig,
+    /// Pending transactions by hash
+    entries: HashMap<blake3::Hash, MempoolEntry>,
+    /// Hash of the pending transaction revealing each nullifier
+    nullifiers: HashMap<[u8; 32], blake3::Hash>,
+    /// Total serialized size of pending transactions
+    bytes: usize,
+    /// Sequence number of the next entry
+    next_seq: u64,
+}
+
+impl Mempool {
+    /// Create a new empty mempool. Transactions persisted in the pending
+    /// txs stores are left there, see [`Mempool::take_persisted`].
+    pub fn new(blockchain: Blockchain, config: MempoolConfig) -> Self {
+        Self {
+            blockchain,
+            config,
+            entries: HashMap::new(),
+            nullifiers: HashMap::new(),
+            bytes: 0,
+            next_seq: 0,
+        }
+    }
+
+    /// Remove and return the transactions persisted in the pending txs
+    /// stores by a previous run. Their receipts are not persisted, so they
+    /// have to be verified again before getting inserted.
+    pub fn take_persisted(&self) -> Result<Vec<Transaction>> {
+        let txs = self.blockchain.get_pending_txs()?;
+        self.blockchain.remove_pending_txs(&txs)?;
+        info!(target: "consensus::mempool", "Took {} persisted pending transactions", txs.len());
+        Ok(txs)
+    }
+
+    /// Replace the mempool ordering policy.
+    pub fn set_ordering(&mut self, ordering: Box<dyn MempoolOrdering>) {
+        self.config.ordering = ordering;
+    }
+
+    fn track(&mut self, entry: MempoolEntry) {
+        for nullifier in &entry.nullifiers {
+            self.nullifiers.insert(nullifier.to_bytes(), entry.hash);
+        }
+        self.bytes += entry.size;
+        self.entries.insert(entry.hash, entry);
+    }
+
+    fn untrack(&mut self, hash: &blake3::Hash) -> Option<MempoolEntry> {
+        let entry = self.entries.remove(hash)?;
+        for nullifier in &entry.nullifiers {
+            self.nullifiers.remove(&nullifier.to_bytes());
+        }
+        self.bytes -= entry.size;
+        Some(entry)
+    }
+
+    /// Drop given entries from memory and from the pending txs stores,
+    /// returning their transactions.
+    fn drop_entries(&mut self, hashes: &[blake3::Hash]) -> Result<Vec<Transaction>> {
+        let removed: Vec<Transaction> =
+            hashes.iter().filter_map(|hash| self.untrack(hash)).map(|entry| entry.tx).collect();
+        if !removed.is_empty() {
+            self.blockchain.remove_pending_txs(&removed)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Insert an already verified transaction, along with the receipt of its
+    /// verification. Pending transactions revealing the same nullifiers get
+    /// replaced if they are ordered after it, and the lowest ordered ones get
+    /// evicted to stay under the size cap. Returns the replaced and evicted
+    /// transactions.
+    pub fn insert(&mut self, tx: Transaction, receipt: &TxReceipt) -> Result<Vec<Transaction>> {
+        self.evict_expired()?;
+
+        let entry = MempoolEntry::new(tx, receipt, self.next_seq);
+        if self.entries.contains_key(&entry.hash) {
+            return Ok(vec![])
+        }
+        if entry.size > self.config.max_bytes {
+            return Err(Error::MempoolFull)
+        }
+
+        // Conflicting transactions must all be ordered after the new one
+        let mut dropped = vec![];
+        for nullifier in &entry.nullifiers {
+            let Some(hash) = self.nullifiers.get(&nullifier.to_bytes()) else { continue };
+            if dropped.contains(hash) {
+                continue
+            }
+            if self.config.ordering.cmp(&entry, &self.entries[hash]) != Ordering::Less {
+                return Err(Error::MempoolConflict)
+            }
+            dropped.push(*hash);
+        }
+
+        // Make room by evicting the lowest ordered transactions
+        let mut bytes = self.bytes - dropped.iter().map(|x| self.entries[x].size).sum::<usize>();
+        if bytes + entry.size > self.config.max_bytes {
+            let mut candidates: Vec<&MempoolEntry> =
+                self.entries.values().filter(|x| !dropped.contains(&x.hash)).collect();
+            candidates.sort_by(|a, b| self.config.ordering.cmp(a, b));
+            while bytes + entry.size > self.config.max_bytes {
+                let lowest = candidates.pop().unwrap();
+                if self.config.ordering.cmp(&entry, lowest) != Ordering::Less {
+                    return Err(Error::MempoolFull)
+                }
+                bytes -= lowest.size;
+                dropped.push(lowest.hash);
+            }
+        }
+
+        let dropped = self.drop_entries(&dropped)?;
+        if !dropped.is_empty() {
+            debug!(target: "consensus::mempool", "Dropped {} pending transactions for {}", dropped.len(), entry.hash);
+        }
+
+        self.blockchain.add_pending_txs(&[entry.tx.clone()])?;
+        self.next_seq += 1;
+        self.track(entry);
+
+        Ok(dropped)
+    }
+
+    /// Remove given transactions, if pending.
+    pub fn remove(&mut self, txs: &[Transaction]) -> Result<()> {
+        let hashes: Vec<blake3::Hash> = txs.iter().map(|tx| blake3::hash(&serialize(tx))).collect();
+        self.drop_entries(&hashes)?;
+        Ok(())
+    }
+
+    /// Evict transactions pending for longer than the configured max age,
+    /// returning them.
+    pub fn evict_expired(&mut self) -> Result<Vec<Transaction>> {
+        let expired: Vec<blake3::Hash> = self
+            .entries
+            .values()
+            .filter(|x| x.received.elapsed() > self.config.max_age)
+            .map(|x| x.hash)
+            .collect();
+
+        let expired = self.drop_entries(&expired)?;
+        if !expired.is_empty() {
+            info!(target: "consensus::mempool", "Evicted {} expired pending transactions", expired.len());
+        }
+
+        Ok(expired)
+    }
+
+    /// Retrieve all pending transactions, ordered by the ordering policy.
+    pub fn txs(&self) -> Vec<Transaction> {
+        let mut entries: Vec<&MempoolEntry> = self.entries.values().collect();
+        entries.sort_by(|a, b| self.config.ordering.cmp(a, b));
+        entries.into_iter().map(|x| x.tx.clone()).collect()
+    }
+
+    /// Check if given transaction is pending.
+    pub fn contains(&self, tx_hash: &blake3::Hash) -> bool {
+        self.entries.contains_key(tx_hash)
+    }
+
+    /// Retrieve a pending transaction by its hash.
+    pub fn get(&self, tx_hash: &blake3::Hash) -> Option<Transaction> {
+        self.entries.get(tx_hash).map(|x| x.tx.clone())
+    }
+
+    /// Total serialized size of pending transactions.
+    pub fn size(&self) -> usize {
+        self.bytes
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp::Ordering;
+
+    use darkfi_sdk::{
+        crypto::{Nullifier, DAO_CONTRACT_ID, MONEY_CONTRACT_ID},
+        event::{ContractEvent, FEE_EVENT_TOPIC, NULLIFIER_EVENT_TOPIC},
+        pasta::pallas,
+        tx::ContractCall,
+    };
+    use darkfi_serial::serialize;
+
+    use super::{FifoOrdering, Mempool, MempoolConfig, MempoolEntry, MempoolOrdering};
+    use crate::{
+        blockchain::{Blockchain, TxReceipt},
+        consensus::{TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP},
+        tx::Transaction,
+        Error, Result,
+    };
+
+    /// Orders transactions by their first call data byte, highest first
+    struct DataOrdering;
+
+    impl MempoolOrdering for DataOrdering {
+        fn cmp(&self, a: &MempoolEntry, b: &MempoolEntry) -> Ordering {
+            let key = |x: &MempoolEntry| x.tx.calls[0].data.last().copied();
+            key(b).cmp(&key(a))
+        }
+    }
+
+    fn dummy_tx(data: Vec<u8>) -> Transaction {
+        let calls = vec![ContractCall { contract_id: *DAO_CONTRACT_ID, data }];
+        Transaction { calls, proofs: vec![], signatures: vec![] }
+    }
+
+    /// A money transfer ending with `tag`, and the receipt reporting
+    /// the nullifier it reveals
+    fn transfer_tx(nullifier: u64, tag: u8) -> (Transaction, TxReceipt) {
+        let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data: vec![0x00, tag] }];
+        let tx = Transaction { calls, proofs: vec![], signatures: vec![] };
+
+        let nullifier = Nullifier::from(pallas::Base::from(nullifier));
+        let event = ContractEvent {
+            contract_id: *MONEY_CONTRACT_ID,
+            call_idx: 0,
+            topics: vec![vec![0x00], NULLIFIER_EVENT_TOPIC.to_vec(), serialize(&nullifier)],
+            data: vec![],
+        };
+
+        (tx, TxReceipt { gas_used: 0, events: vec![event] })
+    }
+
+    /// A transaction padded with `padding` bytes, and the receipt reporting
+    /// the minimum fee it proved paying
+    fn fee_tx(fee: u64, padding: usize) -> (Transaction, TxReceipt) {
+        let mut data = vec![0x04];
+        data.extend(serialize(&fee));
+        data.extend(vec![0; padding]);
+        let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data }];
+        let tx = Transaction { calls, proofs: vec![], signatures: vec![] };
+
+        let event = ContractEvent {
+            contract_id: *MONEY_CONTRACT_ID,
+            call_idx: 0,
+            topics: vec![vec![0x04], FEE_EVENT_TOPIC.to_vec()],
+            data: serialize(&fee),
+        };
+
+        (tx, TxReceipt { gas_used: 0, events: vec![event] })
+    }
+
+    fn mempool(max_bytes: usize) -> Result<(Blockchain, Mempool)> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        let config = MempoolConfig { max_bytes, ..Default::default() };
+        let mempool = Mempool::new(blockchain.clone(), config);
+        Ok((blockchain, mempool))
+    }
+
+    #[test]
+    fn test_mempool_size_cap() -> Result<()> {
+        let txs: Vec<Transaction> = (0..4).map(|i| dummy_tx(vec![i; 100])).collect();
+        let tx_size = serialize(&txs[0]).len();
+        let (blockchain, mut mempool) = mempool(tx_size * 3)?;
+
+        // Oversized transactions never get in
+        let result = mempool.insert(dummy_tx(vec![0; tx_size * 3]), &TxReceipt::default());
+        assert!(matches!(result, Err(Error::MempoolFull)));
+
+        // Without fees, a full mempool rejects newcomers
+        for tx in &txs[..3] {
+            assert!(mempool.insert(tx.clone(), &TxReceipt::default())?.is_empty());
+        }
+        let result = mempool.insert(txs[3].clone(), &TxReceipt::default());
+        assert!(matches!(result, Err(Error::MempoolFull)));
+        assert_eq!(mempool.size(), tx_size * 3);
+        assert_eq!(mempool.txs(), txs[..3]);
+
+        // Higher ordered newcomers evict the lowest ordered transactions
+        mempool.set_ordering(Box::new(DataOrdering));
+        assert_eq!(mempool.insert(txs[3].clone(), &TxReceipt::default())?, vec![txs[0].clone()]);
+        assert_eq!(mempool.txs(), vec![txs[3].clone(), txs[2].clone(), txs[1].clone()]);
+
+        // Removed transactions are gone from the stores too
+        mempool.remove(&txs[2..])?;
+        assert_eq!(mempool.txs(), vec![txs[1].clone()]);
+        assert_eq!(blockchain.get_pending_txs()?, vec![txs[1].clone()]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_conflicts() -> Result<()> {
+        let (blockchain, mut mempool) = mempool(usize::MAX)?;
+
+        let (tx1, receipt1) = transfer_tx(1, 1);
+        let (tx2, receipt2) = transfer_tx(1, 2);
+        let (tx3, receipt3) = transfer_tx(2, 3);
+        mempool.insert(tx1.clone(), &receipt1)?;
+        mempool.insert(tx3.clone(), &receipt3)?;
+
+        // Without fees, the first transaction revealing a nullifier stays
+        let result = mempool.insert(tx2.clone(), &receipt2);
+        assert!(matches!(result, Err(Error::MempoolConflict)));
+
+        // Nullifiers only come from receipts
+        let (tx4, _) = transfer_tx(1, 4);
+        assert!(mempool.insert(tx4.clone(), &TxReceipt::default())?.is_empty());
+        mempool.remove(&[tx4])?;
+
+        // Higher ordered transactions replace conflicting ones
+        mempool.set_ordering(Box::new(DataOrdering));
+        assert_eq!(mempool.insert(tx2.clone(), &receipt2)?, vec![tx1.clone()]);
+        assert!(!mempool.contains(&blake3::hash(&serialize(&tx1))));
+        assert_eq!(mempool.txs(), vec![tx3.clone(), tx2.clone()]);
+
+        // Pending transactions are persisted, to be verified again after a restart
+        let reloaded = Mempool::new(blockchain.clone(), MempoolConfig::default());
+        assert!(reloaded.is_empty());
+        assert_eq!(reloaded.take_persisted()?, vec![tx3, tx2]);
+        assert!(blockchain.get_pending_txs()?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_mempool_fee_rate() -> Result<()> {
+        let (_, mut mempool) = mempool(usize::MAX)?;
+
+        let (free, free_receipt) = (dummy_tx(vec![0; 10]), TxReceipt::default());
+        let (cheap, cheap_receipt) = fee_tx(10, 1000);
+        let (small, small_receipt) = fee_tx(10, 0);
+        let (large, large_receipt) = fee_tx(20, 1000);
+        for (tx, receipt) in [
+            (&free, &free_receipt),
+            (&cheap, &cheap_receipt),
+            (&small, &small_receipt),
+            (&large, &large_receipt),
+        ] {
+            mempool.insert(tx.clone(), receipt)?;
+        }
+
+        // Smaller transactions pay more per byte for the same fee
+        assert_eq!(mempool.txs(), vec![small.clone(), large.clone(), cheap.clone(), free.clone()]);
+
+        mempool.set_ordering(Box::new(FifoOrdering));
+        assert_eq!(mempool.txs(), vec![free, cheap, small, large]);
+
+        Ok(())
+    }
+}
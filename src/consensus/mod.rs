@@ -18,7 +18,7 @@
 
 /// Block definition
 pub mod block;
-pub use block::{Block, BlockInfo, BlockProposal, Header};
+pub use block::{Block, BlockInfo, BlockProposal, Header, TxInclusionProof};
 
 /// Constants
 pub mod constants;
@@ -31,6 +31,10 @@ pub use constants::{
 pub mod lead_info;
 pub use lead_info::{LeadInfo, LeadProof};
 
+/// Chain parameters
+pub mod params;
+pub use params::ChainParams;
+
 /// Consensus state
 pub mod state;
 pub use state::SlotCheckpoint;
@@ -39,6 +43,18 @@ pub use state::SlotCheckpoint;
 pub mod validator;
 pub use validator::{ValidatorState, ValidatorStatePtr};
 
+/// Light client following block headers
+pub mod light_client;
+pub use light_client::LightClient;
+
+/// Test chains with valid leader proofs
+#[cfg(test)]
+pub(crate) mod testing;
+
+/// Pending transactions pool
+pub mod mempool;
+pub use mempool::{Mempool, MempoolConfig, MempoolOrdering};
+
 /// P2P net protocols
 pub mod proto;
 
@@ -47,7 +63,10 @@ pub mod task;
 
 /// Lamport clock
 pub mod clock;
-pub use clock::{Clock, Ticks};
+pub use clock::{Clock, SimulatedTime, SystemTime, Ticks, TimeSource, TimeSourcePtr};
+
+/// Deterministic consensus simulation
+pub mod simulation;
 
 /// Consensus participation coin functions and definitions
 pub mod lead_coin;
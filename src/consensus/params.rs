@@ -0,0 +1,190 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::path::PathBuf;
+
+use super::constants::{
+    BLOCK_MAGIC_BYTES, EPOCH_LENGTH, FINAL_SYNC_DUR, MAINNET_BOOTSTRAP_TIMESTAMP,
+    MAINNET_GENESIS_HASH_BYTES, MAINNET_GENESIS_TIMESTAMP, MAINNET_INITIAL_DISTRIBUTION,
+    MIN_TX_FEE, REWARD, SLOT_TIME, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
+    TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION, TXS_CAP, TX_GAS_LIMIT,
+};
+use crate::{util::time::Timestamp, Error, Result};
+
+/// Parameters of the chain a node runs on. Nodes of the same network
+/// must use the same parameters.
+#[derive(Clone, Debug)]
+pub struct ChainParams {
+    /// Magic bytes of the network blocks
+    pub network_magic: [u8; 4],
+    /// Network bootstrap timestamp
+    pub bootstrap_ts: Timestamp,
+    /// Genesis block creation timestamp
+    pub genesis_ts: Timestamp,
+    /// Genesis block data
+    pub genesis_data: blake3::Hash,
+    /// Total sum of initial staking coins
+    pub initial_distribution: u64,
+    /// Slot time in seconds
+    pub slot_time: u64,
+    /// Finalization sync period duration (should be >=2/3 of slot time)
+    pub final_sync_dur: u64,
+    /// Number of slots in one epoch
+    pub epoch_length: u64,
+    /// Transactions included in a block cap
+    pub txs_cap: usize,
+    /// Gas a transaction can spend executing its contract calls
+    pub tx_gas_limit: u64,
+    /// Minimum fee a transaction must prove paying, 0 doesn't require a fee call
+    pub min_tx_fee: u64,
+    /// Block leader reward
+    pub reward: u64,
+    /// Slots after which the block leader reward halves, 0 keeps it constant
+    pub reward_halving_interval: u64,
+    /// File the slot leaders count history is appended to
+    pub leader_history_log: Option<PathBuf>,
+    /// File the controller output history is appended to
+    pub f_history_log: Option<PathBuf>,
+    /// File the lottery history is appended to
+    pub lottery_history_log: Option<PathBuf>,
+}
+
+impl ChainParams {
+    /// Parameters of the mainnet chain
+    pub fn mainnet() -> Self {
+        Self {
+            bootstrap_ts: *MAINNET_BOOTSTRAP_TIMESTAMP,
+            genesis_ts: *MAINNET_GENESIS_TIMESTAMP,
+            genesis_data: *MAINNET_GENESIS_HASH_BYTES,
+            initial_distribution: *MAINNET_INITIAL_DISTRIBUTION,
+            ..Self::testnet()
+        }
+    }
+
+    /// Parameters of the testnet chain
+    pub fn testnet() -> Self {
+        Self {
+            network_magic: BLOCK_MAGIC_BYTES,
+            bootstrap_ts: *TESTNET_BOOTSTRAP_TIMESTAMP,
+            genesis_ts: *TESTNET_GENESIS_TIMESTAMP,
+            genesis_data: *TESTNET_GENESIS_HASH_BYTES,
+            initial_distribution: *TESTNET_INITIAL_DISTRIBUTION,
+            slot_time: SLOT_TIME,
+            final_sync_dur: FINAL_SYNC_DUR,
+            epoch_length: EPOCH_LENGTH as u64,
+            txs_cap: TXS_CAP,
+            tx_gas_limit: TX_GAS_LIMIT,
+            min_tx_fee: MIN_TX_FEE,
+            reward: REWARD,
+            reward_halving_interval: 0,
+            leader_history_log: None,
+            f_history_log: None,
+            lottery_history_log: None,
+        }
+    }
+
+    /// Parameters of a local development chain, using short slots and epochs
+    pub fn devnet() -> Self {
+        Self {
+            network_magic: [0x64, 0x65, 0x76, 0x6e],
+            genesis_data: blake3::hash(b"darkfi_devnet"),
+            slot_time: 20,
+            final_sync_dur: 14,
+            epoch_length: 5,
+            // Local transactions don't need to pay fees
+            min_tx_fee: 0,
+            ..Self::testnet()
+        }
+    }
+
+    /// Parameters of the given named chain
+    pub fn from_chain(chain: &str) -> Result<Self> {
+        match chain {
+            "mainnet" => Ok(Self::mainnet()),
+            "testnet" => Ok(Self::testnet()),
+            "devnet" => Ok(Self::devnet()),
+            _ => Err(Error::UnsupportedChain),
+        }
+    }
+
+    /// Block leader reward of the given slot
+    pub fn reward(&self, slot: u64) -> u64 {
+        if self.reward_halving_interval == 0 {
+            return self.reward
+        }
+
+        let halvings = slot / self.reward_halving_interval;
+        if halvings >= u64::BITS as u64 {
+            return 0
+        }
+
+        self.reward >> halvings
+    }
+
+    /// Total rewards of the given number of rewarded slots, counting from genesis
+    pub fn total_rewards(&self, slots: u64) -> u64 {
+        if self.reward_halving_interval == 0 {
+            return slots * self.reward
+        }
+
+        let mut total = 0;
+        let mut slot = 0;
+        while slot < slots {
+            let reward = self.reward(slot);
+            if reward == 0 {
+                break
+            }
+            let era_slots = (self.reward_halving_interval - slot % self.reward_halving_interval)
+                .min(slots - slot);
+            total += era_slots * reward;
+            slot += era_slots;
+        }
+
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ChainParams;
+
+    #[test]
+    fn reward_schedule() {
+        let mut params = ChainParams::testnet();
+        params.reward = 8;
+        assert_eq!(params.reward(1000), 8);
+        assert_eq!(params.total_rewards(10), 80);
+
+        params.reward_halving_interval = 10;
+        assert_eq!(params.reward(0), 8);
+        assert_eq!(params.reward(9), 8);
+        assert_eq!(params.reward(10), 4);
+        assert_eq!(params.reward(35), 1);
+        assert_eq!(params.reward(40), 0);
+        assert_eq!(params.reward(u64::MAX), 0);
+        assert_eq!(params.total_rewards(15), 80 + 20);
+        assert_eq!(params.total_rewards(1000), 80 + 40 + 20 + 10);
+    }
+
+    #[test]
+    fn min_tx_fee() {
+        assert!(ChainParams::mainnet().min_tx_fee > 0);
+        assert!(ChainParams::testnet().min_tx_fee > 0);
+        assert_eq!(ChainParams::devnet().min_tx_fee, 0);
+    }
+}
@@ -18,11 +18,11 @@
 
 /// Block proposal protocol
 mod protocol_proposal;
-pub use protocol_proposal::ProtocolProposal;
+pub use protocol_proposal::{ProposalInventory, ProtocolProposal};
 
 /// Transaction broadcast protocol
 mod protocol_tx;
-pub use protocol_tx::ProtocolTx;
+pub use protocol_tx::{ProtocolTx, TxInventory};
 
 /// Validator + Replicator blockchain sync protocol
 mod protocol_sync;
@@ -20,15 +20,14 @@ use async_std::sync::Arc;
 use async_trait::async_trait;
 use log::{debug, error, trace};
 use smol::Executor;
-use url::Url;
 
 use crate::{
     consensus::{BlockProposal, ValidatorStatePtr},
     net::{
-        ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
+        ChannelPtr, InvHash, InvStore, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
         ProtocolJobsManager, ProtocolJobsManagerPtr,
     },
-    Result,
+    Error, Result,
 };
 
 pub struct ProtocolProposal {
@@ -36,7 +35,33 @@ pub struct ProtocolProposal {
     jobsman: ProtocolJobsManagerPtr,
     state: ValidatorStatePtr,
     p2p: P2pPtr,
-    channel_address: Url,
+    channel: ChannelPtr,
+}
+
+/// Ban score given to a peer relaying a proposal that fails validation
+const BAN_SCORE_INVALID_PROPOSAL: u32 = 20;
+
+/// Inventory of the proposals held in our fork chains, served to peers
+/// missing them.
+pub struct ProposalInventory {
+    state: ValidatorStatePtr,
+}
+
+impl ProposalInventory {
+    pub fn new(state: ValidatorStatePtr) -> Arc<Self> {
+        Arc::new(Self { state })
+    }
+}
+
+#[async_trait]
+impl InvStore<BlockProposal> for ProposalInventory {
+    async fn contains(&self, hash: &InvHash) -> bool {
+        self.state.read().await.consensus.proposal_exists(&blake3::Hash::from(*hash))
+    }
+
+    async fn get(&self, hash: &InvHash) -> Option<BlockProposal> {
+        self.state.read().await.consensus.get_proposal(&blake3::Hash::from(*hash))
+    }
 }
 
 impl ProtocolProposal {
@@ -51,21 +76,18 @@ impl ProtocolProposal {
 
         let proposal_sub = channel.subscribe_msg::<BlockProposal>().await?;
 
-        let channel_address = channel.address();
-
         Ok(Arc::new(Self {
             proposal_sub,
-            jobsman: ProtocolJobsManager::new("ProposalProtocol", channel),
+            jobsman: ProtocolJobsManager::new("ProposalProtocol", channel.clone()),
             state,
             p2p,
-            channel_address,
+            channel,
         }))
     }
 
     async fn handle_receive_proposal(self: Arc<Self>) -> Result<()> {
         debug!(target: "consensus::protocol_proposal::handle_receive_proposal()", "START");
 
-        let exclude_list = vec![self.channel_address.clone()];
         loop {
             let proposal = match self.proposal_sub.receive().await {
                 Ok(v) => v,
@@ -100,13 +122,14 @@ impl ProtocolProposal {
                 continue
             }
 
-            match lock.receive_proposal(&proposal_copy, None).await {
+            let received = lock.receive_proposal(&proposal_copy, None).await;
+            drop(lock);
+
+            match received {
                 Ok(broadcast) => {
                     if broadcast {
-                        // Broadcast proposal to rest of nodes
-                        if let Err(e) =
-                            self.p2p.broadcast_with_exclude(proposal_copy, &exclude_list).await
-                        {
+                        // Announce proposal to rest of nodes
+                        if let Err(e) = self.p2p.broadcast_inv(&proposal_copy).await {
                             error!(
                                 target: "consensus::protocol_proposal::handle_receive_proposal()",
                                 "proposal broadcast fail: {}",
@@ -121,6 +144,17 @@ impl ProtocolProposal {
                         "receive_proposal error: {}",
                         e
                     );
+                    // Timing related errors can be hit by honest peers,
+                    // so only punish proposals that are invalid by themselves.
+                    if is_invalid_proposal(&e) {
+                        self.p2p
+                            .add_ban_score(
+                                &self.channel,
+                                BAN_SCORE_INVALID_PROPOSAL,
+                                "invalid proposal",
+                            )
+                            .await;
+                    }
                     continue
                 }
             }
@@ -128,6 +162,20 @@ impl ProtocolProposal {
     }
 }
 
+/// Check if a `receive_proposal` error means the proposal itself is invalid.
+fn is_invalid_proposal(err: &Error) -> bool {
+    matches!(
+        err,
+        Error::ProposalProposerNotEligible |
+            Error::ProposalTxsExceedCapError |
+            Error::InvalidSignature |
+            Error::ProposalHashesMissmatchError |
+            Error::ProposalHeadersMissmatchError |
+            Error::LeaderProofVerification |
+            Error::ProposalPublicValuesMismatched
+    )
+}
+
 #[async_trait]
 impl ProtocolBase for ProtocolProposal {
     async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
@@ -23,7 +23,8 @@ use smol::Executor;
 
 use crate::{
     consensus::{
-        block::{BlockInfo, BlockOrder, BlockResponse},
+        block::{BlockInfo, BlockOrder, BlockResponse, BodyOrder, HeaderOrder, HeaderResponse},
+        constants::{SYNC_BODIES_BATCH, SYNC_HEADERS_BATCH},
         state::{SlotCheckpoint, SlotCheckpointRequest, SlotCheckpointResponse},
         ValidatorStatePtr,
     },
@@ -40,6 +41,8 @@ const BATCH: u64 = 10;
 pub struct ProtocolSync {
     channel: ChannelPtr,
     request_sub: MessageSubscription<BlockOrder>,
+    header_request_sub: MessageSubscription<HeaderOrder>,
+    body_request_sub: MessageSubscription<BodyOrder>,
     slot_checkpoin_request_sub: MessageSubscription<SlotCheckpointRequest>,
     block_sub: MessageSubscription<BlockInfo>,
     slot_checkpoints_sub: MessageSubscription<SlotCheckpoint>,
@@ -58,11 +61,15 @@ impl ProtocolSync {
     ) -> Result<ProtocolBasePtr> {
         let msg_subsystem = channel.get_message_subsystem();
         msg_subsystem.add_dispatch::<BlockOrder>().await;
+        msg_subsystem.add_dispatch::<HeaderOrder>().await;
+        msg_subsystem.add_dispatch::<BodyOrder>().await;
         msg_subsystem.add_dispatch::<SlotCheckpointRequest>().await;
         msg_subsystem.add_dispatch::<BlockInfo>().await;
         msg_subsystem.add_dispatch::<SlotCheckpoint>().await;
 
         let request_sub = channel.subscribe_msg::<BlockOrder>().await?;
+        let header_request_sub = channel.subscribe_msg::<HeaderOrder>().await?;
+        let body_request_sub = channel.subscribe_msg::<BodyOrder>().await?;
         let slot_checkpoin_request_sub = channel.subscribe_msg::<SlotCheckpointRequest>().await?;
         let block_sub = channel.subscribe_msg::<BlockInfo>().await?;
         let slot_checkpoints_sub = channel.subscribe_msg::<SlotCheckpoint>().await?;
@@ -70,6 +77,8 @@ impl ProtocolSync {
         Ok(Arc::new(Self {
             channel: channel.clone(),
             request_sub,
+            header_request_sub,
+            body_request_sub,
             slot_checkpoin_request_sub,
             block_sub,
             slot_checkpoints_sub,
@@ -134,6 +143,121 @@ impl ProtocolSync {
         }
     }
 
+    async fn handle_receive_header_request(self: Arc<Self>) -> Result<()> {
+        debug!(
+            target: "consensus::protocol_sync::handle_receive_header_request()",
+            "START"
+        );
+        loop {
+            let order = match self.header_request_sub.receive().await {
+                Ok(v) => v,
+                Err(e) => {
+                    debug!(
+                        target: "consensus::protocol_sync::handle_receive_header_request()",
+                        "recv fail: {}",
+                        e
+                    );
+                    continue
+                }
+            };
+
+            debug!(
+                target: "consensus::protocol_sync::handle_receive_header_request()",
+                "received {:?}",
+                order
+            );
+
+            let headers = match self
+                .state
+                .read()
+                .await
+                .blockchain
+                .get_sync_headers_after(order.slot, SYNC_HEADERS_BATCH)
+            {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(
+                        target: "consensus::protocol_sync::handle_receive_header_request()",
+                        "get_sync_headers_after fail: {}",
+                        e
+                    );
+                    continue
+                }
+            };
+            debug!(
+                target: "consensus::protocol_sync::handle_receive_header_request()",
+                "Found {} headers",
+                headers.len()
+            );
+
+            let response = HeaderResponse { headers };
+            if let Err(e) = self.channel.send(response).await {
+                error!(
+                    target: "consensus::protocol_sync::handle_receive_header_request()",
+                    "channel send fail: {}",
+                    e
+                )
+            };
+        }
+    }
+
+    async fn handle_receive_body_request(self: Arc<Self>) -> Result<()> {
+        debug!(
+            target: "consensus::protocol_sync::handle_receive_body_request()",
+            "START"
+        );
+        loop {
+            let order = match self.body_request_sub.receive().await {
+                Ok(v) => v,
+                Err(e) => {
+                    debug!(
+                        target: "consensus::protocol_sync::handle_receive_body_request()",
+                        "recv fail: {}",
+                        e
+                    );
+                    continue
+                }
+            };
+
+            debug!(
+                target: "consensus::protocol_sync::handle_receive_body_request()",
+                "received {:?}",
+                order
+            );
+
+            // We don't serve more blocks than a single batch
+            let hashes = &order.hashes[..order.hashes.len().min(SYNC_BODIES_BATCH)];
+
+            // Unknown blocks get an empty response, so the requester can
+            // move on to another peer without waiting for a timeout.
+            let blocks = match self.state.read().await.blockchain.get_blocks_by_hash(hashes) {
+                Ok(v) => v,
+                Err(e) => {
+                    debug!(
+                        target: "consensus::protocol_sync::handle_receive_body_request()",
+                        "get_blocks_by_hash fail: {}",
+                        e
+                    );
+                    vec![]
+                }
+            };
+            debug!(
+                target: "consensus::protocol_sync::handle_receive_body_request()",
+                "Found {} blocks",
+                blocks.len()
+            );
+
+            let response = BlockResponse { blocks };
+            if let Err(e) = self.channel.send(response).await {
+                error!(
+                    target: "consensus::protocol_sync::handle_receive_body_request()",
+                    "channel send fail: {}",
+                    e
+                )
+            };
+        }
+    }
+
     async fn handle_receive_block(self: Arc<Self>) -> Result<()> {
         debug!(target: "consensus::protocol_sync::handle_receive_block()", "START");
         let exclude_list = vec![self.channel.address()];
@@ -159,26 +283,10 @@ impl ProtocolSync {
                 continue
             }
 
-            // Check if node started participating in consensus.
-            // Consensus-mode enabled nodes have already performed these steps,
-            // during proposal finalization. They still listen to this sub,
-            // in case they go out of sync and become a none-consensus node.
-            if self.consensus_mode {
-                let lock = self.state.read().await;
-                let current = lock.consensus.current_slot();
-                let participating = lock.consensus.participating;
-                if participating.is_some() {
-                    let slot = participating.unwrap();
-                    if current >= slot {
-                        debug!(
-                            target: "consensus::protocol_sync::handle_receive_block()",
-                            "node runs in consensus mode, skipping..."
-                        );
-                        continue
-                    }
-                }
-            }
-
+            // Consensus-mode enabled nodes have already finalized the blocks
+            // they hold proposals of, so these get ignored as existing ones.
+            // Blocks they missed still have to be applied, otherwise they
+            // would keep extending a stale canonical chain.
             info!(
                 target: "consensus::protocol_sync::handle_receive_block()",
                 "Received block: {}",
@@ -382,6 +490,14 @@ impl ProtocolBase for ProtocolSync {
         debug!(target: "consensus::protocol_sync::start()", "START");
         self.jobsman.clone().start(executor.clone());
         self.jobsman.clone().spawn(self.clone().handle_receive_request(), executor.clone()).await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_header_request(), executor.clone())
+            .await;
+        self.jobsman
+            .clone()
+            .spawn(self.clone().handle_receive_body_request(), executor.clone())
+            .await;
         self.jobsman
             .clone()
             .spawn(self.clone().handle_receive_slot_checkpoint_request(), executor.clone())
@@ -99,17 +99,7 @@ impl ProtocolSyncConsensus {
             for fork in &lock.consensus.forks {
                 forks.push(fork.clone().into());
             }
-            let pending_txs = match lock.blockchain.get_pending_txs() {
-                Ok(v) => v,
-                Err(e) => {
-                    debug!(
-                        target: "consensus::protocol_sync_consensus::handle_receive_request()",
-                        "Failed querying pending txs store: {}",
-                        e
-                    );
-                    vec![]
-                }
-            };
+            let pending_txs = lock.mempool.txs();
             let slot_checkpoints = lock.consensus.slot_checkpoints.clone();
             let mut f_history = vec![];
             for f in &lock.consensus.f_history {
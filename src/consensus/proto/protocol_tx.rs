@@ -20,17 +20,16 @@ use async_std::sync::Arc;
 use async_trait::async_trait;
 use log::{debug, error};
 use smol::Executor;
-use url::Url;
 
 use crate::{
     consensus::ValidatorStatePtr,
     net,
     net::{
-        ChannelPtr, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
+        ChannelPtr, InvHash, InvStore, MessageSubscription, P2pPtr, ProtocolBase, ProtocolBasePtr,
         ProtocolJobsManager, ProtocolJobsManagerPtr,
     },
     tx::Transaction,
-    Result,
+    Error, Result,
 };
 
 pub struct ProtocolTx {
@@ -38,13 +37,58 @@ pub struct ProtocolTx {
     jobsman: ProtocolJobsManagerPtr,
     state: ValidatorStatePtr,
     p2p: P2pPtr,
-    channel_address: Url,
+    channel: ChannelPtr,
 }
 
+/// Ban score given to a peer relaying a malformed transaction
+const BAN_SCORE_MALFORMED_TX: u32 = 10;
+
 impl net::Message for Transaction {
     fn name() -> &'static str {
         "tx"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
+}
+
+impl net::InvItem for Transaction {
+    fn inv_hash(&self) -> InvHash {
+        *self.hash().as_bytes()
+    }
+}
+
+/// Inventory of transactions we can serve to peers: the ones pending in
+/// our mempool, as well as the ones already in the blockchain.
+pub struct TxInventory {
+    state: ValidatorStatePtr,
+}
+
+impl TxInventory {
+    pub fn new(state: ValidatorStatePtr) -> Arc<Self> {
+        Arc::new(Self { state })
+    }
+}
+
+#[async_trait]
+impl InvStore<Transaction> for TxInventory {
+    async fn contains(&self, hash: &InvHash) -> bool {
+        let tx_hash = blake3::Hash::from(*hash);
+        let state = self.state.read().await;
+        state.mempool.contains(&tx_hash) ||
+            state.blockchain.transactions.contains(&tx_hash).unwrap_or(false)
+    }
+
+    async fn get(&self, hash: &InvHash) -> Option<Transaction> {
+        let tx_hash = blake3::Hash::from(*hash);
+        let state = self.state.read().await;
+        if let Some(tx) = state.mempool.get(&tx_hash) {
+            return Some(tx)
+        }
+
+        state.blockchain.transactions.get(&[tx_hash], false).ok()?.pop().flatten()
+    }
 }
 
 impl ProtocolTx {
@@ -61,14 +105,13 @@ impl ProtocolTx {
         msg_subsystem.add_dispatch::<Transaction>().await;
 
         let tx_sub = channel.subscribe_msg::<Transaction>().await?;
-        let channel_address = channel.address();
 
         Ok(Arc::new(Self {
             tx_sub,
-            jobsman: ProtocolJobsManager::new("TxProtocol", channel),
+            jobsman: ProtocolJobsManager::new("TxProtocol", channel.clone()),
             state,
             p2p,
-            channel_address,
+            channel,
         }))
     }
 
@@ -77,7 +120,6 @@ impl ProtocolTx {
             target: "consensus::protocol_tx::handle_receive_tx()",
             "START"
         );
-        let exclude_list = vec![self.channel_address.clone()];
         loop {
             let tx = match self.tx_sub.receive().await {
                 Ok(v) => v,
@@ -103,14 +145,30 @@ impl ProtocolTx {
             let tx_copy = (*tx).clone();
 
             // Nodes use unconfirmed_txs vector as seen_txs pool.
-            if self.state.write().await.append_tx(tx_copy.clone()).await {
-                if let Err(e) = self.p2p.broadcast_with_exclude(tx_copy, &exclude_list).await {
-                    error!(
-                        target: "consensus::protocol_tx::handle_receive_tx()",
-                        "p2p broadcast fail: {}",
-                        e
-                    );
-                };
+            let appended = self.state.write().await.append_tx(tx_copy.clone()).await;
+            match appended {
+                Ok(true) => {
+                    // Announce the transaction, peers request it if they miss it
+                    if let Err(e) = self.p2p.broadcast_inv(&tx_copy).await {
+                        error!(
+                            target: "consensus::protocol_tx::handle_receive_tx()",
+                            "p2p broadcast fail: {}",
+                            e
+                        );
+                    };
+                }
+                // A transaction failing against our state might have been valid
+                // for the relaying peer, so only malformed ones get punished.
+                Err(Error::MalformedTransaction) => {
+                    self.p2p
+                        .add_ban_score(
+                            &self.channel,
+                            BAN_SCORE_MALFORMED_TX,
+                            "malformed transaction",
+                        )
+                        .await;
+                }
+                Ok(false) | Err(_) => {}
             }
         }
     }
@@ -0,0 +1,370 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Deterministic consensus simulation.
+//!
+//! A [`Simulation`] runs a set of [`ValidatorState`] nodes on a shared
+//! [`SimulatedTime`], driving them slot by slot through the same proposal
+//! and finalization periods the proposal task executes, including the
+//! broadcast of finalized blocks to the rest of the network. Slot leaders and
+//! proposal delays come from a script instead of the slot lottery and the
+//! network, and proposals carry no leader proof, as nodes run in single-node
+//! mode. This way thousands of slots run in seconds, so invariants like no
+//! conflicting finalization can be checked on arbitrary leader schedules.
+
+use std::sync::Arc;
+
+use darkfi_sdk::{
+    crypto::{schnorr::SchnorrSecret, Keypair, SecretKey},
+    pasta::pallas,
+};
+use log::{debug, info};
+use rand::{rngs::StdRng, SeedableRng};
+
+use super::{
+    clock::{SimulatedTime, TimeSource},
+    constants, BlockProposal, ChainParams, Header, LeadInfo, LeadProof, ValidatorState,
+    ValidatorStatePtr,
+};
+use crate::{util::time::Timestamp, wallet::WalletDb, Result};
+
+/// A node taking part in a [`Simulation`]
+pub struct SimulatedNode {
+    /// Validator state of the node
+    pub state: ValidatorStatePtr,
+    /// Keypair the node signs its proposals with
+    pub keypair: Keypair,
+}
+
+/// Scripted events of a simulated slot
+#[derive(Clone, Debug, Default)]
+pub struct SlotScript {
+    /// Nodes winning the slot lottery
+    pub leaders: Vec<usize>,
+    /// Nodes receiving the slot proposals only in the next slot
+    pub delayed: Vec<usize>,
+}
+
+impl SlotScript {
+    /// Slot won by the given nodes, with all proposals delivered in time
+    pub fn leaders(leaders: &[usize]) -> Self {
+        Self { leaders: leaders.to_vec(), delayed: vec![] }
+    }
+}
+
+/// Deterministic consensus simulation driver
+pub struct Simulation {
+    /// Chain parameters of the simulated network
+    pub params: ChainParams,
+    /// Time source shared by all nodes
+    pub time: Arc<SimulatedTime>,
+    /// Simulated nodes
+    pub nodes: Vec<SimulatedNode>,
+    /// Last simulated slot
+    pub slot: u64,
+    /// Count of proposals nodes rejected
+    pub rejected: u64,
+    /// Delayed proposals, along with the node they get delivered to
+    delayed: Vec<(usize, BlockProposal)>,
+}
+
+impl Simulation {
+    /// Create a simulation of `n` nodes, at the genesis slot of given chain.
+    pub async fn new(n: usize, params: ChainParams) -> Result<Self> {
+        let time = SimulatedTime::new(params.genesis_ts);
+
+        let mut nodes = Vec::with_capacity(n);
+        for i in 0..n {
+            let sled_db = sled::Config::new().temporary(true).open()?;
+            let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+            let state =
+                ValidatorState::new(&sled_db, params.clone(), wallet, vec![], false, true).await?;
+            {
+                let mut lock = state.write().await;
+                lock.consensus.time = time.clone();
+                lock.consensus.participating = Some(1);
+            }
+
+            let keypair = Keypair::new(SecretKey::from(pallas::Base::from(i as u64 + 1)));
+            nodes.push(SimulatedNode { state, keypair });
+        }
+
+        Ok(Self { params, time, nodes, slot: 0, rejected: 0, delayed: vec![] })
+    }
+
+    /// Run the next slot, following the given script.
+    pub async fn run_slot(&mut self, script: &SlotScript) -> Result<()> {
+        self.slot += 1;
+        let slot_start = self.params.genesis_ts.0 + (self.slot * self.params.slot_time) as i64;
+        debug!(target: "consensus::simulation", "Running slot {}: {:?}", self.slot, script);
+
+        // Proposal period
+        self.time.set(Timestamp(slot_start));
+
+        // Proposals delayed in the previous slot arrive now
+        for (node, proposal) in std::mem::take(&mut self.delayed) {
+            self.deliver(node, &proposal).await;
+        }
+
+        // Leaders create their proposals at the same time, each extending
+        // the longest fork chain it holds.
+        let mut proposals = Vec::with_capacity(script.leaders.len());
+        for leader in &script.leaders {
+            proposals.push((*leader, self.propose(*leader).await?));
+        }
+
+        // Leaders store their own proposal first, and then broadcast it
+        for (leader, proposal) in &proposals {
+            self.deliver(*leader, proposal).await;
+        }
+        for node in 0..self.nodes.len() {
+            for (leader, proposal) in &proposals {
+                if *leader == node {
+                    continue
+                }
+                if script.delayed.contains(&node) {
+                    self.delayed.push((node, proposal.clone()));
+                    continue
+                }
+                self.deliver(node, proposal).await;
+            }
+        }
+
+        // Finalization period
+        let final_sync_start = self.params.slot_time - self.params.final_sync_dur;
+        self.time.set(Timestamp(slot_start + final_sync_start as i64));
+        let mut finalized = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let (blocks, _) = node.state.write().await.chain_finalization().await?;
+            finalized.push(blocks);
+        }
+
+        // Finalized blocks get broadcasted, so nodes that missed their
+        // proposals follow the canonical chain.
+        for (sender, blocks) in finalized.iter().enumerate() {
+            for (node, receiver) in self.nodes.iter().enumerate() {
+                if node == sender {
+                    continue
+                }
+                let mut state = receiver.state.write().await;
+                for block in blocks {
+                    state.receive_finalized_block(block.clone()).await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run the next slots, following the given scripts.
+    pub async fn run(&mut self, scripts: &[SlotScript]) -> Result<()> {
+        for script in scripts {
+            self.run_slot(script).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Create a proposal of the given node for the current slot.
+    async fn propose(&self, node: usize) -> Result<BlockProposal> {
+        let state = self.nodes[node].state.read().await;
+        let keypair = &self.nodes[node].keypair;
+
+        let fork_index = state.consensus.longest_chain_index();
+        let previous = if fork_index == -1 {
+            state.blockchain.last()?.1
+        } else {
+            state.consensus.forks[fork_index as usize].sequence.last().unwrap().proposal.hash
+        };
+
+        let header = Header::new(
+            previous,
+            state.consensus.slot_epoch(self.slot),
+            self.slot,
+            self.time.now(),
+            Header::txs_root(&[]),
+        );
+
+        // Signatures are seeded, so the same script always produces the same blocks
+        let mut rng = StdRng::seed_from_u64((self.slot << 16) | node as u64);
+        let signature = keypair.secret.sign(&mut rng, header.headerhash().as_bytes());
+
+        // The proposal nullifier has to be unique, the rest of the
+        // public inputs are only checked along with the leader proof.
+        let mut public_inputs = vec![pallas::Base::zero(); constants::PI_REWARD_INDEX + 1];
+        public_inputs[constants::PI_NULLIFIER_INDEX] =
+            pallas::Base::from((self.slot << 16) | node as u64);
+
+        let lead_info = LeadInfo::new(
+            signature,
+            keypair.public,
+            public_inputs,
+            state.consensus.bootstrap_slot,
+            pallas::Base::zero(),
+            LeadProof::default(),
+            state.consensus.previous_leaders,
+        );
+
+        Ok(BlockProposal::new(self.params.network_magic, header, vec![], lead_info))
+    }
+
+    /// Deliver a proposal to the given node.
+    async fn deliver(&mut self, node: usize, proposal: &BlockProposal) {
+        let state = self.nodes[node].state.clone();
+        if let Err(e) = state.write().await.receive_proposal(proposal, None).await {
+            info!(target: "consensus::simulation", "Node {} rejected proposal {}: {}", node, proposal.hash, e);
+            self.rejected += 1;
+        }
+    }
+
+    /// Retrieve the canonical chain of the given node, as (slot, blockhash) tuples.
+    pub async fn finalized(&self, node: usize) -> Result<Vec<(u64, blake3::Hash)>> {
+        self.nodes[node].state.read().await.blockchain.order.get_all()
+    }
+
+    /// Find the first height at which the canonical chains of two nodes differ,
+    /// meaning conflicting blocks got finalized.
+    pub async fn conflicting_finalization(&self) -> Result<Option<usize>> {
+        let mut chains = Vec::with_capacity(self.nodes.len());
+        for node in 0..self.nodes.len() {
+            chains.push(self.finalized(node).await?);
+        }
+
+        let mut conflict = None;
+        for (i, a) in chains.iter().enumerate() {
+            for b in &chains[i + 1..] {
+                if let Some(height) = a.iter().zip(b.iter()).position(|(x, y)| x != y) {
+                    conflict = Some(conflict.map_or(height, |c: usize| c.min(height)));
+                }
+            }
+        }
+
+        Ok(conflict)
+    }
+
+    /// Assert the simulation invariants hold:
+    /// * Every node canonical chain links each block to the previous one
+    /// * No conflicting blocks got finalized across nodes
+    pub async fn assert_invariants(&self) -> Result<()> {
+        for node in 0..self.nodes.len() {
+            let chain = self.finalized(node).await?;
+            let hashes: Vec<blake3::Hash> = chain.iter().map(|(_, hash)| *hash).collect();
+            let state = self.nodes[node].state.read().await;
+            let blocks = state.blockchain.get_blocks_by_hash(&hashes)?;
+            for (i, block) in blocks.iter().enumerate().skip(1) {
+                assert_eq!(
+                    block.header.previous,
+                    hashes[i - 1],
+                    "Node {} finalized block at slot {} not extending its previous one",
+                    node,
+                    block.header.slot
+                );
+            }
+        }
+
+        let conflict = self.conflicting_finalization().await?;
+        assert_eq!(conflict, None, "Nodes finalized conflicting blocks");
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{Simulation, SlotScript};
+    use crate::{consensus::ChainParams, Result};
+
+    const NODES: usize = 3;
+
+    #[async_std::test]
+    async fn simulation_single_leaders() -> Result<()> {
+        let mut sim = Simulation::new(NODES, ChainParams::devnet()).await?;
+
+        // Every slot has a single leader, so every proposal gets finalized
+        let scripts: Vec<SlotScript> =
+            (0..1000).map(|slot| SlotScript::leaders(&[slot % NODES])).collect();
+        sim.run(&scripts).await?;
+
+        sim.assert_invariants().await?;
+        assert_eq!(sim.rejected, 0);
+        for node in 0..NODES {
+            assert_eq!(sim.finalized(node).await?.len(), 1001);
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn simulation_forks() -> Result<()> {
+        let mut sim = Simulation::new(NODES, ChainParams::devnet()).await?;
+
+        // Slots have zero to all nodes as leaders, creating competing forks
+        let mut rng = StdRng::seed_from_u64(42);
+        let mut scripts = Vec::with_capacity(2001);
+        for _ in 0..2000 {
+            let leaders: Vec<usize> = (0..NODES).filter(|_| rng.gen_ratio(1, 3)).collect();
+            scripts.push(SlotScript::leaders(&leaders));
+        }
+        // A single leader resolves any forks left
+        scripts.push(SlotScript::leaders(&[0]));
+        sim.run(&scripts).await?;
+
+        sim.assert_invariants().await?;
+        assert_eq!(sim.rejected, 0);
+        let chain = sim.finalized(0).await?;
+        assert!(chain.len() > 1);
+        for node in 1..NODES {
+            assert_eq!(sim.finalized(node).await?, chain);
+        }
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn simulation_delayed_proposals() -> Result<()> {
+        let mut sim = Simulation::new(NODES, ChainParams::devnet()).await?;
+
+        // Node 2 receives the slot 1 proposal too late, but follows the
+        // finalized block, so it can extend it in the next slot.
+        let scripts =
+            vec![SlotScript { leaders: vec![0], delayed: vec![2] }, SlotScript::leaders(&[1])];
+        sim.run(&scripts).await?;
+
+        sim.assert_invariants().await?;
+        assert_eq!(sim.rejected, 1);
+        for node in 0..NODES {
+            assert_eq!(sim.finalized(node).await?.len(), 3);
+        }
+
+        // Once the lagging node wins a slot, its proposal extends the
+        // canonical chain of the rest of the network.
+        sim.run_slot(&SlotScript::leaders(&[2])).await?;
+        sim.assert_invariants().await?;
+        assert_eq!(sim.rejected, 1);
+        assert_eq!(sim.conflicting_finalization().await?, None);
+        let chain = sim.finalized(0).await?;
+        assert_eq!(chain.len(), 4);
+        for node in 1..NODES {
+            assert_eq!(sim.finalized(node).await?, chain);
+        }
+
+        Ok(())
+    }
+}
@@ -18,10 +18,11 @@
 
 use std::time::Duration;
 
-use chrono::{NaiveDateTime, Utc};
+use chrono::NaiveDateTime;
 use darkfi_sdk::{
-    crypto::{constants::MERKLE_DEPTH, MerkleNode},
-    incrementalmerkletree::bridgetree::BridgeTree,
+    crypto::{constants::MERKLE_DEPTH, MerkleNode, MerkleTree, MONEY_CONTRACT_ID},
+    incrementalmerkletree::{bridgetree::BridgeTree, Tree},
+    lead,
     pasta::{group::ff::PrimeField, pallas},
 };
 use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
@@ -30,15 +31,13 @@ use rand::{thread_rng, Rng};
 use sqlx::Row;
 
 use super::{
+    clock::{SystemTime, TimeSourcePtr},
     constants,
     lead_coin::{LeadCoin, LeadCoinSecrets},
     utils::fbig2base,
-    Block, BlockProposal, Float10,
-};
-use crate::{
-    blockchain::Blockchain, net, tx::Transaction, util::time::Timestamp, wallet::WalletPtr, Error,
-    Result,
+    Block, BlockProposal, ChainParams, Float10, LeadInfo,
 };
+use crate::{blockchain::Blockchain, net, tx::Transaction, wallet::WalletPtr, Error, Result};
 
 use std::{
     fs::File,
@@ -51,14 +50,12 @@ pub struct ConsensusState {
     pub wallet: WalletPtr,
     /// Canonical (finalized) blockchain
     pub blockchain: Blockchain,
-    /// Network bootstrap timestamp
-    pub bootstrap_ts: Timestamp,
-    /// Genesis block creation timestamp
-    pub genesis_ts: Timestamp,
+    /// Chain parameters
+    pub params: ChainParams,
+    /// Source of the current time
+    pub time: TimeSourcePtr,
     /// Genesis block hash
     pub genesis_block: blake3::Hash,
-    /// Total sum of initial staking coins
-    pub initial_distribution: u64,
     /// Flag to enable single-node mode
     pub single_node: bool,
     /// Slot the network was bootstrapped
@@ -94,20 +91,19 @@ impl ConsensusState {
     pub fn new(
         wallet: WalletPtr,
         blockchain: Blockchain,
-        bootstrap_ts: Timestamp,
-        genesis_ts: Timestamp,
-        genesis_data: blake3::Hash,
-        initial_distribution: u64,
+        params: ChainParams,
         single_node: bool,
     ) -> Result<Self> {
-        let genesis_block = Block::genesis_block(genesis_ts, genesis_data).blockhash();
+        let genesis_block =
+            Block::genesis_block(params.genesis_ts, params.genesis_data).blockhash();
+        let coins_tree =
+            BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(params.epoch_length as usize * 100);
         Ok(Self {
             wallet,
             blockchain,
-            bootstrap_ts,
-            genesis_ts,
+            params,
+            time: SystemTime::new(),
             genesis_block,
-            initial_distribution,
             single_node,
             bootstrap_slot: 0,
             participating: None,
@@ -120,7 +116,7 @@ impl ConsensusState {
             f_history: vec![constants::FLOAT10_ZERO.clone()],
             err_history: vec![constants::FLOAT10_ZERO.clone(), constants::FLOAT10_ZERO.clone()],
             coins: vec![],
-            coins_tree: BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(constants::EPOCH_LENGTH * 100),
+            coins_tree,
             nullifiers: vec![],
         })
     }
@@ -131,20 +127,21 @@ impl ConsensusState {
     }
 
     /// Calculates the epoch of the provided slot.
-    /// Epoch duration is configured using the `EPOCH_LENGTH` value.
+    /// Epoch duration is configured using the `epoch_length` chain parameter.
     pub fn slot_epoch(&self, slot: u64) -> u64 {
-        slot / constants::EPOCH_LENGTH as u64
+        slot / self.params.epoch_length
     }
 
     /// Calculates current slot, based on elapsed time from the genesis block.
-    /// Slot duration is configured using the `SLOT_TIME` constant.
+    /// Slot duration is configured using the `slot_time` chain parameter.
     pub fn current_slot(&self) -> u64 {
-        self.genesis_ts.elapsed() / constants::SLOT_TIME
+        let elapsed = (self.time.now().0 - self.params.genesis_ts.0) as u64;
+        elapsed / self.params.slot_time
     }
 
     /// Calculates the relative number of the provided slot.
     pub fn relative_slot(&self, slot: u64) -> u64 {
-        slot % constants::EPOCH_LENGTH as u64
+        slot % self.params.epoch_length
     }
 
     /// Finds the last slot a proposal or block was generated.
@@ -169,27 +166,27 @@ impl ConsensusState {
     }
 
     /// Calculates seconds until next Nth slot starting time.
-    /// Slots duration is configured using the SLOT_TIME constant.
+    /// Slots duration is configured using the `slot_time` chain parameter.
     pub fn next_n_slot_start(&self, n: u64) -> Duration {
         assert!(n > 0);
-        let start_time = NaiveDateTime::from_timestamp_opt(self.genesis_ts.0, 0).unwrap();
+        let start_time = NaiveDateTime::from_timestamp_opt(self.params.genesis_ts.0, 0).unwrap();
         let current_slot = self.current_slot() + n;
         let next_slot_start =
-            (current_slot * constants::SLOT_TIME) + (start_time.timestamp() as u64);
+            (current_slot * self.params.slot_time) + (start_time.timestamp() as u64);
         let next_slot_start = NaiveDateTime::from_timestamp_opt(next_slot_start as i64, 0).unwrap();
-        let current_time = NaiveDateTime::from_timestamp_opt(Utc::now().timestamp(), 0).unwrap();
+        let current_time = NaiveDateTime::from_timestamp_opt(self.time.now().0, 0).unwrap();
         let diff = next_slot_start - current_time;
 
         Duration::new(diff.num_seconds().try_into().unwrap(), 0)
     }
 
     /// Calculate slots until next Nth epoch.
-    /// Epoch duration is configured using the EPOCH_LENGTH value.
+    /// Epoch duration is configured using the `epoch_length` chain parameter.
     pub fn slots_to_next_n_epoch(&self, n: u64) -> u64 {
         assert!(n > 0);
         let slots_till_next_epoch =
-            constants::EPOCH_LENGTH as u64 - self.relative_slot(self.current_slot());
-        ((n - 1) * constants::EPOCH_LENGTH as u64) + slots_till_next_epoch
+            self.params.epoch_length - self.relative_slot(self.current_slot());
+        ((n - 1) * self.params.epoch_length) + slots_till_next_epoch
     }
 
     /// Calculates seconds until next Nth epoch starting time.
@@ -275,73 +272,228 @@ impl ConsensusState {
     }
 
     /// Generate coins for provided sigmas.
+    /// Competing coins are the wallet lead coins found in the money contract
+    /// lead coins and not yet spent, either staked or derived from a staked
+    /// coin after winning slots. The coin commitments tree is rebuilt from the
+    /// contract's lead coins, so their Merkle paths are current.
     /// NOTE: The strategy here is having a single competing coin per slot.
-    // TODO: DRK coin need to be burned, and consensus coin to be minted.
     async fn create_coins(&mut self) -> Result<Vec<LeadCoin>> {
-        // TODO: cleanup LeadCoinSecrets, no need to keep a vector
-        let (seeds, epoch_secrets) = {
-            let mut rng = thread_rng();
-            let mut seeds: Vec<u64> = Vec::with_capacity(constants::EPOCH_LENGTH);
-            for _ in 0..constants::EPOCH_LENGTH {
-                seeds.push(rng.gen());
+        // Retrieve staked coins secrets and the coins derived from them from wallet
+        let mut conn = self.wallet.conn.acquire().await?;
+        let query_str = format!("SELECT * FROM {}", constants::CONSENSUS_COIN_TABLE);
+        let rows = sqlx::query(&query_str).fetch_all(&mut conn).await?;
+        let mut wallet_coins: Vec<LeadCoin> = Vec::with_capacity(rows.len());
+        for row in rows {
+            let coin: Vec<u8> = row.try_get(constants::CONSENSUS_COIN_COL)?;
+            wallet_coins.push(deserialize(&coin)?);
+        }
+
+        // Retrieve lead coins and their leaf positions from contract state
+        let lead_coins = self.contract_tree(lead::MONEY_CONTRACT_LEAD_COINS_TREE);
+        let lead_nullifiers = self.contract_tree(lead::MONEY_CONTRACT_LEAD_NULLIFIERS_TREE);
+        let mut leaves: Vec<(u32, pallas::Base)> = vec![];
+        if let Some(tree) = &lead_coins {
+            for record in tree.iter() {
+                let (key, value) = record?;
+                leaves.push((deserialize(&value)?, deserialize(&key)?));
+            }
+        }
+        leaves.sort_by_key(|(position, _)| *position);
+
+        // Rebuild the coin commitments tree, witnessing our unspent coins
+        let epoch_length = self.params.epoch_length as usize;
+        let mut coins_tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(epoch_length * 100);
+        let mut coins: Vec<LeadCoin> = Vec::with_capacity(epoch_length);
+        for (_, leaf) in &leaves {
+            coins_tree.append(&MerkleNode::from(*leaf));
+
+            let Some(coin) = wallet_coins.iter().find(|coin| coin.commitment_hash() == *leaf)
+            else {
+                continue
+            };
+            if let Some(tree) = &lead_nullifiers {
+                if tree.contains_key(serialize(&coin.sn()))? {
+                    continue
+                }
             }
-            (seeds, LeadCoinSecrets::generate())
+
+            let mut coin = coin.clone();
+            let position = coins_tree.witness().unwrap();
+            coin.coin1_commitment_pos = u32::try_from(usize::from(position)).unwrap();
+            coins.push(coin);
+        }
+
+        let root = coins_tree.root(0).unwrap();
+        for coin in &mut coins {
+            let position = (coin.coin1_commitment_pos as usize).into();
+            coin.coin1_commitment_root = root;
+            coin.coin1_commitment_merkle_path =
+                coins_tree.authentication_path(position, &root).unwrap();
+        }
+        self.coins_tree = coins_tree;
+
+        if !coins.is_empty() {
+            let value: u64 = coins.iter().map(|c| c.value).sum();
+            info!(target: "consensus::state", "create_coins(): Will use {} staked LeadCoins with total value: {}", coins.len(), value);
+            return Ok(coins)
+        }
+
+        // Without stake, we compete with a zero value coin, which is not persisted.
+        // It is not in the contract state, so it is kept out of the coins tree.
+        info!(target: "consensus::state", "create_coins(): No staked LeadCoin was found, generating new one...");
+        let seed: u64 = thread_rng().gen();
+        let epoch_secrets = LeadCoinSecrets::generate(epoch_length);
+        let coin = LeadCoin::new(
+            0,
+            self.current_slot(),
+            epoch_secrets.secret_keys[0].inner(),
+            epoch_secrets.merkle_roots[0],
+            0,
+            epoch_secrets.merkle_paths[0].clone(),
+            pallas::Base::from(seed),
+            &mut self.coins_tree.clone(),
+        );
+        info!(target: "consensus::state", "create_coins(): Will use LeadCoin with value: {}", coin.value);
+        coins.push(coin);
+
+        Ok(coins)
+    }
+
+    /// Open a money contract state tree, if the contract has created it.
+    fn contract_tree(&self, tree_name: &str) -> Option<sled::Tree> {
+        self.blockchain
+            .contracts
+            .lookup(&self.blockchain.sled_db, &MONEY_CONTRACT_ID, tree_name)
+            .ok()
+    }
+
+    /// Retrieve the money contract lead coins Merkle tree.
+    fn contract_lead_coins_tree(&self) -> Result<MerkleTree> {
+        let Some(info) = self.contract_tree(lead::MONEY_CONTRACT_INFO_TREE) else {
+            return Err(Error::ContractStateNotFound)
+        };
+        let Some(tree_data) = info.get(serialize(&lead::MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE))?
+        else {
+            return Err(Error::ContractStateNotFound)
         };
 
-        // LeadCoin matrix containing node competing coins.
-        let mut coins: Vec<LeadCoin> = Vec::with_capacity(constants::EPOCH_LENGTH);
+        // The serialized Merkle tree is prefixed with its leaf count
+        Ok(deserialize(&tree_data[4..])?)
+    }
 
-        // Retrieve coin from wallet
-        // NOTE: In future this will be retrieved from the money contract.
-        // Get a wallet connection
-        let mut conn = self.wallet.conn.acquire().await?;
+    /// Check if given Merkle root is a root of the lead coins tree, either in
+    /// the money contract state or after the coins derived by the leaders of
+    /// given fork chain get appended to it, as they will once finalized.
+    pub fn is_lead_coin_root(&self, fork_index: i64, root: &MerkleNode) -> Result<bool> {
+        let Some(roots) = self.contract_tree(lead::MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE) else {
+            return Ok(false)
+        };
+        if roots.contains_key(serialize(root))? {
+            return Ok(true)
+        }
+        if fork_index < 0 {
+            return Ok(false)
+        }
 
-        // Execute the query and see if we find any rows
-        let query_str = format!("SELECT * FROM {}", constants::CONSENSUS_COIN_TABLE);
-        let mut query = sqlx::query(&query_str);
-        let coin = match query.fetch_one(&mut conn).await {
-            Ok(row) => {
-                let bytes: Vec<u8> = row.try_get(constants::CONSENSUS_COIN_COL)?;
-                deserialize(&bytes)?
+        // Only coins derived from staked coins get appended
+        let mut tree = self.contract_lead_coins_tree()?;
+        let mut fork_roots = vec![];
+        for state_checkpoint in &self.forks[fork_index as usize].sequence {
+            let lf = &state_checkpoint.proposal.block.lead_info;
+            let leader_root = lf.commitment_root();
+            if !roots.contains_key(serialize(&leader_root))? && !fork_roots.contains(&leader_root) {
+                continue
             }
-            Err(_) => {
-                // If no records are found, we generate a new coin and save it to the database
-                info!(target: "consensus::state", "create_coins(): No LeadCoin was found in DB, generating new one...");
-                // Temporarily, we compete with fixed stake.
-                // This stake should be based on how many nodes we want to run, and they all
-                // must sum to initial distribution total coins.
-                //let stake = self.initial_distribution;
-                let c = LeadCoin::new(
-                    0,
-                    self.current_slot(),
-                    epoch_secrets.secret_keys[0].inner(),
-                    epoch_secrets.merkle_roots[0],
-                    0,
-                    epoch_secrets.merkle_paths[0].clone(),
-                    pallas::Base::from(seeds[0]),
-                    &mut self.coins_tree,
-                );
-                let query_str = format!(
-                    "INSERT INTO {} ({}) VALUES (?1);",
-                    constants::CONSENSUS_COIN_TABLE,
-                    constants::CONSENSUS_COIN_COL
-                );
-                query = sqlx::query(&query_str);
-                query = query.bind(serialize(&c));
-                query.execute(&mut conn).await?;
-                c
+            tree.append(&MerkleNode::from(lf.derived_commitment_hash()));
+            fork_roots.push(tree.root(0).unwrap());
+        }
+
+        Ok(fork_roots.contains(root))
+    }
+
+    /// Verify that a leader competed with an unspent staked lead coin, proven
+    /// in the lead coins tree of the money contract or of given fork chain.
+    /// Until a coin gets staked, the tree is empty, so unstaked coins can
+    /// compete to bootstrap the chain.
+    pub fn verify_lead_coin(&self, fork_index: i64, lead_info: &LeadInfo) -> Result<()> {
+        if lead_info.public_inputs.len() <= constants::PI_REWARD_INDEX {
+            return Err(Error::InvalidPublicInputsError)
+        }
+
+        let sn = lead_info.public_inputs[constants::PI_NULLIFIER_INDEX];
+        if let Some(tree) = self.contract_tree(lead::MONEY_CONTRACT_LEAD_NULLIFIERS_TREE) {
+            if tree.contains_key(serialize(&sn))? {
+                info!(target: "consensus::state", "verify_lead_coin(): Leader coin is spent");
+                return Err(Error::ProposalIsSpent)
             }
+        }
+
+        let Some(roots) = self.contract_tree(lead::MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE) else {
+            return Ok(())
         };
-        info!(target: "consensus::state", "create_coins(): Will use LeadCoin with value: {}", coin.value);
-        coins.push(coin);
+        if roots.is_empty() {
+            return Ok(())
+        }
 
-        Ok(coins)
+        if !self.is_lead_coin_root(fork_index, &lead_info.commitment_root())? {
+            info!(target: "consensus::state", "verify_lead_coin(): Leader coin is not staked");
+            return Err(Error::ProposalCoinNotStaked)
+        }
+
+        Ok(())
     }
 
-    /// Leadership reward, assuming constant reward
-    /// TODO (res) implement reward mechanism with accord to DRK,DARK token-economics
-    fn reward(&self) -> u64 {
-        constants::REWARD
+    /// Store the secrets of a lead coin staked through the money contract,
+    /// so it competes once the stake is finalized and coins get recreated.
+    pub async fn add_staked_coin(&self, coin: &LeadCoin) -> Result<()> {
+        let mut conn = self.wallet.conn.acquire().await?;
+        let query_str = format!(
+            "INSERT INTO {} ({}, {}) VALUES (?1, ?2);",
+            constants::CONSENSUS_COIN_TABLE,
+            constants::CONSENSUS_COIN_COL_STAKED,
+            constants::CONSENSUS_COIN_COL
+        );
+        let coin = serialize(coin);
+        sqlx::query(&query_str).bind(&coin).bind(&coin).execute(&mut conn).await?;
+        Ok(())
+    }
+
+    /// Store the coin derived from a wallet coin that won a slot, next to the
+    /// coin it was staked as. It competes once its block is finalized and the
+    /// money contract mints it, while the winning coin gets spent. Coins not
+    /// staked, like the zero value one, are not persisted.
+    pub async fn add_derived_coin(&self, spent: &LeadCoin, derived: &LeadCoin) -> Result<()> {
+        let mut conn = self.wallet.conn.acquire().await?;
+        let query_str = format!(
+            "SELECT {}, {} FROM {}",
+            constants::CONSENSUS_COIN_COL_STAKED,
+            constants::CONSENSUS_COIN_COL,
+            constants::CONSENSUS_COIN_TABLE
+        );
+        let rows = sqlx::query(&query_str).fetch_all(&mut conn).await?;
+        for row in rows {
+            let bytes: Vec<u8> = row.try_get(constants::CONSENSUS_COIN_COL)?;
+            let coin: LeadCoin = deserialize(&bytes)?;
+            if coin.commitment_hash() != spent.commitment_hash() {
+                continue
+            }
+
+            let staked: Vec<u8> = row.try_get(constants::CONSENSUS_COIN_COL_STAKED)?;
+            let query_str = format!(
+                "INSERT INTO {} ({}, {}) VALUES (?1, ?2);",
+                constants::CONSENSUS_COIN_TABLE,
+                constants::CONSENSUS_COIN_COL_STAKED,
+                constants::CONSENSUS_COIN_COL
+            );
+            sqlx::query(&query_str)
+                .bind(staked)
+                .bind(serialize(derived))
+                .execute(&mut conn)
+                .await?;
+            break
+        }
+
+        Ok(())
     }
 
     /// Auxillary function to calculate total slot rewards.
@@ -353,15 +505,15 @@ impl ConsensusState {
         // Calculate rewarded slots
         let rewarded_slots = blocks + max_fork_length;
 
-        rewarded_slots * self.reward()
+        self.params.total_rewards(rewarded_slots)
     }
 
-    /// Network total stake, assuming constant reward.
+    /// Network total stake, following the chain parameters reward schedule.
     /// Only used for fine-tuning. At genesis epoch first slot, of absolute index 0,
     /// if no stake was distributed, the total stake would be 0.
     /// To avoid division by zero, we asume total stake at first division is GENESIS_TOTAL_STAKE(1).
     fn total_stake(&self) -> u64 {
-        let total_stake = self.slot_rewards() + self.initial_distribution;
+        let total_stake = self.slot_rewards() + self.params.initial_distribution;
         if total_stake == 0 {
             return constants::GENESIS_TOTAL_STAKE
         }
@@ -371,11 +523,10 @@ impl ConsensusState {
     fn f_err(&mut self) -> Float10 {
         info!(target: "consensus::state", "Previous leaders: {}", self.previous_leaders);
         // Write counter to file
-        let mut count_str: String = self.previous_leaders.to_string();
-        count_str.push(',');
-        let f =
-            File::options().append(true).create(true).open(constants::LEADER_HISTORY_LOG).unwrap();
-        {
+        if let Some(path) = &self.params.leader_history_log {
+            let mut count_str: String = self.previous_leaders.to_string();
+            count_str.push(',');
+            let f = File::options().append(true).create(true).open(path).unwrap();
             let mut writer = BufWriter::new(f);
             let _ = writer.write(&count_str.into_bytes()).unwrap();
         }
@@ -419,9 +570,8 @@ impl ConsensusState {
             f = constants::MAX_F.clone()
         }
         // log f history
-        let file =
-            File::options().append(true).create(true).open(constants::F_HISTORY_LOG).unwrap();
-        {
+        if let Some(path) = &self.params.f_history_log {
+            let file = File::options().append(true).create(true).open(path).unwrap();
             let mut f_history = format!("{:}", f);
             f_history.push(',');
             let mut writer = BufWriter::new(file);
@@ -473,6 +623,7 @@ impl ConsensusState {
                 sigma2,
                 self.get_eta(),
                 pallas::Base::from(self.current_slot()),
+                self.params.lottery_history_log.as_deref(),
             );
 
             if first_winning && !won {
@@ -580,10 +731,52 @@ impl ConsensusState {
         false
     }
 
+    /// Retrieve a proposal held in any of our chains by its hash.
+    pub fn get_proposal(&self, input_proposal: &blake3::Hash) -> Option<BlockProposal> {
+        for chain in self.forks.iter() {
+            for state_checkpoint in chain.sequence.iter().rev() {
+                if input_proposal == &state_checkpoint.proposal.hash {
+                    return Some(state_checkpoint.proposal.clone())
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Update the fork chains after a block received from the network got
+    /// appended to the canonical chain. Forks starting with the block have it
+    /// removed, and the canonical coin states are set from its state checkpoint.
+    /// All other forks no longer extend the canonical chain, so they are dropped.
+    pub fn prune_forks(&mut self, finalized: &blake3::Hash) {
+        let mut forks = vec![];
+        for mut fork in std::mem::take(&mut self.forks) {
+            if fork.sequence[0].proposal.hash != *finalized {
+                continue
+            }
+
+            let state_checkpoint = fork.sequence.remove(0);
+            self.coins = state_checkpoint.coins;
+            self.coins_tree = state_checkpoint.coins_tree;
+            self.nullifiers = state_checkpoint.nullifiers;
+            if !fork.sequence.is_empty() {
+                forks.push(fork);
+            }
+        }
+
+        self.forks = forks;
+    }
+
     /// Utility function to extract leader selection lottery randomness(eta),
     /// defined as the hash of the last block, converted to pallas base.
     pub fn get_eta(&self) -> pallas::Base {
         let (_, hash) = self.blockchain.last().unwrap();
+        Self::block_eta(&hash)
+    }
+
+    /// Convert provided block hash to the leader selection lottery randomness(eta)
+    /// it yields, when being the last block.
+    pub fn block_eta(hash: &blake3::Hash) -> pallas::Base {
         let mut bytes: [u8; 32] = *hash.as_bytes();
         // Read first 254 bits
         bytes[30] = 0;
@@ -653,6 +846,10 @@ impl net::Message for ConsensusRequest {
     fn name() -> &'static str {
         "consensusrequest"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used for consensus syncing.
@@ -682,6 +879,10 @@ impl net::Message for ConsensusResponse {
     fn name() -> &'static str {
         "consensusresponse"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used for consensus syncing.
@@ -692,6 +893,10 @@ impl net::Message for ConsensusSlotCheckpointsRequest {
     fn name() -> &'static str {
         "consensusslotcheckpointsrequest"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used for consensus syncing.
@@ -709,10 +914,14 @@ impl net::Message for ConsensusSlotCheckpointsResponse {
     fn name() -> &'static str {
         "consensusslotcheckpointsresponse"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used to keep track of slot validation parameters.
-#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+#[derive(Debug, Clone, PartialEq, Eq, SerialEncodable, SerialDecodable)]
 pub struct SlotCheckpoint {
     /// Slot UID
     pub slot: u64,
@@ -756,6 +965,10 @@ impl net::Message for SlotCheckpointRequest {
     fn name() -> &'static str {
         "slotcheckpointrequest"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used for slot checkpoints syncing
@@ -769,6 +982,10 @@ impl net::Message for SlotCheckpointResponse {
     fn name() -> &'static str {
         "slotcheckpointresponse"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Bulk
+    }
 }
 
 /// Auxiliary structure used to keep track of consensus state checkpoints.
@@ -810,13 +1027,14 @@ impl From<StateCheckpoint> for StateCheckpointInfo {
     }
 }
 
-impl From<StateCheckpointInfo> for StateCheckpoint {
-    fn from(state_checkpoint_info: StateCheckpointInfo) -> Self {
-        Self {
-            proposal: state_checkpoint_info.proposal,
+impl StateCheckpointInfo {
+    /// Build the state checkpoint, sizing its coins tree for given epoch length.
+    pub fn into_state_checkpoint(self, epoch_length: u64) -> StateCheckpoint {
+        StateCheckpoint {
+            proposal: self.proposal,
             coins: vec![],
-            coins_tree: BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(constants::EPOCH_LENGTH * 100),
-            nullifiers: state_checkpoint_info.nullifiers,
+            coins_tree: BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(epoch_length as usize * 100),
+            nullifiers: self.nullifiers,
         }
     }
 }
@@ -895,13 +1113,14 @@ impl From<Fork> for ForkInfo {
     }
 }
 
-impl From<ForkInfo> for Fork {
-    fn from(fork_info: ForkInfo) -> Self {
+impl ForkInfo {
+    /// Build the fork, sizing its checkpoints coins trees for given epoch length.
+    pub fn into_fork(self, epoch_length: u64) -> Fork {
         let mut sequence = vec![];
-        for checkpoint in fork_info.sequence {
-            sequence.push(checkpoint.into());
+        for checkpoint in self.sequence {
+            sequence.push(checkpoint.into_state_checkpoint(epoch_length));
         }
-        Self { genesis_block: fork_info.genesis_block, sequence }
+        Fork { genesis_block: self.genesis_block, sequence }
     }
 }
 
@@ -911,8 +1130,7 @@ mod tests {
         consensus::{
             state::{Blockchain, ConsensusState},
             utils::fbig2base,
-            Float10, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
-            TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION,
+            ChainParams, Float10, TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP,
         },
         wallet::WalletDb,
         Result,
@@ -925,15 +1143,7 @@ mod tests {
         let sled_db = sled::Config::new().temporary(true).open()?;
         let blockchain =
             Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
-        let state = ConsensusState::new(
-            wallet,
-            blockchain,
-            *TESTNET_BOOTSTRAP_TIMESTAMP,
-            *TESTNET_GENESIS_TIMESTAMP,
-            *TESTNET_GENESIS_HASH_BYTES,
-            *TESTNET_INITIAL_DISTRIBUTION,
-            true,
-        )?;
+        let state = ConsensusState::new(wallet, blockchain, ChainParams::testnet(), true)?;
 
         let precision_diff = Float10::try_from(
             "10000000000000000000000000000000000000000000000000000000000000000000000000",
@@ -16,109 +16,519 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::{
+    collections::VecDeque,
+    sync::atomic::{AtomicU32, Ordering},
+    time::Duration,
+};
+
+use async_std::{
+    future::timeout,
+    sync::{Arc, Mutex},
+};
+use futures::future::join_all;
+use log::{debug, info, warn};
+use rand::seq::SliceRandom;
+
 use crate::{
     consensus::{
-        block::{BlockOrder, BlockResponse},
+        block::{BlockInfo, BlockResponse, BodyOrder, HeaderOrder, HeaderResponse, SyncHeader},
+        constants::{
+            SYNC_BODIES_BATCH, SYNC_HEADER_PEERS, SYNC_INVALID_DATA_BAN_SCORE,
+            SYNC_PEER_MAX_FAILURES, SYNC_REQUEST_TIMEOUT,
+        },
         state::{SlotCheckpointRequest, SlotCheckpointResponse},
-        ValidatorStatePtr,
+        LightClient, SlotCheckpoint, ValidatorStatePtr,
     },
-    net, Result,
+    net::{self, ChannelPtr, MessageSubscription},
+    Error, Result,
 };
-use log::{debug, info, warn};
+
+/// A peer we sync the blockchain from.
+struct SyncPeer {
+    channel: ChannelPtr,
+    slot_checkpoints_sub: MessageSubscription<SlotCheckpointResponse>,
+    headers_sub: MessageSubscription<HeaderResponse>,
+    blocks_sub: MessageSubscription<BlockResponse>,
+    /// Failed or timed out requests
+    failures: AtomicU32,
+}
+
+impl SyncPeer {
+    async fn new(channel: ChannelPtr) -> Result<Self> {
+        let msg_subsystem = channel.get_message_subsystem();
+        msg_subsystem.add_dispatch::<SlotCheckpointResponse>().await;
+        msg_subsystem.add_dispatch::<HeaderResponse>().await;
+        msg_subsystem.add_dispatch::<BlockResponse>().await;
+
+        let slot_checkpoints_sub = channel.subscribe_msg::<SlotCheckpointResponse>().await?;
+        let headers_sub = channel.subscribe_msg::<HeaderResponse>().await?;
+        let blocks_sub = channel.subscribe_msg::<BlockResponse>().await?;
+
+        Ok(Self {
+            channel,
+            slot_checkpoints_sub,
+            headers_sub,
+            blocks_sub,
+            failures: AtomicU32::new(0),
+        })
+    }
+
+    async fn unsubscribe(&self) {
+        self.slot_checkpoints_sub.unsubscribe().await;
+        self.headers_sub.unsubscribe().await;
+        self.blocks_sub.unsubscribe().await;
+    }
+
+    fn is_usable(&self) -> bool {
+        self.failures.load(Ordering::Relaxed) < SYNC_PEER_MAX_FAILURES
+    }
+
+    fn fail(&self) {
+        self.failures.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Stop syncing from this peer and add to its ban score.
+    async fn punish(&self, p2p: &net::P2pPtr, reason: &str) {
+        warn!(target: "consensus::block_sync", "Peer {} sent {}", self.channel.address(), reason);
+        self.failures.store(SYNC_PEER_MAX_FAILURES, Ordering::Relaxed);
+        p2p.add_ban_score(&self.channel, SYNC_INVALID_DATA_BAN_SCORE, reason).await;
+    }
+
+    /// Send a request to the peer and wait for its response, counting a
+    /// failure if it doesn't arrive in time.
+    async fn request<M: net::Message, R: net::Message>(
+        &self,
+        request: M,
+        sub: &MessageSubscription<R>,
+    ) -> Option<Arc<R>> {
+        // Drop late responses to earlier requests that timed out
+        sub.clear();
+
+        if let Err(e) = self.channel.send(request).await {
+            debug!(target: "consensus::block_sync", "Sending {} request to {} failed: {}", M::name(), self.channel.address(), e);
+            self.fail();
+            return None
+        }
+
+        match timeout(Duration::from_secs(SYNC_REQUEST_TIMEOUT), sub.receive()).await {
+            Ok(Ok(response)) => Some(response),
+            Ok(Err(e)) => {
+                debug!(target: "consensus::block_sync", "Receiving {} from {} failed: {}", R::name(), self.channel.address(), e);
+                self.fail();
+                None
+            }
+            Err(_) => {
+                debug!(target: "consensus::block_sync", "Peer {} timed out on {} request", self.channel.address(), M::name());
+                self.fail();
+                None
+            }
+        }
+    }
+}
 
 /// async task used for block syncing.
+/// Slot checkpoints are fetched from one peer at a time. Blocks are synced
+/// header-first: the header chain is requested from several peers at once,
+/// and its leader proofs get verified against the slot checkpoints like a
+/// [`LightClient`] does. The block bodies then get downloaded in parallel
+/// from all peers. Peers sending invalid data get punished, and syncing
+/// goes on with the remaining ones.
 pub async fn block_sync_task(p2p: net::P2pPtr, state: ValidatorStatePtr) -> Result<()> {
     info!(target: "consensus::block_sync", "Starting blockchain sync...");
-    // Getting a random connected channel to ask from peers
-    match p2p.clone().random_channel().await {
-        Some(channel) => {
-            let msg_subsystem = channel.get_message_subsystem();
-
-            // Communication setup for slot checkpoints
-            msg_subsystem.add_dispatch::<SlotCheckpointResponse>().await;
-            let slot_checkpoint_response_sub =
-                channel.subscribe_msg::<SlotCheckpointResponse>().await?;
-
-            // Communication setup for blocks
-            msg_subsystem.add_dispatch::<BlockResponse>().await;
-            let block_response_sub = channel.subscribe_msg::<BlockResponse>().await?;
-
-            // Node loops until both slot checkpoints and blocks have been synced
-            let mut slot_checkpoints_synced = false;
-            let mut blocks_synced = false;
-            loop {
-                // Node sends the last known slot checkpoint of the canonical blockchain
-                // and loops until the response is the same slot (used to utilize batch requests).
-                let mut last = state.read().await.blockchain.last_slot_checkpoint()?;
-                info!(target: "consensus::block_sync", "Last known slot checkpoint: {:?}", last.slot);
-
-                loop {
-                    // Node creates a `SlotCheckpointRequest` and sends it
-                    let request = SlotCheckpointRequest { slot: last.slot };
-                    channel.send(request).await?;
-
-                    // Node stores response data.
-                    let resp = slot_checkpoint_response_sub.receive().await?;
-
-                    // Verify and store retrieved checkpoints
-                    debug!(target: "consensus::block_sync", "block_sync_task(): Processing received slot checkpoints");
-                    state.write().await.receive_slot_checkpoints(&resp.slot_checkpoints).await?;
-
-                    let last_received = state.read().await.blockchain.last_slot_checkpoint()?;
-                    info!(target: "consensus::block_sync", "Last received slot checkpoint: {:?}", last_received.slot);
-
-                    if last.slot == last_received.slot {
-                        break
-                    }
+    let channels = p2p.peer_channels().await;
+    if channels.is_empty() {
+        warn!(target: "consensus::block_sync", "Node is not connected to other nodes");
+        state.write().await.synced = true;
+        info!(target: "consensus::block_sync", "Blockchain synced!");
+        return Ok(())
+    }
 
-                    blocks_synced = false;
-                    last = last_received;
-                }
+    let mut peers = Vec::with_capacity(channels.len());
+    for channel in channels {
+        peers.push(SyncPeer::new(channel).await?);
+    }
+    info!(target: "consensus::block_sync", "Syncing from {} peers", peers.len());
 
-                // We force a recheck of slot checkpoints after blocks have been synced
-                if blocks_synced {
-                    slot_checkpoints_synced = true;
-                }
+    let result = sync_loop(&p2p, &state, &peers).await;
+    for peer in &peers {
+        peer.unsubscribe().await;
+    }
+    result?;
 
-                // Node sends the last known block hash of the canonical blockchain
-                // and loops until the response is the same block (used to utilize
-                // batch requests).
-                let mut last = state.read().await.blockchain.last()?;
-                info!(target: "consensus::block_sync", "Last known block: {:?} - {:?}", last.0, last.1);
+    state.write().await.synced = true;
+    info!(target: "consensus::block_sync", "Blockchain synced!");
+    Ok(())
+}
 
-                loop {
-                    // Node creates a `BlockOrder` and sends it
-                    let order = BlockOrder { slot: last.0, block: last.1 };
-                    channel.send(order).await?;
+/// Node loops until both slot checkpoints and blocks have been synced.
+/// Slot checkpoints are rechecked every time new blocks were received.
+async fn sync_loop(p2p: &net::P2pPtr, state: &ValidatorStatePtr, peers: &[SyncPeer]) -> Result<()> {
+    loop {
+        let new_slot_checkpoints = sync_slot_checkpoints(state, peers).await?;
+        let new_blocks = sync_blocks(p2p, state, peers).await?;
 
-                    // Node stores response data.
-                    let resp = block_response_sub.receive().await?;
+        if !new_slot_checkpoints && !new_blocks {
+            return Ok(())
+        }
+    }
+}
 
-                    // Verify and store retrieved blocks
-                    debug!(target: "consensus::block_sync", "block_sync_task(): Processing received blocks");
-                    state.write().await.receive_sync_blocks(&resp.blocks).await?;
+/// Usable peers in random order, so requests rotate between them.
+fn usable_peers(peers: &[SyncPeer], n: usize) -> Vec<&SyncPeer> {
+    let mut usable: Vec<&SyncPeer> = peers.iter().filter(|peer| peer.is_usable()).collect();
+    usable.shuffle(&mut rand::thread_rng());
+    usable.truncate(n);
+    usable
+}
 
-                    let last_received = state.read().await.blockchain.last()?;
-                    info!(target: "consensus::block_sync", "Last received block: {:?} - {:?}", last_received.0, last_received.1);
+/// Sync slot checkpoints, returning whether any new ones were received.
+async fn sync_slot_checkpoints(state: &ValidatorStatePtr, peers: &[SyncPeer]) -> Result<bool> {
+    // Node sends the last known slot checkpoint of the canonical blockchain
+    // and loops until the response is the same slot (used to utilize batch requests).
+    let mut last = state.read().await.blockchain.last_slot_checkpoint()?;
+    info!(target: "consensus::block_sync", "Last known slot checkpoint: {:?}", last.slot);
 
-                    if last == last_received {
-                        blocks_synced = true;
-                        break
-                    }
+    let mut progress = false;
+    loop {
+        let request = SlotCheckpointRequest { slot: last.slot };
+
+        // Node tries its peers in turn, until one of them answers
+        let mut response = None;
+        for peer in usable_peers(peers, peers.len()) {
+            response = peer.request(request.clone(), &peer.slot_checkpoints_sub).await;
+            if response.is_some() {
+                break
+            }
+        }
+        let Some(response) = response else { return Err(Error::SyncPeersExhausted) };
+
+        // Verify and store retrieved checkpoints
+        debug!(target: "consensus::block_sync", "sync_slot_checkpoints(): Processing received slot checkpoints");
+        state.write().await.receive_slot_checkpoints(&response.slot_checkpoints).await?;
+
+        let last_received = state.read().await.blockchain.last_slot_checkpoint()?;
+        info!(target: "consensus::block_sync", "Last received slot checkpoint: {:?}", last_received.slot);
+
+        if last.slot == last_received.slot {
+            return Ok(progress)
+        }
+
+        progress = true;
+        last = last_received;
+    }
+}
+
+/// Sync blocks header-first, returning whether any new ones were received.
+async fn sync_blocks(
+    p2p: &net::P2pPtr,
+    state: &ValidatorStatePtr,
+    peers: &[SyncPeer],
+) -> Result<bool> {
+    let params = state.read().await.consensus.params.clone();
+    let mut light_client =
+        LightClient::from_blockchain(params.clone(), &state.read().await.blockchain)?;
+
+    let mut progress = false;
+    loop {
+        let last = state.read().await.blockchain.last()?;
+        info!(target: "consensus::block_sync", "Last known block: {:?} - {:?}", last.0, last.1);
+
+        let (headers, sources, followed) = sync_headers(p2p, state, peers, &light_client).await?;
+        if headers.is_empty() {
+            return Ok(progress)
+        }
 
-                    slot_checkpoints_synced = false;
-                    last = last_received;
+        info!(target: "consensus::block_sync", "Downloading {} blocks", headers.len());
+        let blocks = sync_bodies(p2p, peers, &headers).await?;
+
+        // Verify and store retrieved blocks one by one, so the ones preceding
+        // an invalid block are kept.
+        debug!(target: "consensus::block_sync", "sync_blocks(): Processing received blocks");
+        let mut applied = 0;
+        for block in &blocks {
+            if let Err(e) = state.write().await.receive_sync_blocks(&[block.clone()]).await {
+                // Headers commit to the whole block, so the peers that sent
+                // a header chain including it are at fault.
+                warn!(target: "consensus::block_sync", "Block {} failed verification: {}", block.blockhash(), e);
+                for (peer, vouched) in &sources {
+                    if *vouched > applied {
+                        peer.punish(p2p, "a chain with an invalid block").await;
+                    }
                 }
+                break
+            }
+            applied += 1;
+        }
+
+        let last_received = state.read().await.blockchain.last()?;
+        info!(target: "consensus::block_sync", "Last received block: {:?} - {:?}", last_received.0, last_received.1);
 
-                if slot_checkpoints_synced && blocks_synced {
-                    break
+        if last_received.1 == followed.last().1 {
+            light_client = followed;
+        } else {
+            light_client =
+                LightClient::from_blockchain(params.clone(), &state.read().await.blockchain)?;
+        }
+
+        // Retry with the remaining peers after an invalid block
+        if applied < blocks.len() {
+            progress |= applied > 0;
+            continue
+        }
+
+        if last == last_received {
+            return Ok(progress)
+        }
+
+        progress = true;
+    }
+}
+
+/// Request the headers following our last block from several peers at
+/// once, keeping the longest chain among their responses that the light
+/// client can follow. Along with the headers, returns the peers that sent
+/// a prefix of them with its length, and the light client following them.
+async fn sync_headers<'a>(
+    p2p: &net::P2pPtr,
+    state: &ValidatorStatePtr,
+    peers: &'a [SyncPeer],
+    light_client: &LightClient,
+) -> Result<(Vec<SyncHeader>, Vec<(&'a SyncPeer, usize)>, LightClient)> {
+    let (last_slot, _) = light_client.last();
+    loop {
+        let candidates = usable_peers(peers, SYNC_HEADER_PEERS);
+        if candidates.is_empty() {
+            return Err(Error::SyncPeersExhausted)
+        }
+
+        let requests = candidates
+            .iter()
+            .map(|peer| peer.request(HeaderOrder { slot: last_slot }, &peer.headers_sub));
+        let responses = join_all(requests).await;
+
+        // Longest chains first, so only the chain we keep gets verified
+        let mut received: Vec<(&SyncPeer, &Vec<SyncHeader>)> = candidates
+            .into_iter()
+            .zip(responses.iter())
+            .filter_map(|(peer, response)| Some((peer, &response.as_ref()?.headers)))
+            .collect();
+        received.sort_by_key(|(_, headers)| std::cmp::Reverse(headers.len()));
+
+        for (peer, headers) in &received {
+            let slots: Vec<u64> = headers.iter().map(|x| x.header.slot).collect();
+            let slot_checkpoints: Vec<SlotCheckpoint> = state
+                .read()
+                .await
+                .blockchain
+                .slot_checkpoints
+                .get(&slots, false)?
+                .into_iter()
+                .flatten()
+                .collect();
+
+            match follow_headers(light_client, &slot_checkpoints, headers) {
+                Ok(followed) => {
+                    let sources = received
+                        .iter()
+                        .filter(|(_, other)| headers.starts_with(other))
+                        .map(|(peer, other)| (*peer, other.len()))
+                        .collect();
+                    return Ok((headers.to_vec(), sources, followed))
+                }
+                Err(e) => {
+                    debug!(target: "consensus::block_sync", "Header chain of {} failed verification: {}", peer.channel.address(), e);
+                    peer.punish(p2p, "an invalid header chain").await;
                 }
             }
         }
-        None => warn!(target: "consensus::block_sync", "Node is not connected to other nodes"),
+    }
+}
+
+/// Download the bodies of the given headers in chunks, spread over all
+/// usable peers. Returns the blocks in chain order, up to the first chunk
+/// no peer could provide.
+async fn sync_bodies(
+    p2p: &net::P2pPtr,
+    peers: &[SyncPeer],
+    headers: &[SyncHeader],
+) -> Result<Vec<BlockInfo>> {
+    let chunks: Vec<&[SyncHeader]> = headers.chunks(SYNC_BODIES_BATCH).collect();
+    let queue = Mutex::new((0..chunks.len()).collect::<VecDeque<usize>>());
+    let downloaded = Mutex::new(vec![None; chunks.len()]);
+
+    // A worker may give up on a chunk after the others have already
+    // finished, so we go again until the queue is empty.
+    while !queue.lock().await.is_empty() {
+        let workers = usable_peers(peers, peers.len());
+        if workers.is_empty() {
+            break
+        }
+
+        let workers = workers
+            .into_iter()
+            .map(|peer| download_bodies(p2p, peer, &chunks, &queue, &downloaded));
+        join_all(workers).await;
+    }
+
+    let blocks: Vec<BlockInfo> =
+        downloaded.into_inner().into_iter().map_while(|x| x).flatten().collect();
+    if blocks.is_empty() {
+        return Err(Error::SyncPeersExhausted)
+    }
+
+    Ok(blocks)
+}
+
+/// Worker downloading queued chunks of block bodies from a single peer,
+/// until the queue is empty or the peer is no longer usable.
+async fn download_bodies(
+    p2p: &net::P2pPtr,
+    peer: &SyncPeer,
+    chunks: &[&[SyncHeader]],
+    queue: &Mutex<VecDeque<usize>>,
+    downloaded: &Mutex<Vec<Option<Vec<BlockInfo>>>>,
+) {
+    while peer.is_usable() {
+        let Some(index) = queue.lock().await.pop_front() else { break };
+
+        let chunk = chunks[index];
+        let hashes = chunk.iter().map(|x| x.blockhash()).collect();
+        let Some(response) = peer.request(BodyOrder { hashes }, &peer.blocks_sub).await else {
+            queue.lock().await.push_back(index);
+            continue
+        };
+
+        // Peers which don't have the blocks answer with less of them
+        if response.blocks.len() != chunk.len() {
+            debug!(target: "consensus::block_sync", "Peer {} is missing requested blocks", peer.channel.address());
+            peer.fail();
+            queue.lock().await.push_back(index);
+            continue
+        }
+
+        if !verify_bodies(chunk, &response.blocks) {
+            peer.punish(p2p, "blocks not matching their headers").await;
+            queue.lock().await.push_back(index);
+            continue
+        }
+
+        downloaded.lock().await[index] = Some(response.blocks.clone());
+    }
+}
+
+/// Verify that the headers form a chain extending the one followed by the
+/// light client, with valid leader proofs for given slot checkpoints.
+/// Returns a light client following them.
+fn follow_headers(
+    light_client: &LightClient,
+    slot_checkpoints: &[SlotCheckpoint],
+    headers: &[SyncHeader],
+) -> Result<LightClient> {
+    let mut light_client = light_client.clone();
+    light_client.add_slot_checkpoints(slot_checkpoints)?;
+    for header in headers {
+        light_client.follow(&header.header, &header.block)?;
+    }
+
+    Ok(light_client)
+}
+
+/// Check that the blocks are the ones described by the headers.
+fn verify_bodies(headers: &[SyncHeader], blocks: &[BlockInfo]) -> bool {
+    headers.len() == blocks.len() &&
+        headers.iter().zip(blocks).all(|(header, block)| {
+            block.header == header.header && block.blockhash() == header.blockhash()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{follow_headers, verify_bodies};
+    use crate::{
+        consensus::{
+            block::SyncHeader, testing::TestChain, Block, BlockInfo, ChainParams, Header, LeadInfo,
+            LightClient,
+        },
+        Result,
     };
 
-    state.write().await.synced = true;
-    info!(target: "consensus::block_sync", "Blockchain synced!");
-    Ok(())
+    /// Build a chain of `n` blocks following the given one
+    fn chain(last_slot: u64, last_hash: blake3::Hash, n: u64) -> Vec<BlockInfo> {
+        let mut previous = last_hash;
+        let mut blocks = vec![];
+        for slot in last_slot + 1..=last_slot + n {
+            let header = Header { previous, slot, ..Header::default() };
+            let block = BlockInfo::new(header, vec![], LeadInfo::default());
+            previous = block.blockhash();
+            blocks.push(block);
+        }
+        blocks
+    }
+
+    fn sync_headers(blocks: &[BlockInfo]) -> Vec<SyncHeader> {
+        blocks
+            .iter()
+            .map(|block| SyncHeader {
+                header: block.header.clone(),
+                block: Block::from(block.clone()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_follow_headers() -> Result<()> {
+        let mut chain = TestChain::new(ChainParams::devnet());
+        chain.extend(1);
+        chain.extend(2);
+        chain.extend(4);
+        let client = LightClient::new(chain.params.clone())?;
+        let checkpoints = &chain.slot_checkpoints;
+        let headers = sync_headers(&chain.blocks);
+
+        assert_eq!(follow_headers(&client, checkpoints, &headers)?.last(), chain.last());
+        assert_eq!(follow_headers(&client, checkpoints, &[])?.last(), client.last());
+        assert_eq!(client.last().0, 0);
+
+        // Not extending our last block
+        assert!(follow_headers(&client, checkpoints, &headers[1..]).is_err());
+
+        // Broken link
+        let mut broken = headers.clone();
+        broken.remove(1);
+        assert!(follow_headers(&client, checkpoints, &broken).is_err());
+
+        // Reordered
+        let mut reordered = headers.clone();
+        reordered.swap(1, 2);
+        assert!(follow_headers(&client, checkpoints, &reordered).is_err());
+
+        // Missing slot checkpoints
+        assert!(follow_headers(&client, &[], &headers).is_err());
+
+        // Valid links, but a leader proof of another block
+        let mut tampered = headers.clone();
+        tampered[2].block.lead_info.proof = headers[1].block.lead_info.proof.clone();
+        assert!(follow_headers(&client, checkpoints, &tampered).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_bodies() {
+        let blocks = chain(0, blake3::hash(b"genesis"), 3);
+        let headers = sync_headers(&blocks);
+
+        assert!(verify_bodies(&headers, &blocks));
+        assert!(!verify_bodies(&headers, &blocks[..2]));
+
+        let mut reordered = blocks.clone();
+        reordered.swap(0, 1);
+        assert!(!verify_bodies(&headers, &reordered));
+
+        // Same header, different block contents
+        let mut tampered = blocks;
+        tampered[2].lead_info.leaders += 1;
+        assert!(!verify_bodies(&headers, &tampered));
+    }
 }
@@ -139,9 +139,10 @@ pub async fn consensus_sync_task(p2p: P2pPtr, state: ValidatorStatePtr) -> Resul
 
     // Node stores response data.
     let mut lock = state.write().await;
+    let epoch_length = lock.consensus.params.epoch_length;
     let mut forks = vec![];
     for fork in &response.forks {
-        forks.push(fork.clone().into());
+        forks.push(fork.clone().into_fork(epoch_length));
     }
     lock.consensus.bootstrap_slot = response.bootstrap_slot;
     lock.consensus.forks = forks;
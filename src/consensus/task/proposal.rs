@@ -25,7 +25,7 @@ use super::consensus_sync_task;
 use crate::{
     consensus::{constants, ValidatorStatePtr},
     net::P2pPtr,
-    util::{async_util::sleep, time::Timestamp},
+    util::async_util::sleep,
 };
 
 /// async task used for participating in the consensus protocol
@@ -39,15 +39,15 @@ pub async fn proposal_task(
     // otherwise wait for current or next slot finalization period for optimal sync conditions.
     // NOTE: Network beign configured to start in the future should always be the case
     // when bootstrapping or restarting a network.
-    let current_ts = Timestamp::current_time();
-    let bootstrap_ts = state.read().await.consensus.bootstrap_ts;
+    let current_ts = state.read().await.consensus.time.now();
+    let bootstrap_ts = state.read().await.consensus.params.bootstrap_ts;
     if current_ts < bootstrap_ts {
         let diff = bootstrap_ts.0 - current_ts.0;
         info!(target: "consensus::proposal", "consensus: Waiting for network bootstrap: {} seconds", diff);
         sleep(diff as u64).await;
     } else {
         let mut sleep_time = state.read().await.consensus.next_n_slot_start(1);
-        let sync_offset = Duration::new(constants::FINAL_SYNC_DUR, 0);
+        let sync_offset = Duration::new(state.read().await.consensus.params.final_sync_dur, 0);
         loop {
             if sleep_time > sync_offset {
                 sleep_time -= sync_offset;
@@ -134,13 +134,14 @@ async fn consensus_loop(
     // greater than an epoch length. Later, this will be enforced via contract,
     // where it will be explicit when a node can produce proposals,
     // and after which slot they can be considered as valid.
+    let epoch_length = state.read().await.consensus.params.epoch_length;
     let mut listened_slots = 0;
     let mut changed_status = false;
     loop {
         // Check if node can start proposing.
         // This code ensures that we only change the status once
         // and listened_slots doesn't increment further.
-        if listened_slots > constants::EPOCH_LENGTH {
+        if listened_slots > epoch_length {
             if !changed_status {
                 info!(target: "consensus::proposal", "consensus: Node can start proposing!");
                 state.write().await.consensus.proposing = true;
@@ -228,8 +229,9 @@ async fn propose_period(consensus_p2p: P2pPtr, state: ValidatorStatePtr) -> bool
     };
 
     // Node checks if it missed finalization period due to proposal creation
+    let final_sync_dur = state.read().await.consensus.params.final_sync_dur;
     let next_slot_start = state.read().await.consensus.next_n_slot_start(1);
-    if next_slot_start.as_secs() <= constants::FINAL_SYNC_DUR {
+    if next_slot_start.as_secs() <= final_sync_dur {
         warn!(
             target: "consensus::proposal",
             "consensus: Node missed slot {} finalization period due to proposal creation, resyncing...",
@@ -279,10 +281,10 @@ async fn finalization_period(
     ex: Arc<smol::Executor<'_>>,
 ) -> bool {
     // Node sleeps until finalization sync period starts
+    let final_sync_dur = state.read().await.consensus.params.final_sync_dur;
     let next_slot_start = state.read().await.consensus.next_n_slot_start(1);
-    if next_slot_start.as_secs() > constants::FINAL_SYNC_DUR {
-        let seconds_sync_period =
-            (next_slot_start - Duration::new(constants::FINAL_SYNC_DUR, 0)).as_secs();
+    if next_slot_start.as_secs() > final_sync_dur {
+        let seconds_sync_period = (next_slot_start - Duration::new(final_sync_dur, 0)).as_secs();
         info!(target: "consensus::proposal", "consensus: Waiting for finalization sync period ({} sec)", seconds_sync_period);
         sleep(seconds_sync_period).await;
     } else {
@@ -0,0 +1,180 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Chains of blocks carrying valid leader proofs, for tests.
+
+use darkfi_sdk::{
+    crypto::{constants::MERKLE_DEPTH, schnorr::SchnorrSecret, MerkleNode, PublicKey, SecretKey},
+    incrementalmerkletree::bridgetree::BridgeTree,
+    pasta::{
+        group::ff::{Field, PrimeField},
+        pallas,
+    },
+};
+use rand::rngs::OsRng;
+
+use super::{
+    constants,
+    lead_coin::{LeadCoin, LeadCoinSecrets},
+    state::ConsensusState,
+    Block, BlockInfo, ChainParams, Header, LeadInfo, LeadProof, SlotCheckpoint,
+};
+use crate::{
+    util::time::Timestamp,
+    zk::{proof::ProvingKey, vm::ZkCircuit, vm_stack::empty_witnesses},
+    zkas::ZkBinary,
+};
+
+/// Chain of blocks extending the genesis block of its parameters,
+/// each one won by a single value coin with a valid leader proof.
+pub(crate) struct TestChain {
+    /// Parameters of the chain
+    pub params: ChainParams,
+    /// Chain blocks, genesis excluded
+    pub blocks: Vec<BlockInfo>,
+    /// Slot checkpoints of the chain blocks slots
+    pub slot_checkpoints: Vec<SlotCheckpoint>,
+    /// Leader proof proving key
+    proving_key: ProvingKey,
+    /// Coin commitments tree of the created coins
+    coins_tree: BridgeTree<MerkleNode, MERKLE_DEPTH>,
+}
+
+impl TestChain {
+    /// Lottery sigmas of the chain slots. With a single value coin the
+    /// target is about 2^252, so roughly one coin out of four wins.
+    pub fn sigmas() -> (pallas::Base, pallas::Base) {
+        (pallas::Base::from_u128(1 << 126).square(), pallas::Base::zero())
+    }
+
+    pub fn new(params: ChainParams) -> Self {
+        let zkbin = ZkBinary::decode(include_bytes!("../../proof/lead.zk.bin")).unwrap();
+        let circuit = ZkCircuit::new(empty_witnesses(&zkbin), zkbin);
+        let proving_key = ProvingKey::build(constants::LEADER_PROOF_K, &circuit);
+
+        Self {
+            params,
+            blocks: vec![],
+            slot_checkpoints: vec![],
+            proving_key,
+            coins_tree: BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100),
+        }
+    }
+
+    /// Slot and hash of the last chain block.
+    pub fn last(&self) -> (u64, blake3::Hash) {
+        match self.blocks.last() {
+            Some(block) => (block.header.slot, block.blockhash()),
+            None => (
+                0,
+                Block::genesis_block(self.params.genesis_ts, self.params.genesis_data).blockhash(),
+            ),
+        }
+    }
+
+    /// Create a coin of given value for given slot.
+    pub fn coin(&mut self, value: u64, slot: u64) -> LeadCoin {
+        let secrets = LeadCoinSecrets::generate(1);
+        LeadCoin::new(
+            value,
+            slot,
+            secrets.secret_keys[0].inner(),
+            secrets.merkle_roots[0],
+            0,
+            secrets.merkle_paths[0].clone(),
+            pallas::Base::random(&mut OsRng),
+            &mut self.coins_tree,
+        )
+    }
+
+    /// Create a single value coin winning the lottery of given slot and eta.
+    pub fn winning_coin(&mut self, slot: u64, eta: pallas::Base) -> LeadCoin {
+        let (sigma1, sigma2) = Self::sigmas();
+        loop {
+            let coin = self.coin(1, slot);
+            if coin.is_leader(sigma1, sigma2, eta, pallas::Base::from(slot), None) {
+                return coin
+            }
+        }
+    }
+
+    /// Create a block of given slot extending given block, led by given coin
+    /// playing the lottery with given eta, and signed with the coin secret key.
+    pub fn block(
+        &self,
+        previous: blake3::Hash,
+        slot: u64,
+        coin: &LeadCoin,
+        eta: pallas::Base,
+    ) -> BlockInfo {
+        let (sigma1, sigma2) = Self::sigmas();
+        let (proof, public_inputs) = coin.create_lead_proof(
+            sigma1,
+            sigma2,
+            eta,
+            pallas::Base::from(slot),
+            &self.proving_key,
+            pallas::Scalar::random(&mut OsRng),
+            self.params.reward(slot),
+        );
+
+        let timestamp =
+            Timestamp(self.params.genesis_ts.0 + (slot * self.params.slot_time) as i64 + 1);
+        let header = Header::new(
+            previous,
+            slot / self.params.epoch_length,
+            slot,
+            timestamp,
+            Header::txs_root(&[]),
+        );
+
+        let secret_key = SecretKey::from(coin.coin1_sk);
+        let signature = secret_key.sign(&mut OsRng, header.headerhash().as_bytes());
+        let lead_info = LeadInfo::new(
+            signature,
+            PublicKey::from_secret(secret_key),
+            public_inputs,
+            coin.slot,
+            eta,
+            LeadProof::from(proof.unwrap()),
+            1,
+        );
+
+        let mut block = BlockInfo::new(header, vec![], lead_info);
+        block.magic = self.params.network_magic;
+        block
+    }
+
+    /// Slot checkpoint of given slot, using the chain sigmas.
+    pub fn slot_checkpoint(slot: u64, eta: pallas::Base) -> SlotCheckpoint {
+        let (sigma1, sigma2) = Self::sigmas();
+        SlotCheckpoint::new(slot, eta, sigma1, sigma2)
+    }
+
+    /// Extend the chain with a valid block of given slot, returning it.
+    pub fn extend(&mut self, slot: u64) -> BlockInfo {
+        let (_, previous) = self.last();
+        let eta = ConsensusState::block_eta(&previous);
+        let coin = self.winning_coin(slot, eta);
+        let block = self.block(previous, slot, &coin, eta);
+
+        self.slot_checkpoints.push(Self::slot_checkpoint(slot, eta));
+        self.blocks.push(block.clone());
+        block
+    }
+}
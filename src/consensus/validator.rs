@@ -16,20 +16,22 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{collections::HashMap, io::Cursor};
+use std::{collections::HashMap, io::Cursor, path::Path};
 
 use async_std::sync::{Arc, RwLock};
 use darkfi_sdk::{
     crypto::{
-        constants::MERKLE_DEPTH,
-        contract_id::{DAO_CONTRACT_ID, MONEY_CONTRACT_ID},
+        contract_id::{DAO_CONTRACT_ID, DEPLOYOOOR_CONTRACT_ID, MONEY_CONTRACT_ID},
         schnorr::{SchnorrPublic, SchnorrSecret},
-        MerkleNode, PublicKey, SecretKey,
+        ContractId, MerkleNode, PublicKey, SecretKey,
     },
-    incrementalmerkletree::{bridgetree::BridgeTree, Tree},
-    pasta::{group::ff::PrimeField, pallas},
+    deploy::{DeployFunction, DeployParamsV1, LockParamsV1},
+    incrementalmerkletree::Tree,
+    lead::{self, DeriveLeadCoinParamsV1},
+    pasta::pallas,
+    ContractCall,
 };
-use darkfi_serial::{serialize, Decodable, Encodable, WriteExt};
+use darkfi_serial::{deserialize, serialize, Decodable, Encodable, WriteExt};
 use halo2_proofs::arithmetic::Field;
 use log::{debug, error, info, warn};
 use rand::rngs::OsRng;
@@ -38,17 +40,20 @@ use serde_json::json;
 use super::{
     constants,
     lead_coin::LeadCoin,
+    mempool::{Mempool, MempoolConfig},
     state::{ConsensusState, Fork, SlotCheckpoint, StateCheckpoint},
-    BlockInfo, BlockProposal, Header, LeadInfo, LeadProof,
+    BlockInfo, BlockProposal, ChainParams, Header, LeadInfo, LeadProof,
 };
 
 use crate::{
-    blockchain::{Blockchain, BlockchainOverlay, BlockchainOverlayPtr},
+    blockchain::{
+        proven_fee, snapshot::SNAPSHOT_BATCH, Blockchain, BlockchainOverlay, BlockchainOverlayPtr,
+        SnapshotReader, StateDiff, TxReceipt,
+    },
     rpc::jsonrpc::JsonNotification,
-    runtime::vm_runtime::Runtime,
+    runtime::{gas, vm_runtime::Runtime},
     system::{Subscriber, SubscriberPtr},
     tx::Transaction,
-    util::time::Timestamp,
     wallet::WalletPtr,
     zk::{
         proof::{ProvingKey, VerifyingKey},
@@ -72,6 +77,8 @@ pub struct ValidatorState {
     pub consensus: ConsensusState,
     /// Canonical (finalized) blockchain
     pub blockchain: Blockchain,
+    /// Pending transactions pool
+    pub mempool: Mempool,
     /// A map of various subscribers exporting live info from the blockchain
     /// TODO: Instead of JsonNotification, it can be an enum of internal objects,
     ///       and then we don't have to deal with json in this module but only
@@ -86,13 +93,9 @@ pub struct ValidatorState {
 }
 
 impl ValidatorState {
-    #[allow(clippy::too_many_arguments)]
     pub async fn new(
         db: &sled::Db,
-        bootstrap_ts: Timestamp,
-        genesis_ts: Timestamp,
-        genesis_data: blake3::Hash,
-        initial_distribution: u64,
+        params: ChainParams,
         wallet: WalletPtr,
         faucet_pubkeys: Vec<PublicKey>,
         enable_participation: bool,
@@ -102,8 +105,8 @@ impl ValidatorState {
 
         debug!(target: "consensus::validator", "Initializing wallet tables for consensus");
 
-        // Initialize consensus coin table.
-        // NOTE: In future this will be redundant as consensus coins will live in the money contract.
+        // Initialize consensus coin table, holding the secrets of our staked coins.
+        // The coins themselves live in the money contract.
         if enable_participation {
             wallet.exec_sql(include_str!("consensus_coin.sql")).await?;
         }
@@ -122,16 +125,9 @@ impl ValidatorState {
             None
         };
 
-        let blockchain = Blockchain::new(db, genesis_ts, genesis_data)?;
-        let consensus = ConsensusState::new(
-            wallet.clone(),
-            blockchain.clone(),
-            bootstrap_ts,
-            genesis_ts,
-            genesis_data,
-            initial_distribution,
-            single_node,
-        )?;
+        let blockchain = Blockchain::new(db, params.genesis_ts, params.genesis_data)?;
+        let consensus =
+            ConsensusState::new(wallet.clone(), blockchain.clone(), params, single_node)?;
 
         // -----NATIVE WASM CONTRACTS-----
         // This is the current place where native contracts are being deployed.
@@ -150,6 +146,7 @@ impl ValidatorState {
         // in the money contract.
         let money_contract_deploy_payload = serialize(&faucet_pubkeys);
         let dao_contract_deploy_payload = vec![];
+        let deployooor_contract_deploy_payload = vec![];
 
         let native_contracts = vec![
             (
@@ -164,13 +161,20 @@ impl ValidatorState {
                 include_bytes!("../contract/dao/dao_contract.wasm").to_vec(),
                 dao_contract_deploy_payload,
             ),
+            (
+                "Deployooor Contract",
+                *DEPLOYOOOR_CONTRACT_ID,
+                include_bytes!("../contract/deployooor/deployooor_contract.wasm").to_vec(),
+                deployooor_contract_deploy_payload,
+            ),
         ];
 
         info!(target: "consensus::validator", "Deploying native wasm contracts");
         let blockchain_overlay = BlockchainOverlay::new(&blockchain)?;
         for nc in native_contracts {
             info!(target: "consensus::validator", "Deploying {} with ContractID {}", nc.0, nc.1);
-            let mut runtime = Runtime::new(&nc.2[..], blockchain_overlay.clone(), nc.1)?;
+            let gas_limit = consensus.params.tx_gas_limit;
+            let mut runtime = Runtime::new(&nc.2[..], blockchain_overlay.clone(), nc.1, gas_limit)?;
             runtime.deploy(&nc.3)?;
             info!(target: "consensus::validator", "Successfully deployed {}", nc.0);
         }
@@ -183,72 +187,88 @@ impl ValidatorState {
         let mut subscribers = HashMap::new();
         let block_subscriber = Subscriber::new();
         let err_txs_subscriber = Subscriber::new();
+        let events_subscriber = Subscriber::new();
         subscribers.insert("blocks", block_subscriber);
         subscribers.insert("err_txs", err_txs_subscriber);
+        subscribers.insert("events", events_subscriber);
+
+        let mempool = Mempool::new(blockchain.clone(), MempoolConfig::default());
+        let pending_txs = mempool.take_persisted()?;
 
         let state = Arc::new(RwLock::new(ValidatorState {
             lead_proving_key,
             lead_verifying_key,
             consensus,
             blockchain,
+            mempool,
             subscribers,
             wallet,
             synced: false,
             single_node,
         }));
 
+        // Pending transactions of a previous run get verified again
+        state.write().await.append_pending_txs(&pending_txs).await;
+
         Ok(state)
     }
 
     /// The node retrieves a transaction, validates its state transition,
-    /// and appends it to the pending txs store.
-    pub async fn append_tx(&mut self, tx: Transaction) -> bool {
+    /// and appends it to the mempool.
+    /// Returns `Ok(false)` if the transaction was already seen or was rejected
+    /// by the mempool, and an error if the transaction failed validation.
+    pub async fn append_tx(&mut self, tx: Transaction) -> Result<bool> {
         let tx_hash = blake3::hash(&serialize(&tx));
         let tx_in_txstore = match self.blockchain.transactions.contains(&tx_hash) {
             Ok(v) => v,
             Err(e) => {
                 error!(target: "consensus::validator", "append_tx(): Failed querying txstore: {}", e);
-                return false
-            }
-        };
-
-        let tx_in_pending_txs_store = match self.blockchain.pending_txs.contains(&tx_hash) {
-            Ok(v) => v,
-            Err(e) => {
-                error!(target: "consensus::validator", "append_tx(): Failed querying pending txs store: {}", e);
-                return false
+                return Ok(false)
             }
         };
 
-        if tx_in_txstore || tx_in_pending_txs_store {
+        if tx_in_txstore || self.mempool.contains(&tx_hash) {
             info!(target: "consensus::validator", "append_tx(): We have already seen this tx.");
-            return false
+            return Ok(false)
+        }
+
+        if !tx.is_well_formed() {
+            error!(target: "consensus::validator", "append_tx(): Malformed transaction detected");
+            return Err(Error::MalformedTransaction)
         }
 
         info!(target: "consensus::validator", "append_tx(): Starting state transition validation");
-        match self.verify_transactions(&[tx.clone()], false).await {
-            Ok(erroneous_txs) => {
+        let receipt = match self.verify_transactions_diff(&[tx.clone()], false).await {
+            Ok((erroneous_txs, _, mut receipts)) => {
                 if !erroneous_txs.is_empty() {
                     error!(target: "consensus::validator", "append_tx(): Erroneous transaction detected");
-                    return false
+                    return Err(Error::ErroneousTxsDetected)
                 }
+                receipts.remove(0)
             }
             Err(e) => {
                 error!(target: "consensus::validator", "append_tx(): Failed to verify transaction: {}", e);
-                return false
+                return Ok(false)
             }
-        }
+        };
 
-        if let Err(e) = self.blockchain.add_pending_txs(&[tx]) {
-            error!(target: "consensus::validator", "append_tx(): Failed to insert transaction to pending txs store: {}", e);
-            return false
+        match self.mempool.insert(tx, &receipt) {
+            Ok(dropped) => {
+                if !dropped.is_empty() {
+                    info!(target: "consensus::validator", "append_tx(): Dropped {} pending transactions", dropped.len());
+                }
+            }
+            Err(e) => {
+                warn!(target: "consensus::validator", "append_tx(): Mempool rejected transaction: {}", e);
+                return Ok(false)
+            }
         }
-        info!(target: "consensus::validator", "append_tx(): Appended tx to pending txs store");
-        true
+        info!(target: "consensus::validator", "append_tx(): Appended tx to mempool");
+        Ok(true)
     }
 
     /// The node retrieves transactions vector, validates their state transition,
-    /// and appends successfull ones to the pending txs store.
+    /// and appends successfull ones to the mempool.
     pub async fn append_pending_txs(&mut self, txs: &[Transaction]) {
         let mut filtered_txs = vec![];
         // Filter already seen transactions
@@ -262,15 +282,7 @@ impl ValidatorState {
                 }
             };
 
-            let tx_in_pending_txs_store = match self.blockchain.pending_txs.contains(&tx_hash) {
-                Ok(v) => v,
-                Err(e) => {
-                    error!(target: "consensus::validator", "append_pending_txs(): Failed querying pending txs store: {}", e);
-                    continue
-                }
-            };
-
-            if tx_in_txstore || tx_in_pending_txs_store {
+            if tx_in_txstore || self.mempool.contains(&tx_hash) {
                 info!(target: "consensus::validator", "append_pending_txs(): We have already seen this tx.");
                 continue
             }
@@ -280,8 +292,11 @@ impl ValidatorState {
 
         // Verify transactions and filter erroneous ones
         info!(target: "consensus::validator", "append_pending_txs(): Starting state transition validation");
-        let erroneous_txs = match self.verify_transactions(&filtered_txs[..], false).await {
-            Ok(erroneous_txs) => erroneous_txs,
+        let (erroneous_txs, _, receipts) = match self
+            .verify_transactions_diff(&filtered_txs[..], false)
+            .await
+        {
+            Ok(v) => v,
             Err(e) => {
                 error!(target: "consensus::validator", "append_pending_txs(): Failed to verify transactions: {}", e);
                 return
@@ -291,17 +306,39 @@ impl ValidatorState {
             filtered_txs.retain(|x| !erroneous_txs.contains(x));
         }
 
-        if let Err(e) = self.blockchain.add_pending_txs(&filtered_txs) {
-            error!(target: "consensus::validator", "append_pending_txs(): Failed to insert transactions to pending txs store: {}", e);
-            return
+        for (tx, receipt) in filtered_txs.into_iter().zip(receipts.iter()) {
+            if let Err(e) = self.mempool.insert(tx, receipt) {
+                warn!(target: "consensus::validator", "append_pending_txs(): Mempool rejected transaction: {}", e);
+            }
         }
-        info!(target: "consensus::validator", "append_pending_txs(): Appended tx to pending txs store");
+        info!(target: "consensus::validator", "append_pending_txs(): Appended txs to mempool");
     }
 
-    /// The node removes erroneous transactions from the pending txs store.
-    async fn purge_pending_txs(&self) -> Result<()> {
-        info!(target: "consensus::validator", "purge_pending_txs(): Removing erroneous transactions from pending transactions store...");
-        let pending_txs = self.blockchain.get_pending_txs()?;
+    /// Roll back the last `n` finalized blocks. The consensus state built on
+    /// top of them gets reset, and the pending transactions get revalidated
+    /// against the reverted state, along with the transactions of the removed
+    /// blocks. Returns the removed blocks, last first.
+    pub async fn rollback(&mut self, n: u64) -> Result<Vec<BlockInfo>> {
+        let removed = self.blockchain.rollback(n)?;
+        self.consensus.reset();
+        self.synced = false;
+
+        let mut txs: Vec<Transaction> =
+            removed.iter().rev().flat_map(|block| block.txs.clone()).collect();
+        let pending_txs = self.mempool.txs();
+        self.mempool.remove(&pending_txs)?;
+        txs.extend(pending_txs);
+        self.append_pending_txs(&txs).await;
+
+        Ok(removed)
+    }
+
+    /// The node evicts expired transactions from the mempool, and revalidates
+    /// the remaining ones, removing erroneous transactions.
+    async fn purge_pending_txs(&mut self) -> Result<()> {
+        info!(target: "consensus::validator", "purge_pending_txs(): Removing erroneous transactions from mempool...");
+        self.mempool.evict_expired()?;
+        let pending_txs = self.mempool.txs();
         if pending_txs.is_empty() {
             info!(target: "consensus::validator", "purge_pending_txs(): No pending transactions found");
             return Ok(())
@@ -312,7 +349,7 @@ impl ValidatorState {
             return Ok(())
         }
         info!(target: "consensus::validator", "purge_pending_txs(): Removing {} erroneous transactions...", erroneous_txs.len());
-        self.blockchain.remove_pending_txs(&erroneous_txs)?;
+        self.mempool.remove(&erroneous_txs)?;
 
         // TODO: Don't hardcode this:
         let err_txs_subscriber = self.subscribers.get("err_txs").unwrap();
@@ -351,14 +388,9 @@ impl ValidatorState {
         if !erroneous_txs.is_empty() {
             unproposed_txs.retain(|x| !erroneous_txs.contains(x));
         }
-        let mut tree = BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100);
-        // The following is pretty weird, so something better should be done.
-        for tx in &unproposed_txs {
-            let mut hash = [0_u8; 32];
-            hash[0..31].copy_from_slice(&blake3::hash(&serialize(tx)).as_bytes()[0..31]);
-            tree.append(&MerkleNode::from(pallas::Base::from_repr(hash).unwrap()));
-        }
-        let root = tree.root(0).unwrap();
+        let txs: Vec<blake3::Hash> =
+            unproposed_txs.iter().map(|x| blake3::hash(&serialize(x))).collect();
+        let root = Header::txs_root(&txs);
 
         // Checking if extending a fork or canonical
         let (prev_hash, coin) = if fork_index == -1 {
@@ -379,6 +411,7 @@ impl ValidatorState {
             pallas::Base::from(self.consensus.current_slot()),
             self.lead_proving_key.as_ref().unwrap(),
             derived_blind,
+            self.consensus.params.reward(slot),
         );
 
         // Signing using coin
@@ -387,7 +420,7 @@ impl ValidatorState {
             prev_hash,
             self.consensus.slot_epoch(slot),
             slot,
-            Timestamp::current_time(),
+            self.consensus.time.now(),
             root,
         );
         let signed_proposal =
@@ -404,7 +437,14 @@ impl ValidatorState {
             self.consensus.previous_leaders,
         );
 
-        Ok(Some((BlockProposal::new(header, unproposed_txs, lead_info), coin, derived_blind)))
+        let proposal = BlockProposal::new(
+            self.consensus.params.network_magic,
+            header,
+            unproposed_txs,
+            lead_info,
+        );
+
+        Ok(Some((proposal, coin, derived_blind)))
     }
 
     /// Retrieve all pending transactions not proposed in previous blocks
@@ -413,11 +453,11 @@ impl ValidatorState {
         let unproposed_txs = if index == -1 {
             // If index is -1 (canonical blockchain) a new fork will be generated,
             // therefore all unproposed transactions can be included in the proposal.
-            self.blockchain.get_pending_txs()?
+            self.mempool.txs()
         } else {
             // We iterate over the fork chain proposals to find already proposed
             // transactions and remove them from the local unproposed_txs vector.
-            let mut filtered_txs = self.blockchain.get_pending_txs()?;
+            let mut filtered_txs = self.mempool.txs();
             let chain = &self.consensus.forks[index as usize];
             for state_checkpoint in &chain.sequence {
                 for tx in &state_checkpoint.proposal.block.txs {
@@ -430,7 +470,7 @@ impl ValidatorState {
         };
 
         // Check if transactions exceed configured cap
-        let cap = constants::TXS_CAP;
+        let cap = self.consensus.params.txs_cap;
         if unproposed_txs.len() > cap {
             return Ok(unproposed_txs[0..cap].to_vec())
         }
@@ -473,13 +513,19 @@ impl ValidatorState {
             return Err(Error::ProposalNotForCurrentSlotError)
         }
 
+        // Ignore proposal if not for our network
+        if proposal.block.magic != self.consensus.params.network_magic {
+            warn!(target: "consensus::validator", "receive_proposal(): Proposal magic bytes don't match our network");
+            return Err(Error::ProposalNetworkMismatchError)
+        }
+
         // Verify that proposer can produce proposals.
         // Nodes that created coins in the bootstrap slot can propose immediately.
         // NOTE: Later, this will be enforced via contract, where it will be explicit
         // when a node can produce proposals, and after which slot they can be considered as valid.
         let elapsed_slots = current - lf.coin_slot;
         if lf.coin_slot != self.consensus.bootstrap_slot &&
-            elapsed_slots <= (constants::EPOCH_LENGTH as u64)
+            elapsed_slots <= self.consensus.params.epoch_length
         {
             warn!(
                 target: "consensus::validator",
@@ -496,12 +542,12 @@ impl ValidatorState {
         }
 
         // Check that proposal transactions don't exceed limit
-        if proposal.block.txs.len() > constants::TXS_CAP {
+        if proposal.block.txs.len() > self.consensus.params.txs_cap {
             warn!(
                 target: "consensus::validator",
                 "receive_proposal(): Received proposal transactions exceed configured cap: {} - {}",
                 proposal.block.txs.len(),
-                constants::TXS_CAP
+                self.consensus.params.txs_cap
             );
             return Err(Error::ProposalTxsExceedCapError)
         }
@@ -535,8 +581,20 @@ impl ValidatorState {
             return Err(Error::ProposalHeadersMissmatchError)
         }
 
+        // Check if proposal transactions match the header merkle root
+        if hdr.root != proposal.block.txs_root() {
+            warn!(target: "consensus::validator", "receive_proposal(): Received proposal transactions don't match header root");
+            return Err(Error::BlockTxsRootMismatch)
+        }
+
         // Ignore node coin validations if we oporate in single-node mode
         if !self.single_node {
+            // Verify proposal coin is an unspent lead coin of the money contract
+            if let Err(e) = self.consensus.verify_lead_coin(index, lf) {
+                warn!(target: "consensus::validator", "receive_proposal(): Proposer {} coin verification failed: {}", lf.public_key, e);
+                return Err(e)
+            }
+
             // Verify proposal leader proof
             if let Err(e) = lf.proof.verify(&self.lead_verifying_key, &lf.public_inputs) {
                 error!(target: "consensus::validator", "receive_proposal(): Error during leader proof verification: {}", e);
@@ -593,8 +651,23 @@ impl ValidatorState {
                     checkpoint.sigma2, prop_sigma2
                 );
             }
+
+            // Validate proposal reward against the chain reward schedule
+            let reward = pallas::Base::from(self.consensus.params.reward(current));
+            let prop_reward = lf.public_inputs[constants::PI_REWARD_INDEX];
+            if reward != prop_reward {
+                error!(
+                    target: "consensus::validator",
+                    "receive_proposal(): Failed to verify reward: {:?}, proposed: {:?}",
+                    reward, prop_reward
+                );
+                return Err(Error::ProposalPublicValuesMismatched)
+            }
         }
 
+        // Coins derived from staked coins get minted in the lead coins tree
+        let staked = self.consensus.is_lead_coin_root(index, &lf.commitment_root())?;
+
         // Create corresponding state checkpoint for validations
         let mut state_checkpoint = match index {
             -1 => {
@@ -643,27 +716,22 @@ impl ValidatorState {
             }
         }
 
-        // TODO: [PLACEHOLDER] Add rewards validation
-
         // If proposal came fromself, we derive new coin
         if let Some((idx, c, derived_blind)) = coin {
             info!(target: "consensus::validator", "receive_proposal(): Storing derived coin...");
-            // Derive coin
-            let derived = c.derive_coin(&mut state_checkpoint.coins_tree, derived_blind);
-            // Update consensus coin in wallet
-            // NOTE: In future this will be redundant as consensus coins will live in the money contract.
-            // Get a wallet connection
-            let mut conn = self.wallet.conn.acquire().await?;
-            let query_str = format!(
-                "UPDATE {} SET {} = ?1",
-                constants::CONSENSUS_COIN_TABLE,
-                constants::CONSENSUS_COIN_COL
-            );
-            let mut query = sqlx::query(&query_str);
-            query = query.bind(serialize(&derived));
-            query.execute(&mut conn).await?;
-
+            // Derive coin, appending it to the coins tree as the money
+            // contract will, once the proposal gets finalized.
+            let reward = self.consensus.params.reward(current);
+            let derived = if staked {
+                c.derive_coin(&mut state_checkpoint.coins_tree, derived_blind, reward)
+            } else {
+                c.derive_coin(&mut state_checkpoint.coins_tree.clone(), derived_blind, reward)
+            };
+            self.consensus.add_derived_coin(&c, &derived).await?;
             state_checkpoint.coins[idx] = derived;
+        } else if staked {
+            let derived = MerkleNode::from(lf.derived_commitment_hash());
+            state_checkpoint.coins_tree.append(&derived);
         }
         // Store proposal coins nullifiers
         state_checkpoint.nullifiers.push(prop_sn);
@@ -747,7 +815,7 @@ impl ValidatorState {
 
         // Adding finalized proposals to canonical
         info!(target: "consensus::validator", "consensus: Adding {} finalized block to canonical chain.", finalized.len());
-        match self.blockchain.add(&finalized) {
+        let hashes = match self.blockchain.add(&finalized) {
             Ok(v) => v,
             Err(e) => {
                 error!(target: "consensus::validator", "consensus: Failed appending finalized blocks to canonical chain: {}", e);
@@ -758,27 +826,40 @@ impl ValidatorState {
         let blocks_subscriber = self.subscribers.get("blocks").unwrap().clone();
 
         // Validating state transitions
-        for proposal in &finalized {
+        for (proposal, hash) in finalized.iter().zip(hashes.iter()) {
             // TODO: Is this the right place? We're already doing this in protocol_sync.
             // TODO: These state transitions have already been checked. (I wrote this, but where?)
             // TODO: FIXME: The state transitions have already been written, they have to be in memory
             //              until this point.
             info!(target: "consensus::validator", "Applying state transition for finalized block");
-            match self.verify_transactions(&proposal.txs, true).await {
-                Ok(erroneous_txs) => {
+            let (diff, receipts) = match self.verify_block_diff(proposal).await {
+                Ok((erroneous_txs, diff, receipts)) => {
                     if !erroneous_txs.is_empty() {
                         error!(target: "consensus::validator", "Finalized block contains erroneous transactions");
                         return Err(Error::ErroneousTxsDetected)
                     }
+                    (diff, receipts)
                 }
                 Err(e) => {
                     error!(target: "consensus::validator", "Finalized block transaction verifications failed: {}", e);
                     return Err(e)
                 }
+            };
+
+            // Store the block state diff so it can be rolled back
+            if let Err(e) = self.blockchain.add_state_diffs(&[*hash], &[diff]) {
+                error!(target: "consensus::validator", "Storing finalized block state diff failed: {}", e);
+                return Err(e)
+            }
+
+            // Store the block transaction receipts and notify about their events
+            if let Err(e) = self.add_tx_receipts(&proposal.txs, &receipts).await {
+                error!(target: "consensus::validator", "Storing finalized block transaction receipts failed: {}", e);
+                return Err(e)
             }
 
-            // Remove proposal transactions from pending txs store
-            if let Err(e) = self.blockchain.remove_pending_txs(&proposal.txs) {
+            // Remove proposal transactions from mempool
+            if let Err(e) = self.mempool.remove(&proposal.txs) {
                 error!(target: "consensus::validator", "Removing finalized block transactions failed: {}", e);
                 return Err(e)
             }
@@ -848,13 +929,27 @@ impl ValidatorState {
         // Verify state transitions for all blocks and their respective transactions.
         info!(target: "consensus::validator", "receive_blocks(): Starting state transition validations");
 
+        let mut diffs = Vec::with_capacity(blocks.len());
+        let mut receipts = Vec::with_capacity(blocks.len());
         for block in blocks {
-            match self.verify_transactions(&block.txs, true).await {
-                Ok(erroneous_txs) => {
+            if block.header.root != block.txs_root() {
+                error!(target: "consensus::validator", "receive_blocks(): Block transactions don't match header root");
+                return Err(Error::BlockTxsRootMismatch)
+            }
+            if !self.single_node {
+                if let Err(e) = self.consensus.verify_lead_coin(-1, &block.lead_info) {
+                    error!(target: "consensus::validator", "receive_blocks(): Block leader coin verification failed: {}", e);
+                    return Err(e)
+                }
+            }
+            match self.verify_block_diff(block).await {
+                Ok((erroneous_txs, diff, block_receipts)) => {
                     if !erroneous_txs.is_empty() {
                         error!(target: "consensus::validator", "receive_blocks(): Block contains erroneous transactions");
                         return Err(Error::ErroneousTxsDetected)
                     }
+                    diffs.push(diff);
+                    receipts.push(block_receipts);
                 }
                 Err(e) => {
                     error!(target: "consensus::validator", "receive_blocks(): Transaction verifications failed: {}", e);
@@ -864,18 +959,50 @@ impl ValidatorState {
         }
 
         info!(target: "consensus::validator", "receive_blocks(): All state transitions passed. Appending blocks to ledger.");
-        self.blockchain.add(blocks)?;
+        let hashes = self.blockchain.add(blocks)?;
+        self.blockchain.add_state_diffs(&hashes, &diffs)?;
+        for (block, block_receipts) in blocks.iter().zip(receipts.iter()) {
+            self.add_tx_receipts(&block.txs, block_receipts).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Store the receipts of given finalized transactions, and notify the
+    /// events subscriber about the events they contain.
+    async fn add_tx_receipts(&self, txs: &[Transaction], receipts: &[TxReceipt]) -> Result<()> {
+        let tx_hashes: Vec<blake3::Hash> =
+            txs.iter().map(|tx| blake3::hash(&serialize(tx))).collect();
+        self.blockchain.add_tx_receipts(&tx_hashes, receipts)?;
+
+        // TODO: Don't hardcode this:
+        let events_subscriber = self.subscribers.get("events").unwrap();
+        for (tx_hash, receipt) in tx_hashes.iter().zip(receipts.iter()) {
+            for event in &receipt.events {
+                let params = json!([
+                    tx_hash.to_hex().as_str(),
+                    bs58::encode(&serialize(event)).into_string()
+                ]);
+                let notif = JsonNotification::new("blockchain.subscribe_events", params);
+                events_subscriber.notify(notif).await;
+            }
+        }
 
         Ok(())
     }
 
     /// Validate and append to canonical state received finalized block.
-    /// Returns boolean flag indicating already existing block.
+    /// Returns boolean flag indicating the block got appended, as existing
+    /// blocks and blocks not extending the canonical chain are ignored.
     pub async fn receive_finalized_block(&mut self, block: BlockInfo) -> Result<bool> {
         if block.header.slot > self.consensus.current_slot() {
             warn!(target: "consensus::validator", "receive_finalized_block(): Ignoring future block: {}", block.header.slot);
             return Ok(false)
         }
+        if block.magic != self.consensus.params.network_magic {
+            warn!(target: "consensus::validator", "receive_finalized_block(): Ignoring block of another network: {}", block.header.slot);
+            return Ok(false)
+        }
         match self.blockchain.has_block(&block) {
             Ok(v) => {
                 if v {
@@ -889,9 +1016,19 @@ impl ValidatorState {
             }
         };
 
+        // Only blocks extending our canonical chain can be appended to it
+        let (_, last_hash) = self.blockchain.last()?;
+        if block.header.previous != last_hash {
+            warn!(target: "consensus::validator", "receive_finalized_block(): Ignoring block not extending canonical chain: {}", block.header.slot);
+            return Ok(false)
+        }
+
         info!(target: "consensus::validator", "receive_finalized_block(): Executing state transitions");
         self.receive_blocks(&[block.clone()]).await?;
 
+        // Forks we hold have to extend the new canonical chain tip
+        self.consensus.prune_forks(&block.blockhash());
+
         // TODO: Don't hardcode this:
         let blocks_subscriber = self.subscribers.get("blocks").unwrap();
         let params = json!([bs58::encode(&serialize(&block)).into_string()]);
@@ -899,8 +1036,8 @@ impl ValidatorState {
         info!(target: "consensus::validator", "consensus: Sending notification about finalized block");
         blocks_subscriber.notify(notif).await;
 
-        info!(target: "consensus::validator", "receive_finalized_block(): Removing block transactions from pending txs store");
-        self.blockchain.remove_pending_txs(&block.txs)?;
+        info!(target: "consensus::validator", "receive_finalized_block(): Removing block transactions from mempool");
+        self.mempool.remove(&block.txs)?;
 
         // Purge pending erroneous txs since canonical state has been changed
         if let Err(e) = self.purge_pending_txs().await {
@@ -919,6 +1056,10 @@ impl ValidatorState {
                 warn!(target: "consensus::validator", "receive_sync_blocks(): Ignoring future block: {}", block.header.slot);
                 continue
             }
+            if block.magic != self.consensus.params.network_magic {
+                warn!(target: "consensus::validator", "receive_sync_blocks(): Ignoring block of another network: {}", block.header.slot);
+                continue
+            }
             match self.blockchain.has_block(block) {
                 Ok(v) => {
                     if v {
@@ -956,13 +1097,17 @@ impl ValidatorState {
 
     /// Validate signatures, wasm execution, and zk proofs for given transaction in
     /// provided runtimes. If all of those succeed, try to execute a state update
-    /// for the contract calls.
+    /// for the contract calls. Returns the transaction receipt, holding the gas
+    /// spent by the transaction, which can't exceed the chain transaction gas
+    /// limit, and the events emitted by its contract calls.
     async fn verify_transaction(
         &self,
         blockchain_overlay: BlockchainOverlayPtr,
         tx: &Transaction,
-    ) -> Result<()> {
+    ) -> Result<TxReceipt> {
         let mut runtimes = HashMap::new();
+        let gas_limit = self.consensus.params.tx_gas_limit;
+        let mut gas_used = 0;
         let tx_hash = blake3::hash(&serialize(tx));
         info!(target: "consensus::validator", "Verifying transaction {}", tx_hash);
 
@@ -992,14 +1137,18 @@ impl ValidatorState {
             // Instantiate the wasm runtime
             let runtime_key = call.contract_id.to_string();
             if !runtimes.contains_key(&runtime_key) {
-                let wasm = self.blockchain.wasm_bincode.get(call.contract_id)?;
-                let r = Runtime::new(&wasm, blockchain_overlay.clone(), call.contract_id)?;
+                // Contracts deployed earlier in the same overlay are also visible
+                let wasm = blockchain_overlay.lock().unwrap().wasm_bincode.get(call.contract_id)?;
+                let r =
+                    Runtime::new(&wasm, blockchain_overlay.clone(), call.contract_id, gas_limit)?;
                 runtimes.insert(runtime_key.clone(), r);
             }
             let runtime = runtimes.get_mut(&runtime_key).unwrap();
 
             info!(target: "consensus::validator", "Executing \"metadata\" call");
-            let metadata = runtime.metadata(&payload)?;
+            runtime.set_gas_limit(gas_limit - gas_used);
+            let (metadata, gas) = runtime.metadata(&payload)?;
+            spend_gas(&mut gas_used, gas, gas_limit)?;
 
             // Decode the metadata retrieved from the execution
             let mut decoder = Cursor::new(&metadata);
@@ -1020,11 +1169,11 @@ impl ValidatorState {
                     continue
                 }
 
-                let (_, vk) = self.blockchain.contracts.get_zkas(
-                    &self.blockchain.sled_db,
-                    &call.contract_id,
-                    zkas_ns,
-                )?;
+                let (_, vk) = blockchain_overlay
+                    .lock()
+                    .unwrap()
+                    .contracts
+                    .get_zkas(&call.contract_id, zkas_ns)?;
 
                 inner_vk_map.insert(zkas_ns.to_string(), vk);
             }
@@ -1035,7 +1184,9 @@ impl ValidatorState {
             // After getting the metadata, we run the "exec" function with the same
             // runtime and the same payload.
             info!(target: "consensus::validator", "Executing \"exec\" call");
-            let state_update = runtime.exec(&payload)?;
+            runtime.set_gas_limit(gas_limit - gas_used);
+            let (state_update, gas) = runtime.exec(&payload)?;
+            spend_gas(&mut gas_used, gas, gas_limit)?;
 
             info!(target: "consensus::validator", "Successfully executed \"exec\" call");
             updates.push(state_update);
@@ -1077,19 +1228,94 @@ impl ValidatorState {
         assert!(tx.calls.len() == updates.len());
 
         info!(target: "consensus::validator", "Performing state updates");
-        for (call, update) in tx.calls.iter().zip(updates.iter()) {
+        let mut events = vec![];
+        for (idx, (call, update)) in tx.calls.iter().zip(updates.iter()).enumerate() {
             // Retrieve already initiated runtime and apply update
-            // TODO: Sum up the gas costs of previous calls during execution
-            //       and verification and these.
             let runtime = runtimes.get_mut(&call.contract_id.to_string()).unwrap();
             info!(target: "consensus::validator", "Executing \"apply\" call");
-            runtime.apply(update)?;
+            runtime.set_gas_limit(gas_limit - gas_used);
+            spend_gas(&mut gas_used, runtime.apply(update)?, gas_limit)?;
+            events.extend(runtime.take_events(idx as u32));
             info!(target: "consensus::validator", "State update applied successfully")
         }
 
-        info!(target: "consensus::validator", "Transaction {} verified successfully", tx_hash);
+        // Deployment calls got authorized by the deployooor contract, so the
+        // host can now deploy the contracts they carry.
+        for call in tx.calls.iter() {
+            if call.contract_id != *DEPLOYOOOR_CONTRACT_ID {
+                continue
+            }
 
-        Ok(())
+            // Only deployed contracts can be locked, since a lock would
+            // otherwise block their deployment forever. Earlier calls of
+            // the transaction may have deployed it.
+            if call.data[0] == DeployFunction::LockV1 as u8 {
+                let params: LockParamsV1 = deserialize(&call.data[1..])?;
+                let contract_id = ContractId::derive_public(params.public_key);
+                match blockchain_overlay.lock().unwrap().wasm_bincode.get(contract_id) {
+                    Ok(_) => continue,
+                    Err(Error::WasmBincodeNotFound) => {
+                        error!(target: "consensus::validator", "Can't lock contract {}, it is not deployed", contract_id);
+                        return Err(Error::ContractNotFound(contract_id.to_string()))
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if call.data[0] != DeployFunction::DeployV1 as u8 {
+                continue
+            }
+
+            let params: DeployParamsV1 = deserialize(&call.data[1..])?;
+            let contract_id = ContractId::derive_public(params.public_key);
+            info!(target: "consensus::validator", "Deploying contract {}", contract_id);
+
+            for zkas_bincode in &params.zkas_bincodes {
+                spend_gas(&mut gas_used, gas::ZKAS_REGISTER, gas_limit)?;
+                blockchain_overlay
+                    .lock()
+                    .unwrap()
+                    .contracts
+                    .insert_zkas(&contract_id, zkas_bincode)?;
+            }
+
+            let mut runtime = Runtime::new(
+                &params.wasm_bincode,
+                blockchain_overlay.clone(),
+                contract_id,
+                gas_limit - gas_used,
+            )?;
+            spend_gas(&mut gas_used, runtime.deploy(&params.ix)?, gas_limit)?;
+            info!(target: "consensus::validator", "Successfully deployed contract {}", contract_id);
+        }
+
+        // The fee value is hidden, so the chain minimum is checked against
+        // the minimum the fee call proved and reported.
+        let receipt = TxReceipt { gas_used, events };
+        let min_fee = self.consensus.params.min_tx_fee;
+        if min_fee > 0 {
+            let fee = proven_fee(&receipt).unwrap_or(0);
+            if fee < min_fee {
+                error!(target: "consensus::validator", "Transaction {} proven fee {} is below minimum", tx_hash, fee);
+                return Err(Error::TxFeeTooLow(fee))
+            }
+        }
+
+        info!(target: "consensus::validator", "Transaction {} verified successfully, gas used: {}", tx_hash, gas_used);
+
+        Ok(receipt)
+    }
+
+    /// Validate given [`Transaction`] against the current state without applying it.
+    /// Returns the gas spent by the transaction.
+    pub async fn simulate_transaction(&self, tx: &Transaction) -> Result<u64> {
+        let blockchain_overlay = BlockchainOverlay::new(&self.blockchain)?;
+        let ret = self.verify_transaction(blockchain_overlay.clone(), tx).await;
+
+        let lock = blockchain_overlay.lock().unwrap();
+        lock.overlay.lock().unwrap().purge_new_trees()?;
+
+        Ok(ret?.gas_used)
     }
 
     /// Validate a set of [`Transaction`] in sequence and apply them if all are valid.
@@ -1101,15 +1327,64 @@ impl ValidatorState {
         txs: &[Transaction],
         write: bool,
     ) -> Result<Vec<Transaction>> {
+        Ok(self.verify_transactions_diff(txs, write).await?.0)
+    }
+
+    /// Validate a set of [`Transaction`] like [`ValidatorState::verify_transactions`],
+    /// also returning the contract state diff of their state transitions, which is
+    /// empty if they were not applied, and the receipts of the valid transactions.
+    pub async fn verify_transactions_diff(
+        &self,
+        txs: &[Transaction],
+        write: bool,
+    ) -> Result<(Vec<Transaction>, StateDiff, Vec<TxReceipt>)> {
+        self.verify_state_transition(txs, None, write).await
+    }
+
+    /// Validate and apply the state transition of given finalized block: its
+    /// transactions, followed by the lead coin its leader derived, which gets
+    /// minted in the money contract. Returns the same as
+    /// [`ValidatorState::verify_transactions_diff`].
+    async fn verify_block_diff(
+        &self,
+        block: &BlockInfo,
+    ) -> Result<(Vec<Transaction>, StateDiff, Vec<TxReceipt>)> {
+        self.verify_state_transition(&block.txs, Some(&block.lead_info), true).await
+    }
+
+    /// Shared implementation of transaction set and block verifications.
+    async fn verify_state_transition(
+        &self,
+        txs: &[Transaction],
+        lead_info: Option<&LeadInfo>,
+        write: bool,
+    ) -> Result<(Vec<Transaction>, StateDiff, Vec<TxReceipt>)> {
         info!(target: "consensus::validator", "Verifying {} transaction(s)", txs.len());
 
         let mut erroneous_txs = vec![];
+        let mut receipts = Vec::with_capacity(txs.len());
         let blockchain_overlay = BlockchainOverlay::new(&self.blockchain)?;
 
         for tx in txs {
-            if let Err(e) = self.verify_transaction(blockchain_overlay.clone(), tx).await {
-                warn!(target: "consensus::validator", "Transaction verification failed: {}", e);
-                erroneous_txs.push(tx.clone());
+            match self.verify_transaction(blockchain_overlay.clone(), tx).await {
+                Ok(receipt) => receipts.push(receipt),
+                Err(e) => {
+                    warn!(target: "consensus::validator", "Transaction verification failed: {}", e);
+                    erroneous_txs.push(tx.clone());
+                }
+            }
+        }
+
+        // The block leader coin transition gets applied after its transactions
+        if let Some(lead_info) = lead_info {
+            if erroneous_txs.is_empty() && write {
+                let gas_limit = self.consensus.params.tx_gas_limit;
+                if let Err(e) = mint_derived_coin(&blockchain_overlay, lead_info, gas_limit) {
+                    error!(target: "consensus::validator", "Minting block leader derived coin failed: {}", e);
+                    let lock = blockchain_overlay.lock().unwrap();
+                    lock.overlay.lock().unwrap().purge_new_trees()?;
+                    return Err(e)
+                }
             }
         }
 
@@ -1118,18 +1393,19 @@ impl ValidatorState {
         if !erroneous_txs.is_empty() {
             warn!(target: "consensus::validator", "Erroneous transactions found in set");
             overlay.purge_new_trees()?;
-            return Ok(erroneous_txs)
+            return Ok((erroneous_txs, StateDiff::default(), receipts))
         }
 
         if !write {
             info!(target: "consensus::validator", "Skipping apply of state updates because write=false");
             overlay.purge_new_trees()?;
-            return Ok(erroneous_txs)
+            return Ok((erroneous_txs, StateDiff::default(), receipts))
         }
 
+        let diff = overlay.diff()?;
         overlay.apply()?;
 
-        Ok(erroneous_txs)
+        Ok((erroneous_txs, diff, receipts))
     }
 
     /// Append to canonical state received finalized slot checkpoints from block sync task.
@@ -1151,6 +1427,47 @@ impl ValidatorState {
         Ok(())
     }
 
+    /// Import a snapshot file into an empty blockchain, verifying all state
+    /// transitions of its blocks like the block sync task does. The resulting
+    /// contract states must match the ones included in the snapshot.
+    pub async fn import_snapshot(&mut self, path: &Path) -> Result<()> {
+        let mut reader = SnapshotReader::open(&self.blockchain, path)?;
+
+        loop {
+            let slot_checkpoints = reader.read_slot_checkpoints(SNAPSHOT_BATCH)?;
+            if slot_checkpoints.is_empty() {
+                break
+            }
+            self.receive_slot_checkpoints(&slot_checkpoints).await?;
+        }
+
+        loop {
+            let blocks = reader.read_blocks(SNAPSHOT_BATCH)?;
+            if blocks.is_empty() {
+                break
+            }
+            self.receive_sync_blocks(&blocks).await?;
+        }
+
+        while let Some((name, entries)) = reader.read_tree()? {
+            let tree = self.blockchain.sled_db.open_tree(&name)?;
+            let matches = tree.len() == entries.len() &&
+                entries.iter().all(|(key, value)| {
+                    matches!(tree.get(key), Ok(Some(v)) if v.as_ref() == value.as_slice())
+                });
+
+            if !matches {
+                let name = bs58::encode(&name).into_string();
+                error!(target: "consensus::validator", "import_snapshot(): Contract tree {} mismatch", name);
+                return Err(Error::SnapshotInvalid(format!("contract tree {} mismatch", name)))
+            }
+        }
+
+        self.blockchain.sled_db.flush_async().await?;
+        info!(target: "consensus::validator", "import_snapshot(): Imported {} blocks", self.blockchain.len());
+        Ok(())
+    }
+
     /// Validate and append to canonical state received finalized slot checkpoint.
     /// Returns boolean flag indicating already existing slot checkpoint.
     pub async fn receive_finalized_slot_checkpoints(
@@ -1176,3 +1493,277 @@ impl ValidatorState {
         Ok(true)
     }
 }
+
+/// Add the gas spent by a contract call to the gas used by its transaction,
+/// failing if the transaction exceeds the gas limit.
+fn spend_gas(gas_used: &mut u64, gas: u64, gas_limit: u64) -> Result<()> {
+    *gas_used += gas;
+    if *gas_used > gas_limit {
+        error!(target: "consensus::validator", "Transaction gas used {} exceeds limit {}", gas_used, gas_limit);
+        return Err(Error::TxGasLimitExceeded(*gas_used, gas_limit))
+    }
+
+    Ok(())
+}
+
+/// Apply the lead coin state transition of a block leader to the money
+/// contract state: the winning coin gets spent, and the coin derived from it,
+/// carrying the slot reward, gets minted in the lead coins tree. Leaders that
+/// competed with coins not staked, like bootstrap zero value coins, leave the
+/// state untouched. The transition is executed and applied by the money
+/// contract, as a `Money::DeriveLeadCoin` call only consensus can make.
+fn mint_derived_coin(
+    blockchain_overlay: &BlockchainOverlayPtr,
+    lead_info: &LeadInfo,
+    gas_limit: u64,
+) -> Result<()> {
+    // Genesis block has no leader
+    if lead_info.public_inputs.len() <= constants::PI_REWARD_INDEX {
+        return Ok(())
+    }
+
+    let merkle_root = lead_info.commitment_root();
+    let wasm = {
+        let lock = blockchain_overlay.lock().unwrap();
+        let lead_coin_roots_tree =
+            lock.contracts.lookup(&MONEY_CONTRACT_ID, lead::MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+        let root = serialize(&merkle_root);
+        let staked = lock.overlay.lock().unwrap().contains_key(&lead_coin_roots_tree, &root)?;
+        if !staked {
+            return Ok(())
+        }
+        lock.wasm_bincode.get(*MONEY_CONTRACT_ID)?
+    };
+
+    let params = DeriveLeadCoinParamsV1 {
+        merkle_root,
+        nullifier: lead_info.public_inputs[constants::PI_NULLIFIER_INDEX],
+        lead_coin: lead_info.derived_commitment_hash(),
+    };
+    let mut data = vec![lead::MONEY_DERIVE_LEAD_COIN_V1];
+    params.encode(&mut data)?;
+    let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data }];
+
+    let mut payload = vec![];
+    payload.write_u32(0)?; // Call index
+    calls.encode(&mut payload)?; // Actual call data
+
+    let mut runtime =
+        Runtime::new(&wasm, blockchain_overlay.clone(), *MONEY_CONTRACT_ID, gas_limit)?;
+    let (update, _) = runtime.exec(&payload)?;
+    runtime.apply(&update)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{
+        crypto::{constants::MERKLE_DEPTH, MerkleNode, MerkleTree, MONEY_CONTRACT_ID},
+        incrementalmerkletree::{bridgetree::BridgeTree, Tree},
+        lead,
+        pasta::{
+            group::ff::{Field, PrimeField},
+            pallas,
+        },
+    };
+    use darkfi_serial::{deserialize, serialize};
+    use rand::rngs::OsRng;
+
+    use super::ValidatorState;
+    use crate::{
+        consensus::{
+            constants,
+            lead_coin::{LeadCoin, LeadCoinSecrets},
+            ChainParams, SimulatedTime,
+        },
+        util::time::Timestamp,
+        wallet::WalletDb,
+        Error, Result,
+    };
+
+    /// Add given coin to the money contract lead coins, as a Money::StakeV1 call would.
+    fn stake_lead_coin(state: &ValidatorState, coin: &LeadCoin) -> Result<()> {
+        let lookup = |tree_name| {
+            state.blockchain.contracts.lookup(
+                &state.blockchain.sled_db,
+                &MONEY_CONTRACT_ID,
+                tree_name,
+            )
+        };
+
+        let info = lookup(lead::MONEY_CONTRACT_INFO_TREE)?;
+        let key = serialize(&lead::MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE);
+        let tree_data = info.get(&key)?.unwrap();
+        let leaf_count: u32 = deserialize(&tree_data[..4])?;
+        let mut tree: MerkleTree = deserialize(&tree_data[4..])?;
+        tree.append(&MerkleNode::from(coin.commitment_hash()));
+
+        let mut tree_data = serialize(&(leaf_count + 1));
+        tree_data.extend(serialize(&tree));
+        info.insert(&key, tree_data)?;
+        lookup(lead::MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?
+            .insert(serialize(&tree.root(0).unwrap()), Vec::<u8>::new())?;
+        lookup(lead::MONEY_CONTRACT_LEAD_COINS_TREE)?
+            .insert(serialize(&coin.commitment_hash()), serialize(&leaf_count))?;
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn legacy_coin_table_is_kept() -> Result<()> {
+        let params = ChainParams::devnet();
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+        // Earlier wallets kept unstaked coins in a single column table
+        wallet.exec_sql("CREATE TABLE consensus_coin (coin BLOB);").await?;
+        wallet.exec_sql("INSERT INTO consensus_coin (coin) VALUES (x'00');").await?;
+
+        let state =
+            ValidatorState::new(&sled_db, params, wallet.clone(), vec![], true, true).await?;
+        let mut lock = state.write().await;
+        lock.consensus.init_coins().await?;
+
+        // Without stake, only the zero value coin competes
+        assert_eq!(lock.consensus.coins.len(), 1);
+        assert_eq!(lock.consensus.coins[0].value, 0);
+
+        // The legacy coins are left in place
+        let mut conn = wallet.conn.acquire().await?;
+        let rows = sqlx::query("SELECT coin FROM consensus_coin;").fetch_all(&mut conn).await?;
+        assert_eq!(rows.len(), 1);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn staked_coin_wins_consecutive_slots() -> Result<()> {
+        let params = ChainParams::devnet();
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+        // Single-node mode skips the leader proof checks, so no slot checkpoints are needed
+        let state =
+            ValidatorState::new(&sled_db, params.clone(), wallet, vec![], true, true).await?;
+        let mut lock = state.write().await;
+        let time = SimulatedTime::new(params.genesis_ts);
+        lock.consensus.time = time.clone();
+        lock.consensus.participating = Some(1);
+        lock.consensus.proposing = true;
+
+        // Stake a coin, as a Money::StakeV1 call followed by drk would
+        let secrets = LeadCoinSecrets::generate(1);
+        let staked = LeadCoin::new(
+            100,
+            lock.consensus.bootstrap_slot,
+            secrets.secret_keys[0].inner(),
+            secrets.merkle_roots[0],
+            0,
+            secrets.merkle_paths[0].clone(),
+            pallas::Base::random(&mut OsRng),
+            &mut BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100),
+        );
+        stake_lead_coin(&lock, &staked)?;
+        lock.consensus.add_staked_coin(&staked).await?;
+
+        // Coins are recreated from the wallet every slot, as after a restart
+        let mut value = staked.value;
+        let mut nullifiers = vec![];
+        for slot in 1..100 {
+            time.set(Timestamp(params.genesis_ts.0 + (slot * params.slot_time) as i64));
+            lock.consensus.init_coins().await?;
+            assert_eq!(lock.consensus.coins.len(), 1);
+            assert_eq!(lock.consensus.coins[0].value, value);
+
+            // Scale sigma1 to the coin value, so the target is 2^252 and
+            // about one slot out of four is won.
+            let coin = lock.consensus.coins[0].clone();
+            let sigma1 = pallas::Base::from_u128(1 << 126).square() *
+                pallas::Base::from(coin.value).invert().unwrap();
+            let sigma2 = pallas::Base::zero();
+            let eta = lock.consensus.get_eta();
+            if !coin.is_leader(sigma1, sigma2, eta, pallas::Base::from(slot), None) {
+                continue
+            }
+
+            let (proposal, coin, blind) = lock.propose(slot, -1, 0, sigma1, sigma2).await?.unwrap();
+            let sn = proposal.block.lead_info.public_inputs[constants::PI_NULLIFIER_INDEX];
+            assert!(!nullifiers.contains(&sn));
+            assert!(lock.receive_proposal(&proposal, Some((0, coin, blind))).await?);
+            let (blocks, _) = lock.chain_finalization().await?;
+            assert_eq!(blocks.len(), 1);
+
+            value += params.reward(slot);
+            nullifiers.push(sn);
+            if nullifiers.len() == 2 {
+                break
+            }
+        }
+        assert_eq!(nullifiers.len(), 2);
+
+        // The wallet holds the coin derived from the second win
+        lock.consensus.init_coins().await?;
+        assert_eq!(lock.consensus.coins[0].value, value);
+
+        Ok(())
+    }
+
+    #[async_std::test]
+    async fn unstaked_coin_proposal_is_rejected() -> Result<()> {
+        let params = ChainParams::devnet();
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+        let state =
+            ValidatorState::new(&sled_db, params.clone(), wallet, vec![], true, false).await?;
+        let mut lock = state.write().await;
+        let time = SimulatedTime::new(params.genesis_ts);
+        time.set(Timestamp(params.genesis_ts.0 + params.slot_time as i64));
+        lock.consensus.time = time;
+        lock.consensus.participating = Some(1);
+        lock.consensus.proposing = true;
+
+        // Stake a coin, so unstaked coins can no longer compete
+        let secrets = LeadCoinSecrets::generate(1);
+        let staked = LeadCoin::new(
+            100,
+            lock.consensus.bootstrap_slot,
+            secrets.secret_keys[0].inner(),
+            secrets.merkle_roots[0],
+            0,
+            secrets.merkle_paths[0].clone(),
+            pallas::Base::random(&mut OsRng),
+            &mut BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100),
+        );
+        stake_lead_coin(&lock, &staked)?;
+        lock.consensus.add_staked_coin(&staked).await?;
+
+        // A coin of the same value, proven in a tree of its own
+        let secrets = LeadCoinSecrets::generate(1);
+        let unstaked = LeadCoin::new(
+            100,
+            lock.consensus.bootstrap_slot,
+            secrets.secret_keys[0].inner(),
+            secrets.merkle_roots[0],
+            0,
+            secrets.merkle_paths[0].clone(),
+            pallas::Base::random(&mut OsRng),
+            &mut BridgeTree::<MerkleNode, MERKLE_DEPTH>::new(100),
+        );
+        lock.consensus.coins = vec![unstaked];
+
+        let sigma1 = pallas::Base::one();
+        let sigma2 = pallas::Base::zero();
+        let (proposal, _, _) = lock.propose(1, -1, 0, sigma1, sigma2).await?.unwrap();
+        assert!(matches!(
+            lock.receive_proposal(&proposal, None).await,
+            Err(Error::ProposalCoinNotStaked)
+        ));
+
+        // The staked coin is proven in the contract lead coins tree
+        lock.consensus.init_coins().await?;
+        assert_eq!(lock.consensus.coins[0].commitment_hash(), staked.commitment_hash());
+        let (proposal, _, _) = lock.propose(1, -1, 0, sigma1, sigma2).await?.unwrap();
+        lock.consensus.verify_lead_coin(-1, &proposal.block.lead_info)?;
+
+        Ok(())
+    }
+}
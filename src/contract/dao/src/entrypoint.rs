@@ -26,6 +26,7 @@ use darkfi_sdk::{
     db::{
         db_contains_key, db_del, db_get, db_init, db_lookup, db_set, set_return_data, zkas_db_set,
     },
+    emit_event,
     error::{ContractError, ContractResult},
     merkle_add, msg, ContractCall,
 };
@@ -33,8 +34,8 @@ use darkfi_serial::{deserialize, serialize, Decodable, Encodable, WriteExt};
 
 use darkfi_money_contract::{
     model::MoneyTransferParamsV1 as MoneyTransferParams,
-    MoneyFunction::TransferV1 as MoneyTransfer, MONEY_CONTRACT_COIN_ROOTS_TREE,
-    MONEY_CONTRACT_NULLIFIERS_TREE,
+    MoneyFunction::{FeeV1 as MoneyFee, TransferV1 as MoneyTransfer},
+    MONEY_CONTRACT_COIN_ROOTS_TREE, MONEY_CONTRACT_NULLIFIERS_TREE,
 };
 
 use crate::{
@@ -132,9 +133,24 @@ fn init_contract(cid: ContractId, _ix: &[u8]) -> ContractResult {
     Ok(())
 }
 
+/// Strip a `Money::FeeV1` call from the front of the transaction calls, shifting
+/// `call_idx` accordingly. The fee call only pays for the transaction, so it is
+/// not part of the call structure enforced by this contract.
+fn strip_fee_call(call_idx: u32, call: Vec<ContractCall>) -> (u32, Vec<ContractCall>) {
+    if call_idx > 0 &&
+        call[0].contract_id == *MONEY_CONTRACT_ID &&
+        call[0].data.first() == Some(&(MoneyFee as u8))
+    {
+        return (call_idx - 1, call[1..].to_vec())
+    }
+
+    (call_idx, call)
+}
+
 fn process_instruction(cid: ContractId, ix: &[u8]) -> ContractResult {
     let (call_idx, call): (u32, Vec<ContractCall>) = deserialize(ix)?;
     assert!(call_idx < call.len() as u32);
+    let (call_idx, call) = strip_fee_call(call_idx, call);
 
     let self_ = &call[call_idx as usize];
     let func = DaoFunction::try_from(self_.data[0])?;
@@ -358,6 +374,10 @@ fn process_update(cid: ContractId, ix: &[u8]) -> ContractResult {
             let pv = DaoBlindAggregateVote::default();
 
             db_set(proposal_vote_db, &serialize(&update.proposal_bulla), &serialize(&pv))?;
+            emit_event(
+                &[vec![DaoFunction::Propose as u8], serialize(&update.proposal_bulla)],
+                &[],
+            )?;
 
             Ok(())
         }
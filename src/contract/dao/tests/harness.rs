@@ -18,10 +18,7 @@
 use std::collections::HashMap;
 
 use darkfi::{
-    consensus::{
-        ValidatorState, ValidatorStatePtr, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
-        TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION,
-    },
+    consensus::{ChainParams, ValidatorState, ValidatorStatePtr},
     runtime::vm_runtime::SMART_CONTRACT_ZKAS_DB_NAME,
     wallet::WalletDb,
     zk::{empty_witnesses, ProvingKey, ZkCircuit},
@@ -119,12 +116,11 @@ impl DaoTestHarness {
 
         let alice_sled_db = sled::Config::new().temporary(true).open()?;
 
+        // The DAO transactions of the tests don't pay fees
+        let params = ChainParams { min_tx_fee: 0, ..ChainParams::testnet() };
         let alice_state = ValidatorState::new(
             &alice_sled_db,
-            *TESTNET_BOOTSTRAP_TIMESTAMP,
-            *TESTNET_GENESIS_TIMESTAMP,
-            *TESTNET_GENESIS_HASH_BYTES,
-            *TESTNET_INITIAL_DISTRIBUTION,
+            params,
             alice_wallet,
             faucet_pubkeys,
             false,
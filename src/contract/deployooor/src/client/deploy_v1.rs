@@ -0,0 +1,60 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi::{zkas::ZkBinary, Result};
+use darkfi_sdk::crypto::Keypair;
+use log::debug;
+
+use crate::model::DeployParamsV1;
+
+pub struct DeployCallDebris {
+    pub params: DeployParamsV1,
+}
+
+/// Struct holding necessary information to build a `Deploy::DeployV1` contract call.
+pub struct DeployCallBuilder {
+    /// Deploy keypair, used to derive the contract ID and sign the call
+    pub deploy_keypair: Keypair,
+    /// Wasm bincode of the contract
+    pub wasm_bincode: Vec<u8>,
+    /// zkas bincodes of the contract circuits
+    pub zkas_bincodes: Vec<Vec<u8>>,
+    /// Payload passed to the contract `__initialize` function
+    pub ix: Vec<u8>,
+}
+
+impl DeployCallBuilder {
+    pub fn build(&self) -> Result<DeployCallDebris> {
+        debug!("Building Deploy::DeployV1 contract call");
+
+        // Make sure the zkas bincodes are valid before sending them
+        // on chain, where the host would otherwise reject them.
+        for zkas_bincode in &self.zkas_bincodes {
+            ZkBinary::decode(zkas_bincode)?;
+        }
+
+        let params = DeployParamsV1 {
+            wasm_bincode: self.wasm_bincode.clone(),
+            zkas_bincodes: self.zkas_bincodes.clone(),
+            public_key: self.deploy_keypair.public,
+            ix: self.ix.clone(),
+        };
+        let debris = DeployCallDebris { params };
+        Ok(debris)
+    }
+}
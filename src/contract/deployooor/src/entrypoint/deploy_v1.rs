@@ -0,0 +1,95 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::{
+    crypto::{ContractId, PublicKey},
+    db::{db_contains_key, db_lookup},
+    error::{ContractError, ContractResult},
+    msg,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
+
+use crate::{
+    error::DeployError,
+    model::{DeployParamsV1, DeployUpdateV1},
+    DeployFunction, DEPLOY_CONTRACT_LOCK_TREE,
+};
+
+/// `get_metadata` function for `Deploy::DeployV1`
+pub(crate) fn deploy_deploy_get_metadata_v1(
+    _cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: DeployParamsV1 = deserialize(&self_.data[1..])?;
+
+    // Public inputs for the ZK proofs we have to verify
+    let zk_public_inputs: Vec<(String, Vec<pallas::Base>)> = vec![];
+    // The call must be signed by the deploy key the contract ID derives from
+    let signature_pubkeys: Vec<PublicKey> = vec![params.public_key];
+
+    // Serialize everything gathered and return it
+    let mut metadata = vec![];
+    zk_public_inputs.encode(&mut metadata)?;
+    signature_pubkeys.encode(&mut metadata)?;
+
+    Ok(metadata)
+}
+
+/// `process_instruction` function for `Deploy::DeployV1`
+pub(crate) fn deploy_deploy_process_instruction_v1(
+    cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: DeployParamsV1 = deserialize(&self_.data[1..])?;
+
+    if params.wasm_bincode.is_empty() {
+        msg!("[DeployV1] Error: Wasm bincode is empty");
+        return Err(DeployError::WasmBincodeEmpty.into())
+    }
+
+    // Locked contracts can't be upgraded
+    let contract_id = ContractId::derive_public(params.public_key);
+    let lock_db = db_lookup(cid, DEPLOY_CONTRACT_LOCK_TREE)?;
+    if db_contains_key(lock_db, &serialize(&contract_id))? {
+        msg!("[DeployV1] Error: Contract {} is locked", contract_id);
+        return Err(DeployError::ContractLocked.into())
+    }
+
+    // The contract itself gets deployed by the host once the call is applied
+    let update = DeployUpdateV1 { contract_id };
+    let mut update_data = vec![];
+    update_data.write_u8(DeployFunction::DeployV1 as u8)?;
+    update.encode(&mut update_data)?;
+
+    Ok(update_data)
+}
+
+/// `process_update` function for `Deploy::DeployV1`
+pub(crate) fn deploy_deploy_process_update_v1(
+    _cid: ContractId,
+    update: DeployUpdateV1,
+) -> ContractResult {
+    msg!("[DeployV1] Deploying contract {}", update.contract_id);
+    Ok(())
+}
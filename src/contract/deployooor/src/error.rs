@@ -0,0 +1,37 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::error::ContractError;
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum DeployError {
+    #[error("Contract is locked")]
+    ContractLocked,
+
+    #[error("Contract wasm bincode is empty")]
+    WasmBincodeEmpty,
+}
+
+impl From<DeployError> for ContractError {
+    fn from(e: DeployError) -> Self {
+        match e {
+            DeployError::ContractLocked => Self::Custom(1),
+            DeployError::WasmBincodeEmpty => Self::Custom(2),
+        }
+    }
+}
@@ -0,0 +1,47 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Smart contract implementing the deployment of user smart contracts,
+//! along with their upgrades and locking.
+//!
+//! The contract itself only authorizes the calls and keeps track of locked
+//! contracts. The deployment is then performed by the host, which runs the
+//! contract initialization and stores its wasm and zkas bincodes.
+
+pub use darkfi_sdk::deploy::DeployFunction;
+
+/// Internal contract errors
+pub mod error;
+
+/// Call parameters definitions
+pub mod model;
+
+#[cfg(not(feature = "no-entrypoint"))]
+/// WASM entrypoint functions
+pub mod entrypoint;
+
+#[cfg(feature = "client")]
+/// Client API for interaction with this smart contract
+pub mod client;
+
+// These are the different sled trees that will be created
+pub const DEPLOY_CONTRACT_INFO_TREE: &str = "info";
+pub const DEPLOY_CONTRACT_LOCK_TREE: &str = "wasm_lock";
+
+// These are keys inside the info tree
+pub const DEPLOY_CONTRACT_DB_VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -0,0 +1,36 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::crypto::ContractId;
+use darkfi_serial::{SerialDecodable, SerialEncodable};
+
+pub use darkfi_sdk::deploy::{DeployParamsV1, LockParamsV1};
+
+/// State update for `Deploy::Deploy`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct DeployUpdateV1 {
+    /// ID of the deployed contract
+    pub contract_id: ContractId,
+}
+
+/// State update for `Deploy::Lock`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct LockUpdateV1 {
+    /// ID of the locked contract
+    pub contract_id: ContractId,
+}
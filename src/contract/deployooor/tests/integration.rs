@@ -0,0 +1,168 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Integration test for functionalities of the deployooor smart contract:
+//!
+//! * Deployment of a contract along with its zkas circuits
+//! * Upgrade of a deployed contract
+//! * Locking of a contract, after which it can't be upgraded
+//! * Rejection of locks of contracts that were never deployed
+//! * Rejection of calls not signed by the deploy key
+
+use darkfi::{
+    consensus::{ChainParams, ValidatorState, ValidatorStatePtr},
+    tx::Transaction,
+    wallet::WalletDb,
+    zkas::ZkBinary,
+    Result,
+};
+use darkfi_sdk::{
+    crypto::{ContractId, Keypair, SecretKey, DEPLOYOOOR_CONTRACT_ID},
+    ContractCall,
+};
+use darkfi_serial::Encodable;
+use log::info;
+use rand::rngs::OsRng;
+
+use darkfi_deployooor_contract::{
+    client::{deploy_v1::DeployCallBuilder, lock_v1::LockCallBuilder},
+    DeployFunction,
+};
+
+fn init_logger() {
+    let mut cfg = simplelog::ConfigBuilder::new();
+    cfg.add_filter_ignore("sled".to_string());
+    cfg.add_filter_ignore("blockchain::contractstore".to_string());
+
+    if let Err(_) = simplelog::TermLogger::init(
+        simplelog::LevelFilter::Info,
+        cfg.build(),
+        simplelog::TerminalMode::Mixed,
+        simplelog::ColorChoice::Auto,
+    ) {
+        info!(target: "deployooor_integration", "Logger already initialized");
+    }
+}
+
+async fn validator() -> Result<ValidatorStatePtr> {
+    let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
+    let sled_db = sled::Config::new().temporary(true).open()?;
+    // Deployments of the tests don't pay fees
+    let params = ChainParams { min_tx_fee: 0, ..ChainParams::testnet() };
+    ValidatorState::new(&sled_db, params, wallet, vec![], false, false).await
+}
+
+fn build_tx(
+    function: DeployFunction,
+    params: &impl Encodable,
+    signer: &SecretKey,
+) -> Result<Transaction> {
+    let mut data = vec![function as u8];
+    params.encode(&mut data)?;
+    let calls = vec![ContractCall { contract_id: *DEPLOYOOOR_CONTRACT_ID, data }];
+    let proofs = vec![vec![]];
+    let mut tx = Transaction { calls, proofs, signatures: vec![] };
+    let sigs = tx.create_sigs(&mut OsRng, &[*signer])?;
+    tx.signatures = vec![sigs];
+
+    Ok(tx)
+}
+
+fn deploy_tx(
+    deploy_keypair: Keypair,
+    wasm_bincode: &[u8],
+    signer: &SecretKey,
+) -> Result<Transaction> {
+    let builder = DeployCallBuilder {
+        deploy_keypair,
+        wasm_bincode: wasm_bincode.to_vec(),
+        zkas_bincodes: vec![include_bytes!("../../money/proof/token_freeze_v1.zk.bin").to_vec()],
+        ix: vec![],
+    };
+    let debris = builder.build()?;
+    build_tx(DeployFunction::DeployV1, &debris.params, signer)
+}
+
+fn lock_tx(deploy_keypair: Keypair) -> Result<Transaction> {
+    let debris = LockCallBuilder { deploy_keypair }.build()?;
+    build_tx(DeployFunction::LockV1, &debris.params, &deploy_keypair.secret)
+}
+
+#[async_std::test]
+async fn deployooor_integration() -> Result<()> {
+    init_logger();
+
+    let state = validator().await?;
+    let deploy_keypair = Keypair::random(&mut OsRng);
+    let contract_id = ContractId::derive_public(deploy_keypair.public);
+
+    // Any wasm contract will do, so we deploy a second instance of this one
+    let wasm_bincode = include_bytes!("../deployooor_contract.wasm");
+    let zkbin = ZkBinary::decode(include_bytes!("../../money/proof/token_freeze_v1.zk.bin"))?;
+
+    info!("Executing lock tx of a contract that is not deployed");
+    let tx = lock_tx(deploy_keypair)?;
+    assert_eq!(state.read().await.verify_transactions(&[tx], true).await?.len(), 1);
+
+    info!("Executing contract deployment tx");
+    let tx = deploy_tx(deploy_keypair, wasm_bincode, &deploy_keypair.secret)?;
+    assert!(state.read().await.verify_transactions(&[tx], true).await?.is_empty());
+
+    {
+        let state = state.read().await;
+        assert_eq!(state.blockchain.wasm_bincode.get(contract_id)?, wasm_bincode.to_vec());
+        let (deployed_zkbin, _) = state.blockchain.contracts.get_zkas(
+            &state.blockchain.sled_db,
+            &contract_id,
+            &zkbin.namespace,
+        )?;
+        assert_eq!(deployed_zkbin.namespace, zkbin.namespace);
+    }
+
+    info!("Executing contract deployment tx signed by a different key");
+    let other_secret = SecretKey::random(&mut OsRng);
+    let tx = deploy_tx(deploy_keypair, wasm_bincode, &other_secret)?;
+    assert_eq!(state.read().await.verify_transactions(&[tx], true).await?.len(), 1);
+
+    info!("Executing contract upgrade tx");
+    let upgraded_bincode = include_bytes!("../../dao/dao_contract.wasm");
+    let tx = deploy_tx(deploy_keypair, upgraded_bincode, &deploy_keypair.secret)?;
+    assert!(state.read().await.verify_transactions(&[tx], true).await?.is_empty());
+    assert_eq!(
+        state.read().await.blockchain.wasm_bincode.get(contract_id)?,
+        upgraded_bincode.to_vec()
+    );
+
+    info!("Executing contract lock tx");
+    let tx = lock_tx(deploy_keypair)?;
+    assert!(state.read().await.verify_transactions(&[tx.clone()], true).await?.is_empty());
+
+    info!("Executing contract lock tx again");
+    assert_eq!(state.read().await.verify_transactions(&[tx], true).await?.len(), 1);
+
+    info!("Executing contract upgrade tx after lock");
+    let tx = deploy_tx(deploy_keypair, wasm_bincode, &deploy_keypair.secret)?;
+    assert_eq!(state.read().await.verify_transactions(&[tx], true).await?.len(), 1);
+    assert_eq!(
+        state.read().await.blockchain.wasm_bincode.get(contract_id)?,
+        upgraded_bincode.to_vec()
+    );
+
+    // Thanks for reading
+    Ok(())
+}
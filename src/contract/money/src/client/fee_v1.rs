@@ -0,0 +1,284 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use darkfi::{
+    tx::Transaction,
+    zk::{halo2::Value, Proof, ProvingKey, Witness, ZkCircuit},
+    zkas::ZkBinary,
+    ClientFailed, Result,
+};
+use darkfi_sdk::{
+    crypto::{
+        note::AeadEncryptedNote, pasta_prelude::*, pedersen_commitment_u64, poseidon_hash, Coin,
+        MerkleNode, MerkleTree, Nullifier, PublicKey, SecretKey, DARK_TOKEN_ID, MONEY_CONTRACT_ID,
+    },
+    incrementalmerkletree::Tree,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::Encodable;
+use log::{debug, error, info};
+use rand::rngs::OsRng;
+
+use crate::{
+    client::{MoneyNote, OwnCoin},
+    model::MoneyFeeParamsV1,
+    MoneyFunction,
+};
+
+pub struct FeeCallDebris {
+    pub params: MoneyFeeParamsV1,
+    pub proofs: Vec<Proof>,
+    pub signature_secret: SecretKey,
+    pub spent_coin: OwnCoin,
+    /// Blinding factor opening the fee value commitment
+    pub fee_blind: pallas::Scalar,
+}
+
+impl FeeCallDebris {
+    /// Insert the `Money::FeeV1` call and its proofs in front of the calls of
+    /// the given unsigned transaction, as the fee call has to come first.
+    /// This must happen before the other calls get signed, since signatures
+    /// commit to all of the transaction calls.
+    pub fn prepend_call(&self, tx: &mut Transaction) -> Result<()> {
+        let mut data = vec![MoneyFunction::FeeV1 as u8];
+        self.params.encode(&mut data)?;
+        tx.calls.insert(0, ContractCall { contract_id: *MONEY_CONTRACT_ID, data });
+        tx.proofs.insert(0, self.proofs.clone());
+        Ok(())
+    }
+
+    /// Sign the fee call inserted by [`FeeCallDebris::prepend_call`], placing
+    /// its signatures in front of the ones of the other calls.
+    pub fn prepend_sigs(&self, tx: &mut Transaction) -> Result<()> {
+        let sigs = tx.create_sigs(&mut OsRng, &[self.signature_secret])?;
+        tx.signatures.insert(0, sigs);
+        Ok(())
+    }
+}
+
+pub struct FeeRevealed {
+    pub nullifier: Nullifier,
+    pub merkle_root: MerkleNode,
+    pub fee_commit: pallas::Point,
+    pub min_fee: u64,
+    pub coin: Coin,
+    pub signature_public: PublicKey,
+}
+
+impl FeeRevealed {
+    pub fn to_vec(&self) -> Vec<pallas::Base> {
+        let (sig_x, sig_y) = self.signature_public.xy();
+        let fee_coords = self.fee_commit.to_affine().coordinates().unwrap();
+
+        // NOTE: It's important to keep these in the same order
+        // as the `constrain_instance` calls in the zkas code.
+        vec![
+            self.nullifier.inner(),
+            self.merkle_root.inner(),
+            *fee_coords.x(),
+            *fee_coords.y(),
+            pallas::Base::from(self.min_fee),
+            DARK_TOKEN_ID.inner(),
+            self.coin.inner(),
+            sig_x,
+            sig_y,
+        ]
+    }
+}
+
+/// Struct holding necessary information to build a `Money::FeeV1` contract call.
+pub struct FeeCallBuilder {
+    /// Native token coin used to pay the fee
+    pub coin: OwnCoin,
+    /// Fee amount to pay
+    pub fee: u64,
+    /// Minimum the fee is proven to reach, revealed to the network
+    pub min_fee: u64,
+    /// Recipient of the change coin
+    pub change_public: PublicKey,
+    /// Merkle tree of coins used to create the inclusion proof
+    pub tree: MerkleTree,
+    /// `Fee_V1` zkas circuit ZkBinary
+    pub fee_zkbin: ZkBinary,
+    /// Proving key for the `Fee_V1` zk circuit
+    pub fee_pk: ProvingKey,
+}
+
+impl FeeCallBuilder {
+    pub fn build(&self) -> Result<FeeCallDebris> {
+        debug!("Building Money::FeeV1 contract call");
+
+        if self.fee == 0 {
+            error!("Fee is zero");
+            return Err(ClientFailed::InvalidAmount(self.fee).into())
+        }
+
+        if self.coin.note.token_id != *DARK_TOKEN_ID {
+            error!("Fee coin is not of the native token");
+            return Err(ClientFailed::InvalidTokenId(self.coin.note.token_id.to_string()).into())
+        }
+
+        // Fee coins can not be bound to other contracts
+        if self.coin.note.spend_hook != pallas::Base::ZERO ||
+            self.coin.note.user_data != pallas::Base::ZERO
+        {
+            error!("Fee coin is bound to a spend hook");
+            return Err(ClientFailed::VerifyError("Fee coin has a spend hook".to_string()).into())
+        }
+
+        if self.coin.note.value < self.fee {
+            error!("Fee coin value is lower than the fee");
+            return Err(ClientFailed::NotEnoughValue(self.coin.note.value).into())
+        }
+
+        if self.fee < self.min_fee {
+            error!("Fee is lower than its proven minimum");
+            return Err(ClientFailed::NotEnoughValue(self.fee).into())
+        }
+
+        let root = self.tree.root(0).unwrap();
+        let merkle_path = self.tree.authentication_path(self.coin.leaf_position, &root).unwrap();
+
+        let signature_secret = SecretKey::random(&mut OsRng);
+        let serial = pallas::Base::random(&mut OsRng);
+        let coin_blind = pallas::Base::random(&mut OsRng);
+        let fee_blind = pallas::Scalar::random(&mut OsRng);
+
+        info!("Creating fee proof");
+        let (proof, public_inputs) = create_fee_proof(
+            &self.fee_zkbin,
+            &self.fee_pk,
+            &self.coin,
+            merkle_path,
+            self.fee,
+            fee_blind,
+            self.min_fee,
+            self.change_public,
+            serial,
+            coin_blind,
+            signature_secret,
+        )?;
+
+        // The fee call reveals no values, so the change coin has no
+        // commitments to open and its blinds are left at zero.
+        let note = MoneyNote {
+            serial,
+            value: self.coin.note.value - self.fee,
+            token_id: *DARK_TOKEN_ID,
+            spend_hook: pallas::Base::ZERO,
+            user_data: pallas::Base::ZERO,
+            coin_blind,
+            value_blind: pallas::Scalar::ZERO,
+            token_blind: pallas::Scalar::ZERO,
+            memo: vec![],
+        };
+
+        let encrypted_note = AeadEncryptedNote::encrypt(&note, &self.change_public, &mut OsRng)?;
+
+        let params = MoneyFeeParamsV1 {
+            nullifier: public_inputs.nullifier,
+            merkle_root: public_inputs.merkle_root,
+            signature_public: public_inputs.signature_public,
+            fee_commit: public_inputs.fee_commit,
+            min_fee: public_inputs.min_fee,
+            coin: public_inputs.coin,
+            note: encrypted_note,
+        };
+
+        let debris = FeeCallDebris {
+            params,
+            proofs: vec![proof],
+            signature_secret,
+            spent_coin: self.coin.clone(),
+            fee_blind,
+        };
+        Ok(debris)
+    }
+}
+
+pub fn create_fee_proof(
+    zkbin: &ZkBinary,
+    pk: &ProvingKey,
+    input: &OwnCoin,
+    merkle_path: Vec<MerkleNode>,
+    fee: u64,
+    fee_blind: pallas::Scalar,
+    min_fee: u64,
+    change_public: PublicKey,
+    serial: pallas::Base,
+    coin_blind: pallas::Base,
+    signature_secret: SecretKey,
+) -> Result<(Proof, FeeRevealed)> {
+    let nullifier = Nullifier::from(poseidon_hash([input.secret.inner(), input.note.serial]));
+    let merkle_root = {
+        let position: u64 = input.leaf_position.into();
+        let mut current = MerkleNode::from(input.coin.inner());
+        for (level, sibling) in merkle_path.iter().enumerate() {
+            let level = level as u8;
+            current = if position & (1 << level) == 0 {
+                MerkleNode::combine(level.into(), &current, sibling)
+            } else {
+                MerkleNode::combine(level.into(), sibling, &current)
+            };
+        }
+        current
+    };
+
+    let (pub_x, pub_y) = change_public.xy();
+    let change_value = input.note.value - fee;
+
+    let coin = Coin::from(poseidon_hash([
+        pub_x,
+        pub_y,
+        pallas::Base::from(change_value),
+        DARK_TOKEN_ID.inner(),
+        serial,
+        pallas::Base::ZERO,
+        pallas::Base::ZERO,
+        coin_blind,
+    ]));
+
+    let signature_public = PublicKey::from_secret(signature_secret);
+    let fee_commit = pedersen_commitment_u64(fee, fee_blind);
+
+    let public_inputs =
+        FeeRevealed { nullifier, merkle_root, fee_commit, min_fee, coin, signature_public };
+
+    let prover_witnesses = vec![
+        Witness::Base(Value::known(input.secret.inner())),
+        Witness::Uint32(Value::known(u64::from(input.leaf_position).try_into().unwrap())),
+        Witness::MerklePath(Value::known(merkle_path.try_into().unwrap())),
+        Witness::Base(Value::known(pallas::Base::from(input.note.value))),
+        Witness::Base(Value::known(input.note.token_id.inner())),
+        Witness::Base(Value::known(input.note.serial)),
+        Witness::Base(Value::known(input.note.coin_blind)),
+        Witness::Base(Value::known(pallas::Base::from(fee))),
+        Witness::Scalar(Value::known(fee_blind)),
+        Witness::Base(Value::known(pallas::Base::from(min_fee))),
+        Witness::Base(Value::known(pub_x)),
+        Witness::Base(Value::known(pub_y)),
+        Witness::Base(Value::known(serial)),
+        Witness::Base(Value::known(coin_blind)),
+        Witness::Base(Value::known(signature_secret.inner())),
+    ];
+
+    let circuit = ZkCircuit::new(prover_witnesses, zkbin.clone());
+    let proof = Proof::create(pk, &[circuit], &public_inputs.to_vec(), &mut OsRng)?;
+
+    Ok((proof, public_inputs))
+}
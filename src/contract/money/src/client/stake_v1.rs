@@ -0,0 +1,296 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use darkfi::{
+    consensus::LeadCoin,
+    zk::{halo2::Value, Proof, ProvingKey, Witness, ZkCircuit},
+    zkas::ZkBinary,
+    ClientFailed, Result,
+};
+use darkfi_sdk::{
+    crypto::{
+        pasta_prelude::*, pedersen_commitment_base, pedersen_commitment_u64, poseidon_hash,
+        MerkleNode, MerkleTree, SecretKey, DARK_TOKEN_ID,
+    },
+    incrementalmerkletree::Tree,
+    pasta::pallas,
+};
+use darkfi_serial::{SerialDecodable, SerialEncodable};
+use log::{debug, error, info};
+use rand::rngs::OsRng;
+
+use crate::{
+    client::{
+        transfer_v1::{create_transfer_burn_proof, TransactionBuilderInputInfo},
+        OwnCoin,
+    },
+    model::{Input, MoneyStakeParamsV1, StakeOutput},
+};
+
+/// Prefix of the lead coin commitment message, as in the lead circuits
+const PREFIX_CM: u64 = 4;
+/// Prefix of the lead coin public key message, as in the lead circuits
+const PREFIX_PK: u64 = 5;
+/// Prefix of the lead coin nullifier message, as in the lead circuits
+const PREFIX_SN: u64 = 6;
+
+/// Secret attributes of a staked lead coin, needed to compete in
+/// consensus with it and to unstake it.
+#[derive(Debug, Clone, SerialEncodable, SerialDecodable)]
+pub struct StakedCoin {
+    /// Staked value
+    pub value: u64,
+    /// Coin creation slot
+    pub slot: u64,
+    /// Coin nonce
+    pub nonce: pallas::Base,
+    /// Coin secret key
+    pub sk: pallas::Base,
+    /// Merkle root of the coin secret key
+    pub sk_root: MerkleNode,
+    /// Coin secret key position in its Merkle tree
+    pub sk_pos: u32,
+    /// Merkle path to the coin secret key
+    pub sk_merkle_path: Vec<MerkleNode>,
+    /// Coin commitment blinding factor
+    pub opening: pallas::Scalar,
+    /// Blinding factor for the staked value commitment
+    pub value_blind: pallas::Scalar,
+}
+
+impl StakedCoin {
+    /// Coin public key, derived from its secret key root and creation slot
+    pub fn public_key(&self) -> pallas::Base {
+        poseidon_hash([
+            pallas::Base::from(PREFIX_PK),
+            self.sk_root.inner(),
+            pallas::Base::from(self.slot),
+            pallas::Base::ZERO,
+        ])
+    }
+
+    /// Coin commitment
+    pub fn commitment(&self) -> pallas::Point {
+        let msg = poseidon_hash([
+            pallas::Base::from(PREFIX_CM),
+            self.public_key(),
+            pallas::Base::from(self.value),
+            self.nonce,
+        ]);
+        pedersen_commitment_base(msg, self.opening)
+    }
+
+    /// Hash of the coin commitment, its leaf in the lead coins Merkle tree
+    pub fn commitment_hash(&self) -> pallas::Base {
+        let coords = self.commitment().to_affine().coordinates().unwrap();
+        poseidon_hash([*coords.x(), *coords.y()])
+    }
+
+    /// Coin nullifier, revealed when unstaking
+    pub fn nullifier(&self) -> pallas::Base {
+        poseidon_hash([
+            pallas::Base::from(PREFIX_SN),
+            self.sk_root.inner(),
+            self.nonce,
+            pallas::Base::ZERO,
+        ])
+    }
+
+    /// Build the consensus `LeadCoin` competing with this stake. Its
+    /// commitment Merkle path gets filled in by consensus from the
+    /// contract state.
+    pub fn lead_coin(&self) -> LeadCoin {
+        LeadCoin {
+            value: self.value,
+            slot: self.slot,
+            nonce: self.nonce,
+            coin1_commitment: self.commitment(),
+            coin1_commitment_root: MerkleNode::from(pallas::Base::ZERO),
+            coin1_commitment_pos: 0,
+            coin1_commitment_merkle_path: vec![MerkleNode::from(pallas::Base::ZERO); 32],
+            coin1_sk: self.sk,
+            coin1_sk_root: self.sk_root,
+            coin1_sk_pos: self.sk_pos,
+            coin1_sk_merkle_path: self.sk_merkle_path.clone(),
+            coin1_blind: self.opening,
+        }
+    }
+}
+
+pub struct StakeCallDebris {
+    pub params: MoneyStakeParamsV1,
+    pub proofs: Vec<Proof>,
+    pub signature_secret: SecretKey,
+    pub spent_coin: OwnCoin,
+    pub staked_coin: StakedCoin,
+}
+
+pub struct StakeRevealed {
+    pub value_commit: pallas::Point,
+    pub public_key: pallas::Base,
+    pub commitment: pallas::Base,
+}
+
+impl StakeRevealed {
+    pub fn to_vec(&self) -> Vec<pallas::Base> {
+        let valcom_coords = self.value_commit.to_affine().coordinates().unwrap();
+
+        // NOTE: It's important to keep these in the same order
+        // as the `constrain_instance` calls in the zkas code.
+        vec![*valcom_coords.x(), *valcom_coords.y(), self.public_key, self.commitment]
+    }
+}
+
+/// Struct holding necessary information to build a `Money::StakeV1` contract call.
+pub struct StakeCallBuilder {
+    /// Native token coin to stake
+    pub coin: OwnCoin,
+    /// Merkle tree of coins used to create the inclusion proof
+    pub tree: MerkleTree,
+    /// Lead coin creation slot
+    pub slot: u64,
+    /// Lead coin secret key
+    pub sk: pallas::Base,
+    /// Merkle root of the lead coin secret key
+    pub sk_root: MerkleNode,
+    /// Lead coin secret key position in its Merkle tree
+    pub sk_pos: u32,
+    /// Merkle path to the lead coin secret key
+    pub sk_merkle_path: Vec<MerkleNode>,
+    /// `Burn_V1` zkas circuit ZkBinary
+    pub burn_zkbin: ZkBinary,
+    /// Proving key for the `Burn_V1` zk circuit
+    pub burn_pk: ProvingKey,
+    /// `Lead_Mint_V1` zkas circuit ZkBinary
+    pub lead_mint_zkbin: ZkBinary,
+    /// Proving key for the `Lead_Mint_V1` zk circuit
+    pub lead_mint_pk: ProvingKey,
+}
+
+impl StakeCallBuilder {
+    pub fn build(&self) -> Result<StakeCallDebris> {
+        debug!("Building Money::StakeV1 contract call");
+
+        if self.coin.note.token_id != *DARK_TOKEN_ID {
+            error!("Staked coin is not of the native token");
+            return Err(ClientFailed::InvalidTokenId(self.coin.note.token_id.to_string()).into())
+        }
+
+        if self.coin.note.spend_hook != pallas::Base::ZERO {
+            error!("Staked coin is bound to a spend hook");
+            return Err(ClientFailed::VerifyError("Staked coin has a spend hook".to_string()).into())
+        }
+
+        let root = self.tree.root(0).unwrap();
+        let merkle_path = self.tree.authentication_path(self.coin.leaf_position, &root).unwrap();
+
+        let input = TransactionBuilderInputInfo {
+            leaf_position: self.coin.leaf_position,
+            merkle_path,
+            secret: self.coin.secret,
+            note: self.coin.note.clone(),
+        };
+
+        // Both proofs commit to the value with the same blind, so the
+        // contract can check they match.
+        let value_blind = pallas::Scalar::random(&mut OsRng);
+        let token_blind = pallas::Scalar::random(&mut OsRng);
+        let user_data_blind = pallas::Base::random(&mut OsRng);
+        let signature_secret = SecretKey::random(&mut OsRng);
+
+        info!("Creating stake burn proof for input");
+        let (burn_proof, burn_revealed) = create_transfer_burn_proof(
+            &self.burn_zkbin,
+            &self.burn_pk,
+            &input,
+            value_blind,
+            token_blind,
+            user_data_blind,
+            signature_secret,
+        )?;
+
+        let staked_coin = StakedCoin {
+            value: self.coin.note.value,
+            slot: self.slot,
+            nonce: pallas::Base::random(&mut OsRng),
+            sk: self.sk,
+            sk_root: self.sk_root,
+            sk_pos: self.sk_pos,
+            sk_merkle_path: self.sk_merkle_path.clone(),
+            opening: pallas::Scalar::random(&mut OsRng),
+            value_blind,
+        };
+
+        info!("Creating lead mint proof for output");
+        let (mint_proof, mint_revealed) =
+            create_lead_mint_proof(&self.lead_mint_zkbin, &self.lead_mint_pk, &staked_coin)?;
+
+        let params = MoneyStakeParamsV1 {
+            input: Input {
+                value_commit: burn_revealed.value_commit,
+                token_commit: burn_revealed.token_commit,
+                nullifier: burn_revealed.nullifier,
+                merkle_root: burn_revealed.merkle_root,
+                spend_hook: burn_revealed.spend_hook,
+                user_data_enc: burn_revealed.user_data_enc,
+                signature_public: burn_revealed.signature_public,
+            },
+            token_blind,
+            output: StakeOutput {
+                value_commit: mint_revealed.value_commit,
+                public_key: mint_revealed.public_key,
+                commitment: mint_revealed.commitment,
+            },
+        };
+
+        let debris = StakeCallDebris {
+            params,
+            proofs: vec![burn_proof, mint_proof],
+            signature_secret,
+            spent_coin: self.coin.clone(),
+            staked_coin,
+        };
+        Ok(debris)
+    }
+}
+
+pub fn create_lead_mint_proof(
+    zkbin: &ZkBinary,
+    pk: &ProvingKey,
+    coin: &StakedCoin,
+) -> Result<(Proof, StakeRevealed)> {
+    let public_inputs = StakeRevealed {
+        value_commit: pedersen_commitment_u64(coin.value, coin.value_blind),
+        public_key: coin.public_key(),
+        commitment: coin.commitment_hash(),
+    };
+
+    let prover_witnesses = vec![
+        Witness::Base(Value::known(coin.sk)),
+        Witness::Base(Value::known(coin.sk_root.inner())),
+        Witness::Base(Value::known(pallas::Base::from(coin.slot))),
+        Witness::Base(Value::known(coin.nonce)),
+        Witness::Scalar(Value::known(coin.opening)),
+        Witness::Base(Value::known(pallas::Base::from(coin.value))),
+        Witness::Scalar(Value::known(coin.value_blind)),
+    ];
+
+    let circuit = ZkCircuit::new(prover_witnesses, zkbin.clone());
+    let proof = Proof::create(pk, &[circuit], &public_inputs.to_vec(), &mut OsRng)?;
+
+    Ok((proof, public_inputs))
+}
@@ -0,0 +1,238 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use darkfi::{
+    zk::{halo2::Value, Proof, ProvingKey, Witness, ZkCircuit},
+    zkas::ZkBinary,
+    Result,
+};
+use darkfi_sdk::{
+    crypto::{
+        note::AeadEncryptedNote, pasta_prelude::*, pedersen_commitment_u64, MerkleNode,
+        MerklePosition, MerkleTree, PublicKey, DARK_TOKEN_ID,
+    },
+    incrementalmerkletree::{Hashable, Tree},
+    pasta::pallas,
+};
+use log::{debug, info};
+use rand::rngs::OsRng;
+
+use crate::{
+    client::{
+        stake_v1::StakedCoin,
+        transfer_v1::{create_transfer_mint_proof, TransactionBuilderOutputInfo},
+        MoneyNote,
+    },
+    model::{MoneyUnstakeParamsV1, Output, UnstakeInput},
+};
+
+pub struct UnstakeCallDebris {
+    pub params: MoneyUnstakeParamsV1,
+    pub proofs: Vec<Proof>,
+}
+
+pub struct UnstakeRevealed {
+    pub value_commit: pallas::Point,
+    pub public_key: pallas::Base,
+    pub commitment: pallas::Base,
+    pub merkle_root: MerkleNode,
+    pub sk_root: MerkleNode,
+    pub nullifier: pallas::Base,
+}
+
+impl UnstakeRevealed {
+    pub fn to_vec(&self) -> Vec<pallas::Base> {
+        let valcom_coords = self.value_commit.to_affine().coordinates().unwrap();
+
+        // NOTE: It's important to keep these in the same order
+        // as the `constrain_instance` calls in the zkas code.
+        vec![
+            *valcom_coords.x(),
+            *valcom_coords.y(),
+            self.public_key,
+            self.commitment,
+            self.merkle_root.inner(),
+            self.sk_root.inner(),
+            self.nullifier,
+        ]
+    }
+}
+
+/// Struct holding necessary information to build a `Money::UnstakeV1` contract call.
+pub struct UnstakeCallBuilder {
+    /// Staked lead coin to unstake
+    pub coin: StakedCoin,
+    /// Lead coin leaf position in the lead coins Merkle tree
+    pub leaf_position: MerklePosition,
+    /// Merkle tree of lead coins used to create the inclusion proof
+    pub tree: MerkleTree,
+    /// Recipient of the unstaked coin
+    pub recipient: PublicKey,
+    /// `Lead_Burn_V1` zkas circuit ZkBinary
+    pub lead_burn_zkbin: ZkBinary,
+    /// Proving key for the `Lead_Burn_V1` zk circuit
+    pub lead_burn_pk: ProvingKey,
+    /// `Mint_V1` zkas circuit ZkBinary
+    pub mint_zkbin: ZkBinary,
+    /// Proving key for the `Mint_V1` zk circuit
+    pub mint_pk: ProvingKey,
+}
+
+impl UnstakeCallBuilder {
+    pub fn build(&self) -> Result<UnstakeCallDebris> {
+        debug!("Building Money::UnstakeV1 contract call");
+
+        let root = self.tree.root(0).unwrap();
+        let merkle_path = self.tree.authentication_path(self.leaf_position, &root).unwrap();
+
+        info!("Creating lead burn proof for input");
+        let (burn_proof, burn_revealed) = create_lead_burn_proof(
+            &self.lead_burn_zkbin,
+            &self.lead_burn_pk,
+            &self.coin,
+            self.leaf_position,
+            merkle_path,
+        )?;
+
+        let output = TransactionBuilderOutputInfo {
+            value: self.coin.value,
+            token_id: *DARK_TOKEN_ID,
+            public_key: self.recipient,
+        };
+
+        // The output reuses the stake value blind, so the contract can
+        // check both value commitments match.
+        let value_blind = self.coin.value_blind;
+        let token_blind = pallas::Scalar::random(&mut OsRng);
+        let serial = pallas::Base::random(&mut OsRng);
+        let coin_blind = pallas::Base::random(&mut OsRng);
+
+        info!("Creating unstake mint proof for output");
+        let (mint_proof, mint_revealed) = create_transfer_mint_proof(
+            &self.mint_zkbin,
+            &self.mint_pk,
+            &output,
+            value_blind,
+            token_blind,
+            serial,
+            pallas::Base::ZERO,
+            pallas::Base::ZERO,
+            coin_blind,
+        )?;
+
+        let note = MoneyNote {
+            serial,
+            value: output.value,
+            token_id: output.token_id,
+            spend_hook: pallas::Base::ZERO,
+            user_data: pallas::Base::ZERO,
+            coin_blind,
+            value_blind,
+            token_blind,
+            memo: vec![],
+        };
+
+        let encrypted_note = AeadEncryptedNote::encrypt(&note, &output.public_key, &mut OsRng)?;
+
+        let params = MoneyUnstakeParamsV1 {
+            input: UnstakeInput {
+                value_commit: burn_revealed.value_commit,
+                public_key: burn_revealed.public_key,
+                commitment: burn_revealed.commitment,
+                merkle_root: burn_revealed.merkle_root,
+                sk_root: burn_revealed.sk_root,
+                nullifier: burn_revealed.nullifier,
+            },
+            token_blind,
+            output: Output {
+                value_commit: mint_revealed.value_commit,
+                token_commit: mint_revealed.token_commit,
+                coin: mint_revealed.coin,
+                note: encrypted_note,
+            },
+        };
+
+        let debris = UnstakeCallDebris { params, proofs: vec![burn_proof, mint_proof] };
+        Ok(debris)
+    }
+}
+
+pub fn create_lead_burn_proof(
+    zkbin: &ZkBinary,
+    pk: &ProvingKey,
+    coin: &StakedCoin,
+    leaf_position: MerklePosition,
+    merkle_path: Vec<MerkleNode>,
+) -> Result<(Proof, UnstakeRevealed)> {
+    let commitment = coin.commitment_hash();
+
+    let merkle_root = {
+        let position: u64 = leaf_position.into();
+        let mut current = MerkleNode::from(commitment);
+        for (level, sibling) in merkle_path.iter().enumerate() {
+            let level = level as u8;
+            current = if position & (1 << level) == 0 {
+                MerkleNode::combine(level.into(), &current, sibling)
+            } else {
+                MerkleNode::combine(level.into(), sibling, &current)
+            };
+        }
+        current
+    };
+
+    let sk_root = {
+        let position = coin.sk_pos as u64;
+        let mut current = MerkleNode::from(coin.sk);
+        for (level, sibling) in coin.sk_merkle_path.iter().enumerate() {
+            let level = level as u8;
+            current = if position & (1 << level) == 0 {
+                MerkleNode::combine(level.into(), &current, sibling)
+            } else {
+                MerkleNode::combine(level.into(), sibling, &current)
+            };
+        }
+        current
+    };
+
+    let public_inputs = UnstakeRevealed {
+        value_commit: pedersen_commitment_u64(coin.value, coin.value_blind),
+        public_key: coin.public_key(),
+        commitment,
+        merkle_root,
+        sk_root,
+        nullifier: coin.nullifier(),
+    };
+
+    let prover_witnesses = vec![
+        Witness::MerklePath(Value::known(merkle_path.try_into().unwrap())),
+        Witness::Uint32(Value::known(u64::from(leaf_position).try_into().unwrap())),
+        Witness::Uint32(Value::known(coin.sk_pos)),
+        Witness::Base(Value::known(coin.sk)),
+        Witness::Base(Value::known(coin.sk_root.inner())),
+        Witness::MerklePath(Value::known(coin.sk_merkle_path.clone().try_into().unwrap())),
+        Witness::Base(Value::known(pallas::Base::from(coin.slot))),
+        Witness::Base(Value::known(coin.nonce)),
+        Witness::Scalar(Value::known(coin.opening)),
+        Witness::Base(Value::known(pallas::Base::from(coin.value))),
+        Witness::Scalar(Value::known(coin.value_blind)),
+    ];
+
+    let circuit = ZkCircuit::new(prover_witnesses, zkbin.clone());
+    let proof = Proof::create(pk, &[circuit], &public_inputs.to_vec(), &mut OsRng)?;
+
+    Ok((proof, public_inputs))
+}
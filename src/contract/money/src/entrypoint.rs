@@ -18,17 +18,22 @@
 
 use darkfi_sdk::{
     crypto::{ContractId, MerkleTree, PublicKey},
-    db::{db_init, db_lookup, db_set, set_return_data, zkas_db_set},
+    db::{db_contains_key, db_init, db_lookup, db_set, set_return_data, zkas_db_set},
     error::{ContractError, ContractResult},
     msg, ContractCall,
 };
 use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
 
 use crate::{
-    model::{MoneyFreezeUpdateV1, MoneyMintUpdateV1, MoneyTransferUpdateV1},
+    model::{
+        MoneyDeriveLeadCoinUpdateV1, MoneyFeeUpdateV1, MoneyFreezeUpdateV1, MoneyMintUpdateV1,
+        MoneyStakeUpdateV1, MoneyTransferUpdateV1, MoneyUnstakeUpdateV1,
+    },
     MoneyFunction, MONEY_CONTRACT_COINS_TREE, MONEY_CONTRACT_COIN_MERKLE_TREE,
     MONEY_CONTRACT_COIN_ROOTS_TREE, MONEY_CONTRACT_DB_VERSION, MONEY_CONTRACT_FAUCET_PUBKEYS,
-    MONEY_CONTRACT_INFO_TREE, MONEY_CONTRACT_NULLIFIERS_TREE, MONEY_CONTRACT_TOKEN_FREEZE_TREE,
+    MONEY_CONTRACT_INFO_TREE, MONEY_CONTRACT_LEAD_COINS_TREE, MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE,
+    MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE,
+    MONEY_CONTRACT_NULLIFIERS_TREE, MONEY_CONTRACT_TOKEN_FREEZE_TREE,
 };
 
 /// `Money::Transfer` functions
@@ -58,6 +63,32 @@ use freeze_v1::{
     money_freeze_process_update_v1,
 };
 
+/// `Money::Fee` functions
+mod fee_v1;
+use fee_v1::{
+    money_fee_get_metadata_v1, money_fee_process_instruction_v1, money_fee_process_update_v1,
+};
+
+/// `Money::Stake` functions
+mod stake_v1;
+use stake_v1::{
+    money_stake_get_metadata_v1, money_stake_process_instruction_v1, money_stake_process_update_v1,
+};
+
+/// `Money::Unstake` functions
+mod unstake_v1;
+use unstake_v1::{
+    money_unstake_get_metadata_v1, money_unstake_process_instruction_v1,
+    money_unstake_process_update_v1,
+};
+
+/// `Money::DeriveLeadCoin` functions
+mod derive_lead_coin_v1;
+use derive_lead_coin_v1::{
+    money_derive_lead_coin_get_metadata_v1, money_derive_lead_coin_process_instruction_v1,
+    money_derive_lead_coin_process_update_v1,
+};
+
 darkfi_sdk::define_contract!(
     init: init_contract,
     exec: process_instruction,
@@ -82,12 +113,18 @@ fn init_contract(cid: ContractId, ix: &[u8]) -> ContractResult {
     let burn_v1_bincode = include_bytes!("../proof/burn_v1.zk.bin");
     let token_mint_v1_bincode = include_bytes!("../proof/token_mint_v1.zk.bin");
     let token_frz_v1_bincode = include_bytes!("../proof/token_freeze_v1.zk.bin");
+    let fee_v1_bincode = include_bytes!("../proof/fee_v1.zk.bin");
+    let lead_mint_v1_bincode = include_bytes!("../proof/lead_mint_v1.zk.bin");
+    let lead_burn_v1_bincode = include_bytes!("../proof/lead_burn_v1.zk.bin");
 
     // For that, we use `zkas_db_set` and pass in the bincode.
     zkas_db_set(&mint_v1_bincode[..])?;
     zkas_db_set(&burn_v1_bincode[..])?;
     zkas_db_set(&token_mint_v1_bincode[..])?;
     zkas_db_set(&token_frz_v1_bincode[..])?;
+    zkas_db_set(&fee_v1_bincode[..])?;
+    zkas_db_set(&lead_mint_v1_bincode[..])?;
+    zkas_db_set(&lead_burn_v1_bincode[..])?;
 
     // Set up a database tree to hold Merkle roots of all coins
     // k=MerkleNode, v=[]
@@ -113,6 +150,24 @@ fn init_contract(cid: ContractId, ix: &[u8]) -> ContractResult {
         db_init(cid, MONEY_CONTRACT_TOKEN_FREEZE_TREE)?;
     }
 
+    // Set up a database tree to hold Merkle roots of all lead coins
+    // k=MerkleNode, v=[]
+    if db_lookup(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE).is_err() {
+        db_init(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+    }
+
+    // Set up a database tree to hold all staked lead coins
+    // k=lead coin commitment hash, v=leaf position in the lead coins Merkle tree
+    if db_lookup(cid, MONEY_CONTRACT_LEAD_COINS_TREE).is_err() {
+        db_init(cid, MONEY_CONTRACT_LEAD_COINS_TREE)?;
+    }
+
+    // Set up a database tree to hold nullifiers of all unstaked lead coins
+    // k=nullifier, v=[]
+    if db_lookup(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE).is_err() {
+        db_init(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE)?;
+    }
+
     // Set up a database tree for arbitrary data
     let info_db = match db_lookup(cid, MONEY_CONTRACT_INFO_TREE) {
         Ok(v) => v,
@@ -131,6 +186,17 @@ fn init_contract(cid: ContractId, ix: &[u8]) -> ContractResult {
         }
     };
 
+    // Create the incrementalmerkletree for staked lead coins
+    if !db_contains_key(info_db, &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE))? {
+        let lead_coin_tree = MerkleTree::new(100);
+        let mut lead_coin_tree_data = vec![];
+
+        lead_coin_tree_data.write_u32(0)?;
+        lead_coin_tree.encode(&mut lead_coin_tree_data)?;
+
+        db_set(info_db, &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE), &lead_coin_tree_data)?;
+    }
+
     // Whitelisted faucets
     db_set(info_db, &serialize(&MONEY_CONTRACT_FAUCET_PUBKEYS), &serialize(&faucet_pubkeys))?;
 
@@ -178,6 +244,26 @@ fn get_metadata(cid: ContractId, ix: &[u8]) -> ContractResult {
             let metadata = money_freeze_get_metadata_v1(cid, call_idx, calls)?;
             Ok(set_return_data(&metadata)?)
         }
+
+        MoneyFunction::FeeV1 => {
+            let metadata = money_fee_get_metadata_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&metadata)?)
+        }
+
+        MoneyFunction::StakeV1 => {
+            let metadata = money_stake_get_metadata_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&metadata)?)
+        }
+
+        MoneyFunction::UnstakeV1 => {
+            let metadata = money_unstake_get_metadata_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&metadata)?)
+        }
+
+        MoneyFunction::DeriveLeadCoinV1 => {
+            let metadata = money_derive_lead_coin_get_metadata_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&metadata)?)
+        }
     }
 }
 
@@ -216,6 +302,27 @@ fn process_instruction(cid: ContractId, ix: &[u8]) -> ContractResult {
             let update_data = money_freeze_process_instruction_v1(cid, call_idx, calls)?;
             Ok(set_return_data(&update_data)?)
         }
+
+        MoneyFunction::FeeV1 => {
+            let update_data = money_fee_process_instruction_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&update_data)?)
+        }
+
+        MoneyFunction::StakeV1 => {
+            let update_data = money_stake_process_instruction_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&update_data)?)
+        }
+
+        MoneyFunction::UnstakeV1 => {
+            let update_data = money_unstake_process_instruction_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&update_data)?)
+        }
+
+        MoneyFunction::DeriveLeadCoinV1 => {
+            let update_data =
+                money_derive_lead_coin_process_instruction_v1(cid, call_idx, calls)?;
+            Ok(set_return_data(&update_data)?)
+        }
     }
 }
 
@@ -246,5 +353,25 @@ fn process_update(cid: ContractId, update_data: &[u8]) -> ContractResult {
             let update: MoneyFreezeUpdateV1 = deserialize(&update_data[1..])?;
             Ok(money_freeze_process_update_v1(cid, update)?)
         }
+
+        MoneyFunction::FeeV1 => {
+            let update: MoneyFeeUpdateV1 = deserialize(&update_data[1..])?;
+            Ok(money_fee_process_update_v1(cid, update)?)
+        }
+
+        MoneyFunction::StakeV1 => {
+            let update: MoneyStakeUpdateV1 = deserialize(&update_data[1..])?;
+            Ok(money_stake_process_update_v1(cid, update)?)
+        }
+
+        MoneyFunction::UnstakeV1 => {
+            let update: MoneyUnstakeUpdateV1 = deserialize(&update_data[1..])?;
+            Ok(money_unstake_process_update_v1(cid, update)?)
+        }
+
+        MoneyFunction::DeriveLeadCoinV1 => {
+            let update: MoneyDeriveLeadCoinUpdateV1 = deserialize(&update_data[1..])?;
+            Ok(money_derive_lead_coin_process_update_v1(cid, update)?)
+        }
     }
 }
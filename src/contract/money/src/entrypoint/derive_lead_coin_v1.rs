@@ -0,0 +1,128 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::{
+    crypto::{ContractId, MerkleNode},
+    db::{db_contains_key, db_get, db_lookup, db_set},
+    error::{ContractError, ContractResult},
+    merkle_add, msg, ContractCall,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
+
+use crate::{
+    error::MoneyError,
+    model::{DeriveLeadCoinParamsV1, MoneyDeriveLeadCoinUpdateV1},
+    MoneyFunction, MONEY_CONTRACT_INFO_TREE, MONEY_CONTRACT_LEAD_COINS_TREE,
+    MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE,
+    MONEY_CONTRACT_LEAD_NULLIFIERS_TREE,
+};
+
+/// `get_metadata` function for `Money::DeriveLeadCoinV1`
+pub(crate) fn money_derive_lead_coin_get_metadata_v1(
+    _cid: ContractId,
+    _call_idx: u32,
+    _calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    // Consensus executes this call directly, once it verified the leader
+    // proof of a finalized block. Transaction calls always get their metadata
+    // verified, so failing here keeps transactions from minting lead coins.
+    msg!("[DeriveLeadCoinV1] Error: Lead coins can only be derived by consensus");
+    Err(MoneyError::DeriveLeadCoinInTransaction.into())
+}
+
+/// `process_instruction` function for `Money::DeriveLeadCoinV1`
+pub(crate) fn money_derive_lead_coin_process_instruction_v1(
+    cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: DeriveLeadCoinParamsV1 = deserialize(&self_.data[1..])?;
+
+    // Access the necessary databases where there is information to
+    // validate this state transition.
+    let info_db = db_lookup(cid, MONEY_CONTRACT_INFO_TREE)?;
+    let lead_coins_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COINS_TREE)?;
+    let lead_coin_roots_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+    let lead_nullifiers_db = db_lookup(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE)?;
+
+    if !db_contains_key(lead_coin_roots_db, &serialize(&params.merkle_root))? {
+        msg!("[DeriveLeadCoinV1] Error: Lead coin Merkle root not found in previous state");
+        return Err(MoneyError::DeriveLeadCoinMerkleRootNotFound.into())
+    }
+
+    // A lead coin can only win a single slot
+    if db_contains_key(lead_nullifiers_db, &serialize(&params.nullifier))? {
+        msg!("[DeriveLeadCoinV1] Error: Duplicate lead coin nullifier found");
+        return Err(MoneyError::DuplicateNullifier.into())
+    }
+
+    if db_contains_key(lead_coins_db, &serialize(&params.lead_coin))? {
+        msg!("[DeriveLeadCoinV1] Error: Duplicate lead coin found");
+        return Err(MoneyError::DuplicateLeadCoin.into())
+    }
+
+    // The serialized Merkle tree is prefixed with its leaf count
+    let Some(tree_data) = db_get(info_db, &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE))?
+    else {
+        msg!("[DeriveLeadCoinV1] Error: Missing lead coin Merkle tree from info db");
+        return Err(ContractError::Internal)
+    };
+    let leaf_count: u32 = deserialize(&tree_data[..4])?;
+
+    // At this point the state transition has passed, so we create a state update
+    let update = MoneyDeriveLeadCoinUpdateV1 {
+        nullifier: params.nullifier,
+        lead_coin: params.lead_coin,
+        position: leaf_count,
+    };
+    let mut update_data = vec![];
+    update_data.write_u8(MoneyFunction::DeriveLeadCoinV1 as u8)?;
+    update.encode(&mut update_data)?;
+    // and return it
+    Ok(update_data)
+}
+
+/// `process_update` function for `Money::DeriveLeadCoinV1`
+pub(crate) fn money_derive_lead_coin_process_update_v1(
+    cid: ContractId,
+    update: MoneyDeriveLeadCoinUpdateV1,
+) -> ContractResult {
+    // Grab all necessary db handles for where we want to write
+    let info_db = db_lookup(cid, MONEY_CONTRACT_INFO_TREE)?;
+    let lead_coins_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COINS_TREE)?;
+    let lead_coin_roots_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+    let lead_nullifiers_db = db_lookup(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE)?;
+
+    msg!("[DeriveLeadCoinV1] Adding new lead coin nullifier to the set");
+    db_set(lead_nullifiers_db, &serialize(&update.nullifier), &[])?;
+
+    msg!("[DeriveLeadCoinV1] Adding new lead coin to the set");
+    db_set(lead_coins_db, &serialize(&update.lead_coin), &serialize(&update.position))?;
+
+    msg!("[DeriveLeadCoinV1] Adding new lead coin to the Merkle tree");
+    let lead_coins = vec![MerkleNode::from(update.lead_coin)];
+    merkle_add(
+        info_db,
+        lead_coin_roots_db,
+        &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE),
+        &lead_coins,
+    )?;
+
+    Ok(())
+}
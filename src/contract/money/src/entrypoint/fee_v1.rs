@@ -0,0 +1,152 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_sdk::{
+    crypto::{pasta_prelude::*, ContractId, PublicKey, DARK_TOKEN_ID},
+    db::{db_contains_key, db_lookup},
+    emit_event,
+    error::{ContractError, ContractResult},
+    event::FEE_EVENT_TOPIC,
+    msg,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
+
+use super::transfer_v1::{money_transfer_apply_update, sibling_nullifiers};
+use crate::{
+    error::MoneyError,
+    model::{MoneyFeeParamsV1, MoneyFeeUpdateV1, MoneyTransferUpdateV1},
+    MoneyFunction, MONEY_CONTRACT_COINS_TREE, MONEY_CONTRACT_COIN_ROOTS_TREE,
+    MONEY_CONTRACT_NULLIFIERS_TREE, MONEY_CONTRACT_ZKAS_FEE_NS_V1,
+};
+
+/// `get_metadata` function for `Money::FeeV1`
+pub(crate) fn money_fee_get_metadata_v1(
+    _cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyFeeParamsV1 = deserialize(&self_.data[1..])?;
+
+    let (sig_x, sig_y) = params.signature_public.xy();
+    let fee_coords = params.fee_commit.to_affine().coordinates().unwrap();
+
+    // It is very important that these are in the same order as the
+    // `constrain_instance` calls in the zkas code.
+    // Otherwise verification will fail.
+    let zk_public_inputs: Vec<(String, Vec<pallas::Base>)> = vec![(
+        MONEY_CONTRACT_ZKAS_FEE_NS_V1.to_string(),
+        vec![
+            params.nullifier.inner(),
+            params.merkle_root.inner(),
+            *fee_coords.x(),
+            *fee_coords.y(),
+            pallas::Base::from(params.min_fee),
+            DARK_TOKEN_ID.inner(),
+            params.coin.inner(),
+            sig_x,
+            sig_y,
+        ],
+    )];
+    let signature_pubkeys: Vec<PublicKey> = vec![params.signature_public];
+
+    // Serialize everything gathered and return it
+    let mut metadata = vec![];
+    zk_public_inputs.encode(&mut metadata)?;
+    signature_pubkeys.encode(&mut metadata)?;
+
+    Ok(metadata)
+}
+
+/// `process_instruction` function for `Money::FeeV1`
+pub(crate) fn money_fee_process_instruction_v1(
+    cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyFeeParamsV1 = deserialize(&self_.data[1..])?;
+
+    // The fee always comes first, so the block producer can find it
+    if call_idx != 0 {
+        msg!("[FeeV1] Error: Fee call is not the first call");
+        return Err(MoneyError::FeeCallNotFirst.into())
+    }
+
+    // Access the necessary databases where there is information to
+    // validate this state transition.
+    let coins_db = db_lookup(cid, MONEY_CONTRACT_COINS_TREE)?;
+    let nullifiers_db = db_lookup(cid, MONEY_CONTRACT_NULLIFIERS_TREE)?;
+    let coin_roots_db = db_lookup(cid, MONEY_CONTRACT_COIN_ROOTS_TREE)?;
+
+    // The Merkle root is used to know whether this is a coin that
+    // existed in a previous state.
+    if !db_contains_key(coin_roots_db, &serialize(&params.merkle_root))? {
+        msg!("[FeeV1] Error: Merkle root not found in previous state");
+        return Err(MoneyError::FeeMerkleRootNotFound.into())
+    }
+
+    // The nullifier should not already exist, nor be revealed by another call
+    // of this transaction. It is the double-spend protection.
+    let (sibling_nullifiers, _) = sibling_nullifiers(cid, call_idx, &calls)?;
+    if sibling_nullifiers.contains(&params.nullifier) ||
+        db_contains_key(nullifiers_db, &serialize(&params.nullifier))?
+    {
+        msg!("[FeeV1] Error: Duplicate nullifier found");
+        return Err(MoneyError::DuplicateNullifier.into())
+    }
+
+    if db_contains_key(coins_db, &serialize(&params.coin))? {
+        msg!("[FeeV1] Error: Duplicate coin found");
+        return Err(MoneyError::DuplicateCoin.into())
+    }
+
+    // The fee value itself is burned, so the state update is the one of
+    // a transfer spending the coin and minting the change, along with the
+    // proven minimum to report.
+    let update = MoneyFeeUpdateV1 {
+        nullifier: params.nullifier,
+        coin: params.coin,
+        min_fee: params.min_fee,
+    };
+    let mut update_data = vec![];
+    update_data.write_u8(MoneyFunction::FeeV1 as u8)?;
+    update.encode(&mut update_data)?;
+    Ok(update_data)
+}
+
+/// `process_update` function for `Money::FeeV1`
+pub(crate) fn money_fee_process_update_v1(
+    cid: ContractId,
+    update: MoneyFeeUpdateV1,
+) -> ContractResult {
+    // In here we can use the same function as we use in `TransferV1`.
+    let transfer_update =
+        MoneyTransferUpdateV1 { nullifiers: vec![update.nullifier], coins: vec![update.coin] };
+    money_transfer_apply_update(cid, MoneyFunction::FeeV1, transfer_update)?;
+
+    // Report the proven minimum, so nodes can enforce the chain minimum fee
+    // and order transactions by it without decoding the call.
+    emit_event(
+        &[vec![MoneyFunction::FeeV1 as u8], FEE_EVENT_TOPIC.to_vec()],
+        &serialize(&update.min_fee),
+    )?;
+    Ok(())
+}
@@ -19,6 +19,7 @@
 use darkfi_sdk::{
     crypto::{poseidon_hash, ContractId, PublicKey, TokenId},
     db::{db_contains_key, db_lookup, db_set},
+    emit_event,
     error::{ContractError, ContractResult},
     msg,
     pasta::pallas,
@@ -102,6 +103,7 @@ pub(crate) fn money_freeze_process_update_v1(
 
     msg!("[MintV1] Freezing mint for token {}", token_id);
     db_set(token_freeze_db, &serialize(&token_id), &[])?;
+    emit_event(&[vec![MoneyFunction::FreezeV1 as u8], serialize(&token_id)], &[])?;
 
     Ok(())
 }
@@ -0,0 +1,209 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use darkfi_sdk::{
+    crypto::{
+        pasta_prelude::*, pedersen_commitment_base, ContractId, MerkleNode, PublicKey,
+        DARK_TOKEN_ID,
+    },
+    db::{db_contains_key, db_get, db_lookup, db_set},
+    error::{ContractError, ContractResult},
+    merkle_add, msg,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
+
+use super::transfer_v1::{emit_nullifier_event, sibling_nullifiers};
+use crate::{
+    error::MoneyError,
+    model::{MoneyStakeParamsV1, MoneyStakeUpdateV1},
+    MoneyFunction, MONEY_CONTRACT_COIN_ROOTS_TREE, MONEY_CONTRACT_INFO_TREE,
+    MONEY_CONTRACT_LEAD_COINS_TREE, MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE,
+    MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE, MONEY_CONTRACT_NULLIFIERS_TREE,
+    MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1,
+};
+
+/// `get_metadata` function for `Money::StakeV1`
+pub(crate) fn money_stake_get_metadata_v1(
+    _cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyStakeParamsV1 = deserialize(&self_.data[1..])?;
+    let input = &params.input;
+    let output = &params.output;
+
+    let input_value_coords = input.value_commit.to_affine().coordinates().unwrap();
+    let input_token_coords = input.token_commit.to_affine().coordinates().unwrap();
+    let output_value_coords = output.value_commit.to_affine().coordinates().unwrap();
+    let (sig_x, sig_y) = input.signature_public.xy();
+
+    // It is very important that these are in the same order as the
+    // `constrain_instance` calls in the zkas code.
+    // Otherwise verification will fail.
+    let zk_public_inputs: Vec<(String, Vec<pallas::Base>)> = vec![
+        (
+            MONEY_CONTRACT_ZKAS_BURN_NS_V1.to_string(),
+            vec![
+                input.nullifier.inner(),
+                *input_value_coords.x(),
+                *input_value_coords.y(),
+                *input_token_coords.x(),
+                *input_token_coords.y(),
+                input.merkle_root.inner(),
+                input.user_data_enc,
+                sig_x,
+                sig_y,
+            ],
+        ),
+        (
+            MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1.to_string(),
+            vec![
+                *output_value_coords.x(),
+                *output_value_coords.y(),
+                output.public_key,
+                output.commitment,
+            ],
+        ),
+    ];
+    let signature_pubkeys: Vec<PublicKey> = vec![input.signature_public];
+
+    // Serialize everything gathered and return it
+    let mut metadata = vec![];
+    zk_public_inputs.encode(&mut metadata)?;
+    signature_pubkeys.encode(&mut metadata)?;
+
+    Ok(metadata)
+}
+
+/// `process_instruction` function for `Money::StakeV1`
+pub(crate) fn money_stake_process_instruction_v1(
+    cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyStakeParamsV1 = deserialize(&self_.data[1..])?;
+    let input = &params.input;
+    let output = &params.output;
+
+    // Access the necessary databases where there is information to
+    // validate this state transition.
+    let info_db = db_lookup(cid, MONEY_CONTRACT_INFO_TREE)?;
+    let nullifiers_db = db_lookup(cid, MONEY_CONTRACT_NULLIFIERS_TREE)?;
+    let coin_roots_db = db_lookup(cid, MONEY_CONTRACT_COIN_ROOTS_TREE)?;
+    let lead_coins_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COINS_TREE)?;
+
+    // The Merkle root is used to know whether this is a coin that
+    // existed in a previous state.
+    if !db_contains_key(coin_roots_db, &serialize(&input.merkle_root))? {
+        msg!("[StakeV1] Error: Merkle root not found in previous state");
+        return Err(MoneyError::TransferMerkleRootNotFound.into())
+    }
+
+    // The nullifier should not already exist, nor be revealed by another call
+    // of this transaction. It is the double-spend protection.
+    let (sibling_nullifiers, _) = sibling_nullifiers(cid, call_idx, &calls)?;
+    if sibling_nullifiers.contains(&input.nullifier) ||
+        db_contains_key(nullifiers_db, &serialize(&input.nullifier))?
+    {
+        msg!("[StakeV1] Error: Duplicate nullifier found");
+        return Err(MoneyError::DuplicateNullifier.into())
+    }
+
+    // Staked coins can not be bound to other contracts
+    if input.spend_hook != pallas::Base::zero() {
+        msg!("[StakeV1] Error: Input spend hook is nonzero");
+        return Err(MoneyError::SpendHookNonZero.into())
+    }
+
+    // Only the native token can be staked
+    if input.token_commit != pedersen_commitment_base(DARK_TOKEN_ID.inner(), params.token_blind) {
+        msg!("[StakeV1] Error: Input used non-native token");
+        return Err(MoneyError::StakeNonNativeToken.into())
+    }
+
+    // The lead coin must carry the exact staked value
+    if input.value_commit != output.value_commit {
+        msg!("[StakeV1] Error: Value commitments do not match");
+        return Err(MoneyError::ValueMismatch.into())
+    }
+
+    if db_contains_key(lead_coins_db, &serialize(&output.commitment))? {
+        msg!("[StakeV1] Error: Duplicate lead coin found");
+        return Err(MoneyError::DuplicateLeadCoin.into())
+    }
+
+    // The serialized Merkle tree is prefixed with its leaf count. Lead coins
+    // of earlier stake calls in this transaction get appended before this one.
+    let Some(tree_data) = db_get(info_db, &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE))?
+    else {
+        msg!("[StakeV1] Error: Missing lead coin Merkle tree from info db");
+        return Err(ContractError::Internal)
+    };
+    let leaf_count: u32 = deserialize(&tree_data[..4])?;
+    let preceding = calls[..call_idx as usize]
+        .iter()
+        .filter(|x| x.contract_id == cid && x.data[0] == MoneyFunction::StakeV1 as u8)
+        .count() as u32;
+
+    // At this point the state transition has passed, so we create a state update
+    let update = MoneyStakeUpdateV1 {
+        nullifier: input.nullifier,
+        lead_coin: output.commitment,
+        position: leaf_count + preceding,
+    };
+    let mut update_data = vec![];
+    update_data.write_u8(MoneyFunction::StakeV1 as u8)?;
+    update.encode(&mut update_data)?;
+    // and return it
+    Ok(update_data)
+}
+
+/// `process_update` function for `Money::StakeV1`
+pub(crate) fn money_stake_process_update_v1(
+    cid: ContractId,
+    update: MoneyStakeUpdateV1,
+) -> ContractResult {
+    // Grab all necessary db handles for where we want to write
+    let info_db = db_lookup(cid, MONEY_CONTRACT_INFO_TREE)?;
+    let nullifiers_db = db_lookup(cid, MONEY_CONTRACT_NULLIFIERS_TREE)?;
+    let lead_coins_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COINS_TREE)?;
+    let lead_coin_roots_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+
+    msg!("[StakeV1] Adding new nullifier to the set");
+    db_set(nullifiers_db, &serialize(&update.nullifier), &[])?;
+    emit_nullifier_event(MoneyFunction::StakeV1, &update.nullifier)?;
+
+    // The leaf position is kept, so consensus can rebuild the Merkle tree
+    // from the set of lead coins.
+    msg!("[StakeV1] Adding new lead coin to the set");
+    db_set(lead_coins_db, &serialize(&update.lead_coin), &serialize(&update.position))?;
+
+    msg!("[StakeV1] Adding new lead coin to the Merkle tree");
+    let lead_coins = vec![MerkleNode::from(update.lead_coin)];
+    merkle_add(
+        info_db,
+        lead_coin_roots_db,
+        &serialize(&MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE),
+        &lead_coins,
+    )?;
+
+    Ok(())
+}
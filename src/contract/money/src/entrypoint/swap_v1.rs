@@ -26,7 +26,9 @@ use darkfi_sdk::{
 };
 use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
 
-use super::transfer_v1::{money_transfer_get_metadata_v1, money_transfer_process_update_v1};
+use super::transfer_v1::{
+    money_transfer_apply_update, money_transfer_get_metadata_v1, sibling_nullifiers,
+};
 use crate::{
     error::MoneyError,
     model::{MoneyTransferParamsV1, MoneyTransferUpdateV1},
@@ -80,6 +82,9 @@ pub(crate) fn money_otcswap_process_instruction_v1(
     let nullifiers_db = db_lookup(cid, MONEY_CONTRACT_NULLIFIERS_TREE)?;
     let coin_roots_db = db_lookup(cid, MONEY_CONTRACT_COIN_ROOTS_TREE)?;
 
+    // Nullifiers revealed by the other calls of this transaction
+    let (sibling_nullifiers, _) = sibling_nullifiers(cid, call_idx, &calls)?;
+
     // We expect two new nullifiers and two new coins
     let mut new_nullifiers = Vec::with_capacity(2);
     let mut new_coins = Vec::with_capacity(2);
@@ -126,6 +131,7 @@ pub(crate) fn money_otcswap_process_instruction_v1(
 
         // The nullifiers should not already exist. It is the double-spend protection.
         if new_nullifiers.contains(&input.nullifier) ||
+            sibling_nullifiers.contains(&input.nullifier) ||
             db_contains_key(nullifiers_db, &serialize(&input.nullifier))?
         {
             msg!("[OtcSwapV1] Error: Duplicate nullifier found in input {}", i);
@@ -167,5 +173,5 @@ pub(crate) fn money_otcswap_process_update_v1(
     update: MoneyTransferUpdateV1,
 ) -> ContractResult {
     // In here we can use the same function as we use in `TransferV1`.
-    Ok(money_transfer_process_update_v1(cid, update)?)
+    Ok(money_transfer_apply_update(cid, MoneyFunction::OtcSwapV1, update)?)
 }
@@ -19,10 +19,12 @@
 use darkfi_sdk::{
     crypto::{
         pasta_prelude::*, pedersen_commitment_base, pedersen_commitment_u64, Coin, ContractId,
-        MerkleNode, PublicKey, DARK_TOKEN_ID,
+        MerkleNode, Nullifier, PublicKey, DARK_TOKEN_ID,
     },
     db::{db_contains_key, db_get, db_lookup, db_set},
+    emit_event,
     error::{ContractError, ContractResult},
+    event::NULLIFIER_EVENT_TOPIC,
     merkle_add, msg,
     pasta::pallas,
     ContractCall,
@@ -31,7 +33,10 @@ use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
 
 use crate::{
     error::MoneyError,
-    model::{MoneyTransferParamsV1, MoneyTransferUpdateV1},
+    model::{
+        MoneyFeeParamsV1, MoneyStakeParamsV1, MoneyTransferParamsV1, MoneyTransferUpdateV1,
+        MoneyUnstakeParamsV1,
+    },
     MoneyFunction, MONEY_CONTRACT_COINS_TREE, MONEY_CONTRACT_COIN_MERKLE_TREE,
     MONEY_CONTRACT_COIN_ROOTS_TREE, MONEY_CONTRACT_FAUCET_PUBKEYS, MONEY_CONTRACT_INFO_TREE,
     MONEY_CONTRACT_NULLIFIERS_TREE, MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_MINT_NS_V1,
@@ -172,6 +177,9 @@ pub(crate) fn money_transfer_process_instruction_v1(
         valcom_total += pedersen_commitment_u64(input.value, input.value_blind);
     }
 
+    // Nullifiers revealed by the other calls of this transaction
+    let (sibling_nullifiers, _) = sibling_nullifiers(cid, call_idx, &calls)?;
+
     // For anonymous inputs, we must also gather all the new nullifiers
     // that are introduced.
     let mut new_nullifiers = Vec::with_capacity(params.inputs.len());
@@ -186,6 +194,7 @@ pub(crate) fn money_transfer_process_instruction_v1(
 
         // The nullifiers should not already exist. It is the double-spend protection.
         if new_nullifiers.contains(&input.nullifier) ||
+            sibling_nullifiers.contains(&input.nullifier) ||
             db_contains_key(nullifiers_db, &serialize(&input.nullifier))?
         {
             msg!("[TransferV1] Error: Duplicate nullifier found (input {})", i);
@@ -266,6 +275,16 @@ pub(crate) fn money_transfer_process_instruction_v1(
 pub(crate) fn money_transfer_process_update_v1(
     cid: ContractId,
     update: MoneyTransferUpdateV1,
+) -> ContractResult {
+    money_transfer_apply_update(cid, MoneyFunction::TransferV1, update)
+}
+
+/// Apply a state update spending and minting coins like `Money::TransferV1`
+/// does, reporting the spent nullifiers as events of the given function.
+pub(crate) fn money_transfer_apply_update(
+    cid: ContractId,
+    function: MoneyFunction,
+    update: MoneyTransferUpdateV1,
 ) -> ContractResult {
     // Grab all necessary db handles for where we want to write
     let info_db = db_lookup(cid, MONEY_CONTRACT_INFO_TREE)?;
@@ -276,6 +295,7 @@ pub(crate) fn money_transfer_process_update_v1(
     msg!("[TransferV1] Adding new nullifiers to the set");
     for nullifier in update.nullifiers {
         db_set(nullifiers_db, &serialize(&nullifier), &[])?;
+        emit_nullifier_event(function, &nullifier)?;
     }
 
     msg!("[TransferV1] Adding new coins to the set");
@@ -289,3 +309,54 @@ pub(crate) fn money_transfer_process_update_v1(
 
     Ok(())
 }
+
+/// Report a nullifier added to the set by given function, so nodes can
+/// index the transactions revealing nullifiers without decoding them.
+pub(crate) fn emit_nullifier_event(
+    function: MoneyFunction,
+    nullifier: &Nullifier,
+) -> ContractResult {
+    emit_event(&[vec![function as u8], NULLIFIER_EVENT_TOPIC.to_vec(), serialize(nullifier)], &[])?;
+    Ok(())
+}
+
+/// Collect the coin nullifiers and lead coin nullifiers revealed by the other
+/// Money calls of the transaction. All calls get executed before any of their
+/// state updates is applied, so the nullifier sets alone can't catch a coin
+/// spent twice within a single transaction.
+pub(crate) fn sibling_nullifiers(
+    cid: ContractId,
+    call_idx: u32,
+    calls: &[ContractCall],
+) -> Result<(Vec<Nullifier>, Vec<pallas::Base>), ContractError> {
+    let mut nullifiers = vec![];
+    let mut lead_nullifiers = vec![];
+
+    for (i, call) in calls.iter().enumerate() {
+        if i == call_idx as usize || call.contract_id != cid {
+            continue
+        }
+
+        match MoneyFunction::try_from(call.data[0])? {
+            MoneyFunction::TransferV1 | MoneyFunction::OtcSwapV1 => {
+                let params: MoneyTransferParamsV1 = deserialize(&call.data[1..])?;
+                nullifiers.extend(params.inputs.iter().map(|x| x.nullifier));
+            }
+            MoneyFunction::FeeV1 => {
+                let params: MoneyFeeParamsV1 = deserialize(&call.data[1..])?;
+                nullifiers.push(params.nullifier);
+            }
+            MoneyFunction::StakeV1 => {
+                let params: MoneyStakeParamsV1 = deserialize(&call.data[1..])?;
+                nullifiers.push(params.input.nullifier);
+            }
+            MoneyFunction::UnstakeV1 => {
+                let params: MoneyUnstakeParamsV1 = deserialize(&call.data[1..])?;
+                lead_nullifiers.push(params.input.nullifier);
+            }
+            MoneyFunction::MintV1 | MoneyFunction::FreezeV1 | MoneyFunction::DeriveLeadCoinV1 => {}
+        }
+    }
+
+    Ok((nullifiers, lead_nullifiers))
+}
@@ -0,0 +1,163 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+use darkfi_sdk::{
+    crypto::{pasta_prelude::*, pedersen_commitment_base, ContractId, PublicKey, DARK_TOKEN_ID},
+    db::{db_contains_key, db_lookup, db_set},
+    error::{ContractError, ContractResult},
+    msg,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::{deserialize, serialize, Encodable, WriteExt};
+
+use super::{mint_v1::money_mint_process_update_v1, transfer_v1::sibling_nullifiers};
+use crate::{
+    error::MoneyError,
+    model::{MoneyMintUpdateV1, MoneyUnstakeParamsV1, MoneyUnstakeUpdateV1},
+    MoneyFunction, MONEY_CONTRACT_COINS_TREE, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE,
+    MONEY_CONTRACT_LEAD_NULLIFIERS_TREE, MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1,
+    MONEY_CONTRACT_ZKAS_MINT_NS_V1,
+};
+
+/// `get_metadata` function for `Money::UnstakeV1`
+pub(crate) fn money_unstake_get_metadata_v1(
+    _cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyUnstakeParamsV1 = deserialize(&self_.data[1..])?;
+    let input = &params.input;
+    let output = &params.output;
+
+    let input_value_coords = input.value_commit.to_affine().coordinates().unwrap();
+    let output_value_coords = output.value_commit.to_affine().coordinates().unwrap();
+    let output_token_coords = output.token_commit.to_affine().coordinates().unwrap();
+
+    // It is very important that these are in the same order as the
+    // `constrain_instance` calls in the zkas code.
+    // Otherwise verification will fail.
+    let zk_public_inputs: Vec<(String, Vec<pallas::Base>)> = vec![
+        (
+            MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1.to_string(),
+            vec![
+                *input_value_coords.x(),
+                *input_value_coords.y(),
+                input.public_key,
+                input.commitment,
+                input.merkle_root.inner(),
+                input.sk_root.inner(),
+                input.nullifier,
+            ],
+        ),
+        (
+            MONEY_CONTRACT_ZKAS_MINT_NS_V1.to_string(),
+            vec![
+                output.coin.inner(),
+                *output_value_coords.x(),
+                *output_value_coords.y(),
+                *output_token_coords.x(),
+                *output_token_coords.y(),
+            ],
+        ),
+    ];
+    // Knowing the lead coin opening is what authorizes the unstake,
+    // so there are no signatures to verify.
+    let signature_pubkeys: Vec<PublicKey> = vec![];
+
+    // Serialize everything gathered and return it
+    let mut metadata = vec![];
+    zk_public_inputs.encode(&mut metadata)?;
+    signature_pubkeys.encode(&mut metadata)?;
+
+    Ok(metadata)
+}
+
+/// `process_instruction` function for `Money::UnstakeV1`
+pub(crate) fn money_unstake_process_instruction_v1(
+    cid: ContractId,
+    call_idx: u32,
+    calls: Vec<ContractCall>,
+) -> Result<Vec<u8>, ContractError> {
+    let self_ = &calls[call_idx as usize];
+    let params: MoneyUnstakeParamsV1 = deserialize(&self_.data[1..])?;
+    let input = &params.input;
+    let output = &params.output;
+
+    // Access the necessary databases where there is information to
+    // validate this state transition.
+    let coins_db = db_lookup(cid, MONEY_CONTRACT_COINS_TREE)?;
+    let lead_coin_roots_db = db_lookup(cid, MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE)?;
+    let lead_nullifiers_db = db_lookup(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE)?;
+
+    // The Merkle root is used to know whether this is a lead coin that
+    // existed in a previous state.
+    if !db_contains_key(lead_coin_roots_db, &serialize(&input.merkle_root))? {
+        msg!("[UnstakeV1] Error: Lead coin Merkle root not found in previous state");
+        return Err(MoneyError::UnstakeMerkleRootNotFound.into())
+    }
+
+    // The nullifier should not already exist, nor be revealed by another call
+    // of this transaction. It is the double-spend protection.
+    let (_, sibling_lead_nullifiers) = sibling_nullifiers(cid, call_idx, &calls)?;
+    if sibling_lead_nullifiers.contains(&input.nullifier) ||
+        db_contains_key(lead_nullifiers_db, &serialize(&input.nullifier))?
+    {
+        msg!("[UnstakeV1] Error: Duplicate lead coin nullifier found");
+        return Err(MoneyError::DuplicateNullifier.into())
+    }
+
+    if db_contains_key(coins_db, &serialize(&output.coin))? {
+        msg!("[UnstakeV1] Error: Duplicate coin found in output");
+        return Err(MoneyError::DuplicateCoin.into())
+    }
+
+    // Unstaked value always returns as the native token
+    if output.token_commit != pedersen_commitment_base(DARK_TOKEN_ID.inner(), params.token_blind) {
+        msg!("[UnstakeV1] Error: Output used non-native token");
+        return Err(MoneyError::StakeNonNativeToken.into())
+    }
+
+    // The minted coin must carry the exact unstaked value
+    if input.value_commit != output.value_commit {
+        msg!("[UnstakeV1] Error: Value commitments do not match");
+        return Err(MoneyError::ValueMismatch.into())
+    }
+
+    // At this point the state transition has passed, so we create a state update
+    let update = MoneyUnstakeUpdateV1 { nullifier: input.nullifier, coin: output.coin };
+    let mut update_data = vec![];
+    update_data.write_u8(MoneyFunction::UnstakeV1 as u8)?;
+    update.encode(&mut update_data)?;
+    // and return it
+    Ok(update_data)
+}
+
+/// `process_update` function for `Money::UnstakeV1`
+pub(crate) fn money_unstake_process_update_v1(
+    cid: ContractId,
+    update: MoneyUnstakeUpdateV1,
+) -> ContractResult {
+    let lead_nullifiers_db = db_lookup(cid, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE)?;
+
+    msg!("[UnstakeV1] Adding new lead coin nullifier to the set");
+    db_set(lead_nullifiers_db, &serialize(&update.nullifier), &[])?;
+
+    // Adding the minted coin is the same as in `MintV1`.
+    Ok(money_mint_process_update_v1(cid, MoneyMintUpdateV1 { coin: update.coin })?)
+}
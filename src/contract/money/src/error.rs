@@ -73,6 +73,27 @@ pub enum MoneyError {
 
     #[error("Token mint is frozen")]
     MintFrozen,
+
+    #[error("Fee call is not the first call of the transaction")]
+    FeeCallNotFirst,
+
+    #[error("Merkle root not found in previous state")]
+    FeeMerkleRootNotFound,
+
+    #[error("Staked coin is not of the native token")]
+    StakeNonNativeToken,
+
+    #[error("Lead coin Merkle root not found in previous state")]
+    UnstakeMerkleRootNotFound,
+
+    #[error("Duplicate lead coin found")]
+    DuplicateLeadCoin,
+
+    #[error("Lead coin Merkle root not found in previous state")]
+    DeriveLeadCoinMerkleRootNotFound,
+
+    #[error("Lead coins can only be derived by consensus")]
+    DeriveLeadCoinInTransaction,
 }
 
 impl From<MoneyError> for ContractError {
@@ -96,6 +117,13 @@ impl From<MoneyError> for ContractError {
             MoneyError::SwapMerkleRootNotFound => Self::Custom(16),
             MoneyError::TokenIdDoesNotDeriveFromMint => Self::Custom(17),
             MoneyError::MintFrozen => Self::Custom(18),
+            MoneyError::FeeCallNotFirst => Self::Custom(19),
+            MoneyError::FeeMerkleRootNotFound => Self::Custom(20),
+            MoneyError::StakeNonNativeToken => Self::Custom(21),
+            MoneyError::UnstakeMerkleRootNotFound => Self::Custom(22),
+            MoneyError::DuplicateLeadCoin => Self::Custom(23),
+            MoneyError::DeriveLeadCoinMerkleRootNotFound => Self::Custom(24),
+            MoneyError::DeriveLeadCoinInTransaction => Self::Custom(25),
         }
     }
 }
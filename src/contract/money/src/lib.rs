@@ -19,7 +19,7 @@
 //! Smart contract implementing money transfers, atomic swaps, token
 //! minting and freezing, and staking/unstaking of consensus tokens.
 
-use darkfi_sdk::error::ContractError;
+use darkfi_sdk::{error::ContractError, lead::MONEY_DERIVE_LEAD_COIN_V1};
 
 /// Functions available in the contract
 #[repr(u8)]
@@ -28,9 +28,11 @@ pub enum MoneyFunction {
     OtcSwapV1 = 0x01,
     MintV1 = 0x02,
     FreezeV1 = 0x03,
-    //Fee = 0x04,
-    //Stake = 0x05,
-    //Unstake = 0x06,
+    FeeV1 = 0x04,
+    StakeV1 = 0x05,
+    UnstakeV1 = 0x06,
+    /// Only executed and applied by consensus, for block leaders
+    DeriveLeadCoinV1 = MONEY_DERIVE_LEAD_COIN_V1,
 }
 
 impl TryFrom<u8> for MoneyFunction {
@@ -42,9 +44,10 @@ impl TryFrom<u8> for MoneyFunction {
             0x01 => Ok(Self::OtcSwapV1),
             0x02 => Ok(Self::MintV1),
             0x03 => Ok(Self::FreezeV1),
-            //0x04 => Ok(Self::Fee),
-            //0x05 => Ok(Self::Stake),
-            //0x06 => Ok(Self::Unstake),
+            0x04 => Ok(Self::FeeV1),
+            0x05 => Ok(Self::StakeV1),
+            0x06 => Ok(Self::UnstakeV1),
+            MONEY_DERIVE_LEAD_COIN_V1 => Ok(Self::DeriveLeadCoinV1),
             _ => Err(ContractError::InvalidFunction),
         }
     }
@@ -64,8 +67,12 @@ pub mod entrypoint;
 /// Client API for interaction with this smart contract
 pub mod client;
 
-// These are the different sled trees that will be created
-pub const MONEY_CONTRACT_INFO_TREE: &str = "info";
+// These are the different sled trees that will be created. The ones holding
+// lead coins are shared with consensus.
+pub use darkfi_sdk::lead::{
+    MONEY_CONTRACT_INFO_TREE, MONEY_CONTRACT_LEAD_COINS_TREE, MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE,
+    MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE, MONEY_CONTRACT_LEAD_NULLIFIERS_TREE,
+};
 pub const MONEY_CONTRACT_COINS_TREE: &str = "coins";
 pub const MONEY_CONTRACT_COIN_ROOTS_TREE: &str = "coin_roots";
 pub const MONEY_CONTRACT_NULLIFIERS_TREE: &str = "nullifiers";
@@ -84,3 +91,9 @@ pub const MONEY_CONTRACT_ZKAS_BURN_NS_V1: &str = "Burn_V1";
 pub const MONEY_CONTRACT_ZKAS_TOKEN_MINT_NS_V1: &str = "TokenMint_V1";
 /// zkas token freeze circuit namespace
 pub const MONEY_CONTRACT_ZKAS_TOKEN_FRZ_NS_V1: &str = "TokenFreeze_V1";
+/// zkas fee circuit namespace
+pub const MONEY_CONTRACT_ZKAS_FEE_NS_V1: &str = "Fee_V1";
+/// zkas lead coin mint circuit namespace
+pub const MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1: &str = "Lead_Mint_V1";
+/// zkas lead coin burn circuit namespace
+pub const MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1: &str = "Lead_Burn_V1";
@@ -22,6 +22,8 @@ use darkfi_sdk::{
 };
 use darkfi_serial::{SerialDecodable, SerialEncodable};
 
+pub use darkfi_sdk::lead::DeriveLeadCoinParamsV1;
+
 /// A contract call's clear input
 #[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
 pub struct ClearInput {
@@ -123,3 +125,118 @@ pub struct MoneyFreezeUpdateV1 {
     /// Mint authority public key
     pub signature_public: PublicKey,
 }
+
+/// Parameters for `Money::Fee`. The fee is paid by spending a single
+/// native token coin, and whatever remains goes to a new change coin.
+/// The paid value is hidden, only proven to reach a public minimum.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyFeeParamsV1 {
+    /// Revealed nullifier of the spent coin
+    pub nullifier: Nullifier,
+    /// Revealed Merkle root
+    pub merkle_root: MerkleNode,
+    /// Public key for the signature
+    pub signature_public: PublicKey,
+    /// Pedersen commitment for the paid fee value
+    pub fee_commit: pallas::Point,
+    /// Minimum value the paid fee is proven to reach
+    pub min_fee: u64,
+    /// Minted change coin
+    pub coin: Coin,
+    /// AEAD encrypted note of the change coin
+    pub note: AeadEncryptedNote,
+}
+
+/// State update for `Money::Fee`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyFeeUpdateV1 {
+    /// Revealed nullifier of the spent coin
+    pub nullifier: Nullifier,
+    /// Minted change coin
+    pub coin: Coin,
+    /// Minimum value the paid fee is proven to reach
+    pub min_fee: u64,
+}
+
+/// A lead coin minted by `Money::Stake`, used to compete in consensus
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct StakeOutput {
+    /// Pedersen commitment for the staked value
+    pub value_commit: pallas::Point,
+    /// Lead coin public key, derived from its secret key root and slot
+    pub public_key: pallas::Base,
+    /// Hash of the lead coin commitment, the leaf in the lead coins Merkle tree
+    pub commitment: pallas::Base,
+}
+
+/// Parameters for `Money::Stake`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyStakeParamsV1 {
+    /// Anonymous input of the staked native token coin
+    pub input: Input,
+    /// Blinding factor for the input's token ID, revealed to prove it is
+    /// the native token
+    pub token_blind: pallas::Scalar,
+    /// Minted lead coin
+    pub output: StakeOutput,
+}
+
+/// State update for `Money::Stake`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyStakeUpdateV1 {
+    /// Nullifier of the staked coin
+    pub nullifier: Nullifier,
+    /// Commitment hash of the minted lead coin
+    pub lead_coin: pallas::Base,
+    /// Leaf position of the lead coin in the lead coins Merkle tree
+    pub position: u32,
+}
+
+/// A lead coin burned by `Money::Unstake`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct UnstakeInput {
+    /// Pedersen commitment for the unstaked value
+    pub value_commit: pallas::Point,
+    /// Lead coin public key
+    pub public_key: pallas::Base,
+    /// Hash of the lead coin commitment
+    pub commitment: pallas::Base,
+    /// Revealed lead coins Merkle root
+    pub merkle_root: MerkleNode,
+    /// Revealed Merkle root of the lead coin secret keys
+    pub sk_root: MerkleNode,
+    /// Revealed lead coin nullifier
+    pub nullifier: pallas::Base,
+}
+
+/// Parameters for `Money::Unstake`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyUnstakeParamsV1 {
+    /// Burned lead coin
+    pub input: UnstakeInput,
+    /// Blinding factor for the output's token ID, revealed to prove it is
+    /// the native token
+    pub token_blind: pallas::Scalar,
+    /// Anonymous output of the unstaked native token coin
+    pub output: Output,
+}
+
+/// State update for `Money::Unstake`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyUnstakeUpdateV1 {
+    /// Nullifier of the burned lead coin
+    pub nullifier: pallas::Base,
+    /// The newly minted coin
+    pub coin: Coin,
+}
+
+/// State update for `Money::DeriveLeadCoin`
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct MoneyDeriveLeadCoinUpdateV1 {
+    /// Nullifier of the winning lead coin
+    pub nullifier: pallas::Base,
+    /// Commitment hash of the derived lead coin
+    pub lead_coin: pallas::Base,
+    /// Leaf position of the derived lead coin in the lead coins Merkle tree
+    pub position: u32,
+}
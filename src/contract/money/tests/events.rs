@@ -0,0 +1,83 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Integration test for contract events:
+//!
+//! * Events emitted by a token mint freeze ending up in its receipt
+//! * Receipts of transactions emitting no events
+//! * Receipts returned without applying the transactions
+
+use darkfi::Result;
+use darkfi_sdk::crypto::{poseidon_hash, Keypair, TokenId, MONEY_CONTRACT_ID};
+use darkfi_serial::serialize;
+use log::info;
+use rand::rngs::OsRng;
+
+use darkfi_money_contract::MoneyFunction;
+
+mod harness;
+use harness::{init_logger, MoneyTestHarness};
+
+#[async_std::test]
+async fn money_events() -> Result<()> {
+    init_logger();
+
+    let th = MoneyTestHarness::new().await?;
+
+    let (airdrop_tx, _) = th.airdrop_native(200, th.alice.keypair.public)?;
+
+    // Airdrops don't emit any events, but still get a receipt.
+    info!("[Faucet] Executing Alice airdrop tx");
+    let (erroneous, _, receipts) =
+        th.faucet.state.read().await.verify_transactions_diff(&[airdrop_tx], true).await?;
+    assert!(erroneous.is_empty());
+    assert_eq!(receipts.len(), 1);
+    assert!(receipts[0].gas_used > 0);
+    assert!(receipts[0].events.is_empty());
+
+    let token_authority = Keypair::random(&mut OsRng);
+    let (frz_tx, _) = th.freeze_token(token_authority)?;
+
+    // Nothing gets applied without writing, but receipts are still returned.
+    info!("[Faucet] Executing token freeze without writing");
+    let (erroneous, diff, receipts) =
+        th.faucet.state.read().await.verify_transactions_diff(&[frz_tx.clone()], false).await?;
+    assert!(erroneous.is_empty());
+    assert!(diff.changes.is_empty());
+    assert_eq!(receipts.len(), 1);
+    assert_eq!(receipts[0].events.len(), 1);
+
+    info!("[Faucet] Executing token freeze");
+    let (erroneous, _, receipts) =
+        th.faucet.state.read().await.verify_transactions_diff(&[frz_tx], true).await?;
+    assert!(erroneous.is_empty());
+    assert_eq!(receipts.len(), 1);
+
+    let (mint_x, mint_y) = token_authority.public.xy();
+    let token_id = TokenId::from(poseidon_hash([mint_x, mint_y]));
+
+    let events = &receipts[0].events;
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].contract_id, *MONEY_CONTRACT_ID);
+    assert_eq!(events[0].call_idx, 0);
+    assert_eq!(events[0].topics, vec![vec![MoneyFunction::FreezeV1 as u8], serialize(&token_id)]);
+    assert!(events[0].data.is_empty());
+
+    // Thanks for reading
+    Ok(())
+}
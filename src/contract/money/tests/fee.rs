@@ -0,0 +1,157 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Integration test for `Money::FeeV1`:
+//!
+//! * Paying a hidden fee with a native token coin, down to the exact minimum
+//! * Rejecting transactions without a fee when the chain requires one
+//! * Rejecting fees proven below the chain minimum
+//! * Rejecting double-spent fee coins
+//! * Rejecting fee coins also spent by another call of the transaction
+//! * Reporting the spent coin nullifier and proven minimum in the receipt
+//! * Rejecting fee calls that do not come first in the transaction
+//! * Paying fees of transfers and airdrops the way drk and faucetd do
+
+use darkfi::{
+    blockchain::{proven_fee, revealed_nullifiers},
+    Result,
+};
+use log::info;
+
+use darkfi_money_contract::{client::MoneyNote, MoneyFunction};
+
+mod harness;
+use harness::{build_tx, build_tx_with_fee, init_logger, MoneyTestHarness};
+
+#[async_std::test]
+async fn money_fee() -> Result<()> {
+    init_logger();
+
+    let mut th = MoneyTestHarness::new().await?;
+
+    // Airdrop some native tokens to Alice, so she can pay fees.
+    let owncoin = th.airdrop_native_to_alice(200).await?;
+
+    // From now on, the chains require fees, so airdrops get rejected.
+    th.faucet.state.write().await.consensus.params.min_tx_fee = 5;
+    th.alice.state.write().await.consensus.params.min_tx_fee = 5;
+    let (free_tx, _) = th.airdrop_native(100, th.bob.keypair.public)?;
+    info!("[Faucet] Executing Bob airdrop tx without a fee");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[free_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // A fee only proven to reach less than the chain minimum is rejected,
+    // even if the paid value is enough.
+    let (low_tx, _) = th.pay_fee(&th.alice, owncoin.clone(), 10, 4)?;
+    info!("[Faucet] Executing Alice fee tx proving a low minimum");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[low_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Spending the fee coin again in a transfer of the same transaction
+    // would mint both the fee change and the transfer outputs from it.
+    let (fee_call, _) = th.pay_fee_call(&th.alice, owncoin.clone(), 10, 5)?;
+    let (transfer_call, _) =
+        th.transfer_call(&th.alice, vec![owncoin.clone()], 200, th.bob.keypair.public)?;
+    let double_spend_tx = build_tx(vec![fee_call, transfer_call])?;
+    info!("[Faucet] Executing Alice fee tx spending the fee coin twice");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[double_spend_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Alice pays exactly the chain minimum, getting the rest back as change.
+    // Only the proven minimum gets revealed.
+    let (fee_tx, fee_params) = th.pay_fee(&th.alice, owncoin, 5, 5)?;
+    assert_eq!(fee_params.min_fee, 5);
+
+    info!("[Faucet] Executing Alice fee tx");
+    let (erroneous, _, receipts) =
+        th.faucet.state.read().await.verify_transactions_diff(&[fee_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    // The contract reports the spent coin nullifier, so nodes can index it.
+    assert_eq!(revealed_nullifiers(&receipts[0]), vec![fee_params.nullifier]);
+    assert_eq!(proven_fee(&receipts[0]), Some(5));
+
+    info!("[Alice] Executing Alice fee tx");
+    let erroneous =
+        th.alice.state.read().await.verify_transactions(&[fee_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    let change: MoneyNote = fee_params.note.decrypt(&th.alice.keypair.secret)?;
+    assert_eq!(change.value, 195);
+
+    // The fee coin can not be spent twice.
+    info!("[Faucet] Executing Alice fee tx again");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[fee_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Thanks for reading
+    Ok(())
+}
+
+#[async_std::test]
+async fn money_fee_call_first() -> Result<()> {
+    init_logger();
+
+    let mut th = MoneyTestHarness::new().await?;
+
+    // Alice gets separate coins to pay fees with and to transfer.
+    let fee_coin = th.airdrop_native_to_alice(200).await?;
+    let transfer_coin = th.airdrop_native_to_alice(100).await?;
+    let airdrop_fee_coin = th.airdrop_native_to_alice(200).await?;
+
+    th.faucet.state.write().await.consensus.params.min_tx_fee = 5;
+
+    // A fee call placed after the call it pays for is rejected.
+    let (fee_call, _) = th.pay_fee_call(&th.alice, fee_coin.clone(), 5, 5)?;
+    let (transfer_call, _) =
+        th.transfer_call(&th.alice, vec![transfer_coin.clone()], 100, th.bob.keypair.public)?;
+    let fee_last_tx = build_tx(vec![transfer_call, fee_call])?;
+    info!("[Faucet] Executing Alice transfer tx with the fee call last");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[fee_last_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // drk prepends the fee call to the unsigned transfer, and signs it once
+    // the transfer signatures are created.
+    let fee = th.fee_debris(&th.alice, fee_coin, 5, 5)?;
+    let (transfer_call, _) =
+        th.transfer_call(&th.alice, vec![transfer_coin], 100, th.bob.keypair.public)?;
+    let transfer_tx = build_tx_with_fee(vec![transfer_call], &fee)?;
+    assert_eq!(transfer_tx.calls[0].data[0], MoneyFunction::FeeV1 as u8);
+    assert_eq!(transfer_tx.signatures.len(), 2);
+
+    info!("[Faucet] Executing Alice transfer tx paying a fee");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[transfer_tx], true).await?;
+    assert!(erroneous.is_empty());
+
+    // faucetd pays the fee of its airdrops the same way, here with one of
+    // Alice's coins standing in for the faucet's own.
+    let fee = th.fee_debris(&th.alice, airdrop_fee_coin, 5, 5)?;
+    let (airdrop_call, _) = th.airdrop_native_call(100, th.bob.keypair.public)?;
+    let airdrop_tx = build_tx_with_fee(vec![airdrop_call], &fee)?;
+    assert_eq!(airdrop_tx.calls[0].data[0], MoneyFunction::FeeV1 as u8);
+
+    info!("[Faucet] Executing Bob airdrop tx paying a fee");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[airdrop_tx], true).await?;
+    assert!(erroneous.is_empty());
+
+    // Thanks for reading
+    Ok(())
+}
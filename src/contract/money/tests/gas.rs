@@ -0,0 +1,61 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! Integration test for transaction gas accounting:
+//!
+//! * Reporting the gas spent by a simulated transaction
+//! * Rejecting transactions exceeding the chain transaction gas limit
+
+use darkfi::Result;
+use log::info;
+
+mod harness;
+use harness::{init_logger, MoneyTestHarness};
+
+#[async_std::test]
+async fn money_gas() -> Result<()> {
+    init_logger();
+
+    let th = MoneyTestHarness::new().await?;
+
+    let (airdrop_tx, _) = th.airdrop_native(200, th.alice.keypair.public)?;
+
+    info!("[Faucet] Simulating Alice airdrop tx");
+    let gas_used = th.faucet.state.read().await.simulate_transaction(&airdrop_tx).await?;
+    assert!(gas_used > 0);
+
+    // Simulation doesn't apply the transaction, so it reports the same gas again.
+    let gas_used_again = th.faucet.state.read().await.simulate_transaction(&airdrop_tx).await?;
+    assert_eq!(gas_used, gas_used_again);
+
+    // Right at the limit, the transaction is still valid.
+    th.faucet.state.write().await.consensus.params.tx_gas_limit = gas_used;
+    info!("[Faucet] Executing Alice airdrop tx at the gas limit");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[airdrop_tx.clone()], false).await?;
+    assert!(erroneous.is_empty());
+
+    // Once the limit is lowered, the transaction gets rejected.
+    th.faucet.state.write().await.consensus.params.tx_gas_limit = gas_used - 1;
+    info!("[Faucet] Executing Alice airdrop tx over the gas limit");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[airdrop_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Thanks for reading
+    Ok(())
+}
@@ -19,19 +19,20 @@
 use std::collections::HashMap;
 
 use darkfi::{
-    consensus::{
-        ValidatorState, ValidatorStatePtr, TESTNET_BOOTSTRAP_TIMESTAMP, TESTNET_GENESIS_HASH_BYTES,
-        TESTNET_GENESIS_TIMESTAMP, TESTNET_INITIAL_DISTRIBUTION,
-    },
+    consensus::{ChainParams, ValidatorState, ValidatorStatePtr},
     runtime::vm_runtime::SMART_CONTRACT_ZKAS_DB_NAME,
     tx::Transaction,
     wallet::{WalletDb, WalletPtr},
-    zk::{empty_witnesses, halo2::Field, ProvingKey, ZkCircuit},
+    zk::{empty_witnesses, halo2::Field, Proof, ProvingKey, ZkCircuit},
     zkas::ZkBinary,
     Result,
 };
 use darkfi_sdk::{
-    crypto::{Keypair, MerkleTree, PublicKey, DARK_TOKEN_ID, MONEY_CONTRACT_ID},
+    crypto::{
+        poseidon_hash, Keypair, MerkleNode, MerklePosition, MerkleTree, Nullifier, PublicKey,
+        SecretKey, DARK_TOKEN_ID, MONEY_CONTRACT_ID,
+    },
+    incrementalmerkletree::Tree,
     pasta::pallas,
     ContractCall,
 };
@@ -41,14 +42,66 @@ use rand::rngs::OsRng;
 
 use darkfi_money_contract::{
     client::{
-        freeze_v1::FreezeCallBuilder, mint_v1::MintCallBuilder, transfer_v1::TransferCallBuilder,
-        OwnCoin,
+        fee_v1::{FeeCallBuilder, FeeCallDebris},
+        freeze_v1::FreezeCallBuilder,
+        mint_v1::MintCallBuilder,
+        stake_v1::{StakeCallBuilder, StakedCoin},
+        transfer_v1::TransferCallBuilder,
+        unstake_v1::UnstakeCallBuilder,
+        MoneyNote, OwnCoin,
     },
-    model::{MoneyFreezeParamsV1, MoneyMintParamsV1, MoneyTransferParamsV1},
-    MoneyFunction, MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_MINT_NS_V1,
-    MONEY_CONTRACT_ZKAS_TOKEN_FRZ_NS_V1, MONEY_CONTRACT_ZKAS_TOKEN_MINT_NS_V1,
+    model::{
+        MoneyFeeParamsV1, MoneyFreezeParamsV1, MoneyMintParamsV1, MoneyStakeParamsV1,
+        MoneyTransferParamsV1, MoneyUnstakeParamsV1,
+    },
+    MoneyFunction, MONEY_CONTRACT_ZKAS_BURN_NS_V1, MONEY_CONTRACT_ZKAS_FEE_NS_V1,
+    MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1, MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1,
+    MONEY_CONTRACT_ZKAS_MINT_NS_V1, MONEY_CONTRACT_ZKAS_TOKEN_FRZ_NS_V1,
+    MONEY_CONTRACT_ZKAS_TOKEN_MINT_NS_V1,
 };
 
+/// Contract call along with its proofs and the secrets signing it
+pub type TxCall = (ContractCall, Vec<Proof>, Vec<SecretKey>);
+
+/// Build a transaction out of given calls, signing each one with its secrets.
+pub fn build_tx(calls: Vec<TxCall>) -> Result<Transaction> {
+    let mut tx = Transaction { calls: vec![], proofs: vec![], signatures: vec![] };
+    let mut secrets = vec![];
+    for (call, proofs, call_secrets) in calls {
+        tx.calls.push(call);
+        tx.proofs.push(proofs);
+        secrets.push(call_secrets);
+    }
+
+    for call_secrets in secrets {
+        let sigs = tx.create_sigs(&mut OsRng, &call_secrets)?;
+        tx.signatures.push(sigs);
+    }
+
+    Ok(tx)
+}
+
+/// Build a transaction out of given calls the way drk and faucetd pay fees:
+/// the fee call gets prepended to the unsigned calls, and signed after them.
+pub fn build_tx_with_fee(calls: Vec<TxCall>, fee: &FeeCallDebris) -> Result<Transaction> {
+    let mut tx = Transaction { calls: vec![], proofs: vec![], signatures: vec![] };
+    let mut secrets = vec![];
+    for (call, proofs, call_secrets) in calls {
+        tx.calls.push(call);
+        tx.proofs.push(proofs);
+        secrets.push(call_secrets);
+    }
+
+    fee.prepend_call(&mut tx)?;
+    for call_secrets in secrets {
+        let sigs = tx.create_sigs(&mut OsRng, &call_secrets)?;
+        tx.signatures.push(sigs);
+    }
+    fee.prepend_sigs(&mut tx)?;
+
+    Ok(tx)
+}
+
 pub fn init_logger() {
     let mut cfg = simplelog::ConfigBuilder::new();
     cfg.add_filter_ignore("sled".to_string());
@@ -82,12 +135,11 @@ impl Wallet {
         let wallet = WalletDb::new("sqlite::memory:", "foo").await?;
         let sled_db = sled::Config::new().temporary(true).open()?;
 
+        // Transactions only pay fees in the tests that require them
+        let params = ChainParams { min_tx_fee: 0, ..ChainParams::testnet() };
         let state = ValidatorState::new(
             &sled_db,
-            *TESTNET_BOOTSTRAP_TIMESTAMP,
-            *TESTNET_GENESIS_TIMESTAMP,
-            *TESTNET_GENESIS_HASH_BYTES,
-            *TESTNET_INITIAL_DISTRIBUTION,
+            params,
             wallet.clone(),
             faucet_pubkeys.to_vec(),
             false,
@@ -152,6 +204,9 @@ impl MoneyTestHarness {
         mkpk!(MONEY_CONTRACT_ZKAS_BURN_NS_V1);
         mkpk!(MONEY_CONTRACT_ZKAS_TOKEN_MINT_NS_V1);
         mkpk!(MONEY_CONTRACT_ZKAS_TOKEN_FRZ_NS_V1);
+        mkpk!(MONEY_CONTRACT_ZKAS_FEE_NS_V1);
+        mkpk!(MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1);
+        mkpk!(MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1);
 
         Ok(Self { faucet, alice, bob, charlie, proving_keys })
     }
@@ -161,6 +216,15 @@ impl MoneyTestHarness {
         value: u64,
         recipient: PublicKey,
     ) -> Result<(Transaction, MoneyTransferParamsV1)> {
+        let (call, params) = self.airdrop_native_call(value, recipient)?;
+        Ok((build_tx(vec![call])?, params))
+    }
+
+    pub fn airdrop_native_call(
+        &self,
+        value: u64,
+        recipient: PublicKey,
+    ) -> Result<(TxCall, MoneyTransferParamsV1)> {
         let (mint_pk, mint_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_MINT_NS_V1).unwrap();
         let (burn_pk, burn_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_BURN_NS_V1).unwrap();
 
@@ -188,13 +252,36 @@ impl MoneyTestHarness {
 
         let mut data = vec![MoneyFunction::TransferV1 as u8];
         debris.params.encode(&mut data)?;
-        let calls = vec![ContractCall { contract_id: *MONEY_CONTRACT_ID, data }];
-        let proofs = vec![debris.proofs];
-        let mut tx = Transaction { calls, proofs, signatures: vec![] };
-        let sigs = tx.create_sigs(&mut OsRng, &debris.signature_secrets)?;
-        tx.signatures = vec![sigs];
+        let call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
 
-        Ok((tx, debris.params))
+        Ok(((call, debris.proofs, debris.signature_secrets), debris.params))
+    }
+
+    /// Airdrop native tokens to Alice, executing the transaction on both the
+    /// faucet and Alice, and return the coin she received.
+    pub async fn airdrop_native_to_alice(&mut self, value: u64) -> Result<OwnCoin> {
+        let (airdrop_tx, airdrop_params) = self.airdrop_native(value, self.alice.keypair.public)?;
+
+        info!("[Faucet] Executing Alice airdrop tx");
+        self.faucet.state.read().await.verify_transactions(&[airdrop_tx.clone()], true).await?;
+        self.faucet.merkle_tree.append(&MerkleNode::from(airdrop_params.outputs[0].coin.inner()));
+
+        info!("[Alice] Executing Alice airdrop tx");
+        self.alice.state.read().await.verify_transactions(&[airdrop_tx], true).await?;
+        self.alice.merkle_tree.append(&MerkleNode::from(airdrop_params.outputs[0].coin.inner()));
+        let leaf_position = self.alice.merkle_tree.witness().unwrap();
+
+        let note: MoneyNote = airdrop_params.outputs[0].note.decrypt(&self.alice.keypair.secret)?;
+        let nullifier =
+            Nullifier::from(poseidon_hash([self.alice.keypair.secret.inner(), note.serial]));
+
+        Ok(OwnCoin {
+            coin: airdrop_params.outputs[0].coin,
+            note,
+            secret: self.alice.keypair.secret,
+            nullifier,
+            leaf_position,
+        })
     }
 
     pub fn mint_token(
@@ -253,4 +340,184 @@ impl MoneyTestHarness {
 
         Ok((tx, debris.params))
     }
+
+    pub fn pay_fee(
+        &self,
+        holder: &Wallet,
+        coin: OwnCoin,
+        fee: u64,
+        min_fee: u64,
+    ) -> Result<(Transaction, MoneyFeeParamsV1)> {
+        let (call, params) = self.pay_fee_call(holder, coin, fee, min_fee)?;
+        Ok((build_tx(vec![call])?, params))
+    }
+
+    pub fn pay_fee_call(
+        &self,
+        holder: &Wallet,
+        coin: OwnCoin,
+        fee: u64,
+        min_fee: u64,
+    ) -> Result<(TxCall, MoneyFeeParamsV1)> {
+        let debris = self.fee_debris(holder, coin, fee, min_fee)?;
+
+        let mut data = vec![MoneyFunction::FeeV1 as u8];
+        debris.params.encode(&mut data)?;
+        let call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
+
+        Ok(((call, debris.proofs, vec![debris.signature_secret]), debris.params))
+    }
+
+    pub fn fee_debris(
+        &self,
+        holder: &Wallet,
+        coin: OwnCoin,
+        fee: u64,
+        min_fee: u64,
+    ) -> Result<FeeCallDebris> {
+        let (fee_pk, fee_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_FEE_NS_V1).unwrap();
+
+        let builder = FeeCallBuilder {
+            coin,
+            fee,
+            min_fee,
+            change_public: holder.keypair.public,
+            tree: holder.merkle_tree.clone(),
+            fee_zkbin: fee_zkbin.clone(),
+            fee_pk: fee_pk.clone(),
+        };
+
+        builder.build()
+    }
+
+    pub fn transfer_call(
+        &self,
+        holder: &Wallet,
+        coins: Vec<OwnCoin>,
+        value: u64,
+        recipient: PublicKey,
+    ) -> Result<(TxCall, MoneyTransferParamsV1)> {
+        let (mint_pk, mint_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_MINT_NS_V1).unwrap();
+        let (burn_pk, burn_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_BURN_NS_V1).unwrap();
+
+        let builder = TransferCallBuilder {
+            keypair: holder.keypair,
+            recipient,
+            value,
+            token_id: *DARK_TOKEN_ID,
+            rcpt_spend_hook: pallas::Base::zero(),
+            rcpt_user_data: pallas::Base::zero(),
+            rcpt_user_data_blind: pallas::Base::random(&mut OsRng),
+            change_spend_hook: pallas::Base::zero(),
+            change_user_data: pallas::Base::zero(),
+            change_user_data_blind: pallas::Base::random(&mut OsRng),
+            coins,
+            tree: holder.merkle_tree.clone(),
+            mint_zkbin: mint_zkbin.clone(),
+            mint_pk: mint_pk.clone(),
+            burn_zkbin: burn_zkbin.clone(),
+            burn_pk: burn_pk.clone(),
+            clear_input: false,
+        };
+        let debris = builder.build()?;
+
+        let mut data = vec![MoneyFunction::TransferV1 as u8];
+        debris.params.encode(&mut data)?;
+        let call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
+
+        Ok(((call, debris.proofs, debris.signature_secrets), debris.params))
+    }
+
+    pub fn stake(
+        &self,
+        holder: &Wallet,
+        coin: OwnCoin,
+        sk: pallas::Base,
+        sk_root: MerkleNode,
+        sk_merkle_path: Vec<MerkleNode>,
+    ) -> Result<(Transaction, MoneyStakeParamsV1, StakedCoin)> {
+        let (call, params, staked_coin) =
+            self.stake_call(holder, coin, sk, sk_root, sk_merkle_path)?;
+        Ok((build_tx(vec![call])?, params, staked_coin))
+    }
+
+    pub fn stake_call(
+        &self,
+        holder: &Wallet,
+        coin: OwnCoin,
+        sk: pallas::Base,
+        sk_root: MerkleNode,
+        sk_merkle_path: Vec<MerkleNode>,
+    ) -> Result<(TxCall, MoneyStakeParamsV1, StakedCoin)> {
+        let (burn_pk, burn_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_BURN_NS_V1).unwrap();
+        let (lead_mint_pk, lead_mint_zkbin) =
+            self.proving_keys.get(&MONEY_CONTRACT_ZKAS_LEAD_MINT_NS_V1).unwrap();
+
+        let builder = StakeCallBuilder {
+            coin,
+            tree: holder.merkle_tree.clone(),
+            slot: 0,
+            sk,
+            sk_root,
+            sk_pos: 0,
+            sk_merkle_path,
+            burn_zkbin: burn_zkbin.clone(),
+            burn_pk: burn_pk.clone(),
+            lead_mint_zkbin: lead_mint_zkbin.clone(),
+            lead_mint_pk: lead_mint_pk.clone(),
+        };
+        let debris = builder.build()?;
+
+        let mut data = vec![MoneyFunction::StakeV1 as u8];
+        debris.params.encode(&mut data)?;
+        let call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
+
+        Ok((
+            (call, debris.proofs, vec![debris.signature_secret]),
+            debris.params,
+            debris.staked_coin,
+        ))
+    }
+
+    pub fn unstake(
+        &self,
+        coin: StakedCoin,
+        leaf_position: MerklePosition,
+        lead_tree: MerkleTree,
+        recipient: PublicKey,
+    ) -> Result<(Transaction, MoneyUnstakeParamsV1)> {
+        let (call, params) = self.unstake_call(coin, leaf_position, lead_tree, recipient)?;
+        Ok((build_tx(vec![call])?, params))
+    }
+
+    pub fn unstake_call(
+        &self,
+        coin: StakedCoin,
+        leaf_position: MerklePosition,
+        lead_tree: MerkleTree,
+        recipient: PublicKey,
+    ) -> Result<(TxCall, MoneyUnstakeParamsV1)> {
+        let (mint_pk, mint_zkbin) = self.proving_keys.get(&MONEY_CONTRACT_ZKAS_MINT_NS_V1).unwrap();
+        let (lead_burn_pk, lead_burn_zkbin) =
+            self.proving_keys.get(&MONEY_CONTRACT_ZKAS_LEAD_BURN_NS_V1).unwrap();
+
+        let builder = UnstakeCallBuilder {
+            coin,
+            leaf_position,
+            tree: lead_tree,
+            recipient,
+            lead_burn_zkbin: lead_burn_zkbin.clone(),
+            lead_burn_pk: lead_burn_pk.clone(),
+            mint_zkbin: mint_zkbin.clone(),
+            mint_pk: mint_pk.clone(),
+        };
+        let debris = builder.build()?;
+
+        let mut data = vec![MoneyFunction::UnstakeV1 as u8];
+        debris.params.encode(&mut data)?;
+        let call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
+
+        // Unstaking is authorized by the lead coin burn proof alone
+        Ok(((call, debris.proofs, vec![]), debris.params))
+    }
 }
@@ -0,0 +1,174 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+//! Integration test for `Money::StakeV1` and `Money::UnstakeV1`:
+//!
+//! * Staking a native token coin into a consensus lead coin
+//! * Rejecting a coin staked twice within a single transaction
+//! * Unstaking the lead coin back into a native token coin
+//! * Rejecting a lead coin unstaked twice within a single transaction
+//! * Rejecting double-unstaked lead coins
+//! * Rejecting transactions deriving lead coins, which only consensus can do
+
+use darkfi::{
+    consensus::{constants::EPOCH_LENGTH, lead_coin::LeadCoinSecrets},
+    Result,
+};
+use darkfi_sdk::{
+    crypto::{MerkleNode, MerkleTree, MONEY_CONTRACT_ID},
+    incrementalmerkletree::Tree,
+    pasta::pallas,
+    ContractCall,
+};
+use darkfi_serial::{serialize, Encodable};
+use log::info;
+
+use darkfi_money_contract::{
+    client::MoneyNote, model::DeriveLeadCoinParamsV1, MoneyFunction,
+    MONEY_CONTRACT_LEAD_COINS_TREE,
+};
+
+mod harness;
+use harness::{build_tx, init_logger, MoneyTestHarness};
+
+#[async_std::test]
+async fn money_stake_unstake() -> Result<()> {
+    init_logger();
+
+    let mut th = MoneyTestHarness::new().await?;
+
+    // Airdrop some native tokens to Alice, so she can stake them.
+    let owncoin = th.airdrop_native_to_alice(200).await?;
+
+    let secrets = LeadCoinSecrets::generate(EPOCH_LENGTH);
+
+    // Staking the same coin twice in one transaction would create two lead
+    // coins backed by a single coin.
+    let mut calls = vec![];
+    for _ in 0..2 {
+        let (call, _, _) = th.stake_call(
+            &th.alice,
+            owncoin.clone(),
+            secrets.secret_keys[0].inner(),
+            secrets.merkle_roots[0],
+            secrets.merkle_paths[0].clone(),
+        )?;
+        calls.push(call);
+    }
+    let double_stake_tx = build_tx(calls)?;
+
+    info!("[Faucet] Executing Alice double stake tx");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[double_stake_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Alice stakes her coin into a lead coin.
+    let (stake_tx, stake_params, staked_coin) = th.stake(
+        &th.alice,
+        owncoin,
+        secrets.secret_keys[0].inner(),
+        secrets.merkle_roots[0],
+        secrets.merkle_paths[0].clone(),
+    )?;
+    assert_eq!(staked_coin.lead_coin().commitment_hash(), stake_params.output.commitment);
+
+    info!("[Faucet] Executing Alice stake tx");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[stake_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    info!("[Alice] Executing Alice stake tx");
+    let erroneous =
+        th.alice.state.read().await.verify_transactions(&[stake_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    // The lead coin is now in the contract state, where consensus reads it from.
+    let alice_state = th.alice.state.read().await;
+    let lead_coins = alice_state.blockchain.contracts.lookup(
+        &alice_state.blockchain.sled_db,
+        &MONEY_CONTRACT_ID,
+        MONEY_CONTRACT_LEAD_COINS_TREE,
+    )?;
+    assert!(lead_coins.contains_key(serialize(&stake_params.output.commitment))?);
+    drop(alice_state);
+
+    // Alice unstakes her lead coin back into a native token coin.
+    let mut lead_tree = MerkleTree::new(100);
+    lead_tree.append(&MerkleNode::from(stake_params.output.commitment));
+    let lead_position = lead_tree.witness().unwrap();
+
+    // Lead coins only get derived by consensus for block leaders, so a
+    // transaction can't mint one, even from a staked lead coin.
+    let params = DeriveLeadCoinParamsV1 {
+        merkle_root: lead_tree.root(0).unwrap(),
+        nullifier: pallas::Base::from(1),
+        lead_coin: pallas::Base::from(2),
+    };
+    let mut data = vec![MoneyFunction::DeriveLeadCoinV1 as u8];
+    params.encode(&mut data)?;
+    let derive_call = ContractCall { contract_id: *MONEY_CONTRACT_ID, data };
+    let derive_tx = build_tx(vec![(derive_call, vec![], vec![])])?;
+
+    info!("[Faucet] Executing Alice derive lead coin tx");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[derive_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Unstaking the same lead coin twice in one transaction, to different
+    // recipients, would mint the stake twice.
+    let (alice_call, _) = th.unstake_call(
+        staked_coin.clone(),
+        lead_position,
+        lead_tree.clone(),
+        th.alice.keypair.public,
+    )?;
+    let (bob_call, _) = th.unstake_call(
+        staked_coin.clone(),
+        lead_position,
+        lead_tree.clone(),
+        th.bob.keypair.public,
+    )?;
+    let double_unstake_tx = build_tx(vec![alice_call, bob_call])?;
+
+    info!("[Faucet] Executing Alice double unstake tx");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[double_unstake_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    let (unstake_tx, unstake_params) =
+        th.unstake(staked_coin, lead_position, lead_tree, th.alice.keypair.public)?;
+
+    info!("[Faucet] Executing Alice unstake tx");
+    let erroneous =
+        th.faucet.state.read().await.verify_transactions(&[unstake_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    info!("[Alice] Executing Alice unstake tx");
+    let erroneous =
+        th.alice.state.read().await.verify_transactions(&[unstake_tx.clone()], true).await?;
+    assert!(erroneous.is_empty());
+
+    let note: MoneyNote = unstake_params.output.note.decrypt(&th.alice.keypair.secret)?;
+    assert_eq!(note.value, 200);
+
+    // The lead coin can not be unstaked twice.
+    info!("[Faucet] Executing Alice unstake tx again");
+    let erroneous = th.faucet.state.read().await.verify_transactions(&[unstake_tx], false).await?;
+    assert_eq!(erroneous.len(), 1);
+
+    // Thanks for reading
+    Ok(())
+}
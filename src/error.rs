@@ -136,12 +136,30 @@ pub enum Error {
     #[error("Accept a new tls connection from the listener {0} failed")]
     AcceptTlsConnectionFailed(String),
 
+    #[error("Noise error: {0}")]
+    NoiseError(String),
+
+    #[error("Untrusted peer public key: {0}")]
+    UntrustedPeerKey(String),
+
     #[error("Network operation failed")]
     NetworkOperationFailed,
 
     #[error("Malformed packet")]
     MalformedPacket,
 
+    #[error("Packet too large: {0} bytes")]
+    PacketTooLarge(u64),
+
+    #[error("Channel send queue is full")]
+    SendQueueFull,
+
+    #[error("Missing dispatcher for command: {0}")]
+    MissingDispatcher(String),
+
+    #[error("Peer is banned")]
+    PeerBanned,
+
     #[error("Socks proxy error: {0}")]
     SocksError(String),
 
@@ -248,6 +266,9 @@ pub enum Error {
     #[error("Proposal received not for current slot")]
     ProposalNotForCurrentSlotError,
 
+    #[error("Proposal belongs to a different network")]
+    ProposalNetworkMismatchError,
+
     #[error("Proposal contains missmatched hashes")]
     ProposalHashesMissmatchError,
 
@@ -260,6 +281,9 @@ pub enum Error {
     #[error("Proposal contains spent coin")]
     ProposalIsSpent,
 
+    #[error("Proposal coin is not a staked lead coin")]
+    ProposalCoinNotStaked,
+
     #[error("Proposal contains more transactions than configured cap")]
     ProposalTxsExceedCapError,
 
@@ -272,9 +296,42 @@ pub enum Error {
     #[error("Proposer is not eligible to produce proposals")]
     ProposalProposerNotEligible,
 
+    #[error("Block transactions don't match the header merkle root")]
+    BlockTxsRootMismatch,
+
+    #[error("Block doesn't extend the followed chain")]
+    BlockNotExtendingChain,
+
+    #[error("Block timestamp or epoch doesn't match its slot")]
+    BlockSlotMismatch,
+
+    #[error("Slot checkpoint {0} doesn't match a previously provided one")]
+    SlotCheckpointMismatch(u64),
+
+    #[error("Transaction {0} is not included in block {1}")]
+    TxNotIncluded(String, String),
+
     #[error("Erroneous transactions detected")]
     ErroneousTxsDetected,
 
+    #[error("Transaction is malformed")]
+    MalformedTransaction,
+
+    #[error("No peer left to sync the blockchain from")]
+    SyncPeersExhausted,
+
+    #[error("Mempool is full")]
+    MempoolFull,
+
+    #[error("Transaction conflicts with a pending transaction")]
+    MempoolConflict,
+
+    #[error("Transaction fee too low: {0}")]
+    TxFeeTooLow(u64),
+
+    #[error("Transaction gas used {0} exceeds gas limit {1}")]
+    TxGasLimitExceeded(u64, u64),
+
     // ===============
     // Database errors
     // ===============
@@ -299,12 +356,21 @@ pub enum Error {
     #[error("Block {0} not found in database")]
     BlockNotFound(String),
 
+    #[error("Nullifier {0} not found in database")]
+    NullifierNotFound(String),
+
+    #[error("State diff of block {0} not found in database")]
+    StateDiffNotFound(String),
+
     #[error("Block in slot {0} not found in database")]
     SlotNotFound(u64),
 
     #[error("Slot checkpoint {0} not found in database")]
     SlotCheckpointNotFound(u64),
 
+    #[error("Invalid blockchain snapshot: {0}")]
+    SnapshotInvalid(String),
+
     #[error("Contract {0} not found in database")]
     ContractNotFound(String),
 
@@ -356,6 +422,10 @@ pub enum Error {
     #[error("wasm runtime out of memory")]
     WasmerOomError(String),
 
+    #[cfg(feature = "wasm-runtime")]
+    #[error("wasm runtime gas limit of {0} exhausted")]
+    WasmerGasExhausted(u64),
+
     // TODO: FIXME: The strings are wrong
     #[cfg(feature = "darkfi-sdk")]
     #[error("contract initialize error")]
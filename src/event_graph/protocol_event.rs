@@ -30,7 +30,7 @@ use rand::{rngs::OsRng, RngCore};
 use super::{get_current_time, EventMsg};
 use crate::{
     event_graph::model::{Event, EventId, ModelPtr},
-    net,
+    net::{self, constants::BAN_SCORE_EMPTY_INV},
     util::async_util::sleep,
     Result,
 };
@@ -262,7 +262,10 @@ where
         loop {
             let inv = self.inv_sub.receive().await?;
             let inv = (*inv).to_owned();
-            let inv_item = inv.invs[0].clone();
+            let Some(inv_item) = inv.invs.first().cloned() else {
+                self.p2p.add_ban_score(&self.channel, BAN_SCORE_EMPTY_INV, "empty inv").await;
+                continue
+            };
 
             // for inv in inv.invs.iter() {
             if !self.seen_inv.push(&inv_item.id).await {
@@ -391,12 +394,20 @@ where
     fn name() -> &'static str {
         "event"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 impl net::Message for Inv {
     fn name() -> &'static str {
         "inv"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Relay
+    }
 }
 
 impl net::Message for SyncEvent {
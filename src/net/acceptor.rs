@@ -24,7 +24,10 @@ use smol::Executor;
 use url::Url;
 
 use super::{
-    transport::{TcpTransport, TorTransport, Transport, TransportListener, TransportName},
+    transport::{
+        MemTransport, NoiseListener, TcpTransport, TorTransport, Transport, TransportListener,
+        TransportName,
+    },
     Channel, ChannelPtr, SessionWeakPtr,
 };
 use crate::{
@@ -86,6 +89,13 @@ impl Acceptor {
                         let tls_listener = $transport.upgrade_listener(listener)?.await?;
                         self.accept(Box::new(tls_listener), executor);
                     }
+                    Some(u) if u == "noise" => {
+                        let session = self.session.lock().await.clone().unwrap();
+                        let noise = session.upgrade().unwrap().p2p().noise_upgrade();
+                        let noise_listener =
+                            NoiseListener::new(Box::new(listener), noise, executor.clone());
+                        self.accept(Box::new(noise_listener), executor);
+                    }
                     Some(u) => return Err(Error::UnsupportedTransportUpgrade(u)),
                 }
             }};
@@ -138,6 +148,13 @@ impl Acceptor {
 
                 accept!(listener, transport, upgrade);
             }
+            TransportName::Mem(upgrade) => {
+                let transport = MemTransport::new(None);
+
+                let listener = transport.clone().listen_on(accept_url.clone());
+
+                accept!(listener, transport, upgrade);
+            }
             _ => unimplemented!(),
         }
         Ok(())
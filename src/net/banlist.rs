@@ -0,0 +1,287 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufReader, Read, Write},
+    path::Path,
+};
+
+use async_std::sync::{Arc, Mutex};
+use darkfi_serial::{deserialize, serialize};
+use log::{debug, info};
+use url::{Host, Url};
+
+use crate::{util::time::unix_timestamp, Result};
+
+/// Atomic pointer to the ban list.
+pub type BanListPtr = Arc<BanList>;
+
+/// Seconds it takes for a misbehaviour score to decay by one point
+const SCORE_DECAY_SECONDS: u64 = 60;
+/// Maximum number of peers whose misbehaviour score is tracked
+const MAX_SCORED_PEERS: usize = 4096;
+
+/// Keeps track of peer misbehaviour scores and banned peers.
+///
+/// Peers are identified by their host, so a ban applies to every port
+/// and transport the peer may use. Addresses without a host (e.g. unix
+/// sockets) or on the loopback interface are identified by their full URL.
+///
+/// Scores decay over time, so only sustained misbehaviour leads to a ban.
+pub struct BanList {
+    /// Accumulated misbehaviour score of each peer, along with the UNIX
+    /// timestamp it was last updated at
+    scores: Mutex<HashMap<String, (u32, u64)>>,
+    /// Banned peers, along with the UNIX timestamp their ban expires at
+    banned: Mutex<HashMap<String, u64>>,
+}
+
+impl BanList {
+    /// Create a new, empty ban list.
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { scores: Mutex::new(HashMap::new()), banned: Mutex::new(HashMap::new()) })
+    }
+
+    /// Increase the misbehaviour score of the peer behind `addr` and
+    /// return its new score.
+    pub async fn add_score(&self, addr: &Url, score: u32) -> u32 {
+        let key = ban_key(addr);
+        let now = unix_timestamp().unwrap_or(0);
+        let mut scores = self.scores.lock().await;
+
+        // Make room for a new peer by forgetting the ones whose score fully
+        // decayed, or else the one with the lowest score.
+        if !scores.contains_key(&key) && scores.len() >= MAX_SCORED_PEERS {
+            scores.retain(|_, (score, updated)| decay(*score, *updated, now) > 0);
+            if scores.len() >= MAX_SCORED_PEERS {
+                let lowest = scores
+                    .iter()
+                    .min_by_key(|(_, (score, updated))| decay(*score, *updated, now))
+                    .map(|(key, _)| key.clone())
+                    .unwrap();
+                scores.remove(&lowest);
+            }
+        }
+
+        let entry = scores.entry(key).or_insert((0, now));
+        let total = decay(entry.0, entry.1, now).saturating_add(score);
+        *entry = (total, now);
+        total
+    }
+
+    /// Return the current misbehaviour score of the peer behind `addr`.
+    pub async fn score(&self, addr: &Url) -> u32 {
+        let now = unix_timestamp().unwrap_or(0);
+        match self.scores.lock().await.get(&ban_key(addr)) {
+            Some((score, updated)) => decay(*score, *updated, now),
+            None => 0,
+        }
+    }
+
+    /// Ban the peer behind `addr` for `duration` seconds. Its misbehaviour
+    /// score gets cleared, so it starts fresh once the ban expires.
+    pub async fn ban(&self, addr: &Url, duration: u64) {
+        self.ban_host(&ban_key(addr), duration).await
+    }
+
+    /// Ban a peer by its host for `duration` seconds.
+    pub async fn ban_host(&self, host: &str, duration: u64) {
+        let now = unix_timestamp().unwrap_or(0);
+        let expiry = now.saturating_add(duration);
+        self.scores.lock().await.remove(host);
+        let mut banned = self.banned.lock().await;
+        banned.retain(|_, expiry| *expiry > now);
+        banned.insert(host.to_string(), expiry);
+        info!(target: "net::banlist::ban_host()", "Banned {} until {}", host, expiry);
+    }
+
+    /// Lift the ban of a peer by its host. Returns `false` if the peer
+    /// was not banned.
+    pub async fn unban_host(&self, host: &str) -> bool {
+        self.scores.lock().await.remove(host);
+        self.banned.lock().await.remove(host).is_some()
+    }
+
+    /// Check if the peer behind `addr` is currently banned. Expired bans
+    /// get removed along the way.
+    pub async fn is_banned(&self, addr: &Url) -> bool {
+        let key = ban_key(addr);
+        let mut banned = self.banned.lock().await;
+        match banned.get(&key) {
+            Some(expiry) => {
+                if *expiry > unix_timestamp().unwrap_or(0) {
+                    return true
+                }
+                debug!(target: "net::banlist::is_banned()", "Ban of {} expired", key);
+                banned.remove(&key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Return all active bans, along with their expiry timestamps.
+    pub async fn list(&self) -> Vec<(String, u64)> {
+        let now = unix_timestamp().unwrap_or(0);
+        let mut banned = self.banned.lock().await;
+        banned.retain(|_, expiry| *expiry > now);
+        banned.iter().map(|(host, expiry)| (host.clone(), *expiry)).collect()
+    }
+
+    /// Load a previously saved ban list from given path. Expired bans
+    /// are skipped.
+    pub async fn load_banlist(&self, path: &Path) -> Result<()> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        let entries: Vec<(String, u64)> = deserialize(&bytes)?;
+        let now = unix_timestamp().unwrap_or(0);
+        let mut banned = self.banned.lock().await;
+        for (host, expiry) in entries {
+            if expiry > now {
+                banned.insert(host, expiry);
+            }
+        }
+
+        info!(target: "net::banlist::load_banlist()", "Loaded {} bans from {:?}", banned.len(), path);
+        Ok(())
+    }
+
+    /// Save the active bans to given path.
+    pub async fn save_banlist(&self, path: &Path) -> Result<()> {
+        let entries = self.list().await;
+        let mut file = File::create(path)?;
+        file.write_all(&serialize(&entries))?;
+        debug!(target: "net::banlist::save_banlist()", "Saved {} bans to {:?}", entries.len(), path);
+        Ok(())
+    }
+}
+
+/// Identify a peer by the host of its address, falling back to the
+/// full URL for hostless addresses. Inbound peers behind a local proxy
+/// such as Tor all connect from the loopback interface, so those are
+/// told apart by their full URL as well.
+pub fn ban_key(addr: &Url) -> String {
+    match addr.host() {
+        Some(Host::Ipv4(ip)) if ip.is_loopback() => addr.to_string(),
+        Some(Host::Ipv6(ip)) if ip.is_loopback() => addr.to_string(),
+        Some(Host::Domain("localhost")) => addr.to_string(),
+        Some(host) => host.to_string(),
+        None => addr.to_string(),
+    }
+}
+
+/// Score left of `score` last updated at `updated`, at time `now`.
+fn decay(score: u32, updated: u64, now: u64) -> u32 {
+    let decayed = now.saturating_sub(updated) / SCORE_DECAY_SECONDS;
+    score.saturating_sub(decayed.try_into().unwrap_or(u32::MAX))
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::{ban_key, decay, BanList, MAX_SCORED_PEERS, SCORE_DECAY_SECONDS};
+
+    #[async_std::test]
+    async fn test_banlist() {
+        let banlist = BanList::new();
+
+        let a = Url::parse("tcp://10.0.0.1:13333").unwrap();
+        let a_other_port = Url::parse("tls://10.0.0.1:41234").unwrap();
+        let b = Url::parse("tcp://10.0.0.2:13333").unwrap();
+
+        // Scores accumulate per host, regardless of port or transport
+        assert_eq!(banlist.add_score(&a, 10).await, 10);
+        assert_eq!(banlist.add_score(&a_other_port, 20).await, 30);
+        assert_eq!(banlist.score(&b).await, 0);
+
+        banlist.ban(&a, 3600).await;
+        assert!(banlist.is_banned(&a_other_port).await);
+        assert!(!banlist.is_banned(&b).await);
+        assert_eq!(banlist.score(&a).await, 0);
+
+        // Expired bans are dropped
+        banlist.ban(&b, 0).await;
+        assert!(!banlist.is_banned(&b).await);
+        assert_eq!(banlist.list().await.len(), 1);
+
+        // Bans survive a save and load roundtrip
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("banlist.bin");
+        banlist.save_banlist(&path).await.unwrap();
+        let loaded = BanList::new();
+        loaded.load_banlist(&path).await.unwrap();
+        assert!(loaded.is_banned(&a).await);
+
+        assert!(loaded.unban_host("10.0.0.1").await);
+        assert!(!loaded.is_banned(&a).await);
+        assert!(!loaded.unban_host("10.0.0.1").await);
+    }
+
+    #[async_std::test]
+    async fn test_banlist_loopback() {
+        let banlist = BanList::new();
+
+        // Tor inbound peers all come from the loopback interface
+        let a = Url::parse("tcp://127.0.0.1:41234").unwrap();
+        let b = Url::parse("tcp://127.0.0.1:41235").unwrap();
+        assert_eq!(ban_key(&a), "tcp://127.0.0.1:41234");
+        assert_eq!(ban_key(&Url::parse("tcp://[::1]:41234").unwrap()), "tcp://[::1]:41234");
+        assert_eq!(ban_key(&Url::parse("tcp://localhost:41234").unwrap()), "tcp://localhost:41234");
+
+        banlist.add_score(&a, 10).await;
+        assert_eq!(banlist.score(&b).await, 0);
+
+        banlist.ban(&a, 3600).await;
+        assert!(banlist.is_banned(&a).await);
+        assert!(!banlist.is_banned(&b).await);
+    }
+
+    #[test]
+    fn test_score_decay() {
+        assert_eq!(decay(10, 1000, 1000), 10);
+        assert_eq!(decay(10, 1000, 1000 + SCORE_DECAY_SECONDS - 1), 10);
+        assert_eq!(decay(10, 1000, 1000 + SCORE_DECAY_SECONDS * 3), 7);
+        assert_eq!(decay(10, 1000, 1000 + SCORE_DECAY_SECONDS * 30), 0);
+        assert_eq!(decay(10, 1000, u64::MAX), 0);
+        // Clock going backwards doesn't increase the score
+        assert_eq!(decay(10, 1000, 0), 10);
+    }
+
+    #[async_std::test]
+    async fn test_scores_capped() {
+        let banlist = BanList::new();
+
+        let worst = Url::parse("tcp://10.0.0.1:13333").unwrap();
+        banlist.add_score(&worst, 100).await;
+
+        for i in 0..MAX_SCORED_PEERS {
+            let addr = Url::parse(&format!("tcp://10.1.{}.{}:13333", i / 256, i % 256)).unwrap();
+            banlist.add_score(&addr, 1).await;
+        }
+
+        // Peers with the lowest scores make room for new ones
+        assert_eq!(banlist.scores.lock().await.len(), MAX_SCORED_PEERS);
+        assert_eq!(banlist.score(&worst).await, 100);
+    }
+}
@@ -16,20 +16,34 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use async_std::sync::{Arc, Mutex};
+use std::{collections::HashMap, time::Duration};
+
+use async_std::{
+    future::timeout,
+    sync::{Arc, Mutex},
+};
 use futures::{
     io::{ReadHalf, WriteHalf},
     AsyncReadExt,
 };
 use log::{debug, error, info};
 use rand::Rng;
+use serde::Serialize;
 use serde_json::json;
 use smol::Executor;
 use url::Url;
 
 use super::{
+    constants::{
+        BAN_SCORE_MALFORMED_MESSAGE, BAN_SCORE_MALFORMED_PACKET, BAN_SCORE_UNKNOWN_COMMAND,
+        INV_KNOWN_CACHE_SIZE, LEGACY_PROTOCOL_VERSION, MAX_PAYLOAD_LEN, PROTOCOL_VERSION,
+    },
+    inventory::{InvCache, InvHash},
     message,
     message_subscriber::{MessageSubscription, MessageSubsystem},
+    protocol::{ServiceBitflag, SERVICE_NONE},
+    rate_limit::{TokenBucket, TokenBucketPtr},
+    send_queue::SendQueue,
     transport::TransportStream,
     Session, SessionBitflag, SessionWeakPtr,
 };
@@ -42,11 +56,43 @@ use crate::{
 /// Atomic pointer to async channel.
 pub type ChannelPtr = Arc<Channel>;
 
+/// Command under which traffic for commands without a dispatcher is
+/// accounted, so peers can't grow the per-command map at will.
+const UNKNOWN_COMMAND: &str = "unknown";
+
+/// Byte and message counters of a channel, or of one of its commands.
+/// Byte counts include packet framing.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TrafficStats {
+    pub msgs_sent: u64,
+    pub bytes_sent: u64,
+    pub msgs_recv: u64,
+    pub bytes_recv: u64,
+}
+
+impl TrafficStats {
+    fn record_sent(&mut self, bytes: usize) {
+        self.msgs_sent += 1;
+        self.bytes_sent += bytes as u64;
+    }
+
+    fn record_recv(&mut self, bytes: usize) {
+        self.msgs_recv += 1;
+        self.bytes_recv += bytes as u64;
+    }
+}
+
 struct ChannelInfo {
     random_id: u32,
     remote_node_id: String,
+    remote_pubkey: Option<String>,
+    remote_version: Option<Arc<message::VersionMessage>>,
     last_msg: String,
     last_status: String,
+    // Totals across all commands
+    traffic: TrafficStats,
+    // Counters broken down by command
+    command_traffic: HashMap<String, TrafficStats>,
     // Message log which is cleared on querying get_info
     log: Option<Mutex<Vec<(NanoTimestamp, String, String)>>>,
 }
@@ -61,8 +107,12 @@ impl ChannelInfo {
         Self {
             random_id: rand::thread_rng().gen(),
             remote_node_id: String::new(),
+            remote_pubkey: None,
+            remote_version: None,
             last_msg: String::new(),
             last_status: String::new(),
+            traffic: TrafficStats::default(),
+            command_traffic: HashMap::new(),
             log,
         }
     }
@@ -82,12 +132,28 @@ impl ChannelInfo {
         json!({
             "random_id": self.random_id,
             "remote_node_id": self.remote_node_id,
+            "remote_pubkey": self.remote_pubkey,
+            "remote_version": self.remote_version.as_ref().map(|v| v.version),
+            "remote_services": self.remote_version.as_ref().map(|v| v.services),
+            "remote_external_addrs": self.remote_version.as_ref().map(|v| v.external_addrs.clone()),
             "last_msg": self.last_msg,
             "last_status": self.last_status,
+            "traffic": self.traffic,
+            "command_traffic": self.command_traffic,
             "log": log,
         })
     }
     // ANCHOR_END: get_info
+
+    fn record_sent(&mut self, command: &str, bytes: usize) {
+        self.traffic.record_sent(bytes);
+        self.command_traffic.entry(command.to_string()).or_default().record_sent(bytes);
+    }
+
+    fn record_recv(&mut self, command: &str, bytes: usize) {
+        self.traffic.record_recv(bytes);
+        self.command_traffic.entry(command.to_string()).or_default().record_recv(bytes);
+    }
 }
 
 /// Async channel for communication between nodes.
@@ -95,11 +161,26 @@ pub struct Channel {
     reader: Mutex<ReadHalf<Box<dyn TransportStream>>>,
     writer: Mutex<WriteHalf<Box<dyn TransportStream>>>,
     address: Url,
+    /// Static public key of the peer, if authenticated by the transport
+    remote_pubkey: Option<[u8; 32]>,
     message_subsystem: MessageSubsystem,
+    /// Outbound packets waiting for the send loop
+    send_queue: SendQueue,
+    /// Time the peer gets to accept a packet before it is disconnected
+    write_timeout: Duration,
     stop_subscriber: SubscriberPtr<Error>,
     receive_task: StoppableTaskPtr,
+    send_task: StoppableTaskPtr,
     stopped: Mutex<bool>,
     info: Mutex<ChannelInfo>,
+    /// Inventory the peer is known to have, so we don't announce it again
+    known_inventory: Mutex<InvCache>,
+    /// Bandwidth limits of this peer
+    inbound_limit: TokenBucketPtr,
+    outbound_limit: TokenBucketPtr,
+    /// Bandwidth limits shared with all other channels
+    global_inbound_limit: TokenBucketPtr,
+    global_outbound_limit: TokenBucketPtr,
     session: SessionWeakPtr,
 }
 
@@ -112,6 +193,7 @@ impl Channel {
         address: Url,
         session: SessionWeakPtr,
     ) -> Arc<Self> {
+        let remote_pubkey = stream.remote_pubkey();
         let (reader, writer) = stream.split();
         let reader = Mutex::new(reader);
         let writer = Mutex::new(writer);
@@ -119,17 +201,29 @@ impl Channel {
         let message_subsystem = MessageSubsystem::new();
         Self::setup_dispatchers(&message_subsystem).await;
 
-        let channel_log = session.upgrade().unwrap().p2p().settings().channel_log;
+        let p2p = session.upgrade().unwrap().p2p();
+        let settings = p2p.settings();
+        let mut info = ChannelInfo::new(settings.channel_log);
+        info.remote_pubkey = remote_pubkey.map(hex::encode);
 
         Arc::new(Self {
             reader,
             writer,
             address,
+            remote_pubkey,
             message_subsystem,
+            send_queue: SendQueue::new(settings.send_queue_len),
+            write_timeout: Duration::from_secs(settings.write_timeout_seconds),
             stop_subscriber: Subscriber::new(),
             receive_task: StoppableTask::new(),
+            send_task: StoppableTask::new(),
             stopped: Mutex::new(false),
-            info: Mutex::new(ChannelInfo::new(channel_log)),
+            info: Mutex::new(info),
+            known_inventory: Mutex::new(InvCache::new(INV_KNOWN_CACHE_SIZE)),
+            inbound_limit: TokenBucket::new(settings.peer_inbound_rate_limit),
+            outbound_limit: TokenBucket::new(settings.peer_outbound_rate_limit),
+            global_inbound_limit: p2p.inbound_limit(),
+            global_outbound_limit: p2p.outbound_limit(),
             session,
         })
     }
@@ -138,8 +232,19 @@ impl Channel {
         self.info.lock().await.get_info().await
     }
 
-    /// Starts the channel. Runs a receive loop to start receiving messages or
-    /// handles a network failure.
+    /// Traffic counters of this channel, summed over all commands.
+    pub async fn traffic(&self) -> TrafficStats {
+        self.info.lock().await.traffic.clone()
+    }
+
+    /// Traffic counters of this channel for a single command.
+    pub async fn command_traffic(&self, command: &str) -> TrafficStats {
+        self.info.lock().await.command_traffic.get(command).cloned().unwrap_or_default()
+    }
+
+    /// Starts the channel. Runs a receive loop to start receiving messages,
+    /// and a send loop writing out queued messages, or handles a network
+    /// failure.
     pub fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) {
         debug!(target: "net::channel::start()", "START, address={}", self.address());
         let self2 = self.clone();
@@ -147,6 +252,13 @@ impl Channel {
             self.clone().main_receive_loop(),
             |result| self2.handle_stop(result),
             Error::NetworkServiceStopped,
+            executor.clone(),
+        );
+        let self2 = self.clone();
+        self.send_task.clone().start(
+            self.clone().main_send_loop(),
+            |result| self2.handle_stop(result),
+            Error::NetworkServiceStopped,
             executor,
         );
         debug!(target: "net::channel::start()", "END, address={}", self.address());
@@ -161,7 +273,9 @@ impl Channel {
             *self.stopped.lock().await = true;
 
             self.stop_subscriber.notify(Error::ChannelStopped).await;
+            self.send_queue.close();
             self.receive_task.stop().await;
+            self.send_task.stop().await;
             self.message_subsystem.trigger_error(Error::ChannelStopped).await;
             debug!(target: "net::channel::stop()", "END, address={}", self.address());
         }
@@ -186,8 +300,9 @@ impl Channel {
     }
 
     /// Sends a message across a channel. Calls function 'send_message' that
-    /// creates a new payload and sends it over the TCP connection as a
-    /// packet. Returns an error if something goes wrong.
+    /// creates a new payload and queues it to be sent over the connection
+    /// as a packet. Returns once the message is queued, or an error if
+    /// something goes wrong.
     pub async fn send<M: message::Message>(&self, message: M) -> Result<()> {
         debug!(
             target: "net::channel::send()",
@@ -230,12 +345,13 @@ impl Channel {
 
     /// Implements send message functionality. Creates a new payload and encodes
     /// it. Then creates a message packet- the base type of the network- and
-    /// copies the payload into it. Then we send the packet over the TCP
-    /// stream.
+    /// copies the payload into it. Then we queue the packet with the
+    /// priority of its message type.
     async fn send_message<M: message::Message>(&self, message: M) -> Result<()> {
         let mut payload = Vec::new();
         message.encode(&mut payload)?;
         let packet = message::Packet { command: String::from(M::name()), payload };
+
         let time = NanoTimestamp::current_time();
         //let time = time::unix_timestamp()?;
 
@@ -246,8 +362,45 @@ impl Channel {
             };
         }
 
-        let stream = &mut *self.writer.lock().await;
-        message::send_packet(stream, packet).await
+        self.send_queue.push(M::priority(), packet).await
+    }
+
+    /// Run the send loop. Writes queued packets to the stream, highest
+    /// priority first, or stops the channel if the peer fails to accept
+    /// them in time.
+    async fn main_send_loop(self: Arc<Self>) -> Result<()> {
+        debug!(target: "net::channel::main_send_loop()", "START, address={}", self.address());
+
+        let writer = &mut *self.writer.lock().await;
+
+        loop {
+            let packet = self.send_queue.pop().await?;
+            let command = packet.command.clone();
+            let wire_len = packet.wire_len();
+
+            // Wait for both our own and the global bandwidth budget
+            self.outbound_limit.consume(wire_len).await;
+            self.global_outbound_limit.consume(wire_len).await;
+
+            let result =
+                match timeout(self.write_timeout, message::send_packet(writer, packet)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(Error::ChannelTimeout),
+                };
+
+            if let Err(err) = result {
+                error!(
+                    target: "net::channel::main_send_loop()",
+                    "Write error on channel {}: {}",
+                    self.address(),
+                    err
+                );
+                self.stop().await;
+                return Err(Error::ChannelStopped)
+            }
+
+            self.info.lock().await.record_sent(&command, wire_len);
+        }
     }
 
     /// Subscribe to a messages on the message subsystem.
@@ -273,6 +426,12 @@ impl Channel {
         self.address.clone()
     }
 
+    /// Static public key of the peer, verified by the transport handshake.
+    /// `None` for transports that don't authenticate peers.
+    pub fn remote_pubkey(&self) -> Option<[u8; 32]> {
+        self.remote_pubkey
+    }
+
     pub async fn remote_node_id(&self) -> String {
         self.info.lock().await.remote_node_id.clone()
     }
@@ -280,6 +439,41 @@ impl Channel {
         self.info.lock().await.remote_node_id = remote_node_id;
     }
 
+    /// Version information the peer sent during the handshake, or `None`
+    /// if the handshake has not completed yet.
+    pub async fn remote_version(&self) -> Option<Arc<message::VersionMessage>> {
+        self.info.lock().await.remote_version.clone()
+    }
+
+    pub async fn set_remote_version(&self, version: Arc<message::VersionMessage>) {
+        self.info.lock().await.remote_version = Some(version);
+    }
+
+    /// Optional services advertised by the peer.
+    pub async fn remote_services(&self) -> ServiceBitflag {
+        self.remote_version().await.map(|v| v.services).unwrap_or(SERVICE_NONE)
+    }
+
+    /// Protocol version agreed with the peer, i.e. the lowest version
+    /// both sides speak.
+    pub async fn negotiated_version(&self) -> u32 {
+        match self.remote_version().await {
+            Some(v) => v.version.min(PROTOCOL_VERSION),
+            None => LEGACY_PROTOCOL_VERSION,
+        }
+    }
+
+    /// Remember that the peer has the given inventory item. Returns `true`
+    /// if it wasn't known yet.
+    pub async fn mark_known(&self, hash: InvHash) -> bool {
+        self.known_inventory.lock().await.insert(hash)
+    }
+
+    /// Returns `true` if the peer is known to have the given inventory item.
+    pub async fn knows(&self, hash: &InvHash) -> bool {
+        self.known_inventory.lock().await.contains(hash)
+    }
+
     /// End of file error. Triggered when unexpected end of file occurs.
     fn is_eof_error(err: Error) -> bool {
         match err {
@@ -297,6 +491,8 @@ impl Channel {
         message_subsystem.add_dispatch::<message::GetAddrsMessage>().await;
         message_subsystem.add_dispatch::<message::AddrsMessage>().await;
         message_subsystem.add_dispatch::<message::ExtAddrsMessage>().await;
+        message_subsystem.add_dispatch::<message::InvMessage>().await;
+        message_subsystem.add_dispatch::<message::GetDataMessage>().await;
     }
 
     /// Convenience function that returns the Message Subsystem.
@@ -312,9 +508,20 @@ impl Channel {
         let reader = &mut *self.reader.lock().await;
 
         loop {
-            let packet = match message::read_packet(reader).await {
+            let packet = match self.read_packet(reader).await {
                 Ok(packet) => packet,
                 Err(err) => {
+                    match err {
+                        Error::MalformedPacket => {
+                            self.add_ban_score(BAN_SCORE_MALFORMED_PACKET, "malformed packet").await
+                        }
+                        Error::PacketTooLarge(len) => {
+                            let reason = format!("oversized packet of {} bytes", len);
+                            self.add_ban_score(BAN_SCORE_MALFORMED_PACKET, &reason).await
+                        }
+                        _ => {}
+                    }
+
                     if Self::is_eof_error(err.clone()) {
                         info!(
                             target: "net::channel::main_receive_loop()",
@@ -338,8 +545,21 @@ impl Channel {
                     return Err(Error::ChannelStopped)
                 }
             };
+
+            // Throttle reading further packets while over the bandwidth
+            // budget, which pushes back on the sender.
+            let wire_len = packet.wire_len();
+            self.inbound_limit.consume(wire_len).await;
+            self.global_inbound_limit.consume(wire_len).await;
+
+            let command = match self.message_subsystem.max_len(&packet.command).await {
+                Some(_) => packet.command.as_str(),
+                None => UNKNOWN_COMMAND,
+            };
+
             {
                 let info = &mut *self.info.lock().await;
+                info.record_recv(command, wire_len);
                 info.last_msg = packet.command.clone();
                 info.last_status = "recv".to_string();
                 let time = NanoTimestamp::current_time();
@@ -350,7 +570,30 @@ impl Channel {
             }
 
             // Send result to our subscribers
-            self.message_subsystem.notify(&packet.command, packet.payload).await;
+            match self.message_subsystem.notify(&packet.command, packet.payload).await {
+                Ok(()) => {}
+                Err(Error::MissingDispatcher(command)) => {
+                    // Peers may legitimately send commands of protocols that
+                    // were gated off or belong to another session, so only
+                    // commands no protocol knows about are scored.
+                    let p2p = self.session().p2p();
+                    if p2p.protocol_registry().is_known_command(&command).await {
+                        debug!(
+                            target: "net::channel::main_receive_loop()",
+                            "Ignoring '{}' message not handled on channel {}",
+                            command,
+                            self.address()
+                        );
+                    } else {
+                        let reason = format!("unknown command '{}'", command);
+                        self.add_ban_score(BAN_SCORE_UNKNOWN_COMMAND, &reason).await;
+                    }
+                }
+                Err(_) => {
+                    let reason = format!("malformed '{}' message", packet.command);
+                    self.add_ban_score(BAN_SCORE_MALFORMED_MESSAGE, &reason).await;
+                }
+            }
         }
     }
 
@@ -376,6 +619,26 @@ impl Channel {
         );
     }
 
+    /// Read a single packet from the stream. The payload is bounded by the
+    /// maximum length of the Message type dispatched for its command.
+    async fn read_packet(
+        &self,
+        reader: &mut ReadHalf<Box<dyn TransportStream>>,
+    ) -> Result<message::Packet> {
+        let command = message::read_command(reader).await?;
+        // Unknown commands still get their payload consumed, so the stream
+        // stays in sync and the peer can be scored for them.
+        let max_len = self.message_subsystem.max_len(&command).await.unwrap_or(MAX_PAYLOAD_LEN);
+        let payload = message::read_payload(reader, max_len).await?;
+        Ok(message::Packet { command, payload })
+    }
+
+    /// Report misbehaviour of this channel's peer to the ban list.
+    async fn add_ban_score(self: &Arc<Self>, score: u32, reason: &str) {
+        let p2p = self.session().p2p();
+        p2p.add_ban_score(self, score, reason).await
+    }
+
     fn session(&self) -> Arc<dyn Session> {
         self.session.upgrade().unwrap()
     }
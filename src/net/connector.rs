@@ -23,7 +23,7 @@ use log::error;
 use url::Url;
 
 use super::{
-    transport::{NymTransport, TcpTransport, TorTransport, Transport, TransportName},
+    transport::{MemTransport, NymTransport, TcpTransport, TorTransport, Transport, TransportName},
     Channel, ChannelPtr, SessionWeakPtr, SettingsPtr,
 };
 use crate::{Error, Result};
@@ -81,6 +81,15 @@ impl Connector {
                         let stream = $transport.upgrade_dialer(stream?)?.await;
                         Channel::new(Box::new(stream?), connect_url, self.session.clone()).await
                     }
+                    Some(u) if u == "noise" => {
+                        let noise = self.session.upgrade().unwrap().p2p().noise_upgrade();
+                        let stream = noise.upgrade_dialer_noise(stream?).await;
+                        if let Err(err) = stream {
+                            error!(target: "net::connector", "Noise handshake with {} failed: {}", connect_url, err);
+                            return Err(err)
+                        }
+                        Channel::new(Box::new(stream?), connect_url, self.session.clone()).await
+                    }
                     Some(u) => return Err(Error::UnsupportedTransportUpgrade(u)),
                 };
 
@@ -113,6 +122,13 @@ impl Connector {
 
                 connect!(stream, transport, upgrade)
             }
+            TransportName::Mem(upgrade) => {
+                let transport = MemTransport::from_inbound(&self.settings.inbound);
+
+                let stream = transport.clone().dial(connect_url.clone(), None);
+
+                connect!(stream, transport, upgrade)
+            }
             _ => unimplemented!(),
         }
     }
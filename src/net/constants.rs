@@ -16,6 +16,70 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+/// Version of the P2P protocol spoken by this node
+pub const PROTOCOL_VERSION: u32 = 1;
+
+/// Version of nodes predating versioned handshakes, whose version message
+/// only carries their node ID
+pub const LEGACY_PROTOCOL_VERSION: u32 = 0;
+
+/// First P2P protocol version relaying items through inventory messages
+pub const INV_PROTOCOL_VERSION: u32 = 1;
+
+/// Clock difference with a peer above which a warning gets logged
+pub const MAX_CLOCK_DRIFT_SECONDS: u64 = 120;
+
+/// Failed connection attempts before a host in the `new` bucket is evicted
+pub const HOST_MAX_FAILURES_NEW: u32 = 3;
+
+/// Failed connection attempts before a host that was once in the `tried`
+/// bucket is evicted
+pub const HOST_MAX_FAILURES_TRIED: u32 = 10;
+
+/// Maximum number of hosts in the `new` bucket
+pub const HOST_MAX_NEW: usize = 4096;
+
+/// Maximum number of hosts in the `new` bucket advertised by a single peer
+pub const HOST_MAX_NEW_PER_SOURCE: usize = 256;
+
+/// Interval in seconds between saves of the known hosts to disk
+pub const HOSTS_SAVE_INTERVAL_SECONDS: u64 = 300;
+
+/// Ban score added when a peer sends a packet with broken framing
+pub const BAN_SCORE_MALFORMED_PACKET: u32 = 50;
+
+/// Ban score added when a peer sends a message we fail to decode
+pub const BAN_SCORE_MALFORMED_MESSAGE: u32 = 10;
+
+/// Ban score added when a peer sends a command we have no dispatcher for
+pub const BAN_SCORE_UNKNOWN_COMMAND: u32 = 1;
+
+/// Maximum length of a packet command string
+pub const MAX_COMMAND_LEN: usize = 64;
+
+/// Default maximum length of a packet payload, used by messages that don't
+/// set their own limit, as well as for commands without a dispatcher
+pub const MAX_PAYLOAD_LEN: usize = 8 * 1024 * 1024;
+
+/// Maximum number of hashes carried by a single inv or getdata message
+pub const MAX_INV_ITEMS: usize = 1000;
+
+/// Number of inventory hashes remembered per channel as known by the peer
+pub const INV_KNOWN_CACHE_SIZE: usize = 16384;
+
+/// Time after which an unanswered getdata gets retried with another peer
+/// that announced the item
+pub const INV_REQUEST_TIMEOUT_SECONDS: u64 = 30;
+
+/// Interval in seconds between checks for timed out getdata requests
+pub const INV_RETRY_INTERVAL_SECONDS: u64 = 5;
+
+/// Ban score added when a peer sends an inv or getdata message with no items
+pub const BAN_SCORE_EMPTY_INV: u32 = 10;
+
+/// Maximum number of outbound peers saved as anchors across restarts
+pub const MAX_ANCHORS: usize = 2;
+
 /// Localnet addresses
 pub const LOCALNET: [&str; 5] = ["localhost", "0.0.0.0", "[::]", "127.0.0.1", "[::1]"];
 
@@ -0,0 +1,186 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+
+use async_std::sync::Arc;
+use futures::Future;
+use smol::Executor;
+use url::Url;
+
+use super::{
+    transport::{LinkConditions, MemNetwork, TransportName},
+    P2p, P2pPtr, Settings,
+};
+use crate::{util::async_util::msleep, Result};
+
+/// Used to give every harness its own endpoint names, so harnesses
+/// running in parallel tests don't interfere with each other.
+static HARNESS_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+/// Interval at which `wait_until` polls its condition
+const POLL_INTERVAL_MS: u64 = 50;
+
+/// A set of `P2p` instances talking to each other over the in-memory
+/// `mem://` transport, all running on the same executor.
+pub struct NetHarness {
+    executor: Arc<Executor<'static>>,
+    names: Vec<String>,
+    nodes: Vec<P2pPtr>,
+}
+
+impl NetHarness {
+    /// Create `n` nodes, each listening on its own `mem://` endpoint.
+    /// `configure` can adjust the settings of every node, given its index
+    /// and the addresses of all nodes.
+    pub async fn new<F>(executor: Arc<Executor<'static>>, n: usize, configure: F) -> Self
+    where
+        F: Fn(usize, &[Url], &mut Settings),
+    {
+        let id = HARNESS_COUNT.fetch_add(1, Ordering::SeqCst);
+        let names: Vec<String> = (0..n).map(|i| format!("harness{}-node{}", id, i)).collect();
+        let addrs: Vec<Url> =
+            names.iter().map(|name| Url::parse(&format!("mem://{}", name)).unwrap()).collect();
+
+        let mut nodes = Vec::with_capacity(n);
+        for (i, addr) in addrs.iter().enumerate() {
+            let mut settings = Settings {
+                inbound: vec![addr.clone()],
+                external_addr: vec![addr.clone()],
+                outbound_transports: vec![TransportName::Mem(None)],
+                localnet: true,
+                // Reconnect quickly once a partition heals
+                connect_timeout_seconds: 1,
+                outbound_retry_seconds: 1,
+                ..Default::default()
+            };
+            configure(i, &addrs, &mut settings);
+            nodes.push(P2p::new(settings).await);
+        }
+
+        Self { executor, names, nodes }
+    }
+
+    /// Create `n` nodes connected to each other through manual peers.
+    pub async fn full_mesh(executor: Arc<Executor<'static>>, n: usize) -> Self {
+        // Each node dials the ones before it, so every pair gets one channel
+        Self::new(executor, n, |i, addrs, settings| settings.peers = addrs[..i].to_vec()).await
+    }
+
+    /// Start all the nodes.
+    pub async fn start(&self) -> Result<()> {
+        for node in &self.nodes {
+            node.clone().start(self.executor.clone()).await?;
+            self.executor.spawn(node.clone().run(self.executor.clone())).detach();
+        }
+
+        Ok(())
+    }
+
+    /// Stop all the nodes.
+    pub async fn stop(&self) {
+        for node in &self.nodes {
+            node.stop().await;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn node(&self, i: usize) -> P2pPtr {
+        self.nodes[i].clone()
+    }
+
+    pub fn nodes(&self) -> &[P2pPtr] {
+        &self.nodes
+    }
+
+    /// Address node `i` listens on.
+    pub fn addr(&self, i: usize) -> Url {
+        Url::parse(&format!("mem://{}", self.names[i])).unwrap()
+    }
+
+    /// Set the conditions of the link between nodes `a` and `b`.
+    pub fn set_link(&self, a: usize, b: usize, conditions: LinkConditions) {
+        MemNetwork::global().set_link(&self.names[a], &self.names[b], conditions);
+    }
+
+    /// Cut the nodes in `a` off from the nodes in `b`.
+    pub fn partition(&self, a: &[usize], b: &[usize]) {
+        let (a, b) = (self.names_of(a), self.names_of(b));
+        MemNetwork::global().partition(&a, &b);
+    }
+
+    /// Undo a partition between the nodes in `a` and `b`.
+    pub fn heal(&self, a: &[usize], b: &[usize]) {
+        let (a, b) = (self.names_of(a), self.names_of(b));
+        MemNetwork::global().heal(&a, &b);
+    }
+
+    /// Number of channels node `i` currently has open.
+    pub async fn channel_count(&self, i: usize) -> usize {
+        self.nodes[i].channels().lock().await.len()
+    }
+
+    /// Wait until every node has at least `count` channels, or `timeout`
+    /// expires. Returns `true` on success.
+    pub async fn wait_connected(&self, count: usize, timeout: Duration) -> bool {
+        self.wait_until(timeout, || async {
+            for i in 0..self.len() {
+                if self.channel_count(i).await < count {
+                    return false
+                }
+            }
+            true
+        })
+        .await
+    }
+
+    /// Poll `condition` until it holds or `timeout` expires. Returns `true`
+    /// if the condition was met.
+    pub async fn wait_until<F, Fut>(&self, timeout: Duration, condition: F) -> bool
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = bool>,
+    {
+        let start = Instant::now();
+        loop {
+            if condition().await {
+                return true
+            }
+
+            if start.elapsed() >= timeout {
+                return false
+            }
+
+            msleep(POLL_INTERVAL_MS).await;
+        }
+    }
+
+    fn names_of(&self, nodes: &[usize]) -> Vec<&str> {
+        nodes.iter().map(|i| self.names[*i].as_str()).collect()
+    }
+}
@@ -17,25 +17,70 @@
  */
 
 use std::{
-    collections::{HashMap, HashSet},
+    cmp::Reverse,
+    collections::HashMap,
+    fs::{self, File},
+    io::{BufReader, Read, Write},
     net::IpAddr,
+    path::Path,
 };
 
 use async_std::sync::{Arc, Mutex};
+use darkfi_serial::{deserialize, serialize, SerialDecodable, SerialEncodable};
 use ipnet::{Ipv4Net, Ipv6Net};
 use iprange::IpRange;
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
+use rand::seq::SliceRandom;
 use url::Url;
 
-use super::constants::{IP4_PRIV_RANGES, IP6_PRIV_RANGES, LOCALNET};
-use crate::util::encoding::base32;
+use super::constants::{
+    HOST_MAX_FAILURES_NEW, HOST_MAX_FAILURES_TRIED, HOST_MAX_NEW, HOST_MAX_NEW_PER_SOURCE,
+    IP4_PRIV_RANGES, IP6_PRIV_RANGES, LOCALNET,
+};
+use crate::{
+    util::{encoding::base32, time::unix_timestamp},
+    Result,
+};
 
 /// Pointer to hosts class.
 pub type HostsPtr = Arc<Hosts>;
 
+/// Metadata we keep for every known host address.
+#[derive(Clone, Debug, Default, SerialEncodable, SerialDecodable)]
+pub struct HostInfo {
+    /// Last time (UNIX seconds) this address was advertised to us
+    pub last_seen: u64,
+    /// Last time (UNIX seconds) we connected to this address, 0 if never
+    pub last_success: u64,
+    /// Number of consecutive failed connection attempts
+    pub failures: u32,
+    /// Peer that advertised this address to us, if known
+    pub source: Option<Url>,
+}
+
+/// Serialized hosts entry, used for on-disk persistence.
+#[derive(SerialEncodable, SerialDecodable)]
+struct HostEntry {
+    addr: Url,
+    info: HostInfo,
+    tried: bool,
+}
+
+/// Host addresses split into two buckets, similar to Bitcoin's addrman.
+/// `new` holds addresses that were advertised to us but that we never
+/// connected to, while `tried` holds addresses we successfully connected
+/// to at least once.
+#[derive(Default)]
+struct HostBuckets {
+    new: HashMap<Url, HostInfo>,
+    tried: HashMap<Url, HostInfo>,
+}
+
 /// Manages a store of network addresses.
 pub struct Hosts {
-    addrs: Mutex<HashSet<Url>>,
+    buckets: Mutex<HostBuckets>,
+    /// Outbound peers of the previous run, tried before any other host
+    anchors: Mutex<Vec<Url>>,
     localnet: bool,
     ipv4_range: IpRange<Ipv4Net>,
     ipv6_range: IpRange<Ipv6Net>,
@@ -54,7 +99,13 @@ impl Hosts {
         ipv4_range.simplify();
         ipv6_range.simplify();
 
-        Arc::new(Self { addrs: Mutex::new(HashSet::new()), localnet, ipv4_range, ipv6_range })
+        Arc::new(Self {
+            buckets: Mutex::new(HostBuckets::default()),
+            anchors: Mutex::new(vec![]),
+            localnet,
+            ipv4_range,
+            ipv6_range,
+        })
     }
 
     /// Add a new host to the host list, after filtering.
@@ -68,13 +119,25 @@ impl Hosts {
             debug!(target: "net::hosts::store()", "hosts::store() [Localnet mode, skipping filterring.]");
             input_addrs
         };
-        let mut addrs_map = self.addrs.lock().await;
-        for addr in addrs {
-            addrs_map.insert(addr);
-        }
+        self.insert(addrs, None).await;
         debug!(target: "net::hosts::store()", "hosts::store() [End]");
     }
 
+    /// Add new hosts advertised by `source` to the host list, after filtering.
+    pub async fn store_from(&self, source: Url, input_addrs: Vec<Url>) {
+        debug!(target: "net::hosts::store_from()", "hosts::store_from() [Start]");
+        let addrs = if !self.localnet {
+            let filtered = filter_localnet(input_addrs);
+            let filtered = filter_invalid(&self.ipv4_range, &self.ipv6_range, filtered);
+            filtered.into_keys().collect()
+        } else {
+            debug!(target: "net::hosts::store_from()", "hosts::store_from() [Localnet mode, skipping filterring.]");
+            input_addrs
+        };
+        self.insert(addrs, Some(source)).await;
+        debug!(target: "net::hosts::store_from()", "hosts::store_from() [End]");
+    }
+
     /// Add a new hosts external adders to the host list, after filtering and verifying
     /// the address url resolves to the provided connection address.
     pub async fn store_ext(&self, connection_addr: Url, input_addrs: Vec<Url>) {
@@ -82,32 +145,257 @@ impl Hosts {
         let addrs = if !self.localnet {
             let filtered = filter_localnet(input_addrs);
             let filtered = filter_invalid(&self.ipv4_range, &self.ipv6_range, filtered);
-            filter_non_resolving(connection_addr, filtered)
+            filter_non_resolving(connection_addr.clone(), filtered)
         } else {
             debug!(target: "net::hosts::store_ext()", "hosts::store_ext() [Localnet mode, skipping filterring.]");
             input_addrs
         };
-        let mut addrs_map = self.addrs.lock().await;
+        self.insert(addrs, Some(connection_addr)).await;
+        debug!(target: "net::hosts::store_ext()", "hosts::store_ext() [End]");
+    }
+
+    /// Insert already filtered addresses. Unknown addresses go into the
+    /// `new` bucket, while known ones only get their `last_seen` refreshed.
+    /// The `new` bucket and each source's share of it are capped, so a single
+    /// peer can't flood it and push out the addresses advertised by others.
+    async fn insert(&self, mut addrs: Vec<Url>, source: Option<Url>) {
+        let now = unix_timestamp().unwrap_or(0);
+        addrs.truncate(HOST_MAX_NEW_PER_SOURCE);
+
+        let buckets = &mut *self.buckets.lock().await;
+        let mut from_source = match &source {
+            Some(s) => buckets.new.values().filter(|info| info.source.as_ref() == Some(s)).count(),
+            None => 0,
+        };
+
         for addr in addrs {
-            addrs_map.insert(addr);
+            if let Some(info) = buckets.tried.get_mut(&addr) {
+                info.last_seen = now;
+                continue
+            }
+
+            if let Some(info) = buckets.new.get_mut(&addr) {
+                info.last_seen = now;
+                continue
+            }
+
+            // A source over its share makes room by evicting its own worst address
+            if source.is_some() && from_source >= HOST_MAX_NEW_PER_SOURCE {
+                evict_worst(&mut buckets.new, source.as_ref());
+                from_source -= 1;
+            }
+
+            // A full bucket makes room by evicting its worst address
+            if buckets.new.len() >= HOST_MAX_NEW {
+                if let Some(info) = evict_worst(&mut buckets.new, None) {
+                    if source.is_some() && info.source == source {
+                        from_source -= 1;
+                    }
+                }
+            }
+
+            let info = HostInfo { last_seen: now, source: source.clone(), ..Default::default() };
+            buckets.new.insert(addr, info);
+            if source.is_some() {
+                from_source += 1;
+            }
+        }
+    }
+
+    /// Mark an address as successfully connected. The address gets moved
+    /// into the `tried` bucket and its failure counter is reset.
+    pub async fn mark_connected(&self, addr: &Url) {
+        let now = unix_timestamp().unwrap_or(0);
+        let buckets = &mut *self.buckets.lock().await;
+        let mut info = match buckets.new.remove(addr) {
+            Some(info) => info,
+            None => buckets.tried.remove(addr).unwrap_or_default(),
+        };
+        info.last_success = now;
+        info.last_seen = now;
+        info.failures = 0;
+        buckets.tried.insert(addr.clone(), info);
+    }
+
+    /// Record a failed connection attempt to an address. Hosts from the
+    /// `tried` bucket get demoted to the `new` bucket, so they are no longer
+    /// preferred for outbound connections until we connect to them again.
+    /// Addresses exceeding their failure limit get evicted from the host list,
+    /// with hosts we once connected to being given more attempts.
+    pub async fn mark_failed(&self, addr: &Url) {
+        let buckets = &mut *self.buckets.lock().await;
+
+        if let Some(info) = buckets.tried.remove(addr) {
+            debug!(target: "net::hosts::mark_failed()", "Demoting tried host {}", addr);
+            buckets.new.insert(addr.clone(), info);
+        }
+
+        if let Some(info) = buckets.new.get_mut(addr) {
+            info.failures += 1;
+            let max_failures = match info.last_success {
+                0 => HOST_MAX_FAILURES_NEW,
+                _ => HOST_MAX_FAILURES_TRIED,
+            };
+            if info.failures >= max_failures {
+                debug!(target: "net::hosts::mark_failed()", "Evicting host {}", addr);
+                buckets.new.remove(addr);
+            }
         }
-        debug!(target: "net::hosts::store_ext()", "hosts::store_ext() [End]");
     }
 
     /// Return the list of hosts.
     pub async fn load_all(&self) -> Vec<Url> {
-        self.addrs.lock().await.iter().cloned().collect()
+        let buckets = self.buckets.lock().await;
+        buckets.tried.keys().chain(buckets.new.keys()).cloned().collect()
+    }
+
+    /// Return the list of hosts ordered by preference for outbound
+    /// connections. The shuffled `tried` bucket comes first, followed by the
+    /// `new` bucket ordered by failure count.
+    pub async fn load_outbound(&self) -> Vec<Url> {
+        let buckets = self.buckets.lock().await;
+        let mut rng = rand::thread_rng();
+
+        let mut tried: Vec<&Url> = buckets.tried.keys().collect();
+        tried.shuffle(&mut rng);
+
+        let mut new: Vec<(&Url, &HostInfo)> = buckets.new.iter().collect();
+        new.shuffle(&mut rng);
+        new.sort_by_key(|(_, info)| info.failures);
+
+        tried.into_iter().chain(new.into_iter().map(|(addr, _)| addr)).cloned().collect()
+    }
+
+    /// Return the metadata of a known host, if any.
+    pub async fn get_info(&self, addr: &Url) -> Option<HostInfo> {
+        let buckets = self.buckets.lock().await;
+        buckets.tried.get(addr).or_else(|| buckets.new.get(addr)).cloned()
     }
 
     /// Remove an Url from the list
     pub async fn remove(&self, url: &Url) -> bool {
-        self.addrs.lock().await.remove(url)
+        let buckets = &mut *self.buckets.lock().await;
+        buckets.tried.remove(url).is_some() | buckets.new.remove(url).is_some()
     }
 
     /// Check if the host list is empty.
     pub async fn is_empty(&self) -> bool {
-        self.addrs.lock().await.is_empty()
+        let buckets = self.buckets.lock().await;
+        buckets.tried.is_empty() && buckets.new.is_empty()
+    }
+
+    /// Check if we have any host we previously connected to.
+    pub async fn is_tried_empty(&self) -> bool {
+        self.buckets.lock().await.tried.is_empty()
+    }
+
+    /// Take the next anchor to connect to, if any is left.
+    pub async fn take_anchor(&self) -> Option<Url> {
+        let mut anchors = self.anchors.lock().await;
+        if anchors.is_empty() {
+            return None
+        }
+
+        Some(anchors.remove(0))
+    }
+
+    /// Load the anchors saved by the previous run from given path. The
+    /// file gets removed afterwards, so anchors that made us crash don't
+    /// get reused on every restart.
+    pub async fn load_anchors(&self, path: &Path) -> Result<()> {
+        let bytes = fs::read(path)?;
+        fs::remove_file(path)?;
+
+        let anchors: Vec<Url> = deserialize(&bytes)?;
+        info!(target: "net::hosts::load_anchors()", "Loaded {} anchors from {:?}", anchors.len(), path);
+        *self.anchors.lock().await = anchors;
+        Ok(())
+    }
+
+    /// Save the given anchors to given path.
+    pub async fn save_anchors(&self, path: &Path, anchors: &[Url]) -> Result<()> {
+        let mut file = File::create(path)?;
+        file.write_all(&serialize(&anchors.to_vec()))?;
+        info!(target: "net::hosts::save_anchors()", "Saved {} anchors to {:?}", anchors.len(), path);
+        Ok(())
     }
+
+    /// Load a previously saved host list from given path. Entries are
+    /// merged with any hosts already in memory.
+    pub async fn load_hosts(&self, path: &Path) -> Result<()> {
+        let file = File::open(path)?;
+        let mut reader = BufReader::new(file);
+        let mut bytes = vec![];
+        reader.read_to_end(&mut bytes)?;
+
+        let entries: Vec<HostEntry> = deserialize(&bytes)?;
+        let buckets = &mut *self.buckets.lock().await;
+        for entry in entries {
+            match entry.tried {
+                true => buckets.tried.insert(entry.addr, entry.info),
+                false => buckets.new.insert(entry.addr, entry.info),
+            };
+        }
+
+        // Files saved before the `new` bucket was capped only keep its best hosts
+        if buckets.new.len() > HOST_MAX_NEW {
+            let mut new: Vec<(Url, HostInfo)> = buckets.new.drain().collect();
+            new.sort_by_key(|(_, info)| Reverse(host_badness(info)));
+            new.truncate(HOST_MAX_NEW);
+            buckets.new = new.into_iter().collect();
+        }
+
+        info!(
+            target: "net::hosts::load_hosts()",
+            "Loaded {} tried and {} new hosts from {:?}",
+            buckets.tried.len(),
+            buckets.new.len(),
+            path
+        );
+        Ok(())
+    }
+
+    /// Save the current host list to given path.
+    pub async fn save_hosts(&self, path: &Path) -> Result<()> {
+        let entries: Vec<HostEntry> = {
+            let buckets = self.buckets.lock().await;
+            let tried = buckets.tried.iter().map(|(addr, info)| HostEntry {
+                addr: addr.clone(),
+                info: info.clone(),
+                tried: true,
+            });
+            let new = buckets.new.iter().map(|(addr, info)| HostEntry {
+                addr: addr.clone(),
+                info: info.clone(),
+                tried: false,
+            });
+            tried.chain(new).collect()
+        };
+
+        let mut file = File::create(path)?;
+        file.write_all(&serialize(&entries))?;
+        info!(target: "net::hosts::save_hosts()", "Saved {} hosts to {:?}", entries.len(), path);
+        Ok(())
+    }
+}
+
+/// Eviction order of `new` bucket hosts: the ones that failed the most go
+/// first, then the ones advertised the longest ago.
+fn host_badness(info: &HostInfo) -> (Reverse<u32>, u64) {
+    (Reverse(info.failures), info.last_seen)
+}
+
+/// Evict the worst host of the `new` bucket, only considering the hosts
+/// advertised by `source` if given. Returns the evicted host metadata.
+fn evict_worst(new: &mut HashMap<Url, HostInfo>, source: Option<&Url>) -> Option<HostInfo> {
+    let addr = new
+        .iter()
+        .filter(|(_, info)| source.is_none() || info.source.as_ref() == source)
+        .min_by_key(|(_, info)| host_badness(info))
+        .map(|(addr, _)| addr.clone())?;
+
+    debug!(target: "net::hosts::evict_worst()", "Evicting host {}", addr);
+    new.remove(&addr)
 }
 
 /// Auxiliary function to filter localnet hosts.
@@ -308,8 +596,13 @@ mod tests {
     use url::Url;
 
     use crate::net::{
-        constants::{IP4_PRIV_RANGES, IP6_PRIV_RANGES},
-        hosts::{filter_invalid, filter_localnet, filter_non_resolving, is_valid_onion},
+        constants::{
+            HOST_MAX_FAILURES_NEW, HOST_MAX_FAILURES_TRIED, HOST_MAX_NEW, HOST_MAX_NEW_PER_SOURCE,
+            IP4_PRIV_RANGES, IP6_PRIV_RANGES,
+        },
+        hosts::{
+            filter_invalid, filter_localnet, filter_non_resolving, is_valid_onion, HostInfo, Hosts,
+        },
     };
 
     #[test]
@@ -473,6 +766,126 @@ mod tests {
         assert_eq!(fake_output_addrs, filtered);
     }
 
+    #[async_std::test]
+    async fn test_host_buckets() {
+        let hosts = Hosts::new(true);
+
+        let a = Url::parse("tcp://127.0.0.1:13333").unwrap();
+        let b = Url::parse("tcp://127.0.0.1:13334").unwrap();
+        let c = Url::parse("tcp://127.0.0.1:13335").unwrap();
+        let source = Url::parse("tcp://127.0.0.1:13336").unwrap();
+
+        hosts.store_from(source.clone(), vec![a.clone(), b.clone(), c.clone()]).await;
+        assert!(hosts.is_tried_empty().await);
+        assert_eq!(hosts.get_info(&a).await.unwrap().source, Some(source));
+
+        // Connected hosts move to the tried bucket and are preferred
+        hosts.mark_connected(&b).await;
+        assert!(!hosts.is_tried_empty().await);
+        assert_eq!(hosts.load_outbound().await[0], b);
+
+        // New hosts get evicted after too many failures
+        for _ in 0..HOST_MAX_FAILURES_NEW {
+            hosts.mark_failed(&c).await;
+        }
+        assert!(hosts.get_info(&c).await.is_none());
+
+        // Failing tried hosts get demoted behind the other new hosts
+        hosts.mark_failed(&b).await;
+        assert!(hosts.is_tried_empty().await);
+        assert_eq!(hosts.get_info(&b).await.unwrap().failures, 1);
+        assert_eq!(hosts.load_outbound().await, vec![a.clone(), b.clone()]);
+
+        // Hosts we once connected to get more attempts before eviction
+        for _ in 1..HOST_MAX_FAILURES_NEW {
+            hosts.mark_failed(&b).await;
+        }
+        assert!(hosts.get_info(&b).await.is_some());
+        for _ in HOST_MAX_FAILURES_NEW..HOST_MAX_FAILURES_TRIED {
+            hosts.mark_failed(&b).await;
+        }
+        assert!(hosts.get_info(&b).await.is_none());
+
+        // Reconnecting promotes the host again
+        hosts.mark_connected(&b).await;
+        assert_eq!(hosts.load_outbound().await[0], b);
+
+        // Buckets survive a save and load roundtrip
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("hosts.bin");
+        hosts.save_hosts(&path).await.unwrap();
+        let loaded = Hosts::new(true);
+        loaded.load_hosts(&path).await.unwrap();
+
+        let loaded_addrs: HashSet<Url> = loaded.load_all().await.into_iter().collect();
+        assert_eq!(loaded_addrs, HashSet::from([a, b.clone()]));
+        assert_eq!(loaded.load_outbound().await[0], b);
+        assert!(loaded.get_info(&b).await.unwrap().last_success > 0);
+    }
+
+    #[async_std::test]
+    async fn test_new_bucket_caps() {
+        let hosts = Hosts::new(true);
+        let addr = |i: usize| {
+            Url::parse(&format!("tcp://10.{}.{}.{}:1", i >> 16, (i >> 8) & 0xff, i & 0xff)).unwrap()
+        };
+        let source = |i: usize| Url::parse(&format!("tcp://192.168.0.{}:1", i)).unwrap();
+        let from_source = |new: &HashMap<Url, HostInfo>, s: &Url| {
+            new.values().filter(|info| info.source.as_ref() == Some(s)).count()
+        };
+
+        // A single advertisement only fills the source's share
+        let addrs: Vec<Url> = (0..HOST_MAX_NEW_PER_SOURCE + 100).map(addr).collect();
+        hosts.store_from(source(0), addrs).await;
+        assert_eq!(hosts.buckets.lock().await.new.len(), HOST_MAX_NEW_PER_SOURCE);
+
+        // Further advertisements replace the source's own hosts
+        let addrs: Vec<Url> = (100_000..100_010).map(addr).collect();
+        hosts.store_from(source(0), addrs).await;
+        let buckets = hosts.buckets.lock().await;
+        assert_eq!(from_source(&buckets.new, &source(0)), HOST_MAX_NEW_PER_SOURCE);
+        assert_eq!(buckets.new.len(), HOST_MAX_NEW_PER_SOURCE);
+        drop(buckets);
+
+        // The bucket stays capped when many sources fill it
+        let sources = HOST_MAX_NEW / HOST_MAX_NEW_PER_SOURCE + 1;
+        for i in 1..=sources {
+            let start = i * HOST_MAX_NEW_PER_SOURCE;
+            let addrs: Vec<Url> = (start..start + HOST_MAX_NEW_PER_SOURCE).map(addr).collect();
+            hosts.store_from(source(i), addrs).await;
+        }
+        assert_eq!(hosts.buckets.lock().await.new.len(), HOST_MAX_NEW);
+
+        // Failing hosts get evicted first
+        let bad = hosts.buckets.lock().await.new.keys().next().unwrap().clone();
+        hosts.mark_failed(&bad).await;
+        assert!(hosts.get_info(&bad).await.is_some());
+        hosts.store_from(source(sources + 1), vec![addr(200_000)]).await;
+        assert!(hosts.get_info(&bad).await.is_none());
+        assert!(hosts.get_info(&addr(200_000)).await.is_some());
+        assert_eq!(hosts.buckets.lock().await.new.len(), HOST_MAX_NEW);
+    }
+
+    #[async_std::test]
+    async fn test_anchors() {
+        let hosts = Hosts::new(true);
+        assert!(hosts.take_anchor().await.is_none());
+
+        let a = Url::parse("tcp://127.0.0.1:13337").unwrap();
+        let b = Url::parse("tcp://127.0.0.1:13338").unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("anchors.bin");
+        hosts.save_anchors(&path, &[a.clone(), b.clone()]).await.unwrap();
+        hosts.load_anchors(&path).await.unwrap();
+
+        // Anchors are used a single time, in order
+        assert!(!path.exists());
+        assert_eq!(hosts.take_anchor().await, Some(a));
+        assert_eq!(hosts.take_anchor().await, Some(b));
+        assert!(hosts.take_anchor().await.is_none());
+    }
+
     #[test]
     fn test_is_valid_onion() {
         // Valid onion
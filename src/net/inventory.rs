@@ -0,0 +1,240 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+use async_std::sync::Arc;
+use async_trait::async_trait;
+use url::Url;
+
+use super::message::Message;
+
+/// Hash identifying an item announced through inventory messages.
+pub type InvHash = [u8; 32];
+
+/// A message which can be relayed by announcing its hash, instead of
+/// sending it in full to every peer.
+pub trait InvItem: Message + Clone {
+    fn inv_hash(&self) -> InvHash;
+}
+
+/// Atomic pointer to an inventory store.
+pub type InvStorePtr<M> = Arc<dyn InvStore<M>>;
+
+/// Application side storage of relayed items. Used to decide which
+/// announced items we need, and to serve the items peers request from us.
+#[async_trait]
+pub trait InvStore<M: InvItem>: Send + Sync {
+    /// Returns `true` if we already have the item, so it's not requested.
+    async fn contains(&self, hash: &InvHash) -> bool;
+
+    /// Fetch an item a peer asked for, `None` if we don't have it (anymore).
+    async fn get(&self, hash: &InvHash) -> Option<M>;
+}
+
+/// Bounded set of inventory hashes. Once full, the oldest hash is
+/// forgotten to make room for a new one.
+pub struct InvCache {
+    hashes: HashSet<InvHash>,
+    order: VecDeque<InvHash>,
+    capacity: usize,
+}
+
+impl InvCache {
+    pub fn new(capacity: usize) -> Self {
+        Self { hashes: HashSet::new(), order: VecDeque::new(), capacity }
+    }
+
+    /// Insert a hash, returning `true` if it wasn't in the cache yet.
+    pub fn insert(&mut self, hash: InvHash) -> bool {
+        if !self.hashes.insert(hash) {
+            return false
+        }
+
+        self.order.push_back(hash);
+        if self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.hashes.remove(&oldest);
+            }
+        }
+
+        true
+    }
+
+    pub fn contains(&self, hash: &InvHash) -> bool {
+        self.hashes.contains(hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+}
+
+/// A getdata request of an announced item, along with the other peers
+/// that announced it and can be asked next.
+struct InvRequest {
+    kind: String,
+    peer: Url,
+    requested_at: Instant,
+    announcers: VecDeque<Url>,
+}
+
+/// Outstanding getdata requests of announced items. Each item is requested
+/// from a single peer at a time. When that peer doesn't deliver it in time,
+/// the request moves on to the next peer that announced it.
+pub struct InvRequests {
+    requests: HashMap<InvHash, InvRequest>,
+    timeout: Duration,
+}
+
+impl InvRequests {
+    pub fn new(timeout: Duration) -> Self {
+        Self { requests: HashMap::new(), timeout }
+    }
+
+    /// Register an announcement of an item of given kind by given peer.
+    /// Returns `true` if the item has to be requested from that peer now.
+    /// Otherwise a request is already in flight, and the peer is kept as
+    /// a fallback for it.
+    pub fn announced(&mut self, kind: &str, hash: InvHash, peer: &Url) -> bool {
+        let Some(request) = self.requests.get_mut(&hash) else {
+            let request = InvRequest {
+                kind: kind.to_string(),
+                peer: peer.clone(),
+                requested_at: Instant::now(),
+                announcers: VecDeque::new(),
+            };
+            self.requests.insert(hash, request);
+            return true
+        };
+
+        if request.peer != *peer && !request.announcers.contains(peer) {
+            request.announcers.push_back(peer.clone());
+        }
+
+        false
+    }
+
+    /// Forget the request of a received item.
+    pub fn received(&mut self, hash: &InvHash) {
+        self.requests.remove(hash);
+    }
+
+    /// Move timed out requests on to the next of their announcers that is
+    /// still connected, returning the peer, kind and hash of the items to
+    /// request again. Requests without any such announcer left are dropped,
+    /// so the item gets requested again once announced.
+    pub fn retry_expired(&mut self, connected: &HashSet<Url>) -> Vec<(Url, String, InvHash)> {
+        let timeout = self.timeout;
+        let mut retries = vec![];
+        self.requests.retain(|hash, request| {
+            if request.requested_at.elapsed() < timeout {
+                return true
+            }
+
+            while let Some(peer) = request.announcers.pop_front() {
+                if connected.contains(&peer) {
+                    request.peer = peer.clone();
+                    request.requested_at = Instant::now();
+                    retries.push((peer, request.kind.clone(), *hash));
+                    return true
+                }
+            }
+
+            false
+        });
+
+        retries
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_inv_cache() {
+        let mut cache = InvCache::new(2);
+        assert!(cache.is_empty());
+
+        assert!(cache.insert([1; 32]));
+        assert!(!cache.insert([1; 32]));
+        assert!(cache.insert([2; 32]));
+        assert_eq!(cache.len(), 2);
+
+        // Oldest hash gets evicted once over capacity
+        assert!(cache.insert([3; 32]));
+        assert_eq!(cache.len(), 2);
+        assert!(!cache.contains(&[1; 32]));
+        assert!(cache.contains(&[2; 32]));
+        assert!(cache.contains(&[3; 32]));
+
+        // An evicted hash counts as new again
+        assert!(cache.insert([1; 32]));
+        assert!(!cache.contains(&[2; 32]));
+    }
+
+    #[test]
+    fn test_inv_requests() {
+        let peers: Vec<Url> =
+            (0..3).map(|i| Url::parse(&format!("tcp://127.0.0.1:{}", 1000 + i)).unwrap()).collect();
+        let connected: HashSet<Url> = [peers[0].clone(), peers[2].clone()].into();
+
+        // Only the first announcer gets asked while the request is in flight
+        let mut requests = InvRequests::new(Duration::from_secs(30));
+        assert!(requests.announced("tx", [1; 32], &peers[0]));
+        assert!(!requests.announced("tx", [1; 32], &peers[1]));
+        assert!(!requests.announced("tx", [1; 32], &peers[0]));
+        assert!(requests.retry_expired(&connected).is_empty());
+        requests.received(&[1; 32]);
+        assert!(requests.is_empty());
+        assert!(requests.announced("tx", [1; 32], &peers[1]));
+
+        // Timed out requests move on to the next connected announcer
+        let mut requests = InvRequests::new(Duration::ZERO);
+        assert!(requests.announced("tx", [1; 32], &peers[0]));
+        assert!(!requests.announced("tx", [1; 32], &peers[1]));
+        assert!(!requests.announced("tx", [1; 32], &peers[2]));
+        assert!(requests.announced("proposal", [2; 32], &peers[0]));
+        assert_eq!(
+            requests.retry_expired(&connected),
+            vec![(peers[2].clone(), "tx".to_string(), [1; 32])]
+        );
+        assert_eq!(requests.len(), 1);
+
+        // Until no announcer is left
+        assert!(requests.retry_expired(&connected).is_empty());
+        assert!(requests.is_empty());
+        assert!(requests.announced("tx", [1; 32], &peers[1]));
+    }
+}
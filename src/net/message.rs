@@ -16,18 +16,54 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
+use std::io;
+
 use darkfi_serial::{Decodable, Encodable, SerialDecodable, SerialEncodable, VarInt};
 use futures::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 use log::debug;
 use url::Url;
 
+use super::{
+    constants::{LEGACY_PROTOCOL_VERSION, MAX_COMMAND_LEN, MAX_INV_ITEMS, MAX_PAYLOAD_LEN},
+    inventory::InvHash,
+    protocol::{ServiceBitflag, SERVICE_NONE},
+};
 use crate::{Error, Result};
 
 const MAGIC_BYTES: [u8; 4] = [0xd9, 0xef, 0xb6, 0x7d];
 
+/// Priority of a message in the send queue of a channel. Queued messages
+/// of a higher priority always go out first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MessagePriority {
+    /// Connection upkeep: handshake, keep-alive and address exchange
+    Control,
+    /// Time sensitive consensus traffic
+    Consensus,
+    /// Gossip of transactions and other items, which can be fetched
+    /// again from other peers
+    Relay,
+    /// Requests, responses and sync data, which must not get lost
+    Bulk,
+}
+
 /// Generic message template.
 pub trait Message: 'static + Encodable + Decodable + Send + Sync {
     fn name() -> &'static str;
+
+    /// Maximum length of the encoded message. Packets carrying a larger
+    /// payload get rejected before it is read from the stream.
+    fn max_len() -> usize {
+        MAX_PAYLOAD_LEN
+    }
+
+    /// Priority of the message in the send queue of a channel. Messages
+    /// default to [`MessagePriority::Bulk`] so they never get dropped, as
+    /// a peer may be waiting on them. Gossip opts into
+    /// [`MessagePriority::Relay`].
+    fn priority() -> MessagePriority {
+        MessagePriority::Bulk
+    }
 }
 
 /// Outbound keep-alive message.
@@ -60,9 +96,61 @@ pub struct ExtAddrsMessage {
 }
 
 /// Requests version information of outbound connection.
-#[derive(SerialEncodable, SerialDecodable)]
+///
+/// Nodes speaking [`LEGACY_PROTOCOL_VERSION`] only send and read the
+/// `node_id`, and ignore trailing bytes. The other fields are thus encoded
+/// after it, and take legacy defaults when a legacy node leaves them out.
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct VersionMessage {
+    /// Only used for debugging. Compromises privacy when set.
     pub node_id: String,
+    /// P2P protocol version spoken by the node
+    pub version: u32,
+    /// Optional services supported by the node
+    pub services: ServiceBitflag,
+    /// External addresses the node advertises
+    pub external_addrs: Vec<Url>,
+    /// UNIX timestamp of the node at the time of sending, 0 if unknown
+    pub timestamp: u64,
+}
+
+impl Encodable for VersionMessage {
+    fn encode<W: io::Write>(&self, mut s: W) -> std::result::Result<usize, io::Error> {
+        let mut len = 0;
+        len += self.node_id.encode(&mut s)?;
+        len += self.version.encode(&mut s)?;
+        len += self.services.encode(&mut s)?;
+        len += self.external_addrs.encode(&mut s)?;
+        len += self.timestamp.encode(&mut s)?;
+        Ok(len)
+    }
+}
+
+impl Decodable for VersionMessage {
+    fn decode<D: io::Read>(mut d: D) -> std::result::Result<Self, io::Error> {
+        let node_id = String::decode(&mut d)?;
+
+        let mut rest = vec![];
+        d.read_to_end(&mut rest)?;
+        if rest.is_empty() {
+            return Ok(Self {
+                node_id,
+                version: LEGACY_PROTOCOL_VERSION,
+                services: SERVICE_NONE,
+                external_addrs: vec![],
+                timestamp: 0,
+            })
+        }
+
+        let mut rest = io::Cursor::new(rest);
+        Ok(Self {
+            node_id,
+            version: Decodable::decode(&mut rest)?,
+            services: Decodable::decode(&mut rest)?,
+            external_addrs: Decodable::decode(&mut rest)?,
+            timestamp: Decodable::decode(&mut rest)?,
+        })
+    }
 }
 
 /// Sends version information to inbound connection. Response to VersionMessage.
@@ -72,46 +160,137 @@ pub struct VerackMessage {
     pub app: String,
 }
 
+/// Announces items we have to a peer. `kind` is the name of the message
+/// type the hashes refer to.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct InvMessage {
+    pub kind: String,
+    pub hashes: Vec<InvHash>,
+}
+
+/// Requests announced items from a peer. Response to InvMessage.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct GetDataMessage {
+    pub kind: String,
+    pub hashes: Vec<InvHash>,
+}
+
+/// Maximum encoded length of inventory messages: the kind string
+/// followed by at most `MAX_INV_ITEMS` hashes, both length-prefixed.
+const MAX_INV_LEN: usize = 9 + MAX_COMMAND_LEN + 9 + MAX_INV_ITEMS * 32;
+
 impl Message for PingMessage {
     fn name() -> &'static str {
         "ping"
     }
+
+    fn max_len() -> usize {
+        4
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for PongMessage {
     fn name() -> &'static str {
         "pong"
     }
+
+    fn max_len() -> usize {
+        4
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for GetAddrsMessage {
     fn name() -> &'static str {
         "getaddr"
     }
+
+    fn max_len() -> usize {
+        0
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for AddrsMessage {
     fn name() -> &'static str {
         "addr"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for ExtAddrsMessage {
     fn name() -> &'static str {
         "extaddr"
     }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for VersionMessage {
     fn name() -> &'static str {
         "version"
     }
+
+    fn max_len() -> usize {
+        4096
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
 }
 
 impl Message for VerackMessage {
     fn name() -> &'static str {
         "verack"
     }
+
+    fn max_len() -> usize {
+        1024
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Control
+    }
+}
+
+impl Message for InvMessage {
+    fn name() -> &'static str {
+        "relayinv"
+    }
+
+    fn max_len() -> usize {
+        MAX_INV_LEN
+    }
+
+    fn priority() -> MessagePriority {
+        MessagePriority::Relay
+    }
+}
+
+impl Message for GetDataMessage {
+    fn name() -> &'static str {
+        "relaygetdata"
+    }
+
+    fn max_len() -> usize {
+        MAX_INV_LEN
+    }
 }
 
 /// Packets are the base type read from the network. Converted to messages and
@@ -121,8 +300,21 @@ pub struct Packet {
     pub payload: Vec<u8>,
 }
 
-/// Reads and decodes an inbound payload.
-pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result<Packet> {
+impl Packet {
+    /// Number of bytes the packet takes on the wire, framing included.
+    pub fn wire_len(&self) -> usize {
+        MAGIC_BYTES.len() +
+            VarInt(self.command.len() as u64).length() +
+            self.command.len() +
+            VarInt(self.payload.len() as u64).length() +
+            self.payload.len()
+    }
+}
+
+/// Reads the header of an inbound packet and returns its command.
+/// Must be followed by [`read_payload`], using the payload limit of the
+/// returned command.
+pub async fn read_command<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result<String> {
     // Packets have a 4 byte header of magic digits
     // This is used for network debugging
     let mut magic = [0u8; 4];
@@ -136,24 +328,37 @@ pub async fn read_packet<R: AsyncRead + Unpin + Sized>(stream: &mut R) -> Result
     }
 
     // The type of the message
-    let command_len = VarInt::decode_async(stream).await?.0 as usize;
-    let mut cmd = vec![0u8; command_len];
-    if command_len > 0 {
-        stream.read_exact(&mut cmd).await?;
+    let command_len = VarInt::decode_async(stream).await?.0;
+    if command_len == 0 || command_len > MAX_COMMAND_LEN as u64 {
+        return Err(Error::MalformedPacket)
     }
-    let cmd = String::from_utf8(cmd)?;
+    let mut cmd = vec![0u8; command_len as usize];
+    stream.read_exact(&mut cmd).await?;
+    let Ok(cmd) = String::from_utf8(cmd) else { return Err(Error::MalformedPacket) };
     debug!(target: "net::message", "read command: {}", cmd);
 
-    let payload_len = VarInt::decode_async(stream).await?.0 as usize;
+    Ok(cmd)
+}
+
+/// Reads the payload of an inbound packet. Payloads longer than `max_len`
+/// are rejected before anything gets allocated for them.
+pub async fn read_payload<R: AsyncRead + Unpin + Sized>(
+    stream: &mut R,
+    max_len: usize,
+) -> Result<Vec<u8>> {
+    let payload_len = VarInt::decode_async(stream).await?.0;
+    if payload_len > max_len as u64 {
+        return Err(Error::PacketTooLarge(payload_len))
+    }
 
     // The message-dependent data (see message types)
-    let mut payload = vec![0u8; payload_len];
+    let mut payload = vec![0u8; payload_len as usize];
     if payload_len > 0 {
         stream.read_exact(&mut payload).await?;
     }
     debug!(target: "net::message", "read payload {} bytes", payload_len);
 
-    Ok(Packet { command: cmd, payload })
+    Ok(payload)
 }
 
 /// Sends an outbound packet by writing data to TCP stream.
@@ -178,5 +383,139 @@ pub async fn send_packet<W: AsyncWrite + Unpin + Sized>(
     }
     debug!(target: "net::message", "sent payload {} bytes", packet.payload.len() as u64);
 
+    // Buffered transports (e.g. Noise) only send out data on flush
+    stream.flush().await?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use darkfi_serial::{deserialize, serialize, Decodable};
+    use futures::io::Cursor;
+    use rand::{rngs::OsRng, Rng, RngCore};
+    use url::Url;
+
+    use super::{read_command, read_payload, send_packet, Packet, VersionMessage, MAGIC_BYTES};
+    use crate::{
+        net::constants::{LEGACY_PROTOCOL_VERSION, MAX_COMMAND_LEN, PROTOCOL_VERSION},
+        Error,
+    };
+
+    async fn encode(command: &str, payload: Vec<u8>) -> Vec<u8> {
+        let mut buf = Cursor::new(vec![]);
+        send_packet(&mut buf, Packet { command: command.to_string(), payload }).await.unwrap();
+        buf.into_inner()
+    }
+
+    async fn decode(bytes: &[u8], max_len: usize) -> crate::Result<(String, Vec<u8>)> {
+        let mut stream = Cursor::new(bytes);
+        let command = read_command(&mut stream).await?;
+        let payload = read_payload(&mut stream, max_len).await?;
+        Ok((command, payload))
+    }
+
+    #[async_std::test]
+    async fn test_packet_roundtrip() {
+        let frame = encode("ping", vec![1, 2, 3, 4]).await;
+        let (command, payload) = decode(&frame, 4).await.unwrap();
+        assert_eq!(command, "ping");
+        assert_eq!(payload, vec![1, 2, 3, 4]);
+
+        // Accounted size matches what actually hits the wire
+        let packet = Packet { command, payload };
+        assert_eq!(packet.wire_len(), frame.len());
+        let packet = Packet { command: "addr".to_string(), payload: vec![0xaa; 300] };
+        assert_eq!(packet.wire_len(), encode("addr", vec![0xaa; 300]).await.len());
+    }
+
+    #[async_std::test]
+    async fn test_truncated_frames() {
+        let frame = encode("addr", vec![0xaa; 64]).await;
+
+        // Every strict prefix of a valid frame must fail to decode
+        for len in 0..frame.len() {
+            assert!(decode(&frame[..len], 64).await.is_err());
+        }
+    }
+
+    #[async_std::test]
+    async fn test_oversized_frames() {
+        let frame = encode("addr", vec![0xaa; 65]).await;
+        assert!(matches!(decode(&frame, 64).await, Err(Error::PacketTooLarge(65))));
+
+        // A huge announced payload length gets rejected without the payload
+        // being present or allocated.
+        let mut frame = MAGIC_BYTES.to_vec();
+        frame.extend_from_slice(&[4, b'p', b'i', b'n', b'g']);
+        frame.extend_from_slice(&[0xff; 9]);
+        assert!(matches!(decode(&frame, 4).await, Err(Error::PacketTooLarge(u64::MAX))));
+
+        // Same for the command string
+        let mut frame = MAGIC_BYTES.to_vec();
+        frame.extend_from_slice(&[0xff; 9]);
+        assert!(matches!(decode(&frame, 4).await, Err(Error::MalformedPacket)));
+
+        let frame = encode(&"a".repeat(MAX_COMMAND_LEN + 1), vec![]).await;
+        assert!(matches!(decode(&frame, 4).await, Err(Error::MalformedPacket)));
+    }
+
+    #[async_std::test]
+    async fn test_malformed_frames() {
+        let mut frame = encode("ping", vec![0; 4]).await;
+        frame[0] ^= 0xff;
+        assert!(matches!(decode(&frame, 4).await, Err(Error::MalformedPacket)));
+
+        // Commands must be valid UTF-8
+        let mut frame = encode("abc", vec![]).await;
+        frame[5] = 0xff;
+        assert!(matches!(decode(&frame, 4).await, Err(Error::MalformedPacket)));
+    }
+
+    #[async_std::test]
+    async fn test_random_frames() {
+        // Random garbage, with and without valid magic bytes, must never
+        // panic or read past the configured limits.
+        for _ in 0..1000 {
+            let len = OsRng.gen_range(0..64);
+            let mut frame = vec![0u8; len];
+            OsRng.fill_bytes(&mut frame);
+            if OsRng.gen_bool(0.5) {
+                frame = [MAGIC_BYTES.to_vec(), frame].concat();
+            }
+
+            if let Ok((command, payload)) = decode(&frame, 16).await {
+                assert!(!command.is_empty() && command.len() <= MAX_COMMAND_LEN);
+                assert!(payload.len() <= 16);
+            }
+        }
+    }
+
+    #[test]
+    fn test_version_message_compat() {
+        let version = VersionMessage {
+            node_id: "foo".to_string(),
+            version: PROTOCOL_VERSION,
+            services: 0b10,
+            external_addrs: vec![Url::parse("tcp://127.0.0.1:1234").unwrap()],
+            timestamp: 1234,
+        };
+        let bytes = serialize(&version);
+        assert_eq!(deserialize::<VersionMessage>(&bytes).unwrap(), version);
+
+        // Legacy nodes only decode the node ID, ignoring the trailing fields
+        assert_eq!(String::decode(&bytes[..]).unwrap(), "foo");
+
+        // Legacy messages decode with legacy defaults
+        let legacy: VersionMessage = deserialize(&serialize(&"bar".to_string())).unwrap();
+        assert_eq!(legacy.node_id, "bar");
+        assert_eq!(legacy.version, LEGACY_PROTOCOL_VERSION);
+        assert_eq!(legacy.services, 0);
+        assert!(legacy.external_addrs.is_empty());
+        assert_eq!(legacy.timestamp, 0);
+
+        // Truncated fields are not mistaken for a legacy message
+        assert!(deserialize::<VersionMessage>(&bytes[..bytes.len() - 1]).is_err());
+        assert!(deserialize::<VersionMessage>(&bytes[..5]).is_err());
+    }
+}
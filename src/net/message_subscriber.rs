@@ -50,6 +50,12 @@ impl<M: Message> MessageSubscription<M> {
         }
     }
 
+    /// Drop all messages already queued for this subscription, e.g. late
+    /// responses to an earlier request that timed out.
+    pub fn clear(&self) {
+        while self.recv_queue.try_recv().is_ok() {}
+    }
+
     /// Unsubscribe from a message subscription. Must be called manually.
     pub async fn unsubscribe(&self) {
         self.parent.clone().unsubscribe(self.id).await
@@ -59,10 +65,12 @@ impl<M: Message> MessageSubscription<M> {
 #[async_trait]
 /// Generic interface for message dispatcher.
 trait MessageDispatcherInterface: Send + Sync {
-    async fn trigger(&self, payload: Vec<u8>);
+    async fn trigger(&self, payload: Vec<u8>) -> Result<()>;
 
     async fn trigger_error(&self, err: Error);
 
+    fn max_len(&self) -> usize;
+
     fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync>;
 }
 
@@ -147,14 +155,16 @@ impl<M: Message> MessageDispatcher<M> {
 // Local implementation of the Message Dispatcher Interface.
 impl<M: Message> MessageDispatcherInterface for MessageDispatcher<M> {
     /// Internal function to deserialize data into a message type and dispatch it across subscriber channels.
-    async fn trigger(&self, payload: Vec<u8>) {
+    /// Returns an error if the payload fails to decode.
+    async fn trigger(&self, payload: Vec<u8>) -> Result<()> {
         // deserialize data into type
         // send down the pipes
         let cursor = Cursor::new(payload);
         match M::decode(cursor) {
             Ok(message) => {
                 let message = Ok(Arc::new(message));
-                self._trigger_all(message).await
+                self._trigger_all(message).await;
+                Ok(())
             }
             Err(err) => {
                 debug!(
@@ -162,6 +172,7 @@ impl<M: Message> MessageDispatcherInterface for MessageDispatcher<M> {
                     "Unable to decode data. Dropping...: {}",
                     err
                 );
+                Err(Error::MalformedPacket)
             }
         }
     }
@@ -171,6 +182,11 @@ impl<M: Message> MessageDispatcherInterface for MessageDispatcher<M> {
         self._trigger_all(Err(err)).await;
     }
 
+    /// Returns the maximum payload length of the dispatched Message type.
+    fn max_len(&self) -> usize {
+        M::max_len()
+    }
+
     /// Converts to Any trait. Enables the dynamic modification of static types.
     fn as_any(self: Arc<Self>) -> Arc<dyn Any + Send + Sync> {
         self
@@ -189,9 +205,15 @@ impl MessageSubsystem {
         MessageSubsystem { dispatchers: Mutex::new(HashMap::new()) }
     }
 
-    /// Add a new dispatcher for specified Message.
+    /// Add a new dispatcher for specified Message. Several protocols may
+    /// handle the same Message, so an existing dispatcher is kept along
+    /// with its subscriptions.
     pub async fn add_dispatch<M: Message>(&self) {
-        self.dispatchers.lock().await.insert(M::name(), Arc::new(MessageDispatcher::<M>::new()));
+        self.dispatchers
+            .lock()
+            .await
+            .entry(M::name())
+            .or_insert_with(|| Arc::new(MessageDispatcher::<M>::new()));
     }
 
     /// Subscribes to a Message. Using the Message name, the method returns an the associated MessageDispatcher from the list of
@@ -218,21 +240,32 @@ impl MessageSubsystem {
         Ok(sub)
     }
 
+    /// Returns the maximum payload length accepted for given command, or
+    /// `None` if there is no dispatcher for it.
+    pub async fn max_len(&self, command: &str) -> Option<usize> {
+        self.dispatchers.lock().await.get(command).map(|dispatcher| dispatcher.max_len())
+    }
+
+    /// Returns the commands there is a dispatcher for.
+    pub async fn commands(&self) -> Vec<&'static str> {
+        self.dispatchers.lock().await.keys().copied().collect()
+    }
+
     /// Transmits a payload to a dispatcher. Returns an error if the payload
-    /// fails to transmit.
-    pub async fn notify(&self, command: &str, payload: Vec<u8>) {
+    /// fails to transmit, either because no dispatcher exists for the
+    /// command or because the payload is malformed.
+    pub async fn notify(&self, command: &str, payload: Vec<u8>) -> Result<()> {
         let dispatcher = self.dispatchers.lock().await.get(command).cloned();
 
         match dispatcher {
-            Some(dispatcher) => {
-                dispatcher.trigger(payload).await;
-            }
+            Some(dispatcher) => dispatcher.trigger(payload).await,
             None => {
                 warn!(
                     target: "net::message_subscriber::notify()",
                     "Command '{}' did not find a dispatcher",
                     command
                 );
+                Err(Error::MissingDispatcher(command.to_string()))
             }
         }
     }
@@ -306,7 +339,7 @@ mod tests {
         // receive message and publish
         //   1. based on string, lookup relevant dispatcher interface
         //   2. publish data there
-        subsystem.notify("verver", payload).await;
+        subsystem.notify("verver", payload).await.unwrap();
 
         // receive
         //    1. do a get easy
@@ -314,6 +347,17 @@ mod tests {
         assert_eq!(msg2.x, 110);
         println!("{}", msg2.x);
 
+        // adding the dispatcher again keeps existing subscriptions alive
+        subsystem.add_dispatch::<MyVersionMessage>().await;
+        let mut payload = Vec::new();
+        MyVersionMessage { x: 111 }.encode(&mut payload).unwrap();
+        subsystem.notify("verver", payload).await.unwrap();
+        assert_eq!(sub.receive().await.unwrap().x, 111);
+
+        // unknown commands and undecodable payloads are reported
+        assert!(subsystem.notify("unknown", vec![]).await.is_err());
+        assert!(subsystem.notify("verver", vec![0x01]).await.is_err());
+
         subsystem.trigger_error(Error::ChannelStopped).await;
 
         let msg2 = sub.receive().await;
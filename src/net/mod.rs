@@ -21,6 +21,12 @@
 /// connections and to handle network errors.
 pub mod acceptor;
 
+/// Ban list keeping track of peer misbehaviour scores. Protocols add to
+/// the score of a peer when they see invalid data, and once the score
+/// crosses the configured threshold the peer gets disconnected and
+/// banned for a configurable period.
+pub mod banlist;
+
 /// Async channel that handles the sending of messages across the network.
 /// Public interface is used to create new channels, to stop and start
 /// a channel, and to send messages.
@@ -32,12 +38,22 @@ pub mod channel;
 /// connection.
 pub mod connector;
 
+/// Test harness running several `P2p` instances in the same process,
+/// connected through the in-memory `mem://` transport. Links between the
+/// nodes can be given latency and packet loss, or be partitioned.
+pub mod harness;
+
 /// Hosts are a list of network addresses used when establishing an outbound
 /// connection. Hosts are shared across the network through the address
 /// protocol. When attempting to connect, a node will loop through addresses in
 /// the host store until it finds ones to connect to.
 pub mod hosts;
 
+/// Inventory types shared by the protocols relaying items through hash
+/// announcements, along with the bounded cache channels use to remember
+/// which items their peer already knows about.
+pub mod inventory;
+
 /// Generic publish/subscribe class that can dispatch any kind of message to a
 /// subscribed list of dispatchers. Dispatchers subscribe to a single
 /// message format of any type. This is a generalized version of the simple
@@ -66,6 +82,11 @@ pub mod message_subscriber;
 /// converted into messages and passed to an event loop.
 pub mod message;
 
+/// Network groups of peer addresses (IPv4 /16, IPv6 /32, single onion or
+/// Nym addresses). The outbound session keeps at most one connection per
+/// group, so a single operator can't easily take over all our slots.
+pub mod netgroup;
+
 /// P2P provides all core functionality to interact with the peer-to-peer
 /// network.
 ///
@@ -100,6 +121,15 @@ pub mod protocol;
 /// which describes the common functions across all sessions.
 pub mod session;
 
+/// Token bucket rate limiting, used to cap the bandwidth of each channel
+/// and of the network as a whole.
+pub mod rate_limit;
+
+/// Per-channel queue of outbound packets. Packets are sent by priority,
+/// so keep-alive and consensus messages don't wait behind bulk sync
+/// responses, and peers that stop reading can't stall their senders.
+pub mod send_queue;
+
 /// Network configuration settings.
 pub mod settings;
 
@@ -110,13 +140,20 @@ pub mod transport;
 pub mod constants;
 
 pub use acceptor::{Acceptor, AcceptorPtr};
-pub use channel::{Channel, ChannelPtr};
+pub use banlist::{BanList, BanListPtr};
+pub use channel::{Channel, ChannelPtr, TrafficStats};
 pub use connector::Connector;
 pub use hosts::{Hosts, HostsPtr};
-pub use message::Message;
+pub use inventory::{InvHash, InvItem, InvRequests, InvStore, InvStorePtr};
+pub use message::{Message, MessagePriority};
 pub use message_subscriber::MessageSubscription;
+pub use netgroup::NetGroup;
 pub use p2p::{P2p, P2pPtr};
-pub use protocol::{ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr};
+pub use protocol::{
+    ProtocolBase, ProtocolBasePtr, ProtocolInventory, ProtocolJobsManager, ProtocolJobsManagerPtr,
+    ProtocolRequirements, ServiceBitflag, SERVICE_NONE,
+};
+pub use rate_limit::{TokenBucket, TokenBucketPtr};
 pub use session::{
     Session, SessionBitflag, SessionWeakPtr, SESSION_ALL, SESSION_INBOUND, SESSION_MANUAL,
     SESSION_OUTBOUND, SESSION_SEED,
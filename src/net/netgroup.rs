@@ -0,0 +1,108 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use url::{Host, Url};
+
+/// Network group of a peer address. Addresses in the same group are
+/// likely run by the same operator, so outbound connections are spread
+/// over as many groups as possible.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NetGroup {
+    /// IPv4 /16 prefix
+    Ipv4([u8; 2]),
+    /// IPv6 /32 prefix
+    Ipv6([u16; 2]),
+    /// A single onion service
+    Onion(String),
+    /// A single Nym address
+    Nym(String),
+    /// A single domain name, which we don't resolve
+    Domain(String),
+}
+
+impl NetGroup {
+    /// Return the group of the given address, `None` if it has no host
+    /// (e.g. unix sockets).
+    pub fn from_url(addr: &Url) -> Option<Self> {
+        let group = match addr.host()? {
+            Host::Ipv4(ip) => Self::from_ipv4(ip),
+            Host::Ipv6(ip) => Self::from_ipv6(ip),
+            // Non-special URL schemes keep IPv4 hosts as opaque strings
+            Host::Domain(domain) => match domain.parse::<IpAddr>() {
+                Ok(IpAddr::V4(ip)) => Self::from_ipv4(ip),
+                Ok(IpAddr::V6(ip)) => Self::from_ipv6(ip),
+                Err(_) if domain.ends_with(".onion") => Self::Onion(domain.to_string()),
+                Err(_) if addr.scheme().starts_with("nym") => Self::Nym(domain.to_string()),
+                Err(_) => Self::Domain(domain.to_string()),
+            },
+        };
+
+        Some(group)
+    }
+
+    fn from_ipv4(ip: Ipv4Addr) -> Self {
+        let octets = ip.octets();
+        Self::Ipv4([octets[0], octets[1]])
+    }
+
+    fn from_ipv6(ip: Ipv6Addr) -> Self {
+        // IPv4-mapped addresses belong to the IPv4 group
+        if let Some(ip) = ip.to_ipv4_mapped() {
+            return Self::from_ipv4(ip)
+        }
+
+        let segments = ip.segments();
+        Self::Ipv6([segments[0], segments[1]])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use url::Url;
+
+    use super::NetGroup;
+
+    fn group(addr: &str) -> Option<NetGroup> {
+        NetGroup::from_url(&Url::parse(addr).unwrap())
+    }
+
+    #[test]
+    fn test_netgroup() {
+        // Same /16, different ports and schemes
+        assert_eq!(group("tcp://1.2.3.4:123"), Some(NetGroup::Ipv4([1, 2])));
+        assert_eq!(group("tcp+tls://1.2.200.1:8080"), group("tcp://1.2.3.4:123"));
+        assert_ne!(group("tcp://1.3.3.4:123"), group("tcp://1.2.3.4:123"));
+
+        // Same /32
+        assert_eq!(group("tcp://[2001:db8::1]:123"), Some(NetGroup::Ipv6([0x2001, 0xdb8])));
+        assert_eq!(group("tcp://[2001:db8:ffff::1]:123"), group("tcp://[2001:db8::1]:123"));
+        assert_ne!(group("tcp://[2001:db9::1]:123"), group("tcp://[2001:db8::1]:123"));
+        assert_eq!(group("tcp://[::ffff:1.2.3.4]:123"), group("tcp://1.2.9.9:123"));
+
+        // One group per onion, nym address and domain
+        let onion = "tor://cbfeuw6f4djcukyf3mizdna3oki6xd4amygprzdv4toa3gyzjiw6zfad.onion:123";
+        assert!(matches!(group(onion), Some(NetGroup::Onion(_))));
+        assert!(matches!(group("nym://someaddress:123"), Some(NetGroup::Nym(_))));
+        assert_eq!(group("tcp://example.com:123"), Some(NetGroup::Domain("example.com".into())));
+        assert_ne!(group("tcp://example.com:123"), group("tcp://example.org:123"));
+
+        assert_eq!(group("unix:///tmp/darkfi.sock"), None);
+    }
+}
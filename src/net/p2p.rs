@@ -19,11 +19,12 @@
 use std::{
     collections::{HashMap, HashSet},
     fmt,
+    time::Duration,
 };
 
 use async_std::sync::{Arc, Mutex};
 use futures::{select, stream::FuturesUnordered, try_join, FutureExt, StreamExt, TryFutureExt};
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
 use rand::Rng;
 use serde_json::json;
 use smol::Executor;
@@ -31,14 +32,22 @@ use url::Url;
 
 use crate::{
     system::{Subscriber, SubscriberPtr, Subscription},
-    util::async_util::sleep,
+    util::{async_util::sleep, path::expand_path},
     Result,
 };
 
 use super::{
-    message::Message,
+    banlist::{ban_key, BanList, BanListPtr},
+    constants::{
+        HOSTS_SAVE_INTERVAL_SECONDS, INV_PROTOCOL_VERSION, INV_REQUEST_TIMEOUT_SECONDS,
+        INV_RETRY_INTERVAL_SECONDS,
+    },
+    inventory::{InvHash, InvItem, InvRequests},
+    message::{GetDataMessage, InvMessage, Message},
     protocol::{register_default_protocols, ProtocolRegistry},
+    rate_limit::{TokenBucket, TokenBucketPtr},
     session::{InboundSession, ManualSession, OutboundSession, SeedSyncSession, Session},
+    transport::{NoiseKeypair, NoiseUpgrade},
     Channel, ChannelPtr, Hosts, HostsPtr, Settings, SettingsPtr,
 };
 
@@ -83,6 +92,17 @@ pub struct P2p {
     // Used both internally and externally
     stop_subscriber: SubscriberPtr<()>,
     hosts: HostsPtr,
+    banlist: BanListPtr,
+    /// Static key identifying this node on Noise transports
+    noise_keypair: Arc<NoiseKeypair>,
+    /// Peer keys accepted on Noise transports, `None` if any is accepted
+    trusted_peer_keys: Option<Arc<Vec<[u8; 32]>>>,
+    /// Bandwidth limit shared by all inbound traffic
+    inbound_limit: TokenBucketPtr,
+    /// Bandwidth limit shared by all outbound traffic
+    outbound_limit: TokenBucketPtr,
+    /// Inventory items requested from peers and not received yet
+    inv_requests: Mutex<InvRequests>,
     protocol_registry: ProtocolRegistry,
 
     // We keep a reference to the sessions used for get info
@@ -106,8 +126,12 @@ impl P2p {
     /// address protocols.
     ///
     /// Creates a weak pointer to self that is used by all sessions to access the p2p parent class.
+    ///
+    /// Panics if the configured node key file can't be loaded.
     pub async fn new(settings: Settings) -> Arc<Self> {
         let settings = Arc::new(settings);
+        let noise_keypair = Arc::new(load_noise_keypair(&settings));
+        let trusted_peer_keys = parse_trusted_peer_keys(&settings);
 
         let self_ = Arc::new(Self {
             pending: Mutex::new(HashSet::new()),
@@ -115,6 +139,14 @@ impl P2p {
             channel_subscriber: Subscriber::new(),
             stop_subscriber: Subscriber::new(),
             hosts: Hosts::new(settings.localnet),
+            banlist: BanList::new(),
+            noise_keypair,
+            trusted_peer_keys,
+            inbound_limit: TokenBucket::new(settings.inbound_rate_limit),
+            outbound_limit: TokenBucket::new(settings.outbound_rate_limit),
+            inv_requests: Mutex::new(InvRequests::new(Duration::from_secs(
+                INV_REQUEST_TIMEOUT_SECONDS,
+            ))),
             protocol_registry: ProtocolRegistry::new(),
             session_manual: Mutex::new(None),
             session_inbound: Mutex::new(None),
@@ -132,6 +164,48 @@ impl P2p {
 
         register_default_protocols(self_.clone()).await;
 
+        // Load previously known hosts, if configured
+        if let Some(hosts_file) = &self_.settings.hosts_file {
+            match expand_path(hosts_file) {
+                Ok(path) => {
+                    if let Err(e) = self_.hosts.load_hosts(&path).await {
+                        info!(target: "net::p2p::new()", "Unable to load saved hosts from {:?}: {}", path, e);
+                    }
+                }
+                Err(e) => {
+                    warn!(target: "net::p2p::new()", "Invalid hosts file path {}: {}", hosts_file, e)
+                }
+            }
+        }
+
+        // Load the anchors of the previous run, if configured
+        if let Some(anchors_file) = &self_.settings.anchors_file {
+            match expand_path(anchors_file) {
+                Ok(path) => {
+                    if let Err(e) = self_.hosts.load_anchors(&path).await {
+                        info!(target: "net::p2p::new()", "Unable to load saved anchors from {:?}: {}", path, e);
+                    }
+                }
+                Err(e) => {
+                    warn!(target: "net::p2p::new()", "Invalid anchors file path {}: {}", anchors_file, e)
+                }
+            }
+        }
+
+        // Load previously banned peers, if configured
+        if let Some(banlist_file) = &self_.settings.banlist_file {
+            match expand_path(banlist_file) {
+                Ok(path) => {
+                    if let Err(e) = self_.banlist.load_banlist(&path).await {
+                        info!(target: "net::p2p::new()", "Unable to load saved banlist from {:?}: {}", path, e);
+                    }
+                }
+                Err(e) => {
+                    warn!(target: "net::p2p::new()", "Invalid banlist file path {}: {}", banlist_file, e)
+                }
+            }
+        }
+
         self_
     }
 
@@ -201,9 +275,14 @@ impl P2p {
         let outbound = self.session_outbound().await;
         outbound.clone().start(executor.clone()).await?;
 
+        let save_task = executor.spawn(self.clone().save_hosts_loop());
+        let inv_task = executor.spawn(self.clone().retry_inv_requests_loop());
+
         let stop_sub = self.subscribe_stop().await;
         // Wait for stop signal
         stop_sub.receive().await;
+        save_task.cancel().await;
+        inv_task.cancel().await;
 
         // Stop the sessions
         manual.stop().await;
@@ -332,10 +411,140 @@ impl P2p {
 
     // ANCHOR: stop
     pub async fn stop(&self) {
-        self.stop_subscriber.notify(()).await
+        // Grab the anchors before the outbound channels go down
+        self.save_anchors().await;
+        self.stop_subscriber.notify(()).await;
+        self.save_hosts().await;
+        self.save_banlist().await;
     }
     // ANCHOR_END: stop
 
+    /// Periodically persist the known hosts, so they survive an unclean
+    /// shutdown.
+    async fn save_hosts_loop(self: Arc<Self>) {
+        loop {
+            sleep(HOSTS_SAVE_INTERVAL_SECONDS).await;
+            self.save_hosts().await;
+        }
+    }
+
+    /// Persist the known hosts to the configured hosts file, if any.
+    pub async fn save_hosts(&self) {
+        let Some(hosts_file) = &self.settings.hosts_file else { return };
+
+        let path = match expand_path(hosts_file) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(target: "net::p2p::save_hosts()", "Invalid hosts file path {}: {}", hosts_file, e);
+                return
+            }
+        };
+
+        if let Err(e) = self.hosts.save_hosts(&path).await {
+            error!(target: "net::p2p::save_hosts()", "Failed saving hosts to {:?}: {}", path, e);
+        }
+    }
+
+    /// Persist the longest-lived outbound peers to the configured anchors
+    /// file, if any.
+    pub async fn save_anchors(&self) {
+        let Some(anchors_file) = &self.settings.anchors_file else { return };
+
+        let path = match expand_path(anchors_file) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(target: "net::p2p::save_anchors()", "Invalid anchors file path {}: {}", anchors_file, e);
+                return
+            }
+        };
+
+        let anchors = self.session_outbound().await.anchors().await;
+        if let Err(e) = self.hosts.save_anchors(&path, &anchors).await {
+            error!(target: "net::p2p::save_anchors()", "Failed saving anchors to {:?}: {}", path, e);
+        }
+    }
+
+    /// Persist the active bans to the configured banlist file, if any.
+    pub async fn save_banlist(&self) {
+        let Some(banlist_file) = &self.settings.banlist_file else { return };
+
+        let path = match expand_path(banlist_file) {
+            Ok(p) => p,
+            Err(e) => {
+                warn!(target: "net::p2p::save_banlist()", "Invalid banlist file path {}: {}", banlist_file, e);
+                return
+            }
+        };
+
+        if let Err(e) = self.banlist.save_banlist(&path).await {
+            error!(target: "net::p2p::save_banlist()", "Failed saving banlist to {:?}: {}", path, e);
+        }
+    }
+
+    /// Add to the misbehaviour score of the peer behind given channel.
+    /// Once the score crosses the configured threshold, the peer gets
+    /// banned and all of its channels get stopped.
+    pub async fn add_ban_score(&self, channel: &ChannelPtr, score: u32, reason: &str) {
+        let addr = channel.address();
+        let total = self.banlist.add_score(&addr, score).await;
+        warn!(
+            target: "net::p2p::add_ban_score()",
+            "Peer {} misbehaved ({}), ban score: {}/{}",
+            addr, reason, total, self.settings.ban_threshold
+        );
+
+        if total >= self.settings.ban_threshold {
+            self.ban_host(&ban_key(&addr), self.settings.ban_duration_seconds).await;
+            // The channel might not be stored yet if it's still handshaking
+            channel.stop().await;
+        }
+    }
+
+    /// Ban a peer by its host for `duration` seconds, and stop all of
+    /// its connected channels.
+    pub async fn ban_host(&self, host: &str, duration: u64) {
+        self.banlist.ban_host(host, duration).await;
+
+        let channels: Vec<ChannelPtr> = self
+            .channels
+            .lock()
+            .await
+            .values()
+            .filter(|c| ban_key(&c.address()) == host)
+            .cloned()
+            .collect();
+
+        for channel in channels {
+            info!(target: "net::p2p::ban_host()", "Disconnecting banned peer {}", channel.address());
+            channel.stop().await;
+        }
+
+        self.save_banlist().await;
+    }
+
+    /// Lift the ban of a peer by its host. Returns `false` if the peer
+    /// was not banned.
+    pub async fn unban_host(&self, host: &str) -> bool {
+        let unbanned = self.banlist.unban_host(host).await;
+        if unbanned {
+            self.save_banlist().await;
+        }
+        unbanned
+    }
+
+    /// Return the active bans and their expiry timestamps.
+    pub async fn get_banlist_info(&self) -> serde_json::Value {
+        let bans: Vec<serde_json::Value> = self
+            .banlist
+            .list()
+            .await
+            .into_iter()
+            .map(|(host, expiry)| json!({ "host": host, "expiry": expiry }))
+            .collect();
+
+        json!(bans)
+    }
+
     /// Broadcasts a message concurrently across all channels.
     // ANCHOR: broadcast
     pub async fn broadcast<M: Message + Clone>(&self, message: M) -> Result<()> {
@@ -405,6 +614,85 @@ impl P2p {
         Ok(())
     }
 
+    /// Announces an item to all channels whose peer doesn't know about it
+    /// yet. Peers missing the item will then request it from us, so it has
+    /// to be available in the store of the matching `ProtocolInventory`.
+    /// Peers predating inventory relaying get sent the full item instead.
+    pub async fn broadcast_inv<M: InvItem>(&self, item: &M) -> Result<()> {
+        let hash = item.inv_hash();
+        let chans: Vec<ChannelPtr> = self.channels.lock().await.values().cloned().collect();
+        let mut futures = FuturesUnordered::new();
+
+        for channel in chans {
+            if !channel.mark_known(hash).await {
+                continue
+            }
+
+            let legacy = channel.negotiated_version().await < INV_PROTOCOL_VERSION;
+            let item = item.clone();
+            futures.push(async move {
+                let sent = if legacy {
+                    channel.send(item).await
+                } else {
+                    channel
+                        .send(InvMessage { kind: M::name().to_string(), hashes: vec![hash] })
+                        .await
+                };
+                sent.map_err(|e| {
+                    format!(
+                        "P2P::broadcast_inv: Announcing item to {} failed: {}",
+                        channel.address(),
+                        e
+                    )
+                })
+            });
+        }
+
+        while let Some(entry) = futures.next().await {
+            if let Err(e) = entry {
+                error!(target: "net::p2p::broadcast_inv()", "{}", e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Register an announcement of an item of given kind by the peer of
+    /// given channel. Returns `true` if the item has to be requested from
+    /// it now. Otherwise it is already requested from another peer, and
+    /// this one gets asked if that request times out.
+    pub async fn request_inv(&self, kind: &str, hash: &InvHash, channel: &ChannelPtr) -> bool {
+        self.inv_requests.lock().await.announced(kind, *hash, &channel.address())
+    }
+
+    /// Mark a requested inventory item as received.
+    pub async fn inv_received(&self, hash: &InvHash) {
+        self.inv_requests.lock().await.received(hash);
+    }
+
+    /// Periodically request the items whose getdata request timed out from
+    /// the next peer that announced them.
+    async fn retry_inv_requests_loop(self: Arc<Self>) {
+        loop {
+            sleep(INV_RETRY_INTERVAL_SECONDS).await;
+            self.retry_inv_requests().await;
+        }
+    }
+
+    async fn retry_inv_requests(&self) {
+        let channels = self.channels.lock().await.clone();
+        let connected: HashSet<Url> = channels.keys().cloned().collect();
+        let retries = self.inv_requests.lock().await.retry_expired(&connected);
+
+        for (peer, kind, hash) in retries {
+            let Some(channel) = channels.get(&peer) else { continue };
+            debug!(target: "net::p2p::retry_inv_requests()", "Requesting {} item again from {}", kind, peer);
+            if let Err(e) = channel.send(GetDataMessage { kind, hashes: vec![hash] }).await {
+                error!(target: "net::p2p::retry_inv_requests()", "Requesting item from {} failed: {}", peer, e);
+            }
+        }
+    }
+
     /// Add channel address to the list of connected channels.
     pub async fn store(&self, channel: ChannelPtr) {
         self.channels.lock().await.insert(channel.address(), channel.clone());
@@ -460,6 +748,31 @@ impl P2p {
         self.hosts.clone()
     }
 
+    /// Return an atomic pointer to the ban list.
+    pub fn banlist(&self) -> BanListPtr {
+        self.banlist.clone()
+    }
+
+    /// Return the static public key identifying this node on Noise transports.
+    pub fn node_pubkey(&self) -> [u8; 32] {
+        self.noise_keypair.public()
+    }
+
+    /// Create a Noise upgrade using this node's static key and trusted peers.
+    pub fn noise_upgrade(&self) -> NoiseUpgrade {
+        NoiseUpgrade::new(self.noise_keypair.clone(), self.trusted_peer_keys.clone())
+    }
+
+    /// Return the bandwidth limit shared by all inbound traffic.
+    pub fn inbound_limit(&self) -> TokenBucketPtr {
+        self.inbound_limit.clone()
+    }
+
+    /// Return the bandwidth limit shared by all outbound traffic.
+    pub fn outbound_limit(&self) -> TokenBucketPtr {
+        self.outbound_limit.clone()
+    }
+
     pub fn protocol_registry(&self) -> &ProtocolRegistry {
         &self.protocol_registry
     }
@@ -496,14 +809,92 @@ impl P2p {
 
     /// Retrieves a random connected channel, exluding seeds
     pub async fn random_channel(self: Arc<Self>) -> Option<Arc<Channel>> {
-        let mut channels_map = self.channels().lock().await.clone();
-        channels_map.retain(|c, _| !self.settings.seeds.contains(c));
-        let mut values = channels_map.values();
+        let channels = self.peer_channels().await;
 
-        if values.len() == 0 {
+        if channels.is_empty() {
             return None
         }
 
-        Some(values.nth(rand::thread_rng().gen_range(0..values.len())).unwrap().clone())
+        Some(channels[rand::thread_rng().gen_range(0..channels.len())].clone())
+    }
+
+    /// Retrieves all connected channels, excluding seeds
+    pub async fn peer_channels(&self) -> Vec<Arc<Channel>> {
+        let channels_map = self.channels().lock().await;
+        channels_map
+            .iter()
+            .filter(|(addr, _)| !self.settings.seeds.contains(addr))
+            .map(|(_, channel)| channel.clone())
+            .collect()
+    }
+}
+
+/// Load the node's static Noise key from the configured file, or use an
+/// ephemeral key when none is configured. A configured key that can't be
+/// loaded is fatal, as peers pinning it would reject a new identity.
+fn load_noise_keypair(settings: &Settings) -> NoiseKeypair {
+    let Some(node_key_file) = &settings.node_key_file else { return NoiseKeypair::generate() };
+
+    let keypair = match expand_path(node_key_file) {
+        Ok(path) => NoiseKeypair::load_or_generate(&path),
+        Err(e) => Err(e),
+    };
+
+    match keypair {
+        Ok(keypair) => {
+            info!(target: "net::p2p", "Node public key: {}", hex::encode(keypair.public()));
+            keypair
+        }
+        Err(e) => {
+            error!(target: "net::p2p", "Failed loading node key from {}: {}", node_key_file, e);
+            panic!("Failed loading node key from {}: {}", node_key_file, e)
+        }
+    }
+}
+
+/// Parse the configured trusted peer keys. Once any key is configured, peer
+/// pinning stays enforced even if some of the keys are invalid.
+fn parse_trusted_peer_keys(settings: &Settings) -> Option<Arc<Vec<[u8; 32]>>> {
+    if settings.trusted_peer_keys.is_empty() {
+        return None
+    }
+
+    let mut keys = vec![];
+    for key in &settings.trusted_peer_keys {
+        match hex::decode(key).ok().and_then(|k| <[u8; 32]>::try_from(k).ok()) {
+            Some(k) => keys.push(k),
+            None => warn!(target: "net::p2p", "Ignoring invalid trusted peer key: {}", key),
+        }
+    }
+
+    Some(Arc::new(keys))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_noise_keypair, Settings};
+
+    #[test]
+    #[should_panic(expected = "Failed loading node key")]
+    fn malformed_node_key_file_is_fatal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.key");
+        std::fs::write(&path, [0u8; 10]).unwrap();
+
+        let settings =
+            Settings { node_key_file: Some(path.to_str().unwrap().into()), ..Default::default() };
+        load_noise_keypair(&settings);
+    }
+
+    #[test]
+    fn node_key_file_keeps_identity() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("node.key");
+
+        let settings =
+            Settings { node_key_file: Some(path.to_str().unwrap().into()), ..Default::default() };
+        let first = load_noise_keypair(&settings);
+        let second = load_noise_keypair(&settings);
+        assert_eq!(first.public(), second.public());
     }
 }
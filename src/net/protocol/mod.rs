@@ -27,6 +27,15 @@
 /// address information to their local store.
 pub mod protocol_address;
 
+/// Inventory relay protocol. Instead of pushing full items to every peer,
+/// nodes announce the hashes of items they have with an inv message. Peers
+/// request only the items they haven't seen yet with a getdata message,
+/// which is answered with the items themselves.
+///
+/// The protocol is generic over the relayed message type, and is registered
+/// by applications along with a store used to look items up.
+pub mod protocol_inventory;
+
 /// Manages the tasks for the network protocol. Used by other connection
 /// protocols to handle asynchronous task execution across the network. Runs all
 /// tasks that are handed to it on an executor that has stopping functionality.
@@ -68,19 +77,27 @@ pub mod protocol_base;
 pub mod protocol_registry;
 
 pub use protocol_address::ProtocolAddress;
+pub use protocol_inventory::ProtocolInventory;
 pub use protocol_jobs_manager::{ProtocolJobsManager, ProtocolJobsManagerPtr};
 pub use protocol_ping::ProtocolPing;
 pub use protocol_seed::ProtocolSeed;
 pub use protocol_version::ProtocolVersion;
 
 pub use protocol_base::{ProtocolBase, ProtocolBasePtr};
-pub use protocol_registry::ProtocolRegistry;
+pub use protocol_registry::{ProtocolRegistry, ProtocolRequirements};
 
 use super::{
     session::{SESSION_ALL, SESSION_SEED},
     P2pPtr,
 };
 
+/// Bitflag of the optional services a node advertises in its version
+/// message. Bits are assigned by the applications running on the network.
+pub type ServiceBitflag = u64;
+
+/// No optional services.
+pub const SERVICE_NONE: ServiceBitflag = 0;
+
 pub async fn register_default_protocols(p2p: P2pPtr) {
     let registry = p2p.protocol_registry();
     registry.register(SESSION_ALL, ProtocolPing::init).await;
@@ -0,0 +1,176 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use log::debug;
+use smol::Executor;
+
+use crate::Result;
+
+use super::{
+    super::{
+        constants::{BAN_SCORE_EMPTY_INV, INV_PROTOCOL_VERSION},
+        inventory::{InvItem, InvStorePtr},
+        message::{GetDataMessage, InvMessage},
+        message_subscriber::MessageSubscription,
+        ChannelPtr, P2pPtr,
+    },
+    ProtocolBase, ProtocolBasePtr, ProtocolJobsManager, ProtocolJobsManagerPtr,
+    ProtocolRequirements, SERVICE_NONE,
+};
+
+/// Inventory relay protocol for items of type `M`. Answers announcements
+/// of items we don't have with a getdata request, and serves the items
+/// peers request from us out of the given store.
+pub struct ProtocolInventory<M: InvItem> {
+    channel: ChannelPtr,
+    inv_sub: MessageSubscription<InvMessage>,
+    getdata_sub: MessageSubscription<GetDataMessage>,
+    item_sub: MessageSubscription<M>,
+    store: InvStorePtr<M>,
+    p2p: P2pPtr,
+    jobsman: ProtocolJobsManagerPtr,
+}
+
+impl<M: InvItem> ProtocolInventory<M> {
+    /// Peers predating inventory relaying get sent full items instead,
+    /// so the protocol must be registered with these requirements.
+    pub const REQUIREMENTS: ProtocolRequirements =
+        ProtocolRequirements { version: INV_PROTOCOL_VERSION, services: SERVICE_NONE };
+
+    /// Create a new inventory protocol for `M`, backed by `store`.
+    pub async fn init(channel: ChannelPtr, p2p: P2pPtr, store: InvStorePtr<M>) -> ProtocolBasePtr {
+        channel.get_message_subsystem().add_dispatch::<M>().await;
+
+        let inv_sub =
+            channel.clone().subscribe_msg::<InvMessage>().await.expect("Missing inv dispatcher!");
+        let getdata_sub = channel
+            .clone()
+            .subscribe_msg::<GetDataMessage>()
+            .await
+            .expect("Missing getdata dispatcher!");
+        let item_sub =
+            channel.clone().subscribe_msg::<M>().await.expect("Missing item dispatcher!");
+
+        Arc::new(Self {
+            channel: channel.clone(),
+            inv_sub,
+            getdata_sub,
+            item_sub,
+            store,
+            p2p,
+            jobsman: ProtocolJobsManager::new("ProtocolInventory", channel),
+        })
+    }
+
+    /// Request the announced items we neither have nor already asked
+    /// another peer for. The peer is asked later if that request times out.
+    async fn handle_receive_inv(self: Arc<Self>) -> Result<()> {
+        debug!(target: "net::protocol_inventory::handle_receive_inv()", "START");
+        loop {
+            let inv = self.inv_sub.receive().await?;
+            if inv.kind != M::name() {
+                continue
+            }
+
+            if inv.hashes.is_empty() {
+                self.p2p.add_ban_score(&self.channel, BAN_SCORE_EMPTY_INV, "empty inv").await;
+                continue
+            }
+
+            let mut wanted = vec![];
+            for hash in &inv.hashes {
+                // The peer has it, so never announce it back
+                self.channel.mark_known(*hash).await;
+
+                if self.store.contains(hash).await ||
+                    !self.p2p.request_inv(&inv.kind, hash, &self.channel).await
+                {
+                    continue
+                }
+
+                wanted.push(*hash);
+            }
+
+            if wanted.is_empty() {
+                continue
+            }
+
+            debug!(
+                target: "net::protocol_inventory::handle_receive_inv()",
+                "Requesting {} {} item(s) from {}",
+                wanted.len(),
+                M::name(),
+                self.channel.address()
+            );
+            self.channel.send(GetDataMessage { kind: inv.kind.clone(), hashes: wanted }).await?;
+        }
+    }
+
+    /// Serve the requested items we have. Unknown hashes are skipped.
+    async fn handle_receive_getdata(self: Arc<Self>) -> Result<()> {
+        debug!(target: "net::protocol_inventory::handle_receive_getdata()", "START");
+        loop {
+            let getdata = self.getdata_sub.receive().await?;
+            if getdata.kind != M::name() {
+                continue
+            }
+
+            if getdata.hashes.is_empty() {
+                self.p2p.add_ban_score(&self.channel, BAN_SCORE_EMPTY_INV, "empty getdata").await;
+                continue
+            }
+
+            for hash in &getdata.hashes {
+                let Some(item) = self.store.get(hash).await else { continue };
+                self.channel.mark_known(*hash).await;
+                self.channel.send(item).await?;
+            }
+        }
+    }
+
+    /// Keep track of the items the peer sent us, whether requested or not.
+    async fn handle_receive_item(self: Arc<Self>) -> Result<()> {
+        debug!(target: "net::protocol_inventory::handle_receive_item()", "START");
+        loop {
+            let item = self.item_sub.receive().await?;
+            let hash = item.inv_hash();
+            self.channel.mark_known(hash).await;
+            self.p2p.inv_received(&hash).await;
+        }
+    }
+}
+
+#[async_trait]
+impl<M: InvItem> ProtocolBase for ProtocolInventory<M> {
+    async fn start(self: Arc<Self>, executor: Arc<Executor<'_>>) -> Result<()> {
+        debug!(target: "net::protocol_inventory::start()", "START");
+        self.jobsman.clone().start(executor.clone());
+        self.jobsman.clone().spawn(self.clone().handle_receive_inv(), executor.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_getdata(), executor.clone()).await;
+        self.jobsman.clone().spawn(self.clone().handle_receive_item(), executor).await;
+        debug!(target: "net::protocol_inventory::start()", "END");
+        Ok(())
+    }
+
+    fn name(&self) -> &'static str {
+        "ProtocolInventory"
+    }
+}
@@ -17,21 +17,55 @@
  */
 
 use async_std::sync::Mutex;
-use std::future::Future;
+use std::{collections::HashSet, future::Future};
 
 use futures::future::BoxFuture;
 use log::debug;
 
 use super::{
-    super::{session::SessionBitflag, ChannelPtr, P2pPtr},
-    ProtocolBasePtr,
+    super::{constants::LEGACY_PROTOCOL_VERSION, session::SessionBitflag, ChannelPtr, P2pPtr},
+    ProtocolBasePtr, ServiceBitflag, SERVICE_NONE,
 };
 
 type Constructor =
     Box<dyn Fn(ChannelPtr, P2pPtr) -> BoxFuture<'static, ProtocolBasePtr> + Send + Sync>;
 
+/// What a peer must support for a protocol to be started on its channel,
+/// as learned during the version handshake.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ProtocolRequirements {
+    /// Lowest protocol version that has to be negotiated with the peer
+    pub version: u32,
+    /// Services the peer has to advertise
+    pub services: ServiceBitflag,
+}
+
+impl ProtocolRequirements {
+    /// Requirements met by every peer, legacy ones included.
+    pub const NONE: Self = Self { version: LEGACY_PROTOCOL_VERSION, services: SERVICE_NONE };
+
+    pub fn new(version: u32, services: ServiceBitflag) -> Self {
+        Self { version, services }
+    }
+
+    /// Returns `true` if a peer with given negotiated version and
+    /// advertised services meets the requirements.
+    pub fn met_by(&self, version: u32, services: ServiceBitflag) -> bool {
+        version >= self.version && services & self.services == self.services
+    }
+
+    /// Returns `true` if the peer of given channel meets the requirements.
+    /// Must be called once the version handshake is complete.
+    pub async fn met_by_channel(&self, channel: &ChannelPtr) -> bool {
+        self.met_by(channel.negotiated_version().await, channel.remote_services().await)
+    }
+}
+
 pub struct ProtocolRegistry {
-    protocol_constructors: Mutex<Vec<(SessionBitflag, Constructor)>>,
+    protocol_constructors: Mutex<Vec<(SessionBitflag, ProtocolRequirements, Constructor)>>,
+    /// Commands handled by the protocols attached so far, whether or not
+    /// they got started on their channel
+    known_commands: Mutex<HashSet<&'static str>>,
 }
 
 impl Default for ProtocolRegistry {
@@ -42,7 +76,10 @@ impl Default for ProtocolRegistry {
 
 impl ProtocolRegistry {
     pub fn new() -> Self {
-        Self { protocol_constructors: Mutex::new(Vec::new()) }
+        Self {
+            protocol_constructors: Mutex::new(Vec::new()),
+            known_commands: Mutex::new(HashSet::new()),
+        }
     }
 
     // add_protocol()?
@@ -50,21 +87,44 @@ impl ProtocolRegistry {
     where
         C: 'static + Fn(ChannelPtr, P2pPtr) -> F + Send + Sync,
         F: 'static + Future<Output = ProtocolBasePtr> + Send,
+    {
+        self.register_with_requirements(session_flags, ProtocolRequirements::NONE, constructor)
+            .await
+    }
+
+    /// Register a protocol that only gets started on channels whose peer
+    /// meets the given requirements once the version handshake is done.
+    pub async fn register_with_requirements<C, F>(
+        &self,
+        session_flags: SessionBitflag,
+        requirements: ProtocolRequirements,
+        constructor: C,
+    ) where
+        C: 'static + Fn(ChannelPtr, P2pPtr) -> F + Send + Sync,
+        F: 'static + Future<Output = ProtocolBasePtr> + Send,
     {
         let constructor = move |channel, p2p| {
             Box::pin(constructor(channel, p2p)) as BoxFuture<'static, ProtocolBasePtr>
         };
-        self.protocol_constructors.lock().await.push((session_flags, Box::new(constructor)));
+        self.protocol_constructors.lock().await.push((
+            session_flags,
+            requirements,
+            Box::new(constructor),
+        ));
     }
 
+    /// Construct the protocols registered for given session, along with
+    /// the requirements the peer has to meet for them to be started.
     pub async fn attach(
         &self,
         selector_id: SessionBitflag,
         channel: ChannelPtr,
         p2p: P2pPtr,
-    ) -> Vec<ProtocolBasePtr> {
-        let mut protocols: Vec<ProtocolBasePtr> = Vec::new();
-        for (session_flags, construct) in self.protocol_constructors.lock().await.iter() {
+    ) -> Vec<(ProtocolRequirements, ProtocolBasePtr)> {
+        let mut protocols = Vec::new();
+        for (session_flags, requirements, construct) in
+            self.protocol_constructors.lock().await.iter()
+        {
             // Skip protocols that are not registered for this session
             if selector_id & session_flags == 0 {
                 debug!(target: "net::protocol_registry", "Skipping {selector_id:#b}, {session_flags:#b}");
@@ -74,8 +134,44 @@ impl ProtocolRegistry {
             let protocol: ProtocolBasePtr = construct(channel.clone(), p2p.clone()).await;
             debug!(target: "net::protocol_registry", "Attached {}", protocol.name());
 
-            protocols.push(protocol)
+            protocols.push((*requirements, protocol))
         }
+
+        // Protocols add dispatchers for the messages they handle when
+        // constructed, so this also covers the ones that won't be started
+        // because the peer doesn't meet their requirements.
+        let commands = channel.get_message_subsystem().commands().await;
+        self.known_commands.lock().await.extend(commands);
+
         protocols
     }
+
+    /// Returns `true` if some protocol attached to a channel handles given
+    /// command, even if it wasn't started on the channel it came in on.
+    pub async fn is_known_command(&self, command: &str) -> bool {
+        self.known_commands.lock().await.contains(command)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_protocol_requirements() {
+        // Legacy peers only run unrestricted protocols
+        assert!(ProtocolRequirements::NONE.met_by(LEGACY_PROTOCOL_VERSION, SERVICE_NONE));
+        let versioned = ProtocolRequirements::new(1, SERVICE_NONE);
+        assert!(!versioned.met_by(LEGACY_PROTOCOL_VERSION, SERVICE_NONE));
+        assert!(versioned.met_by(1, SERVICE_NONE));
+        assert!(versioned.met_by(2, 0b100));
+
+        // All required services have to be advertised
+        let gated = ProtocolRequirements::new(1, 0b101);
+        assert!(!gated.met_by(1, 0b001));
+        assert!(!gated.met_by(1, 0b110));
+        assert!(gated.met_by(1, 0b101));
+        assert!(gated.met_by(1, 0b111));
+        assert!(!gated.met_by(LEGACY_PROTOCOL_VERSION, 0b111));
+    }
 }
@@ -90,7 +90,7 @@ impl ProtocolBase for ProtocolSeed {
         let addrs_msg = self.addr_sub.receive().await?;
         debug!(target: "net::protocol_seed::start()", "Received {} addrs", addrs_msg.addrs.len()
         );
-        self.hosts.store(addrs_msg.addrs.clone()).await;
+        self.hosts.store_from(self.channel.address(), addrs_msg.addrs.clone()).await;
 
         debug!(target: "net::protocol_seed::start()", "END");
         Ok(())
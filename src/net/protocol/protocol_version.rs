@@ -22,10 +22,13 @@ use std::{sync::Arc, time::Duration};
 use log::*;
 use smol::Executor;
 
-use crate::{Error, Result};
+use crate::{util::time::unix_timestamp, Error, Result};
 
 use super::super::{
-    message, message_subscriber::MessageSubscription, ChannelPtr, HostsPtr, SettingsPtr,
+    constants::{MAX_CLOCK_DRIFT_SECONDS, PROTOCOL_VERSION},
+    message,
+    message_subscriber::MessageSubscription,
+    ChannelPtr, HostsPtr, SettingsPtr,
 };
 
 /// Implements the protocol version handshake sent out by nodes at the beginning
@@ -102,7 +105,13 @@ impl ProtocolVersion {
     async fn send_version(self: Arc<Self>) -> Result<()> {
         debug!(target: "net::protocol_version::send_version()", "START");
 
-        let version = message::VersionMessage { node_id: self.settings.node_id.clone() };
+        let version = message::VersionMessage {
+            node_id: self.settings.node_id.clone(),
+            version: PROTOCOL_VERSION,
+            services: self.settings.services,
+            external_addrs: self.settings.external_addr.clone(),
+            timestamp: unix_timestamp()?,
+        };
 
         self.channel.clone().send(version).await?;
 
@@ -166,7 +175,33 @@ impl ProtocolVersion {
         debug!(target: "net::protocol_version::recv_version()", "START");
         // Receive version message
         let version = self.version_sub.receive().await?;
-        self.channel.set_remote_node_id(version.node_id.clone()).await;
+
+        // Legacy peers don't send their time
+        let now = unix_timestamp()?;
+        if version.timestamp != 0 && now.abs_diff(version.timestamp) > MAX_CLOCK_DRIFT_SECONDS {
+            warn!(
+                target: "net::protocol_version::recv_version()",
+                "Clock of peer {} is off by {} seconds",
+                self.channel.address(),
+                now as i64 - version.timestamp as i64
+            );
+        }
+
+        debug!(
+            target: "net::protocol_version::recv_version()",
+            "Peer {} speaks protocol version {} with services {:#b}",
+            self.channel.address(),
+            version.version,
+            version.services
+        );
+        // Prefer the key authenticated by the transport over the
+        // self-reported node ID.
+        let node_id = match self.channel.remote_pubkey() {
+            Some(pubkey) => hex::encode(pubkey),
+            None => version.node_id.clone(),
+        };
+        self.channel.set_remote_node_id(node_id).await;
+        self.channel.set_remote_version(version).await;
 
         // Send version acknowledgement
         let verack =
@@ -0,0 +1,121 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::time::{Duration, Instant};
+
+use async_std::sync::{Arc, Mutex};
+use smol::Timer;
+
+/// Atomic pointer to a token bucket.
+pub type TokenBucketPtr = Arc<TokenBucket>;
+
+/// Token bucket limiting throughput to a number of bytes per second.
+///
+/// The bucket holds at most one second worth of tokens. Consuming more
+/// tokens than are available puts the bucket in debt, and the caller
+/// waits until the debt is paid back. This way packets bigger than the
+/// bucket still go through, while the long-run rate is respected.
+pub struct TokenBucket {
+    /// Refill rate in bytes per second, 0 for unlimited
+    rate: u64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl BucketState {
+    /// Refill the bucket up to `now`, take `amount` tokens out of it and
+    /// return how long the caller has to wait for the bucket to be out of debt.
+    fn reserve(&mut self, rate: u64, amount: u64, now: Instant) -> Duration {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.last_refill = now;
+
+        self.tokens -= amount as f64;
+        if self.tokens >= 0.0 {
+            return Duration::ZERO
+        }
+
+        Duration::from_secs_f64(-self.tokens / rate as f64)
+    }
+}
+
+impl TokenBucket {
+    /// Create a new, full bucket. A `rate` of 0 disables limiting.
+    pub fn new(rate: u64) -> TokenBucketPtr {
+        let state = BucketState { tokens: rate as f64, last_refill: Instant::now() };
+        Arc::new(Self { rate, state: Mutex::new(state) })
+    }
+
+    /// Returns `true` if the bucket actually limits anything.
+    pub fn is_limited(&self) -> bool {
+        self.rate != 0
+    }
+
+    /// Take `amount` bytes worth of tokens, waiting if the bucket runs dry.
+    pub async fn consume(&self, amount: usize) {
+        if !self.is_limited() {
+            return
+        }
+
+        let delay = self.state.lock().await.reserve(self.rate, amount as u64, Instant::now());
+        if !delay.is_zero() {
+            Timer::after(delay).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{BucketState, TokenBucket};
+
+    #[test]
+    fn test_token_bucket_reserve() {
+        let start = Instant::now();
+        let mut state = BucketState { tokens: 1000.0, last_refill: start };
+
+        // Full bucket lets a burst through
+        assert_eq!(state.reserve(1000, 600, start), Duration::ZERO);
+        assert_eq!(state.reserve(1000, 400, start), Duration::ZERO);
+
+        // Empty bucket goes in debt, a quarter second for 250 bytes
+        assert_eq!(state.reserve(1000, 250, start), Duration::from_millis(250));
+
+        // Refills over time, but never above one second worth of tokens
+        let later = start + Duration::from_secs(10);
+        assert_eq!(state.reserve(1000, 1000, later), Duration::ZERO);
+
+        // Oversized requests go through after paying back their debt
+        assert_eq!(state.reserve(1000, 3000, later), Duration::from_secs(3));
+    }
+
+    #[async_std::test]
+    async fn test_token_bucket_unlimited() {
+        let bucket = TokenBucket::new(0);
+        assert!(!bucket.is_limited());
+
+        let start = Instant::now();
+        bucket.consume(usize::MAX).await;
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
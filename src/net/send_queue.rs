@@ -0,0 +1,195 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use log::debug;
+use smol::channel::{bounded, Receiver, Sender, TrySendError};
+
+use super::message::{MessagePriority, Packet};
+use crate::{Error, Result};
+
+/// Queue priorities, from highest to lowest
+const PRIORITIES: [MessagePriority; 4] = [
+    MessagePriority::Control,
+    MessagePriority::Consensus,
+    MessagePriority::Relay,
+    MessagePriority::Bulk,
+];
+
+/// What happens to a packet pushed onto a full queue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// The sender waits until the queue has room again
+    Wait,
+    /// The packet gets dropped
+    Drop,
+    /// The peer is considered stalled and gets disconnected
+    Disconnect,
+}
+
+impl MessagePriority {
+    /// Policy applied when the queue of this priority is full. Gossip can
+    /// be fetched again from other peers so it gets dropped, while requests
+    /// and responses push back on their sender. A peer not even reading
+    /// control or consensus messages is of no use to us.
+    pub fn overflow_policy(&self) -> OverflowPolicy {
+        match self {
+            Self::Control | Self::Consensus => OverflowPolicy::Disconnect,
+            Self::Relay => OverflowPolicy::Drop,
+            Self::Bulk => OverflowPolicy::Wait,
+        }
+    }
+}
+
+/// Outbound packets of a channel waiting to be written to the stream,
+/// with one bounded queue per priority.
+pub struct SendQueue {
+    queues: Vec<(Sender<Packet>, Receiver<Packet>)>,
+    /// Wakes up the writer when a packet gets queued
+    wakeup: (Sender<()>, Receiver<()>),
+}
+
+impl SendQueue {
+    /// Create a new queue holding at most `capacity` packets per priority.
+    pub fn new(capacity: usize) -> Self {
+        let queues = PRIORITIES.iter().map(|_| bounded(capacity)).collect();
+        Self { queues, wakeup: bounded(1) }
+    }
+
+    /// Queue a packet, applying the overflow policy of its priority if its
+    /// queue is full. Returns `Error::SendQueueFull` if the peer should be
+    /// disconnected.
+    pub async fn push(&self, priority: MessagePriority, packet: Packet) -> Result<()> {
+        let sender = &self.queues[priority as usize].0;
+
+        match sender.try_send(packet) {
+            Ok(()) => {}
+            Err(TrySendError::Closed(_)) => return Err(Error::ChannelStopped),
+            Err(TrySendError::Full(packet)) => match priority.overflow_policy() {
+                OverflowPolicy::Wait => {
+                    sender.send(packet).await.map_err(|_| Error::ChannelStopped)?
+                }
+                OverflowPolicy::Drop => {
+                    debug!(target: "net::send_queue::push()", "Queue full, dropped '{}' packet", packet.command);
+                    return Ok(())
+                }
+                OverflowPolicy::Disconnect => return Err(Error::SendQueueFull),
+            },
+        }
+
+        // The writer may already have a wakeup pending, which is enough
+        let _ = self.wakeup.0.try_send(());
+        Ok(())
+    }
+
+    /// Take the next packet to send, waiting for one if all queues are
+    /// empty. Fails once the queue is closed.
+    pub async fn pop(&self) -> Result<Packet> {
+        loop {
+            for (_, receiver) in &self.queues {
+                if let Ok(packet) = receiver.try_recv() {
+                    return Ok(packet)
+                }
+            }
+
+            self.wakeup.1.recv().await.map_err(|_| Error::ChannelStopped)?;
+        }
+    }
+
+    /// Close the queue, failing any pending or future push and pop.
+    pub fn close(&self) {
+        for (sender, _) in &self.queues {
+            sender.close();
+        }
+        self.wakeup.0.close();
+    }
+
+    /// Number of packets waiting to be sent.
+    pub fn len(&self) -> usize {
+        self.queues.iter().map(|(sender, _)| sender.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_std::{sync::Arc, task};
+
+    use super::SendQueue;
+    use crate::{
+        net::message::{MessagePriority, Packet},
+        Error,
+    };
+
+    fn packet(command: &str) -> Packet {
+        Packet { command: command.to_string(), payload: vec![] }
+    }
+
+    #[async_std::test]
+    async fn test_send_queue_priorities() {
+        let queue = SendQueue::new(8);
+
+        queue.push(MessagePriority::Bulk, packet("block")).await.unwrap();
+        queue.push(MessagePriority::Relay, packet("tx")).await.unwrap();
+        queue.push(MessagePriority::Control, packet("ping")).await.unwrap();
+        queue.push(MessagePriority::Consensus, packet("proposal")).await.unwrap();
+        queue.push(MessagePriority::Control, packet("pong")).await.unwrap();
+        assert_eq!(queue.len(), 5);
+
+        // Highest priority first, in order within a priority
+        for command in ["ping", "pong", "proposal", "tx", "block"] {
+            assert_eq!(queue.pop().await.unwrap().command, command);
+        }
+        assert!(queue.is_empty());
+
+        queue.close();
+        assert!(matches!(queue.pop().await, Err(Error::ChannelStopped)));
+    }
+
+    #[async_std::test]
+    async fn test_send_queue_overflow() {
+        let queue = Arc::new(SendQueue::new(1));
+
+        // Relay packets get dropped
+        queue.push(MessagePriority::Relay, packet("tx1")).await.unwrap();
+        queue.push(MessagePriority::Relay, packet("tx2")).await.unwrap();
+        assert_eq!(queue.len(), 1);
+
+        // Control packets disconnect the peer
+        queue.push(MessagePriority::Control, packet("ping")).await.unwrap();
+        let result = queue.push(MessagePriority::Control, packet("ping")).await;
+        assert!(matches!(result, Err(Error::SendQueueFull)));
+
+        // Bulk packets wait for room
+        queue.push(MessagePriority::Bulk, packet("block1")).await.unwrap();
+        let queue_ = queue.clone();
+        let pusher =
+            task::spawn(async move { queue_.push(MessagePriority::Bulk, packet("block2")).await });
+        task::sleep(Duration::from_millis(100)).await;
+        assert_eq!(queue.len(), 3);
+        for command in ["ping", "tx1", "block1"] {
+            assert_eq!(queue.pop().await.unwrap().command, command);
+        }
+        pusher.await.unwrap();
+        assert_eq!(queue.pop().await.unwrap().command, "block2");
+    }
+}
@@ -22,7 +22,7 @@ use async_trait::async_trait;
 use log::debug;
 use smol::Executor;
 
-use crate::Result;
+use crate::{Error, Result};
 
 use super::{p2p::P2pPtr, protocol::ProtocolVersion, ChannelPtr};
 
@@ -122,11 +122,18 @@ pub trait Session: Sync {
     ) -> Result<()> {
         debug!(target: "net", "Session::register_channel() [START]");
 
+        // Refuse to talk to banned peers
+        let p2p = self.p2p();
+        if p2p.banlist().is_banned(&channel.address()).await {
+            debug!(target: "net", "Session::register_channel(): Peer {} is banned", channel.address());
+            channel.stop().await;
+            return Err(Error::PeerBanned)
+        }
+
         // Protocols should all be initialized but not started
         // We do this so that the protocols can begin receiving and buffering messages
         // while the handshake protocol is ongoing.
         // They are currently in sleep mode.
-        let p2p = self.p2p();
         let protocols =
             p2p.protocol_registry().attach(self.type_id(), channel.clone(), p2p.clone()).await;
 
@@ -138,7 +145,7 @@ pub trait Session: Sync {
             self.perform_handshake_protocols(protocol_version, channel.clone(), executor.clone());
 
         // Switch on the channel
-        channel.start(executor.clone());
+        channel.clone().start(executor.clone());
 
         // Wait for handshake to finish.
         handshake_task.await?;
@@ -146,10 +153,21 @@ pub trait Session: Sync {
         // Now the channel is ready
         debug!(target: "net", "Session handshake complete. Activating remaining protocols");
 
-        // Now start all the protocols
+        // Now start all the protocols whose requirements the peer meets.
         // They are responsible for managing their own lifetimes and
         // correctly self destructing when the channel ends.
-        for protocol in protocols {
+        // The others get dropped, along with their message subscriptions.
+        for (requirements, protocol) in protocols {
+            if !requirements.met_by_channel(&channel).await {
+                debug!(
+                    target: "net",
+                    "Session: Peer doesn't meet {:?} for {}, skipping",
+                    requirements,
+                    protocol.name()
+                );
+                continue
+            }
+
             // Activate protocol
             protocol.start(executor.clone()).await?;
         }
@@ -21,15 +21,14 @@ use std::fmt;
 use async_std::sync::{Arc, Mutex, Weak};
 use async_trait::async_trait;
 use log::{debug, error, info, warn};
-use rand::seq::SliceRandom;
 use serde_json::{json, Value};
 use smol::Executor;
 use url::Url;
 
 use crate::{
-    net::{message, transport::TransportName},
+    net::{constants::MAX_ANCHORS, message, transport::TransportName, NetGroup},
     system::{StoppableTask, StoppableTaskPtr, Subscriber, SubscriberPtr, Subscription},
-    util::async_util,
+    util::{async_util, time::unix_timestamp},
     Error, Result,
 };
 
@@ -64,6 +63,8 @@ struct OutboundInfo {
     addr: Option<Url>,
     channel: Option<ChannelPtr>,
     state: OutboundState,
+    /// UNIX timestamp of when the slot got connected
+    connected_since: u64,
 }
 
 impl OutboundInfo {
@@ -88,7 +89,7 @@ impl OutboundInfo {
 
 impl Default for OutboundInfo {
     fn default() -> Self {
-        Self { addr: None, channel: None, state: OutboundState::Open }
+        Self { addr: None, channel: None, state: OutboundState::Open, connected_since: 0 }
     }
 }
 
@@ -193,11 +194,6 @@ impl OutboundSession {
     ) -> Result<()> {
         let addr = self.load_address(slot_number).await?;
         info!(target: "net::outbound_session", "#{} processing outbound [{}]", slot_number, addr);
-        {
-            let info = &mut self.slot_info.lock().await[slot_number as usize];
-            info.addr = Some(addr.clone());
-            info.state = OutboundState::Pending;
-        }
 
         // Check that addr transport is in configured outbound transport
         let addr_transport = TransportName::try_from(addr.clone())?;
@@ -229,10 +225,14 @@ impl OutboundSession {
 
                     // Remove pending lock since register_channel will add the channel to p2p
                     self.p2p().remove_pending(&addr).await;
+
+                    // Remember this host as working, so we prefer it on future restarts
+                    self.p2p().hosts().mark_connected(&addr).await;
                     {
                         let info = &mut self.slot_info.lock().await[slot_number as usize];
                         info.channel = Some(channel.clone());
                         info.state = OutboundState::Connected;
+                        info.connected_since = unix_timestamp().unwrap_or(0);
                     }
 
                     // Notify that channel processing has been finished
@@ -243,6 +243,9 @@ impl OutboundSession {
                     // Wait for channel to close
                     stop_sub.unwrap().receive().await;
 
+                    // Free the slot, along with its network group
+                    self.slot_info.lock().await[slot_number as usize] = Default::default();
+
                     return Ok(())
                 }
                 Err(err) => {
@@ -251,15 +254,11 @@ impl OutboundSession {
             }
         }
 
-        // Remove url from hosts
-        self.p2p().hosts().remove(&addr).await;
+        // Record the failure, hosts failing too often get evicted
+        self.p2p().hosts().mark_failed(&addr).await;
+        self.p2p().remove_pending(&addr).await;
 
-        {
-            let info = &mut self.slot_info.lock().await[slot_number as usize];
-            info.addr = None;
-            info.channel = None;
-            info.state = OutboundState::Open;
-        }
+        self.slot_info.lock().await[slot_number as usize] = Default::default();
 
         // Notify that channel processing has been finished (failed)
         if *self.notify.lock().await {
@@ -270,29 +269,37 @@ impl OutboundSession {
     }
 
     /// Loops through host addresses to find a outbound address that we can
-    /// connect to. Checks whether address is valid by making sure it isn't
+    /// connect to, preferring the anchors saved by the previous run and then
+    /// hosts we successfully connected to before.
+    /// Checks whether address is valid by making sure it isn't
     /// our own inbound address, then checks whether it is already connected
-    /// (exists) or connecting (pending). If no address was found, we try to
+    /// (exists) or connecting (pending), and that no other slot uses its
+    /// network group. If no address was found, we try to
     /// to discover new peers. Keeps looping until address is found that passes all checks.
     async fn load_address(&self, slot_number: u32) -> Result<Url> {
         loop {
             let p2p = self.p2p();
             let self_inbound_addr = p2p.settings().external_addr.clone();
 
-            let mut addrs;
-
-            {
-                let hosts = p2p.hosts().load_all().await;
-                addrs = hosts;
+            // Anchors are tried once, before any other host
+            let mut addrs = vec![];
+            if let Some(anchor) = p2p.hosts().take_anchor().await {
+                addrs.push(anchor);
             }
 
-            addrs.shuffle(&mut rand::thread_rng());
+            // Hosts we previously connected to come first
+            addrs.extend(p2p.hosts().load_outbound().await);
 
             for addr in addrs {
                 if p2p.exists(&addr).await? {
                     continue
                 }
 
+                // Skip banned peers
+                if p2p.banlist().is_banned(&addr).await {
+                    continue
+                }
+
                 // Check if address is in peers list
                 if p2p.settings().peers.contains(&addr) {
                     continue
@@ -307,6 +314,11 @@ impl OutboundSession {
                     continue
                 }
 
+                if !self.reserve_slot(slot_number, &addr).await {
+                    p2p.remove_pending(&addr).await;
+                    continue
+                }
+
                 return Ok(addr)
             }
 
@@ -323,6 +335,48 @@ impl OutboundSession {
         }
     }
 
+    /// Assign an address to a slot, unless another slot already uses its
+    /// network group. Group diversity is not enforced in localnet mode,
+    /// where all peers usually share a single address.
+    async fn reserve_slot(&self, slot_number: u32, addr: &Url) -> bool {
+        let slot_info = &mut *self.slot_info.lock().await;
+
+        if !self.p2p().settings().localnet {
+            if let Some(group) = NetGroup::from_url(addr) {
+                let taken = slot_info.iter().enumerate().any(|(i, info)| {
+                    i != slot_number as usize &&
+                        info.addr.as_ref().and_then(NetGroup::from_url).as_ref() == Some(&group)
+                });
+
+                if taken {
+                    debug!(target: "net::outbound_session", "#{} skipping {}, its network group is taken", slot_number, addr);
+                    return false
+                }
+            }
+        }
+
+        let info = &mut slot_info[slot_number as usize];
+        info.addr = Some(addr.clone());
+        info.state = OutboundState::Pending;
+        true
+    }
+
+    /// Return the addresses of the longest-lived outbound connections, to
+    /// be used as anchors on the next start.
+    pub async fn anchors(&self) -> Vec<Url> {
+        let mut connected: Vec<(u64, Url)> = self
+            .slot_info
+            .lock()
+            .await
+            .iter()
+            .filter(|info| matches!(info.state, OutboundState::Connected))
+            .filter_map(|info| Some((info.connected_since, info.addr.clone()?)))
+            .collect();
+
+        connected.sort_by_key(|(since, _)| *since);
+        connected.into_iter().take(MAX_ANCHORS).map(|(_, addr)| addr).collect()
+    }
+
     /// Try to find new peers to update available hosts.
     async fn peer_discovery(&self, slot_number: u32) -> Result<()> {
         // Check that another slot(thread) already tries to update hosts
@@ -63,7 +63,12 @@ impl SeedSyncSession {
             return Ok(())
         }
 
-        // if cached addresses then quit
+        // If we have hosts we previously connected to and nothing to advertise,
+        // there is no need to bother the seeds again.
+        if settings.external_addr.is_empty() && !self.p2p().hosts().is_tried_empty().await {
+            info!(target: "net::seedsync_session", "Skipping seed sync process since we have known working hosts.");
+            return Ok(())
+        }
 
         let mut tasks = Vec::new();
 
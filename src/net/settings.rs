@@ -23,7 +23,10 @@ use structopt::StructOpt;
 use structopt_toml::StructOptToml;
 use url::Url;
 
-use crate::net::transport::TransportName;
+use crate::net::{
+    protocol::{ServiceBitflag, SERVICE_NONE},
+    transport::TransportName,
+};
 
 /// Atomic pointer to network settings.
 pub type SettingsPtr = Arc<Settings>;
@@ -59,6 +62,8 @@ pub struct Settings {
     pub node_id: String,
     /// Application version, used for verification between peers
     pub app_version: Option<String>,
+    /// Optional services advertised to peers during the version handshake
+    pub services: ServiceBitflag,
     /// Prefered transports for outbound connections
     pub outbound_transports: Vec<TransportName>,
     /// Allow localnet hosts
@@ -67,6 +72,39 @@ pub struct Settings {
     pub peer_discovery: bool,
     /// Enable channel logging
     pub channel_log: bool,
+    /// Path to the file used to persist known hosts across restarts
+    pub hosts_file: Option<String>,
+    /// Path to the file used to persist outbound anchor peers across restarts
+    pub anchors_file: Option<String>,
+    /// Misbehaviour score at which a peer gets banned
+    pub ban_threshold: u32,
+    /// Duration of a peer ban
+    pub ban_duration_seconds: u64,
+    /// Path to the file used to persist banned peers across restarts
+    pub banlist_file: Option<String>,
+    /// Path to the file holding the static key used by Noise transports.
+    /// An ephemeral key is used when not set.
+    pub node_key_file: Option<String>,
+    /// Hex-encoded static keys of the peers accepted over Noise transports.
+    /// Any peer is accepted when empty.
+    pub trusted_peer_keys: Vec<String>,
+    /// Maximum inbound bandwidth across all channels in bytes per second,
+    /// 0 for unlimited
+    pub inbound_rate_limit: u64,
+    /// Maximum outbound bandwidth across all channels in bytes per second,
+    /// 0 for unlimited
+    pub outbound_rate_limit: u64,
+    /// Maximum inbound bandwidth of a single peer in bytes per second,
+    /// 0 for unlimited
+    pub peer_inbound_rate_limit: u64,
+    /// Maximum outbound bandwidth of a single peer in bytes per second,
+    /// 0 for unlimited
+    pub peer_outbound_rate_limit: u64,
+    /// Maximum number of queued outbound messages of a channel, per priority
+    pub send_queue_len: usize,
+    /// Time a peer gets to accept a single outbound message before it is
+    /// considered stalled and disconnected
+    pub write_timeout_seconds: u64,
 }
 
 impl Default for Settings {
@@ -85,10 +123,24 @@ impl Default for Settings {
             seeds: Vec::new(),
             node_id: String::new(),
             app_version: Some(option_env!("CARGO_PKG_VERSION").unwrap_or("").to_string()),
+            services: SERVICE_NONE,
             outbound_transports: get_outbound_transports(vec![]),
             localnet: false,
             peer_discovery: true,
             channel_log: false,
+            hosts_file: None,
+            anchors_file: None,
+            ban_threshold: 100,
+            ban_duration_seconds: 86400,
+            banlist_file: None,
+            node_key_file: None,
+            trusted_peer_keys: Vec::new(),
+            inbound_rate_limit: 0,
+            outbound_rate_limit: 0,
+            peer_inbound_rate_limit: 0,
+            peer_outbound_rate_limit: 0,
+            send_queue_len: 256,
+            write_timeout_seconds: 60,
         }
     }
 }
@@ -157,6 +209,11 @@ pub struct SettingsOpt {
     #[structopt(skip)]
     pub app_version: Option<String>,
 
+    /// Optional services advertised to peers during the version handshake
+    #[serde(default)]
+    #[structopt(skip)]
+    pub services: ServiceBitflag,
+
     /// Prefered transports for outbound connections
     #[serde(default)]
     #[structopt(long = "transports")]
@@ -176,6 +233,64 @@ pub struct SettingsOpt {
     #[serde(default)]
     #[structopt(long)]
     pub channel_log: bool,
+
+    /// Path to the file used to persist known hosts across restarts
+    #[serde(default)]
+    #[structopt(long)]
+    pub hosts_file: Option<String>,
+
+    /// Path to the file used to persist outbound anchor peers across restarts
+    #[serde(default)]
+    #[structopt(long)]
+    pub anchors_file: Option<String>,
+
+    /// Misbehaviour score at which a peer gets banned
+    #[structopt(skip)]
+    pub ban_threshold: Option<u32>,
+
+    /// Duration of a peer ban
+    #[structopt(skip)]
+    pub ban_duration_seconds: Option<u64>,
+
+    /// Path to the file used to persist banned peers across restarts
+    #[serde(default)]
+    #[structopt(long)]
+    pub banlist_file: Option<String>,
+
+    /// Path to the file holding the static key used by Noise transports
+    #[serde(default)]
+    #[structopt(long)]
+    pub node_key_file: Option<String>,
+
+    /// Hex-encoded static keys of the peers accepted over Noise transports
+    #[serde(default)]
+    #[structopt(long = "trusted-peer-key")]
+    pub trusted_peer_keys: Vec<String>,
+
+    /// Maximum inbound bandwidth across all channels in bytes per second
+    #[structopt(skip)]
+    pub inbound_rate_limit: Option<u64>,
+
+    /// Maximum outbound bandwidth across all channels in bytes per second
+    #[structopt(skip)]
+    pub outbound_rate_limit: Option<u64>,
+
+    /// Maximum inbound bandwidth of a single peer in bytes per second
+    #[structopt(skip)]
+    pub peer_inbound_rate_limit: Option<u64>,
+
+    /// Maximum outbound bandwidth of a single peer in bytes per second
+    #[structopt(skip)]
+    pub peer_outbound_rate_limit: Option<u64>,
+
+    /// Maximum number of queued outbound messages of a channel, per priority
+    #[structopt(skip)]
+    pub send_queue_len: Option<usize>,
+
+    /// Time a peer gets to accept a single outbound message before it is
+    /// considered stalled and disconnected
+    #[structopt(skip)]
+    pub write_timeout_seconds: Option<u64>,
 }
 
 impl From<SettingsOpt> for Settings {
@@ -194,10 +309,24 @@ impl From<SettingsOpt> for Settings {
             seeds: settings_opt.seeds,
             node_id: settings_opt.node_id,
             app_version: settings_opt.app_version,
+            services: settings_opt.services,
             outbound_transports: get_outbound_transports(settings_opt.outbound_transports),
             localnet: settings_opt.localnet,
             peer_discovery: settings_opt.peer_discovery,
             channel_log: settings_opt.channel_log,
+            hosts_file: settings_opt.hosts_file,
+            anchors_file: settings_opt.anchors_file,
+            ban_threshold: settings_opt.ban_threshold.unwrap_or(100),
+            ban_duration_seconds: settings_opt.ban_duration_seconds.unwrap_or(86400),
+            banlist_file: settings_opt.banlist_file,
+            node_key_file: settings_opt.node_key_file,
+            trusted_peer_keys: settings_opt.trusted_peer_keys,
+            inbound_rate_limit: settings_opt.inbound_rate_limit.unwrap_or(0),
+            outbound_rate_limit: settings_opt.outbound_rate_limit.unwrap_or(0),
+            peer_inbound_rate_limit: settings_opt.peer_inbound_rate_limit.unwrap_or(0),
+            peer_outbound_rate_limit: settings_opt.peer_outbound_rate_limit.unwrap_or(0),
+            send_queue_len: settings_opt.send_queue_len.unwrap_or(256),
+            write_timeout_seconds: settings_opt.write_timeout_seconds.unwrap_or(60),
         }
     }
 }
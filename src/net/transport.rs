@@ -28,6 +28,9 @@ use crate::Result;
 mod upgrade_tls;
 pub use upgrade_tls::TlsUpgrade;
 
+mod upgrade_noise;
+pub use upgrade_noise::{NoiseKeypair, NoiseListener, NoiseStream, NoiseUpgrade};
+
 mod tcp;
 pub use tcp::TcpTransport;
 
@@ -40,6 +43,9 @@ pub use unix::UnixTransport;
 mod nym;
 pub use nym::NymTransport;
 
+mod mem;
+pub use mem::{LinkConditions, MemListener, MemNetwork, MemStream, MemTransport};
+
 /// A helper function to convert SocketAddr to Url and add scheme
 pub(crate) fn socket_addr_to_url(addr: SocketAddr, scheme: &str) -> Result<Url> {
     let url = Url::parse(&format!("{}://{}", scheme, addr))?;
@@ -47,7 +53,12 @@ pub(crate) fn socket_addr_to_url(addr: SocketAddr, scheme: &str) -> Result<Url>
 }
 
 /// Used as wrapper for stream used by Transport trait
-pub trait TransportStream: AsyncWrite + AsyncRead + Unpin + Send + Sync {}
+pub trait TransportStream: AsyncWrite + AsyncRead + Unpin + Send + Sync {
+    /// Static public key of the remote peer, if the stream authenticated it
+    fn remote_pubkey(&self) -> Option<[u8; 32]> {
+        None
+    }
+}
 
 /// Used as wrapper for listener used by Transport trait
 #[async_trait]
@@ -60,6 +71,7 @@ pub enum TransportName {
     Tcp(Option<String>),
     Tor(Option<String>),
     Nym(Option<String>),
+    Mem(Option<String>),
     Unix,
 }
 
@@ -72,6 +84,8 @@ impl TransportName {
             Self::Tor(Some(opt)) => format!("tor+{}", opt),
             Self::Nym(None) => "nym".into(),
             Self::Nym(Some(opt)) => format!("nym+{}", opt),
+            Self::Mem(None) => "mem".into(),
+            Self::Mem(Some(opt)) => format!("mem+{}", opt),
             Self::Unix => "unix".into(),
         }
     }
@@ -84,10 +98,15 @@ impl TryFrom<&str> for TransportName {
         let transport_name = match scheme {
             "tcp" => Self::Tcp(None),
             "tcp+tls" | "tls" => Self::Tcp(Some("tls".into())),
+            "tcp+noise" => Self::Tcp(Some("noise".into())),
             "tor" => Self::Tor(None),
             "tor+tls" => Self::Tor(Some("tls".into())),
+            "tor+noise" => Self::Tor(Some("noise".into())),
             "nym" => Self::Nym(None),
             "nym+tls" => Self::Nym(Some("tls".into())),
+            "nym+noise" => Self::Nym(Some("noise".into())),
+            "mem" => Self::Mem(None),
+            "mem+noise" => Self::Mem(Some("noise".into())),
             "unix" => Self::Unix,
             n => return Err(crate::Error::UnsupportedTransport(n.into())),
         };
@@ -107,6 +126,9 @@ impl TryFrom<Url> for TransportName {
 /// Base transports can optionally be upgraded with TLS in order to support encryption.
 /// The implementation of our TLS authentication can be found in the
 /// [`upgrade_tls`](TlsUpgrade) module.
+/// Alternatively, any base transport stream can be upgraded with the Noise
+/// handshake found in the [`upgrade_noise`](NoiseUpgrade) module, which also
+/// authenticates the static key of the remote peer.
 pub trait Transport {
     type Acceptor;
     type Connector;
@@ -0,0 +1,463 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+//! In-process transport connecting nodes running in the same process
+//! through in-memory pipes. Used to test multi-node setups without
+//! sockets.
+//!
+//! Endpoints are named by the host of their `mem://` URL. The links
+//! between endpoints can be given latency and packet loss, and groups of
+//! endpoints can be partitioned from each other.
+//!
+//! Data written to a stream between two flushes travels as one packet.
+//! Since `net::message` flushes after every message, loss drops whole
+//! messages and never corrupts the framing of the ones that go through.
+
+use std::{
+    collections::{HashMap, HashSet},
+    io,
+    pin::Pin,
+    sync::Mutex,
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use futures::{prelude::*, ready};
+use futures_rustls::{TlsAcceptor, TlsStream};
+use lazy_static::lazy_static;
+use log::debug;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use smol::{
+    channel::{unbounded, Receiver, Sender},
+    Timer,
+};
+use url::Url;
+
+use super::{Transport, TransportListener, TransportStream};
+use crate::{Error, Result};
+
+lazy_static! {
+    static ref MEM_NETWORK: MemNetwork = MemNetwork::new();
+}
+
+/// Conditions applied to the packets sent between two endpoints.
+#[derive(Clone, Debug, Default)]
+pub struct LinkConditions {
+    /// One-way delay added to every packet
+    pub latency: Duration,
+    /// Probability, between 0 and 1, of a packet getting dropped
+    pub loss: f64,
+}
+
+/// A packet in flight between two endpoints.
+struct Packet {
+    deliver_at: Instant,
+    data: Vec<u8>,
+}
+
+/// Both directions of an established connection, kept so it can be cut
+/// when its endpoints get partitioned.
+struct Connection {
+    endpoints: (String, String),
+    senders: (Sender<Packet>, Sender<Packet>),
+}
+
+struct NetworkState {
+    listeners: HashMap<String, Sender<(MemStream, Url)>>,
+    links: HashMap<(String, String), LinkConditions>,
+    default_link: LinkConditions,
+    partitions: HashSet<(String, String)>,
+    connections: Vec<Connection>,
+    next_id: u64,
+    rng: StdRng,
+}
+
+/// Order-independent key for the link between two endpoints.
+fn link_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+/// The in-memory network shared by all `mem://` endpoints of the process.
+pub struct MemNetwork {
+    state: Mutex<NetworkState>,
+}
+
+impl MemNetwork {
+    fn new() -> Self {
+        let state = NetworkState {
+            listeners: HashMap::new(),
+            links: HashMap::new(),
+            default_link: LinkConditions::default(),
+            partitions: HashSet::new(),
+            connections: vec![],
+            next_id: 0,
+            rng: StdRng::seed_from_u64(0),
+        };
+
+        Self { state: Mutex::new(state) }
+    }
+
+    /// Return the network of this process.
+    pub fn global() -> &'static Self {
+        &MEM_NETWORK
+    }
+
+    /// Reseed the generator deciding which packets get lost.
+    pub fn set_seed(&self, seed: u64) {
+        self.state.lock().unwrap().rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Set the conditions of links without specific ones.
+    pub fn set_default_link(&self, conditions: LinkConditions) {
+        self.state.lock().unwrap().default_link = conditions;
+    }
+
+    /// Set the conditions of the link between endpoints `a` and `b`,
+    /// in both directions.
+    pub fn set_link(&self, a: &str, b: &str, conditions: LinkConditions) {
+        self.state.lock().unwrap().links.insert(link_key(a, b), conditions);
+    }
+
+    /// Cut every endpoint in `a` off from every endpoint in `b`. New
+    /// connections between them get refused, and existing ones get closed.
+    pub fn partition(&self, a: &[&str], b: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        for x in a {
+            for y in b {
+                state.partitions.insert(link_key(x, y));
+            }
+        }
+
+        let state = &mut *state;
+        state.connections.retain(|conn| {
+            let (x, y) = &conn.endpoints;
+            if state.partitions.contains(&link_key(x, y)) {
+                debug!(target: "net::mem", "Partition closes connection {} <-> {}", x, y);
+                conn.senders.0.close();
+                conn.senders.1.close();
+                return false
+            }
+            true
+        });
+    }
+
+    /// Undo a partition between the endpoints in `a` and `b`.
+    pub fn heal(&self, a: &[&str], b: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        for x in a {
+            for y in b {
+                state.partitions.remove(&link_key(x, y));
+            }
+        }
+    }
+
+    /// Returns `true` if endpoints `a` and `b` can't reach each other.
+    pub fn is_partitioned(&self, a: &str, b: &str) -> bool {
+        self.state.lock().unwrap().partitions.contains(&link_key(a, b))
+    }
+
+    fn bind(&self, name: &str) -> Result<MemListener> {
+        let mut state = self.state.lock().unwrap();
+        if state.listeners.contains_key(name) {
+            return Err(Error::BindFailed(format!("mem://{}", name)))
+        }
+
+        let (sender, receiver) = unbounded();
+        state.listeners.insert(name.to_string(), sender);
+        Ok(MemListener { name: name.to_string(), receiver })
+    }
+
+    fn unbind(&self, name: &str) {
+        self.state.lock().unwrap().listeners.remove(name);
+    }
+
+    fn connect(&self, local: Option<&str>, remote: &str) -> Result<MemStream> {
+        let mut state = self.state.lock().unwrap();
+        state.next_id += 1;
+        let id = state.next_id;
+        let local = match local {
+            Some(name) => name.to_string(),
+            None => format!("anon{}", id),
+        };
+
+        let refused = |reason: &str| {
+            let msg = format!("mem://{} {}", remote, reason);
+            Err(io::Error::new(io::ErrorKind::ConnectionRefused, msg).into())
+        };
+
+        if state.partitions.contains(&link_key(&local, remote)) {
+            return refused("is unreachable")
+        }
+
+        let Some(listener) = state.listeners.get(remote) else {
+            return refused("is not listening")
+        };
+
+        let (local_tx, remote_rx) = unbounded();
+        let (remote_tx, local_rx) = unbounded();
+
+        let local_stream = MemStream::new(&local, remote, local_tx.clone(), local_rx);
+        let remote_stream = MemStream::new(remote, &local, remote_tx.clone(), remote_rx);
+
+        // Like an ephemeral TCP port, tells apart connections from the same peer
+        let port = (id % u16::MAX as u64) + 1;
+        let dialer_url = Url::parse(&format!("mem://{}:{}", local, port))?;
+        if listener.try_send((remote_stream, dialer_url)).is_err() {
+            return refused("is not listening")
+        }
+
+        state.connections.retain(|conn| !conn.senders.0.is_closed() && !conn.senders.1.is_closed());
+        state.connections.push(Connection {
+            endpoints: (local, remote.to_string()),
+            senders: (local_tx, remote_tx),
+        });
+
+        Ok(local_stream)
+    }
+
+    /// Decide the fate of a packet sent from `from` to `to`: `None` if it
+    /// gets lost, otherwise the time it gets delivered.
+    fn route(&self, from: &str, to: &str) -> io::Result<Option<Instant>> {
+        let mut state = self.state.lock().unwrap();
+        let key = link_key(from, to);
+        if state.partitions.contains(&key) {
+            return Err(io::ErrorKind::BrokenPipe.into())
+        }
+
+        let conditions = state.links.get(&key).unwrap_or(&state.default_link).clone();
+        if conditions.loss > 0.0 && state.rng.gen_bool(conditions.loss.min(1.0)) {
+            return Ok(None)
+        }
+
+        Ok(Some(Instant::now() + conditions.latency))
+    }
+}
+
+/// One end of an in-memory connection.
+pub struct MemStream {
+    local: String,
+    remote: String,
+    sender: Sender<Packet>,
+    receiver: Receiver<Packet>,
+    write_buf: Vec<u8>,
+    read_buf: Vec<u8>,
+    read_pos: usize,
+    /// Packet received but still delayed by the link latency
+    delayed: Option<(Timer, Vec<u8>)>,
+}
+
+impl MemStream {
+    fn new(local: &str, remote: &str, sender: Sender<Packet>, receiver: Receiver<Packet>) -> Self {
+        Self {
+            local: local.to_string(),
+            remote: remote.to_string(),
+            sender,
+            receiver,
+            write_buf: vec![],
+            read_buf: vec![],
+            read_pos: 0,
+            delayed: None,
+        }
+    }
+}
+
+impl Drop for MemStream {
+    fn drop(&mut self) {
+        // The network keeps a sender around, so signal EOF explicitly
+        self.sender.close();
+    }
+}
+
+impl TransportStream for MemStream {}
+
+impl AsyncRead for MemStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            if self.read_pos < self.read_buf.len() {
+                let n = buf.len().min(self.read_buf.len() - self.read_pos);
+                buf[..n].copy_from_slice(&self.read_buf[self.read_pos..self.read_pos + n]);
+                self.read_pos += n;
+                return Poll::Ready(Ok(n))
+            }
+
+            if let Some((timer, _)) = &mut self.delayed {
+                ready!(Pin::new(timer).poll(cx));
+                let (_, data) = self.delayed.take().unwrap();
+                self.read_buf = data;
+                self.read_pos = 0;
+                continue
+            }
+
+            match ready!(self.receiver.poll_next_unpin(cx)) {
+                // Closed by the remote or by a partition
+                None => return Poll::Ready(Ok(0)),
+                Some(packet) if packet.deliver_at > Instant::now() => {
+                    self.delayed = Some((Timer::at(packet.deliver_at), packet.data));
+                }
+                Some(packet) => {
+                    self.read_buf = packet.data;
+                    self.read_pos = 0;
+                }
+            }
+        }
+    }
+}
+
+impl AsyncWrite for MemStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        if self.sender.is_closed() {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+        }
+
+        self.write_buf.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        if self.write_buf.is_empty() {
+            return Poll::Ready(Ok(()))
+        }
+
+        let data = std::mem::take(&mut self.write_buf);
+        let Some(deliver_at) = MEM_NETWORK.route(&self.local, &self.remote)? else {
+            debug!(target: "net::mem", "Dropped {} bytes from {} to {}", data.len(), self.local, self.remote);
+            return Poll::Ready(Ok(()))
+        };
+
+        if self.sender.try_send(Packet { deliver_at, data }).is_err() {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()))
+        }
+
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        ready!(self.as_mut().poll_flush(cx))?;
+        self.sender.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Listener accepting in-memory connections made to its endpoint name.
+pub struct MemListener {
+    name: String,
+    receiver: Receiver<(MemStream, Url)>,
+}
+
+impl Drop for MemListener {
+    fn drop(&mut self) {
+        MEM_NETWORK.unbind(&self.name);
+    }
+}
+
+#[async_trait]
+impl TransportListener for MemListener {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        match self.receiver.recv().await {
+            Ok((stream, url)) => Ok((Box::new(stream), url)),
+            Err(_) => Err(Error::AcceptConnectionFailed(format!("mem://{}", self.name))),
+        }
+    }
+}
+
+#[async_trait]
+impl TransportListener for (TlsAcceptor, MemListener) {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        Err(Error::UnsupportedTransportUpgrade("tls".to_string()))
+    }
+}
+
+#[derive(Clone)]
+pub struct MemTransport {
+    /// Endpoint name outbound connections originate from, used to apply
+    /// link conditions and partitions. A unique name is made up if `None`.
+    local: Option<String>,
+}
+
+impl Transport for MemTransport {
+    type Acceptor = MemListener;
+    type Connector = MemStream;
+
+    type Listener = Pin<Box<dyn Future<Output = Result<Self::Acceptor>> + Send>>;
+    type Dial = Pin<Box<dyn Future<Output = Result<Self::Connector>> + Send>>;
+
+    type TlsListener = Pin<Box<dyn Future<Output = Result<(TlsAcceptor, Self::Acceptor)>> + Send>>;
+    type TlsDialer = Pin<Box<dyn Future<Output = Result<TlsStream<Self::Connector>>> + Send>>;
+
+    fn listen_on(self, url: Url) -> Result<Self::Listener> {
+        let name = Self::endpoint(&url)?;
+        debug!(target: "net::mem", "{} transport: listening on {}", url.scheme(), name);
+        Ok(Box::pin(async move { MEM_NETWORK.bind(&name) }))
+    }
+
+    fn upgrade_listener(self, _acceptor: Self::Acceptor) -> Result<Self::TlsListener> {
+        Err(Error::UnsupportedTransportUpgrade("tls".to_string()))
+    }
+
+    fn dial(self, url: Url, _timeout: Option<Duration>) -> Result<Self::Dial> {
+        let remote = Self::endpoint(&url)?;
+        debug!(target: "net::mem", "{} transport: dialing {}", url.scheme(), remote);
+        Ok(Box::pin(async move { MEM_NETWORK.connect(self.local.as_deref(), &remote) }))
+    }
+
+    fn upgrade_dialer(self, _connector: Self::Connector) -> Result<Self::TlsDialer> {
+        Err(Error::UnsupportedTransportUpgrade("tls".to_string()))
+    }
+}
+
+impl MemTransport {
+    pub fn new(local: Option<String>) -> Self {
+        Self { local }
+    }
+
+    /// Create a transport dialing from the first `mem://` endpoint found
+    /// in `inbound`, if any.
+    pub fn from_inbound(inbound: &[Url]) -> Self {
+        let local = inbound
+            .iter()
+            .find(|url| url.scheme().starts_with("mem"))
+            .and_then(|url| url.host_str())
+            .map(String::from);
+        Self::new(local)
+    }
+
+    fn endpoint(url: &Url) -> Result<String> {
+        match url.scheme() {
+            "mem" | "mem+noise" => {}
+            x => return Err(Error::UnsupportedTransport(x.to_string())),
+        }
+
+        match url.host_str() {
+            Some(host) => Ok(host.to_string()),
+            None => Err(Error::UnsupportedTransport(url.to_string())),
+        }
+    }
+}
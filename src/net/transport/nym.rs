@@ -102,7 +102,7 @@ impl Transport for NymTransport {
 
     fn listen_on(self, url: Url) -> Result<Self::Listener> {
         match url.scheme() {
-            "nym" | "nym+tls" => {}
+            "nym" | "nym+tls" | "nym+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
         Ok(Box::pin(self.do_listen(url)))
@@ -115,7 +115,7 @@ impl Transport for NymTransport {
 
     fn dial(self, url: Url, _timeout: Option<Duration>) -> Result<Self::Dial> {
         match url.scheme() {
-            "nym" | "nym+tls" => {}
+            "nym" | "nym+tls" | "nym+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
 
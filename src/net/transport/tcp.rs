@@ -91,7 +91,7 @@ impl Transport for TcpTransport {
 
     fn listen_on(self, url: Url) -> Result<Self::Listener> {
         match url.scheme() {
-            "tcp" | "tcp+tls" | "tls" => {}
+            "tcp" | "tcp+tls" | "tls" | "tcp+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
 
@@ -107,7 +107,7 @@ impl Transport for TcpTransport {
 
     fn dial(self, url: Url, timeout: Option<Duration>) -> Result<Self::Dial> {
         match url.scheme() {
-            "tcp" | "tcp+tls" | "tls" => {}
+            "tcp" | "tcp+tls" | "tls" | "tcp+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
 
@@ -295,7 +295,7 @@ impl Transport for TorTransport {
 
     fn listen_on(self, url: Url) -> Result<Self::Listener> {
         match url.scheme() {
-            "tor" | "tor+tls" => {}
+            "tor" | "tor+tls" | "tor+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
         Ok(Box::pin(self.do_listen(url)))
@@ -308,8 +308,8 @@ impl Transport for TorTransport {
 
     fn dial(self, url: Url, _timeout: Option<Duration>) -> Result<Self::Dial> {
         match url.scheme() {
-            "tor" | "tor+tls" => {}
-            "tcp" | "tcp+tls" | "tls" => {}
+            "tor" | "tor+tls" | "tor+noise" => {}
+            "tcp" | "tcp+tls" | "tls" | "tcp+noise" => {}
             x => return Err(Error::UnsupportedTransport(x.to_string())),
         }
         Ok(Box::pin(self.do_dial(url)))
@@ -0,0 +1,517 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::{
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    os::unix::fs::OpenOptionsExt,
+    path::Path,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use async_std::{future::timeout, sync::Arc};
+use async_trait::async_trait;
+use futures::prelude::*;
+use log::{debug, error, info};
+use smol::Executor;
+use snow::{params::NoiseParams, Builder, HandshakeState, TransportState};
+use url::Url;
+
+use super::{TransportListener, TransportStream};
+use crate::{Error, Result};
+
+/// Noise protocol used for the handshake. In the XX pattern both sides
+/// transmit their static keys, so each node learns the identity of its peer.
+const NOISE_PARAMS: &str = "Noise_XX_25519_ChaChaPoly_BLAKE2s";
+
+/// Maximum length of a single Noise message
+const MAX_NOISE_MSG_LEN: usize = 65535;
+
+/// Length of the authentication tag appended to each encrypted message
+const NOISE_TAG_LEN: usize = 16;
+
+/// Maximum plaintext carried by a single encrypted frame
+const MAX_FRAME_PLAINTEXT_LEN: usize = MAX_NOISE_MSG_LEN - NOISE_TAG_LEN;
+
+/// Time given to a peer to complete the handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn noise_params() -> NoiseParams {
+    NOISE_PARAMS.parse().unwrap()
+}
+
+fn noise_err(err: snow::Error) -> Error {
+    Error::NoiseError(err.to_string())
+}
+
+/// Static X25519 keypair identifying a node on Noise transports.
+#[derive(Clone)]
+pub struct NoiseKeypair {
+    secret: [u8; 32],
+    public: [u8; 32],
+}
+
+impl NoiseKeypair {
+    /// Generate a new random keypair.
+    pub fn generate() -> Self {
+        let keypair = Builder::new(noise_params()).generate_keypair().unwrap();
+        let mut secret = [0u8; 32];
+        let mut public = [0u8; 32];
+        secret.copy_from_slice(&keypair.private);
+        public.copy_from_slice(&keypair.public);
+        Self { secret, public }
+    }
+
+    /// Load a keypair from given path, generating and saving a new one if
+    /// the file does not exist.
+    pub fn load_or_generate(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            let keypair = Self::generate();
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // The file holds the secret key, so only the owner can read it
+            let mut file =
+                OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+            file.write_all(&[keypair.secret, keypair.public].concat())?;
+            info!(
+                target: "net::upgrade_noise",
+                "Generated new node key {} at {:?}",
+                hex::encode(keypair.public),
+                path
+            );
+            return Ok(keypair)
+        }
+
+        let bytes = fs::read(path)?;
+        if bytes.len() != 64 {
+            return Err(Error::NoiseError(format!("Invalid node key file {:?}", path)))
+        }
+
+        let mut secret = [0u8; 32];
+        let mut public = [0u8; 32];
+        secret.copy_from_slice(&bytes[..32]);
+        public.copy_from_slice(&bytes[32..]);
+        Ok(Self { secret, public })
+    }
+
+    /// Public key of the keypair.
+    pub fn public(&self) -> [u8; 32] {
+        self.public
+    }
+}
+
+/// Authenticates and encrypts streams of any base transport using the Noise
+/// XX handshake. Optionally restricts the peers we talk to by their static
+/// public keys.
+#[derive(Clone)]
+pub struct NoiseUpgrade {
+    /// Static keypair of this node
+    keypair: Arc<NoiseKeypair>,
+    /// Public keys of the peers we accept, or `None` to accept any peer
+    trusted_keys: Option<Arc<Vec<[u8; 32]>>>,
+}
+
+impl NoiseUpgrade {
+    pub fn new(keypair: Arc<NoiseKeypair>, trusted_keys: Option<Arc<Vec<[u8; 32]>>>) -> Self {
+        Self { keypair, trusted_keys }
+    }
+
+    /// Perform the handshake as the initiator on an outbound stream.
+    pub async fn upgrade_dialer_noise<IO>(self, stream: IO) -> Result<NoiseStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let handshake = Builder::new(noise_params())
+            .local_private_key(&self.keypair.secret)
+            .build_initiator()
+            .map_err(noise_err)?;
+
+        match timeout(HANDSHAKE_TIMEOUT, self.handshake(stream, handshake, true)).await {
+            Ok(stream) => stream,
+            Err(_) => Err(Error::NoiseError("Handshake timed out".to_string())),
+        }
+    }
+
+    /// Perform the handshake as the responder on an inbound stream.
+    pub async fn upgrade_listener_noise<IO>(self, stream: IO) -> Result<NoiseStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let handshake = Builder::new(noise_params())
+            .local_private_key(&self.keypair.secret)
+            .build_responder()
+            .map_err(noise_err)?;
+
+        match timeout(HANDSHAKE_TIMEOUT, self.handshake(stream, handshake, false)).await {
+            Ok(stream) => stream,
+            Err(_) => Err(Error::NoiseError("Handshake timed out".to_string())),
+        }
+    }
+
+    async fn handshake<IO>(
+        &self,
+        mut stream: IO,
+        mut handshake: HandshakeState,
+        initiator: bool,
+    ) -> Result<NoiseStream<IO>>
+    where
+        IO: AsyncRead + AsyncWrite + Unpin,
+    {
+        let mut buf = vec![0u8; MAX_NOISE_MSG_LEN];
+
+        // XX: -> e, <- e ee s es, -> s se
+        let mut our_turn = initiator;
+        while !handshake.is_handshake_finished() {
+            if our_turn {
+                let len = handshake.write_message(&[], &mut buf).map_err(noise_err)?;
+                write_frame(&mut stream, &buf[..len]).await?;
+            } else {
+                let msg = read_frame(&mut stream).await?;
+                handshake.read_message(&msg, &mut buf).map_err(noise_err)?;
+            }
+            our_turn = !our_turn;
+        }
+
+        let mut remote_pubkey = [0u8; 32];
+        match handshake.get_remote_static() {
+            Some(key) if key.len() == 32 => remote_pubkey.copy_from_slice(key),
+            _ => return Err(Error::NoiseError("Missing remote static key".to_string())),
+        }
+
+        if let Some(trusted_keys) = &self.trusted_keys {
+            if !trusted_keys.contains(&remote_pubkey) {
+                return Err(Error::UntrustedPeerKey(hex::encode(remote_pubkey)))
+            }
+        }
+
+        debug!(
+            target: "net::upgrade_noise",
+            "Handshake complete with peer {}",
+            hex::encode(remote_pubkey)
+        );
+
+        let transport = handshake.into_transport_mode().map_err(noise_err)?;
+        Ok(NoiseStream::new(stream, transport, remote_pubkey))
+    }
+}
+
+/// Write a length-prefixed frame to the stream.
+async fn write_frame<W: AsyncWrite + Unpin>(stream: &mut W, frame: &[u8]) -> Result<()> {
+    stream.write_all(&(frame.len() as u16).to_be_bytes()).await?;
+    stream.write_all(frame).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+/// Read a length-prefixed frame from the stream.
+async fn read_frame<R: AsyncRead + Unpin>(stream: &mut R) -> Result<Vec<u8>> {
+    let mut len = [0u8; 2];
+    stream.read_exact(&mut len).await?;
+    let mut frame = vec![0u8; u16::from_be_bytes(len) as usize];
+    stream.read_exact(&mut frame).await?;
+    Ok(frame)
+}
+
+/// Stream encrypted with an established Noise session. Data is sent in
+/// length-prefixed frames, each holding a single Noise transport message.
+pub struct NoiseStream<IO> {
+    inner: IO,
+    noise: TransportState,
+    remote_pubkey: [u8; 32],
+    /// Length prefix of the frame being read
+    read_len: [u8; 2],
+    read_len_pos: usize,
+    /// Ciphertext of the frame being read
+    read_frame: Vec<u8>,
+    read_frame_pos: usize,
+    /// Decrypted data not yet handed to the reader
+    plaintext: Vec<u8>,
+    plaintext_pos: usize,
+    /// Encrypted frame not yet fully written to the inner stream
+    write_frame: Vec<u8>,
+    write_frame_pos: usize,
+}
+
+impl<IO> NoiseStream<IO> {
+    fn new(inner: IO, noise: TransportState, remote_pubkey: [u8; 32]) -> Self {
+        Self {
+            inner,
+            noise,
+            remote_pubkey,
+            read_len: [0u8; 2],
+            read_len_pos: 0,
+            read_frame: vec![],
+            read_frame_pos: 0,
+            plaintext: vec![],
+            plaintext_pos: 0,
+            write_frame: vec![],
+            write_frame_pos: 0,
+        }
+    }
+
+    /// Static public key of the remote peer, as verified by the handshake.
+    pub fn remote_pubkey(&self) -> [u8; 32] {
+        self.remote_pubkey
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> NoiseStream<IO> {
+    /// Write out the pending encrypted frame, if any.
+    fn poll_write_frame(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while self.write_frame_pos < self.write_frame.len() {
+            let n =
+                futures::ready!(Pin::new(&mut self.inner)
+                    .poll_write(cx, &self.write_frame[self.write_frame_pos..]))?;
+            if n == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()))
+            }
+            self.write_frame_pos += n;
+        }
+
+        self.write_frame.clear();
+        self.write_frame_pos = 0;
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<IO: AsyncRead + Unpin> AsyncRead for NoiseStream<IO> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        loop {
+            // Hand out buffered plaintext first
+            if this.plaintext_pos < this.plaintext.len() {
+                let n = buf.len().min(this.plaintext.len() - this.plaintext_pos);
+                buf[..n]
+                    .copy_from_slice(&this.plaintext[this.plaintext_pos..this.plaintext_pos + n]);
+                this.plaintext_pos += n;
+                return Poll::Ready(Ok(n))
+            }
+
+            // Read the length prefix of the next frame
+            while this.read_len_pos < 2 {
+                let n = futures::ready!(Pin::new(&mut this.inner)
+                    .poll_read(cx, &mut this.read_len[this.read_len_pos..]))?;
+                if n == 0 {
+                    if this.read_len_pos == 0 {
+                        return Poll::Ready(Ok(0))
+                    }
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()))
+                }
+                this.read_len_pos += n;
+                if this.read_len_pos == 2 {
+                    this.read_frame = vec![0u8; u16::from_be_bytes(this.read_len) as usize];
+                    this.read_frame_pos = 0;
+                }
+            }
+
+            // Read the frame itself
+            while this.read_frame_pos < this.read_frame.len() {
+                let n = futures::ready!(Pin::new(&mut this.inner)
+                    .poll_read(cx, &mut this.read_frame[this.read_frame_pos..]))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::UnexpectedEof.into()))
+                }
+                this.read_frame_pos += n;
+            }
+
+            let mut plaintext = vec![0u8; this.read_frame.len()];
+            let len = match this.noise.read_message(&this.read_frame, &mut plaintext) {
+                Ok(len) => len,
+                Err(e) => {
+                    error!(target: "net::upgrade_noise", "Failed decrypting frame: {}", e);
+                    return Poll::Ready(Err(io::Error::new(io::ErrorKind::InvalidData, e)))
+                }
+            };
+            plaintext.truncate(len);
+            this.plaintext = plaintext;
+            this.plaintext_pos = 0;
+            this.read_len_pos = 0;
+        }
+    }
+}
+
+impl<IO: AsyncWrite + Unpin> AsyncWrite for NoiseStream<IO> {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0))
+        }
+
+        // Only one frame is buffered at a time
+        futures::ready!(this.poll_write_frame(cx))?;
+
+        let n = buf.len().min(MAX_FRAME_PLAINTEXT_LEN);
+        let mut frame = vec![0u8; 2 + n + NOISE_TAG_LEN];
+        let len = match this.noise.write_message(&buf[..n], &mut frame[2..]) {
+            Ok(len) => len,
+            Err(e) => return Poll::Ready(Err(io::Error::new(io::ErrorKind::Other, e))),
+        };
+        frame[..2].copy_from_slice(&(len as u16).to_be_bytes());
+        frame.truncate(2 + len);
+        this.write_frame = frame;
+        this.write_frame_pos = 0;
+
+        // The frame is now ours to deliver, so try to get it out right away
+        // but report the data as written regardless.
+        let _ = this.poll_write_frame(cx);
+        Poll::Ready(Ok(n))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_write_frame(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        futures::ready!(this.poll_write_frame(cx))?;
+        Pin::new(&mut this.inner).poll_close(cx)
+    }
+}
+
+impl<IO: AsyncRead + AsyncWrite + Unpin + Send + Sync> TransportStream for NoiseStream<IO> {
+    fn remote_pubkey(&self) -> Option<[u8; 32]> {
+        Some(self.remote_pubkey)
+    }
+}
+
+/// Listener performing the Noise handshake on each accepted connection of
+/// the wrapped base transport listener. Handshakes run in their own tasks,
+/// so peers stalling them don't hold back other inbound connections.
+pub struct NoiseListener {
+    /// Streams that completed the handshake, or base listener errors
+    streams: smol::channel::Receiver<Result<(Box<dyn TransportStream>, Url)>>,
+    /// Task accepting connections of the base listener
+    _accept_task: smol::Task<()>,
+}
+
+impl NoiseListener {
+    pub fn new(
+        inner: Box<dyn TransportListener>,
+        upgrade: NoiseUpgrade,
+        executor: Arc<Executor<'_>>,
+    ) -> Self {
+        let (sender, streams) = smol::channel::unbounded();
+        let _accept_task =
+            executor.spawn(Self::accept_loop(inner, upgrade, sender, executor.clone()));
+        Self { streams, _accept_task }
+    }
+
+    /// Accept connections of the base listener, handing each one to a new
+    /// task performing the handshake.
+    async fn accept_loop(
+        inner: Box<dyn TransportListener>,
+        upgrade: NoiseUpgrade,
+        sender: smol::channel::Sender<Result<(Box<dyn TransportStream>, Url)>>,
+        executor: Arc<Executor<'_>>,
+    ) {
+        loop {
+            let (stream, mut url) = match inner.next().await {
+                Ok(v) => v,
+                Err(err) => {
+                    if sender.send(Err(err)).await.is_err() {
+                        return
+                    }
+                    continue
+                }
+            };
+
+            let upgrade = upgrade.clone();
+            let sender = sender.clone();
+            executor
+                .spawn(async move {
+                    let stream = match upgrade.upgrade_listener_noise(stream).await {
+                        Ok(stream) => stream,
+                        Err(err) => {
+                            error!(target: "net::upgrade_noise", "Noise handshake with {} failed: {}", url, err);
+                            return
+                        }
+                    };
+
+                    let scheme = format!("{}+noise", url.scheme());
+                    let _ = url.set_scheme(&scheme);
+
+                    let stream: Box<dyn TransportStream> = Box::new(stream);
+                    let _ = sender.send(Ok((stream, url))).await;
+                })
+                .detach();
+        }
+    }
+}
+
+#[async_trait]
+impl TransportListener for NoiseListener {
+    async fn next(&self) -> Result<(Box<dyn TransportStream>, Url)> {
+        match self.streams.recv().await {
+            Ok(result) => result,
+            Err(_) => Err(Error::NetworkServiceStopped),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_std::{future::timeout, sync::Arc};
+    use smol::Executor;
+    use url::Url;
+
+    use super::{NoiseKeypair, NoiseListener, NoiseUpgrade};
+    use crate::net::transport::{MemTransport, Transport, TransportListener};
+
+    #[test]
+    fn stalled_handshake_does_not_block_accepts() {
+        let executor = Arc::new(Executor::new());
+        smol::block_on(executor.run(async {
+            let url = Url::parse("mem://noise-stalled-handshake").unwrap();
+            let listener = MemTransport::new(None).listen_on(url.clone()).unwrap().await.unwrap();
+            let upgrade = NoiseUpgrade::new(Arc::new(NoiseKeypair::generate()), None);
+            let listener = NoiseListener::new(Box::new(listener), upgrade, executor.clone());
+
+            // This peer connects and never starts the handshake
+            let _silent = MemTransport::new(None).dial(url.clone(), None).unwrap().await.unwrap();
+
+            let keypair = Arc::new(NoiseKeypair::generate());
+            let public = keypair.public();
+            let stream = MemTransport::new(None).dial(url, None).unwrap().await.unwrap();
+            let dialer =
+                executor.spawn(NoiseUpgrade::new(keypair, None).upgrade_dialer_noise(stream));
+
+            // The second peer gets accepted well before the first one times out
+            let (stream, url) =
+                timeout(Duration::from_secs(5), listener.next()).await.unwrap().unwrap();
+            assert_eq!(stream.remote_pubkey(), Some(public));
+            assert_eq!(url.scheme(), "mem+noise");
+            dialer.await.unwrap();
+        }));
+    }
+}
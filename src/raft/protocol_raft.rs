@@ -133,4 +133,8 @@ impl net::Message for NetMsg {
     fn name() -> &'static str {
         "netmsg"
     }
+
+    fn priority() -> net::MessagePriority {
+        net::MessagePriority::Consensus
+    }
 }
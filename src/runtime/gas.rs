@@ -0,0 +1,336 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::sync::Mutex;
+
+use log::error;
+use wasmer::{
+    wasmparser::{BlockType, Operator},
+    ExportIndex, FunctionEnvMut, FunctionMiddleware, GlobalInit, GlobalType, LocalFunctionIndex,
+    MiddlewareError, MiddlewareReaderState, ModuleMiddleware, Mutability, Type,
+};
+use wasmer_middlewares::metering::{get_remaining_points, set_remaining_points, MeteringPoints};
+use wasmer_types::ModuleInfo;
+
+use super::vm_runtime::Env;
+
+/// Gas cost charged by any host function call
+pub const HOST_CALL: u64 = 1_000;
+
+/// Gas cost of initializing or looking up a database
+pub const DB_HANDLE: u64 = 10_000;
+
+/// Gas cost of each byte read from a database, keys included
+pub const DB_READ_BYTE: u64 = 10;
+
+/// Gas cost of each byte written to a database, keys included
+pub const DB_WRITE_BYTE: u64 = 100;
+
+/// Gas cost of each entry returned by a database range read
+pub const DB_RANGE_ENTRY: u64 = 1_000;
+
+/// Gas cost of each byte of the coins appended to a Merkle tree
+pub const MERKLE_ADD_BYTE: u64 = 1_000;
+
+/// Gas cost of registering a zkas circuit, which builds its `VerifyingKey`
+pub const ZKAS_REGISTER: u64 = 10_000_000;
+
+/// Gas cost of each byte of an emitted event, topics included
+pub const EVENT_BYTE: u64 = 100;
+
+/// Gas cost of invoking another contract, which instantiates its wasm module
+pub const INVOKE_CONTRACT: u64 = 1_000_000;
+
+/// Gas cost of each byte of the payload passed to an invoked contract
+pub const INVOKE_PAYLOAD_BYTE: u64 = 10;
+
+/// Gas cost of each byte written by the `memory.copy` and `memory.fill`
+/// operators, on top of their flat operator cost
+pub const MEMORY_BULK_BYTE: u64 = 1;
+
+/// Gas cost of each executed wasm operator. Control flow markers are free,
+/// while calls, memory accesses and expensive arithmetic are priced over
+/// the plain operators costing one point.
+/// https://docs.rs/wasmparser/latest/wasmparser/enum.Operator.html
+pub fn operator_cost(operator: &Operator) -> u64 {
+    match operator {
+        Operator::Nop { .. } |
+        Operator::Unreachable { .. } |
+        Operator::Block { .. } |
+        Operator::Loop { .. } |
+        Operator::If { .. } |
+        Operator::Else { .. } |
+        Operator::End { .. } => 0,
+
+        Operator::Br { .. } | Operator::BrIf { .. } | Operator::Return { .. } => 2,
+        Operator::BrTable { .. } => 3,
+
+        Operator::Call { .. } => 10,
+        Operator::CallIndirect { .. } => 15,
+
+        Operator::I32Load { .. } |
+        Operator::I64Load { .. } |
+        Operator::F32Load { .. } |
+        Operator::F64Load { .. } |
+        Operator::I32Load8S { .. } |
+        Operator::I32Load8U { .. } |
+        Operator::I32Load16S { .. } |
+        Operator::I32Load16U { .. } |
+        Operator::I64Load8S { .. } |
+        Operator::I64Load8U { .. } |
+        Operator::I64Load16S { .. } |
+        Operator::I64Load16U { .. } |
+        Operator::I64Load32S { .. } |
+        Operator::I64Load32U { .. } => 3,
+
+        Operator::I32Store { .. } |
+        Operator::I64Store { .. } |
+        Operator::F32Store { .. } |
+        Operator::F64Store { .. } |
+        Operator::I32Store8 { .. } |
+        Operator::I32Store16 { .. } |
+        Operator::I64Store8 { .. } |
+        Operator::I64Store16 { .. } |
+        Operator::I64Store32 { .. } => 4,
+
+        Operator::MemorySize { .. } => 2,
+        Operator::MemoryGrow { .. } => 10_000,
+        // Their length is charged by `BulkMemoryMetering`
+        Operator::MemoryCopy { .. } | Operator::MemoryFill { .. } => 100,
+
+        Operator::I32Mul { .. } | Operator::I64Mul { .. } => 3,
+
+        Operator::I32DivS { .. } |
+        Operator::I32DivU { .. } |
+        Operator::I32RemS { .. } |
+        Operator::I32RemU { .. } |
+        Operator::I64DivS { .. } |
+        Operator::I64DivU { .. } |
+        Operator::I64RemS { .. } |
+        Operator::I64RemU { .. } => 8,
+
+        Operator::F32Add { .. } |
+        Operator::F32Sub { .. } |
+        Operator::F32Mul { .. } |
+        Operator::F64Add { .. } |
+        Operator::F64Sub { .. } |
+        Operator::F64Mul { .. } => 4,
+
+        Operator::F32Div { .. } |
+        Operator::F32Sqrt { .. } |
+        Operator::F64Div { .. } |
+        Operator::F64Sqrt { .. } => 12,
+
+        _ => 1,
+    }
+}
+
+/// Middleware charging `MEMORY_BULK_BYTE` for each byte written by the bulk
+/// memory operators, whose length is only known at runtime. It charges the
+/// points of the `Metering` middleware, so it must be pushed after it.
+#[derive(Debug, Default)]
+pub struct BulkMemoryMetering {
+    global_indexes: Mutex<Option<BulkMemoryGlobals>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BulkMemoryGlobals {
+    /// `Metering` remaining points
+    remaining_points: u32,
+    /// `Metering` points exhausted flag
+    points_exhausted: u32,
+    /// Length operand of the bulk memory operator being charged
+    length: u32,
+}
+
+impl ModuleMiddleware for BulkMemoryMetering {
+    fn generate_function_middleware(&self, _: LocalFunctionIndex) -> Box<dyn FunctionMiddleware> {
+        let globals = self.global_indexes.lock().unwrap().expect("Module info not transformed");
+        Box::new(FunctionBulkMemoryMetering { globals })
+    }
+
+    fn transform_module_info(&self, module_info: &mut ModuleInfo) -> Result<(), MiddlewareError> {
+        let mut global_indexes = self.global_indexes.lock().unwrap();
+        if global_indexes.is_some() {
+            panic!("BulkMemoryMetering: Attempting to use the middleware from multiple modules");
+        }
+
+        let metering_global = |name: &str| match module_info.exports.get(name) {
+            Some(ExportIndex::Global(index)) => Ok(index.as_u32()),
+            _ => Err(MiddlewareError::new(
+                "BulkMemoryMetering",
+                format!("Missing {} global, `Metering` must be pushed first", name),
+            )),
+        };
+        let remaining_points = metering_global("wasmer_metering_remaining_points")?;
+        let points_exhausted = metering_global("wasmer_metering_points_exhausted")?;
+
+        let length = module_info.globals.push(GlobalType::new(Type::I32, Mutability::Var));
+        module_info.global_initializers.push(GlobalInit::I32Const(0));
+
+        *global_indexes =
+            Some(BulkMemoryGlobals { remaining_points, points_exhausted, length: length.as_u32() });
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+struct FunctionBulkMemoryMetering {
+    globals: BulkMemoryGlobals,
+}
+
+impl FunctionMiddleware for FunctionBulkMemoryMetering {
+    fn feed<'a>(
+        &mut self,
+        operator: Operator<'a>,
+        state: &mut MiddlewareReaderState<'a>,
+    ) -> Result<(), MiddlewareError> {
+        if !matches!(operator, Operator::MemoryCopy { .. } | Operator::MemoryFill { .. }) {
+            state.push_operator(operator);
+            return Ok(())
+        }
+
+        let globals = self.globals;
+        let cost = [
+            Operator::GlobalGet { global_index: globals.length },
+            Operator::I64ExtendI32U,
+            Operator::I64Const { value: MEMORY_BULK_BYTE as i64 },
+            Operator::I64Mul,
+        ];
+
+        // Stash the length operand from the top of the stack, and run out
+        // of gas the same way `Metering` does if its cost can't be paid.
+        let mut ops = vec![
+            Operator::GlobalSet { global_index: globals.length },
+            Operator::GlobalGet { global_index: globals.remaining_points },
+        ];
+        ops.extend_from_slice(&cost);
+        ops.extend_from_slice(&[
+            Operator::I64LtU,
+            Operator::If { blockty: BlockType::Empty },
+            Operator::I32Const { value: 1 },
+            Operator::GlobalSet { global_index: globals.points_exhausted },
+            Operator::Unreachable,
+            Operator::End,
+            Operator::GlobalGet { global_index: globals.remaining_points },
+        ]);
+        ops.extend_from_slice(&cost);
+        ops.extend_from_slice(&[
+            Operator::I64Sub,
+            Operator::GlobalSet { global_index: globals.remaining_points },
+            Operator::GlobalGet { global_index: globals.length },
+            operator,
+        ]);
+
+        for op in ops {
+            state.push_operator(op);
+        }
+
+        Ok(())
+    }
+}
+
+/// Subtract given gas from the remaining points of the running instance.
+/// Returns `false` if not enough points remain, in which case they are all
+/// consumed and the host function must return an error without doing any
+/// work, so the execution fails as out of gas.
+#[must_use]
+pub(crate) fn subtract_gas(ctx: &mut FunctionEnvMut<Env>, gas: u64) -> bool {
+    let (env, mut store) = ctx.data_and_store_mut();
+    let instance = env.instance.as_ref().unwrap();
+
+    let remaining = match get_remaining_points(&mut store, instance) {
+        MeteringPoints::Remaining(rem) => rem,
+        MeteringPoints::Exhausted => 0,
+    };
+
+    if remaining < gas {
+        error!(target: "runtime::gas", "Host function needs {} gas, {} remaining", gas, remaining);
+        set_remaining_points(&mut store, instance, 0);
+        return false
+    }
+
+    set_remaining_points(&mut store, instance, remaining - gas);
+    true
+}
+
+/// Gas left to the running instance, zero if exhausted
+pub(crate) fn remaining_gas(ctx: &mut FunctionEnvMut<Env>) -> u64 {
+    let (env, mut store) = ctx.data_and_store_mut();
+    let instance = env.instance.as_ref().unwrap();
+
+    match get_remaining_points(&mut store, instance) {
+        MeteringPoints::Remaining(rem) => rem,
+        MeteringPoints::Exhausted => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{crypto::ContractId, pasta::pallas};
+
+    use super::MEMORY_BULK_BYTE;
+    use crate::{
+        blockchain::{Blockchain, BlockchainOverlay},
+        consensus::{TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP},
+        runtime::vm_runtime::Runtime,
+        Error, Result,
+    };
+
+    const GAS_LIMIT: u64 = 100_000_000;
+
+    /// Contract whose metadata runs given bulk memory operator over `len` bytes
+    fn bulk_memory_wat(op: &str, len: u32) -> String {
+        format!(
+            r#"(module
+                (memory (export "memory") 1)
+                (func (export "__metadata") (param i32) (result i64)
+                    ({} (i32.const 0) (i32.const 0) (i32.const {}))
+                    (i64.const 0)))"#,
+            op, len,
+        )
+    }
+
+    fn metadata(wat: &str, gas_limit: u64) -> Result<u64> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        let overlay = BlockchainOverlay::new(&blockchain)?;
+        let cid = ContractId::from(pallas::Base::from(1));
+
+        let mut runtime = Runtime::new(wat.as_bytes(), overlay, cid, gas_limit)?;
+        Ok(runtime.metadata(&[])?.1)
+    }
+
+    #[test]
+    fn bulk_memory_charged_per_byte() -> Result<()> {
+        let len = 65536;
+        for op in ["memory.copy", "memory.fill"] {
+            let empty_gas = metadata(&bulk_memory_wat(op, 0), GAS_LIMIT)?;
+            let full_gas = metadata(&bulk_memory_wat(op, len), GAS_LIMIT)?;
+            assert_eq!(full_gas - empty_gas, len as u64 * MEMORY_BULK_BYTE);
+
+            // Running out of gas before touching the memory
+            let ret = metadata(&bulk_memory_wat(op, len), empty_gas + 1);
+            assert!(matches!(ret, Err(Error::WasmerGasExhausted(_))));
+        }
+
+        Ok(())
+    }
+}
@@ -22,7 +22,7 @@ use darkfi_sdk::{
     crypto::ContractId,
     db::{
         CALLER_ACCESS_DENIED, DB_CONTAINS_KEY_FAILED, DB_DEL_FAILED, DB_GET_FAILED, DB_INIT_FAILED,
-        DB_LOOKUP_FAILED, DB_SET_FAILED, DB_SUCCESS,
+        DB_LOOKUP_FAILED, DB_RANGE_FAILED, DB_RANGE_MAX_LIMIT, DB_SET_FAILED, DB_SUCCESS,
     },
 };
 use darkfi_serial::{deserialize, serialize, Decodable};
@@ -30,7 +30,10 @@ use log::{debug, error, info};
 use wasmer::{FunctionEnvMut, WasmPtr};
 
 use crate::{
-    runtime::vm_runtime::{ContractSection, Env, SMART_CONTRACT_ZKAS_DB_NAME},
+    runtime::{
+        gas,
+        vm_runtime::{ContractSection, Env, SMART_CONTRACT_ZKAS_DB_NAME},
+    },
     zk::{empty_witnesses, VerifyingKey, ZkCircuit},
     zkas::ZkBinary,
 };
@@ -48,7 +51,11 @@ impl DbHandle {
 }
 
 /// Only deploy() can call this. Creates a new database instance for this contract.
-pub(crate) fn db_init(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn db_init(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::DB_HANDLE) {
+        return DB_INIT_FAILED
+    }
+
     let env = ctx.data();
 
     // Exit as soon as possible
@@ -123,7 +130,11 @@ pub(crate) fn db_init(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i
 }
 
 /// Everyone can call this. Lookups up a database handle from its name.
-pub(crate) fn db_lookup(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn db_lookup(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::DB_HANDLE) {
+        return DB_LOOKUP_FAILED
+    }
+
     let env = ctx.data();
 
     match env.contract_section {
@@ -197,7 +208,11 @@ pub(crate) fn db_lookup(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) ->
 }
 
 /// Set a value within the transaction.
-pub(crate) fn db_set(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn db_set(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::DB_WRITE_BYTE * len as u64) {
+        return DB_SET_FAILED
+    }
+
     let env = ctx.data();
 
     if env.contract_section != ContractSection::Deploy &&
@@ -288,7 +303,11 @@ pub(crate) fn db_set(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i3
 }
 
 /// Remove a key from the database.
-pub(crate) fn db_del(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn db_del(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::DB_WRITE_BYTE * len as u64) {
+        return DB_DEL_FAILED
+    }
+
     let env = ctx.data();
 
     if env.contract_section != ContractSection::Deploy &&
@@ -363,7 +382,11 @@ pub(crate) fn db_del(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i3
 }
 
 /// Will read a key from the key-value store.
-pub(crate) fn db_get(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i64 {
+pub(crate) fn db_get(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i64 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::DB_READ_BYTE * len as u64) {
+        return DB_GET_FAILED.into()
+    }
+
     let env = ctx.data();
 
     if env.contract_section != ContractSection::Deploy &&
@@ -438,6 +461,13 @@ pub(crate) fn db_get(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i6
         return -127
     };
 
+    // Charge for the read value bytes
+    drop(db_handles);
+    if !gas::subtract_gas(&mut ctx, gas::DB_READ_BYTE * return_data.len() as u64) {
+        return DB_GET_FAILED.into()
+    }
+    let env = ctx.data();
+
     // Copy Vec<u8> to the VM
     let mut objects = env.objects.borrow_mut();
     objects.push(return_data.to_vec());
@@ -445,7 +475,11 @@ pub(crate) fn db_get(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i6
 }
 
 /// Everyone can call this. Will check if a given db contains given key.
-pub(crate) fn db_contains_key(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn db_contains_key(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::DB_READ_BYTE * len as u64) {
+        return DB_CONTAINS_KEY_FAILED
+    }
+
     let env = ctx.data();
 
     if env.contract_section != ContractSection::Deploy &&
@@ -517,9 +551,140 @@ pub(crate) fn db_contains_key(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u
     }
 }
 
+/// Everyone can call this. Will read an ordered range of entries from a db,
+/// returning them serialized as `Vec<(Vec<u8>, Vec<u8>)>`.
+pub(crate) fn db_range(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i64 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::DB_READ_BYTE * len as u64) {
+        return DB_RANGE_FAILED.into()
+    }
+
+    let env = ctx.data();
+
+    if env.contract_section != ContractSection::Deploy &&
+        env.contract_section != ContractSection::Exec &&
+        env.contract_section != ContractSection::Update &&
+        env.contract_section != ContractSection::Metadata
+    {
+        error!(target: "runtime::db::db_range()", "db_range called in unauthorized section");
+        return CALLER_ACCESS_DENIED.into()
+    }
+
+    let memory_view = env.memory_view(&ctx);
+
+    let Ok(mem_slice) = ptr.slice(&memory_view, len) else {
+        error!(target: "runtime::db::db_range()", "Failed to make slice from ptr");
+        return DB_RANGE_FAILED.into()
+    };
+
+    let mut buf = vec![0_u8; len as usize];
+    if let Err(e) = mem_slice.read_slice(&mut buf) {
+        error!(target: "runtime::db::db_range()", "Failed to read from memory slice: {}", e);
+        return DB_RANGE_FAILED.into()
+    };
+
+    // The buffer should deserialize into:
+    // - DbHandle
+    // - start (as Option<Vec<u8>>)
+    // - end (as Option<Vec<u8>>)
+    // - reverse (as bool)
+    // - limit (as u32)
+    let mut buf_reader = Cursor::new(buf);
+
+    let db_handle: u32 = match Decodable::decode(&mut buf_reader) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Failed to decode DbHandle: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+    let db_handle = db_handle as usize;
+
+    let start: Option<Vec<u8>> = match Decodable::decode(&mut buf_reader) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Failed to decode start key: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+
+    let end: Option<Vec<u8>> = match Decodable::decode(&mut buf_reader) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Failed to decode end key: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+
+    let reverse: bool = match Decodable::decode(&mut buf_reader) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Failed to decode reverse flag: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+
+    let limit: u32 = match Decodable::decode(&mut buf_reader) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Failed to decode limit: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+
+    if limit > DB_RANGE_MAX_LIMIT {
+        error!(target: "runtime::db::db_range()", "Requested limit {} is too large", limit);
+        return DB_RANGE_FAILED.into()
+    }
+
+    let db_handles = env.db_handles.borrow();
+
+    if db_handles.len() <= db_handle {
+        error!(target: "runtime::db::db_range()", "Requested DbHandle that is out of bounds");
+        return DB_RANGE_FAILED.into()
+    }
+
+    let handle_idx = db_handle;
+    let db_handle = &db_handles[handle_idx];
+
+    let entries = match env.blockchain.lock().unwrap().overlay.lock().unwrap().range(
+        &db_handle.tree,
+        start.as_deref(),
+        end.as_deref(),
+        reverse,
+        limit as usize,
+    ) {
+        Ok(v) => v,
+        Err(e) => {
+            error!(target: "runtime::db::db_range()", "Internal error iterating tree: {}", e);
+            return DB_RANGE_FAILED.into()
+        }
+    };
+
+    let return_data = serialize(&entries);
+
+    // Charge for the returned entries and their bytes
+    drop(db_handles);
+    if !gas::subtract_gas(
+        &mut ctx,
+        gas::DB_RANGE_ENTRY * entries.len() as u64 + gas::DB_READ_BYTE * return_data.len() as u64,
+    ) {
+        return DB_RANGE_FAILED.into()
+    }
+    let env = ctx.data();
+
+    // Copy Vec<u8> to the VM
+    let mut objects = env.objects.borrow_mut();
+    objects.push(return_data);
+    (objects.len() - 1) as i64
+}
+
 /// Only `deploy()` can call this. Given a zkas circuit, create a VerifyingKey and insert
 /// them both into the db.
-pub(crate) fn zkas_db_set(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn zkas_db_set(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::ZKAS_REGISTER) {
+        return DB_SET_FAILED
+    }
+
     let env = ctx.data();
 
     if env.contract_section != ContractSection::Deploy {
@@ -0,0 +1,84 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io::Cursor;
+
+use darkfi_sdk::event::EVENT_MAX_TOPICS;
+use darkfi_serial::Decodable;
+use log::error;
+use wasmer::{FunctionEnvMut, WasmPtr};
+
+use crate::runtime::{
+    gas,
+    vm_runtime::{ContractSection, Env},
+};
+
+/// Only update() can call this. Emit an event with given topics and data,
+/// collected by the runtime for the transaction receipt.
+pub(crate) fn emit_event(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i64 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::EVENT_BYTE * len as u64) {
+        return darkfi_sdk::error::INTERNAL_ERROR
+    }
+
+    let env = ctx.data();
+    match env.contract_section {
+        ContractSection::Update => {
+            let memory_view = env.memory_view(&ctx);
+
+            let Ok(mem_slice) = ptr.slice(&memory_view, len) else {
+                error!(target: "runtime::event", "Failed to make slice from ptr");
+                return darkfi_sdk::error::INTERNAL_ERROR
+            };
+
+            let mut buf = vec![0_u8; len as usize];
+            if let Err(e) = mem_slice.read_slice(&mut buf) {
+                error!(target: "runtime::event", "Failed to read from memory slice: {}", e);
+                return darkfi_sdk::error::INTERNAL_ERROR
+            };
+
+            // The buffer should deserialize into:
+            // - topics (as Vec<Vec<u8>>)
+            // - data (as Vec<u8>)
+            let mut buf_reader = Cursor::new(buf);
+            let topics: Vec<Vec<u8>> = match Decodable::decode(&mut buf_reader) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "runtime::event", "Failed to decode topics: {}", e);
+                    return darkfi_sdk::error::INTERNAL_ERROR
+                }
+            };
+
+            let data: Vec<u8> = match Decodable::decode(&mut buf_reader) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "runtime::event", "Failed to decode data: {}", e);
+                    return darkfi_sdk::error::INTERNAL_ERROR
+                }
+            };
+
+            if topics.len() > EVENT_MAX_TOPICS {
+                error!(target: "runtime::event", "Event has {} topics, over the limit", topics.len());
+                return darkfi_sdk::error::INTERNAL_ERROR
+            }
+
+            env.events.borrow_mut().push((topics, data));
+            0
+        }
+        _ => darkfi_sdk::error::CALLER_ACCESS_DENIED,
+    }
+}
@@ -0,0 +1,382 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::io::Cursor;
+
+use darkfi_sdk::crypto::ContractId;
+use darkfi_serial::Decodable;
+use log::{debug, error};
+use wasmer::{FunctionEnvMut, WasmPtr};
+
+use crate::runtime::{
+    gas,
+    vm_runtime::{ContractSection, Env, Runtime, MAX_CALL_DEPTH},
+};
+
+/// Synchronously invoke a section of another deployed contract within the same
+/// blockchain overlay, and return the index of its return data in the object store.
+///
+/// Only exec() and metadata() can invoke, and only the `Metadata` and `Exec`
+/// sections can be invoked. Invocations are read-only: the state update of an
+/// invoked `Exec` is returned to the caller as its return data and never gets
+/// applied, so a call chain is unable to write any contract state.
+/// Invoking a contract that is already part of the current call
+/// chain is denied, and the chain can't be deeper than `MAX_CALL_DEPTH`.
+/// The invoked contract is limited to the caller's remaining gas, and the gas
+/// it spends is subtracted from the caller.
+pub(crate) fn invoke_contract(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i64 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::INVOKE_CONTRACT) {
+        return darkfi_sdk::error::INVOKE_FAILED
+    }
+
+    let env = ctx.data();
+    match env.contract_section {
+        ContractSection::Exec | ContractSection::Metadata => {
+            let memory_view = env.memory_view(&ctx);
+
+            let Ok(mem_slice) = ptr.slice(&memory_view, len) else {
+                error!(target: "runtime::invoke", "Failed to make slice from ptr");
+                return darkfi_sdk::error::INTERNAL_ERROR
+            };
+
+            let mut buf = vec![0_u8; len as usize];
+            if let Err(e) = mem_slice.read_slice(&mut buf) {
+                error!(target: "runtime::invoke", "Failed to read from memory slice: {}", e);
+                return darkfi_sdk::error::INTERNAL_ERROR
+            };
+
+            // The buffer should deserialize into:
+            // - ContractId (the contract being invoked)
+            // - u8 (the invoked section)
+            // - Vec<u8> (the payload passed to the invoked section)
+            let mut buf_reader = Cursor::new(buf);
+            let contract_id: ContractId = match Decodable::decode(&mut buf_reader) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "runtime::invoke", "Failed to decode ContractId: {}", e);
+                    return darkfi_sdk::error::INTERNAL_ERROR
+                }
+            };
+
+            let section: u8 = match Decodable::decode(&mut buf_reader) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "runtime::invoke", "Failed to decode section: {}", e);
+                    return darkfi_sdk::error::INTERNAL_ERROR
+                }
+            };
+
+            let payload: Vec<u8> = match Decodable::decode(&mut buf_reader) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(target: "runtime::invoke", "Failed to decode payload: {}", e);
+                    return darkfi_sdk::error::INTERNAL_ERROR
+                }
+            };
+
+            // Make sure there is nothing left in the buffer
+            if buf_reader.position() != len as u64 {
+                error!(target: "runtime::invoke", "Trailing bytes in argument stream");
+                return darkfi_sdk::error::INTERNAL_ERROR
+            }
+
+            let section = match section {
+                0x00 => ContractSection::Metadata,
+                0x01 => ContractSection::Exec,
+                _ => {
+                    error!(target: "runtime::invoke", "Invalid section: {}", section);
+                    return darkfi_sdk::error::CALLER_ACCESS_DENIED
+                }
+            };
+
+            // Reentrancy is not allowed
+            if env.call_stack.contains(&contract_id) {
+                error!(
+                    target: "runtime::invoke",
+                    "Contract {} is already in the call stack", contract_id,
+                );
+                return darkfi_sdk::error::CALLER_ACCESS_DENIED
+            }
+
+            if env.call_stack.len() >= MAX_CALL_DEPTH {
+                error!(target: "runtime::invoke", "Maximum call depth of {} reached", MAX_CALL_DEPTH);
+                return darkfi_sdk::error::INVOKE_FAILED
+            }
+
+            let wasm_bytes = match env.blockchain.lock().unwrap().wasm_bincode.get(contract_id) {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(
+                        target: "runtime::invoke",
+                        "Failed to find wasm bincode of contract {}: {}", contract_id, e,
+                    );
+                    return darkfi_sdk::error::INVOKE_FAILED
+                }
+            };
+
+            let blockchain = env.blockchain.clone();
+            let mut call_stack = env.call_stack.clone();
+            call_stack.push(contract_id);
+
+            // The invoked contract can spend whatever gas the caller has left
+            if !gas::subtract_gas(&mut ctx, gas::INVOKE_PAYLOAD_BYTE * payload.len() as u64) {
+                return darkfi_sdk::error::INVOKE_FAILED
+            }
+            let gas_limit = gas::remaining_gas(&mut ctx);
+            if gas_limit == 0 {
+                error!(target: "runtime::invoke", "No gas left to invoke contract {}", contract_id);
+                return darkfi_sdk::error::INVOKE_FAILED
+            }
+
+            debug!(
+                target: "runtime::invoke",
+                "Invoking {} of contract {}", section.name(), contract_id,
+            );
+
+            let mut runtime =
+                match Runtime::with_call_stack(&wasm_bytes, blockchain, call_stack, gas_limit) {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!(
+                            target: "runtime::invoke",
+                            "Failed to instantiate contract {}: {}", contract_id, e,
+                        );
+                        return darkfi_sdk::error::INVOKE_FAILED
+                    }
+                };
+
+            let result = runtime.call(section, &payload);
+
+            // Charge the caller for the gas spent by the invoked contract,
+            // which can't exceed what the caller had left.
+            if !gas::subtract_gas(&mut ctx, gas_limit - runtime.remaining_gas()) {
+                return darkfi_sdk::error::INVOKE_FAILED
+            }
+
+            let retdata = match result {
+                Ok((retdata, _)) => retdata,
+                Err(e) => {
+                    error!(
+                        target: "runtime::invoke",
+                        "Invocation of contract {} failed: {}", contract_id, e,
+                    );
+                    return darkfi_sdk::error::INVOKE_FAILED
+                }
+            };
+
+            let env = ctx.data();
+            let mut objects = env.objects.borrow_mut();
+            objects.push(retdata);
+            (objects.len() - 1) as i64
+        }
+        _ => darkfi_sdk::error::CALLER_ACCESS_DENIED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use darkfi_sdk::{
+        crypto::ContractId, error::ContractError, invoke::InvokeSection, pasta::pallas,
+    };
+    use darkfi_serial::serialize;
+
+    use crate::{
+        blockchain::{Blockchain, BlockchainOverlay, BlockchainOverlayPtr},
+        consensus::{TESTNET_GENESIS_HASH_BYTES, TESTNET_GENESIS_TIMESTAMP},
+        runtime::{
+            gas,
+            vm_runtime::{Runtime, MAX_CALL_DEPTH},
+        },
+        Error, Result,
+    };
+
+    const GAS_LIMIT: u64 = 100_000_000;
+
+    fn contract_id(n: u64) -> ContractId {
+        ContractId::from(pallas::Base::from(n))
+    }
+
+    /// Contract whose metadata returns the return data of the metadata of `callee`
+    fn caller_wat(callee: ContractId) -> String {
+        caller_wat_section(callee, InvokeSection::Metadata as u8)
+    }
+
+    /// Contract whose metadata returns the return data of the given section of `callee`
+    fn caller_wat_section(callee: ContractId, section: u8) -> String {
+        // Serialized ContractId, invoked section, empty payload
+        let mut args = serialize(&callee);
+        args.extend_from_slice(&[section, 0x00]);
+        let args: String = args.iter().map(|b| format!("\\{:02x}", b)).collect();
+
+        format!(
+            r#"(module
+                (import "env" "invoke_contract_" (func $invoke (param i32 i32) (result i64)))
+                (import "env" "get_object_size_" (func $obj_size (param i32) (result i64)))
+                (import "env" "get_object_bytes_" (func $obj_bytes (param i32 i32) (result i64)))
+                (import "env" "set_return_data_" (func $set_ret (param i32 i32) (result i64)))
+                (memory (export "memory") 1)
+                (data (i32.const 1024) "{}")
+                (func (export "__metadata") (param i32) (result i64)
+                    (local $ret i64)
+                    (local.set $ret (call $invoke (i32.const 1024) (i32.const {})))
+                    (if (i64.lt_s (local.get $ret) (i64.const 0))
+                        (then (return (local.get $ret))))
+                    (drop (call $obj_bytes (i32.const 2048) (i32.wrap_i64 (local.get $ret))))
+                    (drop (call $set_ret
+                        (i32.const 2048)
+                        (i32.wrap_i64 (call $obj_size (i32.wrap_i64 (local.get $ret))))))
+                    (i64.const 0)))"#,
+            args,
+            args.len() / 3,
+        )
+    }
+
+    /// Contract whose metadata returns "leaf", and whose exec returns "exec"
+    const LEAF_WAT: &str = r#"(module
+        (import "env" "set_return_data_" (func $set_ret (param i32 i32) (result i64)))
+        (memory (export "memory") 1)
+        (data (i32.const 1024) "leaf")
+        (data (i32.const 1056) "exec")
+        (func (export "__metadata") (param i32) (result i64)
+            (drop (call $set_ret (i32.const 1024) (i32.const 4)))
+            (i64.const 0))
+        (func (export "__entrypoint") (param i32) (result i64)
+            (drop (call $set_ret (i32.const 1056) (i32.const 4)))
+            (i64.const 0)))"#;
+
+    /// Contract whose metadata loops until it runs out of gas
+    const BURNER_WAT: &str = r#"(module
+        (memory (export "memory") 1)
+        (func (export "__metadata") (param i32) (result i64)
+            (loop $l (br $l))
+            (i64.const 0)))"#;
+
+    fn overlay() -> Result<BlockchainOverlayPtr> {
+        let sled_db = sled::Config::new().temporary(true).open()?;
+        let blockchain =
+            Blockchain::new(&sled_db, *TESTNET_GENESIS_TIMESTAMP, *TESTNET_GENESIS_HASH_BYTES)?;
+        BlockchainOverlay::new(&blockchain)
+    }
+
+    fn deploy(overlay: &BlockchainOverlayPtr, cid: ContractId, wat: &str) -> Result<()> {
+        overlay.lock().unwrap().wasm_bincode.insert(cid, wat.as_bytes())
+    }
+
+    fn metadata(overlay: &BlockchainOverlayPtr, cid: ContractId) -> Result<(Vec<u8>, u64)> {
+        let wasm = overlay.lock().unwrap().wasm_bincode.get(cid)?;
+        Runtime::new(&wasm, overlay.clone(), cid, GAS_LIMIT)?.metadata(&[])
+    }
+
+    #[test]
+    fn invoke_return_data_and_gas() -> Result<()> {
+        let overlay = overlay()?;
+        deploy(&overlay, contract_id(1), &caller_wat(contract_id(2)))?;
+        deploy(&overlay, contract_id(2), LEAF_WAT)?;
+
+        let (leaf_retdata, leaf_gas) = metadata(&overlay, contract_id(2))?;
+        let (retdata, gas_used) = metadata(&overlay, contract_id(1))?;
+        assert_eq!(retdata, leaf_retdata);
+        assert_eq!(retdata, b"leaf");
+
+        // The caller pays for the invocation and the gas of the invoked contract
+        assert!(gas_used > leaf_gas + gas::HOST_CALL + gas::INVOKE_CONTRACT);
+
+        Ok(())
+    }
+
+    #[test]
+    fn invoke_exec_section() -> Result<()> {
+        let overlay = overlay()?;
+        let exec = InvokeSection::Exec as u8;
+        deploy(&overlay, contract_id(1), &caller_wat_section(contract_id(3), exec))?;
+        deploy(&overlay, contract_id(2), &caller_wat_section(contract_id(3), 0x02))?;
+        deploy(&overlay, contract_id(3), LEAF_WAT)?;
+
+        // The exec return data is handed to the caller instead of being applied
+        let (retdata, _) = metadata(&overlay, contract_id(1))?;
+        assert_eq!(retdata, b"exec");
+
+        // Sections able to write contract state can't be invoked
+        let ret = metadata(&overlay, contract_id(2));
+        assert!(matches!(ret, Err(Error::ContractError(ContractError::CallerAccessDenied))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invoke_gas_limited_by_caller() -> Result<()> {
+        let overlay = overlay()?;
+        deploy(&overlay, contract_id(1), &caller_wat(contract_id(2)))?;
+        deploy(&overlay, contract_id(2), BURNER_WAT)?;
+
+        // The invoked contract burns all of the caller's gas, and no more
+        let wasm = overlay.lock().unwrap().wasm_bincode.get(contract_id(1))?;
+        let mut runtime = Runtime::new(&wasm, overlay.clone(), contract_id(1), GAS_LIMIT)?;
+        let ret = runtime.metadata(&[]);
+        assert!(matches!(ret, Err(Error::WasmerGasExhausted(GAS_LIMIT))));
+        assert_eq!(runtime.remaining_gas(), 0);
+
+        // Without enough gas to invoke, the caller fails before the invocation
+        let mut runtime = Runtime::new(&wasm, overlay.clone(), contract_id(1), gas::HOST_CALL)?;
+        let ret = runtime.metadata(&[]);
+        assert!(matches!(ret, Err(Error::WasmerGasExhausted(gas::HOST_CALL))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invoke_reentrancy_denied() -> Result<()> {
+        let overlay = overlay()?;
+
+        // Invoking itself
+        deploy(&overlay, contract_id(1), &caller_wat(contract_id(1)))?;
+        let ret = metadata(&overlay, contract_id(1));
+        assert!(matches!(ret, Err(Error::ContractError(ContractError::CallerAccessDenied))));
+
+        // Invoking its caller, which makes the caller's invocation fail
+        deploy(&overlay, contract_id(2), &caller_wat(contract_id(3)))?;
+        deploy(&overlay, contract_id(3), &caller_wat(contract_id(2)))?;
+        let ret = metadata(&overlay, contract_id(2));
+        assert!(matches!(ret, Err(Error::ContractError(ContractError::InvokeFailed))));
+
+        Ok(())
+    }
+
+    #[test]
+    fn invoke_call_depth_limit() -> Result<()> {
+        let overlay = overlay()?;
+
+        // Chain of contracts 1 -> 2 -> ... -> n, with n the leaf
+        let deploy_chain = |n: u64| -> Result<()> {
+            for i in 1..n {
+                deploy(&overlay, contract_id(i), &caller_wat(contract_id(i + 1)))?;
+            }
+            deploy(&overlay, contract_id(n), LEAF_WAT)
+        };
+
+        deploy_chain(MAX_CALL_DEPTH as u64)?;
+        let (retdata, _) = metadata(&overlay, contract_id(1))?;
+        assert_eq!(retdata, b"leaf");
+
+        deploy_chain(MAX_CALL_DEPTH as u64 + 1)?;
+        let ret = metadata(&overlay, contract_id(1));
+        assert!(matches!(ret, Err(Error::ContractError(ContractError::InvokeFailed))));
+
+        Ok(())
+    }
+}
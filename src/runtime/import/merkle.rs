@@ -26,11 +26,18 @@ use darkfi_serial::{serialize, Decodable, Encodable, WriteExt};
 use log::{debug, error};
 use wasmer::{FunctionEnvMut, WasmPtr};
 
-use crate::runtime::vm_runtime::{ContractSection, Env};
+use crate::runtime::{
+    gas,
+    vm_runtime::{ContractSection, Env},
+};
 
 type MerkleTree = BridgeTree<MerkleNode, { MERKLE_DEPTH }>;
 
-pub(crate) fn merkle_add(ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+pub(crate) fn merkle_add(mut ctx: FunctionEnvMut<Env>, ptr: WasmPtr<u8>, len: u32) -> i32 {
+    if !gas::subtract_gas(&mut ctx, gas::HOST_CALL + gas::MERKLE_ADD_BYTE * len as u64) {
+        return -2
+    }
+
     let env = ctx.data();
     match env.contract_section {
         ContractSection::Update => {
@@ -22,5 +22,11 @@ pub(crate) mod db;
 /// Host functions for merkle tree functions
 pub(crate) mod merkle;
 
+/// Host functions for cross-contract calls
+pub(crate) mod invoke;
+
+/// Host functions for emitting contract events
+pub(crate) mod event;
+
 /// Host functions for utilities
 pub(crate) mod util;
@@ -21,31 +21,31 @@ use std::{
     sync::Arc,
 };
 
-use darkfi_sdk::{crypto::ContractId, entrypoint};
+use darkfi_sdk::{crypto::ContractId, entrypoint, event::ContractEvent};
 use darkfi_serial::serialize;
 use log::{debug, error, info};
 use wasmer::{
-    imports, wasmparser::Operator, AsStoreRef, CompilerConfig, Function, FunctionEnv, Instance,
-    Memory, MemoryView, Module, Pages, Store, Value, WASM_PAGE_SIZE,
+    imports, AsStoreRef, CompilerConfig, Function, FunctionEnv, Instance, Memory, MemoryView,
+    Module, Pages, Store, Value, WASM_PAGE_SIZE,
 };
 use wasmer_compiler_singlepass::Singlepass;
 use wasmer_middlewares::{
-    metering::{get_remaining_points, MeteringPoints},
+    metering::{get_remaining_points, set_remaining_points, MeteringPoints},
     Metering,
 };
 
-use super::{import, import::db::DbHandle, memory::MemoryManipulation};
+use super::{gas, import, import::db::DbHandle, memory::MemoryManipulation};
 use crate::{blockchain::BlockchainOverlayPtr, Error, Result};
 
 /// Name of the wasm linear memory in our guest module
 const MEMORY: &str = "memory";
 
-/// Gas limit for a contract
-const GAS_LIMIT: u64 = 200000000;
-
 /// The hardcoded db name for the zkas circuits database tree
 pub const SMART_CONTRACT_ZKAS_DB_NAME: &str = "_zkas";
 
+/// Maximum depth of nested cross-contract calls, the outermost contract included
+pub const MAX_CALL_DEPTH: usize = 4;
+
 #[derive(Clone, Copy, PartialEq)]
 pub enum ContractSection {
     /// Setup function of a contract
@@ -88,10 +88,16 @@ pub struct Env {
     pub contract_return_data: Cell<Option<Vec<u8>>>,
     /// Logs produced by the contract
     pub logs: RefCell<Vec<String>>,
+    /// Events emitted by the contract, as topics and data
+    pub events: RefCell<Vec<(Vec<Vec<u8>>, Vec<u8>)>>,
     /// Direct memory access to the VM
     pub memory: Option<Memory>,
     /// Object store for transferring memory from the host to VM
     pub objects: RefCell<Vec<Vec<u8>>>,
+    /// The running instance, used by host functions to charge gas
+    pub instance: Option<Arc<Instance>>,
+    /// Contracts being executed in the current call chain, ending with this one
+    pub call_stack: Vec<ContractId>,
 }
 
 impl Env {
@@ -113,41 +119,48 @@ impl Env {
 }
 
 pub struct Runtime {
-    pub instance: Instance,
+    pub instance: Arc<Instance>,
     pub store: Store,
     pub ctx: FunctionEnv<Env>,
+    /// Gas limit of the runtime, shared by all of its calls
+    gas_limit: u64,
 }
 
 impl Runtime {
     /// Create a new wasm runtime instance that contains the given wasm module.
+    /// All calls executed with the runtime can spend up to `gas_limit` gas in total.
     pub fn new(
         wasm_bytes: &[u8],
         blockchain: BlockchainOverlayPtr,
         contract_id: ContractId,
+        gas_limit: u64,
+    ) -> Result<Self> {
+        Self::with_call_stack(wasm_bytes, blockchain, vec![contract_id], gas_limit)
+    }
+
+    /// Create a new wasm runtime instance for the last contract of `call_stack`,
+    /// which holds the contracts of the call chain it is invoked from.
+    pub(crate) fn with_call_stack(
+        wasm_bytes: &[u8],
+        blockchain: BlockchainOverlayPtr,
+        call_stack: Vec<ContractId>,
+        gas_limit: u64,
     ) -> Result<Self> {
         info!(target: "runtime::vm_runtime", "Instantiating a new runtime");
-        // TODO: Add necessary operators
-        // This function will be called for each `Operator` encountered during
-        // the wasm module execution. It should return the cost of the operator
-        // that it received as its first argument.
-        // https://docs.rs/wasmparser/latest/wasmparser/enum.Operator.html
-        let cost_function = |operator: &Operator| -> u64 {
-            match operator {
-                Operator::LocalGet { .. } => 1,
-                Operator::I32Const { .. } => 1,
-                Operator::I32Add { .. } => 2,
-                _ => 0,
-            }
-        };
+        let contract_id = *call_stack.last().unwrap();
 
         // `Metering` needs to be conigured with a limit and a cost function.
         // For each `Operator`, the metering middleware will call the cost
         // function and subtract the cost from the remaining points.
-        let metering = Arc::new(Metering::new(GAS_LIMIT, cost_function));
+        let metering = Arc::new(Metering::new(gas_limit, gas::operator_cost));
+        // Bulk memory operators are additionally charged for their length,
+        // from the points of `Metering`, so this goes after it.
+        let bulk_memory_metering = Arc::new(gas::BulkMemoryMetering::default());
 
         // Define the compiler and middleware, engine, and store
         let mut compiler_config = Singlepass::new();
         compiler_config.push_middleware(metering);
+        compiler_config.push_middleware(bulk_memory_metering);
         let mut store = Store::new(compiler_config);
 
         debug!(target: "runtime::vm_runtime", "Compiling module");
@@ -169,8 +182,11 @@ impl Runtime {
                 contract_section: ContractSection::Null,
                 contract_return_data: Cell::new(None),
                 logs,
+                events: RefCell::new(vec![]),
                 memory: None,
                 objects: RefCell::new(vec![]),
+                instance: None,
+                call_stack,
             },
         );
 
@@ -212,6 +228,12 @@ impl Runtime {
                     import::db::db_contains_key,
                 ),
 
+                "db_range_" => Function::new_typed_with_env(
+                    &mut store,
+                    &ctx,
+                    import::db::db_range,
+                ),
+
                 "db_set_" => Function::new_typed_with_env(
                     &mut store,
                     &ctx,
@@ -253,19 +275,38 @@ impl Runtime {
                     &ctx,
                     import::merkle::merkle_add,
                 ),
+
+                "invoke_contract_" => Function::new_typed_with_env(
+                    &mut store,
+                    &ctx,
+                    import::invoke::invoke_contract,
+                ),
+
+                "emit_event_" => Function::new_typed_with_env(
+                    &mut store,
+                    &ctx,
+                    import::event::emit_event,
+                ),
             }
         };
 
         debug!(target: "runtime::vm_runtime", "Instantiating module");
-        let instance = Instance::new(&mut store, &module, &imports)?;
+        let instance = Arc::new(Instance::new(&mut store, &module, &imports)?);
 
         let mut env_mut = ctx.as_mut(&mut store);
         env_mut.memory = Some(instance.exports.get_with_generics(MEMORY)?);
+        env_mut.instance = Some(instance.clone());
 
-        Ok(Self { instance, store, ctx })
+        Ok(Self { instance, store, ctx, gas_limit })
     }
 
-    fn call(&mut self, section: ContractSection, payload: &[u8]) -> Result<Vec<u8>> {
+    /// Execute the given section of the contract, returning its return data
+    /// along with the gas spent during the call.
+    pub(crate) fn call(
+        &mut self,
+        section: ContractSection,
+        payload: &[u8],
+    ) -> Result<(Vec<u8>, u64)> {
         debug!(target: "runtime::vm_runtime", "Calling {} method", section.name());
 
         let mut env_mut = self.ctx.as_mut(&mut self.store);
@@ -287,6 +328,7 @@ impl Runtime {
         let entrypoint = self.instance.exports.get_function(section.name())?;
 
         debug!(target: "runtime::vm_runtime", "Executing wasm");
+        let gas_before = self.remaining_gas();
         let ret = match entrypoint.call(&mut self.store, &[Value::I32(0_i32)]) {
             Ok(retvals) => {
                 self.print_logs();
@@ -296,11 +338,17 @@ impl Runtime {
             Err(e) => {
                 self.print_logs();
                 debug!(target: "runtime::vm_runtime", "{}", self.gas_info());
+                // Host functions lacking gas consume all of it before failing
+                if self.remaining_gas() == 0 {
+                    error!(target: "runtime::vm_runtime", "Gas limit of {} exhausted", self.gas_limit);
+                    return Err(Error::WasmerGasExhausted(self.gas_limit))
+                }
                 // WasmerRuntimeError panics are handled here. Return from run() immediately.
                 error!(target: "runtime::vm_runtime", "Wasmer Runtime Error: {:#?}", e);
                 return Err(e.into())
             }
         };
+        let gas_used = gas_before - self.remaining_gas();
 
         debug!(target: "runtime::vm_runtime", "wasm executed successfully");
         debug!(target: "runtime::vm_runtime", "Contract returned: {:?}", ret[0]);
@@ -318,7 +366,12 @@ impl Runtime {
         };
 
         match retval {
-            entrypoint::SUCCESS => Ok(retdata),
+            entrypoint::SUCCESS => Ok((retdata, gas_used)),
+            // The contract returned the error of a host function lacking gas
+            _ if self.remaining_gas() == 0 => {
+                error!(target: "runtime::vm_runtime", "Gas limit of {} exhausted", self.gas_limit);
+                Err(Error::WasmerGasExhausted(self.gas_limit))
+            }
             // FIXME: we should be able to see the error returned from the contract
             // We can put sdk::Error inside of this.
             _ => {
@@ -336,7 +389,8 @@ impl Runtime {
     /// state, and it can create, delete, modify, read, and write to databases it's allowed to.
     /// The permissions for this are handled by the `ContractId` in the overlay db API so we
     /// assume that the contract is only able to do write operations on its own overlay trees.
-    pub fn deploy(&mut self, payload: &[u8]) -> Result<()> {
+    /// Returns the gas spent during the call.
+    pub fn deploy(&mut self, payload: &[u8]) -> Result<u64> {
         info!(target: "runtime::vm_runtime", "[wasm-runtime] Running deploy");
 
         // Scoped for borrows
@@ -364,7 +418,7 @@ impl Runtime {
         }
 
         debug!(target: "runtime::vm_runtime", "[wasm-runtime] payload: {:?}", payload);
-        let _ = self.call(ContractSection::Deploy, payload)?;
+        let (_, gas_used) = self.call(ContractSection::Deploy, payload)?;
 
         // Update the wasm bincode in the WasmStore
         let env_mut = self.ctx.as_mut(&mut self.store);
@@ -375,14 +429,15 @@ impl Runtime {
             .wasm_bincode
             .insert(env_mut.contract_id, &env_mut.contract_bincode)?;
 
-        Ok(())
+        Ok(gas_used)
     }
 
     /// This funcion runs when someone wants to execute a smart contract.
     /// The runtime will look for an `ENTRYPOINT` symbol in the wasm code, and
     /// execute it if found. A payload is also passed as an instruction that can
     /// be used inside the vm by the runtime.
-    pub fn exec(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+    /// Returns the state update along with the gas spent during the call.
+    pub fn exec(&mut self, payload: &[u8]) -> Result<(Vec<u8>, u64)> {
         debug!(target: "runtime::vm_runtime", "exec: {:?}", payload);
         self.call(ContractSection::Exec, payload)
     }
@@ -392,21 +447,39 @@ impl Runtime {
     /// The runtime will lok for an `UPDATE` symbol in the wasm code, and execute
     /// it if found. The function does not take an arbitrary payload, but just takes
     /// a state update from `env` and passes it into the wasm runtime.
-    pub fn apply(&mut self, update: &[u8]) -> Result<()> {
+    /// Returns the gas spent during the call.
+    pub fn apply(&mut self, update: &[u8]) -> Result<u64> {
         debug!(target: "runtime::vm_runtime", "apply: {:?}", update);
-        let _ = self.call(ContractSection::Update, update)?;
+        let (_, gas_used) = self.call(ContractSection::Update, update)?;
 
-        Ok(())
+        Ok(gas_used)
     }
 
     /// This function runs first in the entire scheme of executing a smart contract.
     /// It is supposed to correctly extract public inputs for any ZK proofs included
     /// in the contract calls, and also extract the public keys used to verify the
     /// call/transaction signatures.
-    pub fn metadata(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+    /// Returns the metadata along with the gas spent during the call.
+    pub fn metadata(&mut self, payload: &[u8]) -> Result<(Vec<u8>, u64)> {
         self.call(ContractSection::Metadata, payload)
     }
 
+    /// Take the events emitted by the contract since the last time they
+    /// were taken, as events of the contract call with given index.
+    pub fn take_events(&self, call_idx: u32) -> Vec<ContractEvent> {
+        let env = self.ctx.as_ref(&self.store);
+        env.events
+            .take()
+            .into_iter()
+            .map(|(topics, data)| ContractEvent {
+                contract_id: env.contract_id,
+                call_idx,
+                topics,
+                data,
+            })
+            .collect()
+    }
+
     fn print_logs(&self) {
         let logs = self.ctx.as_ref(&self.store).logs.borrow();
         for msg in logs.iter() {
@@ -419,14 +492,29 @@ impl Runtime {
 
         match remaining_points {
             MeteringPoints::Remaining(rem) => {
-                format!("Gas used: {}/{}", GAS_LIMIT - rem, GAS_LIMIT)
+                format!("Gas used: {}/{}", self.gas_limit - rem, self.gas_limit)
             }
             MeteringPoints::Exhausted => {
-                format!("Gas fully exhausted: {}/{}", GAS_LIMIT + 1, GAS_LIMIT)
+                format!("Gas fully exhausted: {}/{}", self.gas_limit + 1, self.gas_limit)
             }
         }
     }
 
+    /// Set the gas the runtime can spend from now on, resetting what is left
+    /// of the previous limit. Used to share a single limit across runtimes.
+    pub fn set_gas_limit(&mut self, gas_limit: u64) {
+        set_remaining_points(&mut self.store, &self.instance, gas_limit);
+        self.gas_limit = gas_limit;
+    }
+
+    /// Gas left to the runtime, zero if exhausted
+    pub(crate) fn remaining_gas(&mut self) -> u64 {
+        match get_remaining_points(&mut self.store, &self.instance) {
+            MeteringPoints::Remaining(rem) => rem,
+            MeteringPoints::Exhausted => 0,
+        }
+    }
+
     /// Set the memory page size
     fn set_memory_page_size(&mut self, pages: u32) -> Result<Pages> {
         // Grab memory by value
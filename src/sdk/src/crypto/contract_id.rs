@@ -36,6 +36,10 @@ lazy_static! {
     /// Contract ID for the native DAO contract
     pub static ref DAO_CONTRACT_ID: ContractId =
         ContractId::from(poseidon_hash([pallas::Base::zero(), pallas::Base::from(1)]));
+
+    /// Contract ID for the native deployment contract
+    pub static ref DEPLOYOOOR_CONTRACT_ID: ContractId =
+        ContractId::from(poseidon_hash([pallas::Base::zero(), pallas::Base::from(2)]));
 }
 
 /// ContractId represents an on-chain identifier for a certain smart contract.
@@ -45,7 +49,11 @@ pub struct ContractId(pallas::Base);
 impl ContractId {
     /// Derive a contract ID from a `SecretKey` (deploy key)
     pub fn derive(deploy_key: SecretKey) -> Self {
-        let public_key = PublicKey::from_secret(deploy_key);
+        Self::derive_public(PublicKey::from_secret(deploy_key))
+    }
+
+    /// Derive a contract ID from the `PublicKey` of a deploy key
+    pub fn derive_public(public_key: PublicKey) -> Self {
         let (x, y) = public_key.xy();
         let hash = poseidon_hash::<2>([x, y]);
         Self(hash)
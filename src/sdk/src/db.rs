@@ -16,7 +16,7 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use darkfi_serial::Encodable;
+use darkfi_serial::{deserialize, Encodable};
 
 use super::{
     crypto::ContractId,
@@ -33,6 +33,10 @@ pub const DB_GET_FAILED: i32 = -4;
 pub const DB_CONTAINS_KEY_FAILED: i32 = -5;
 pub const DB_SET_FAILED: i32 = -6;
 pub const DB_DEL_FAILED: i32 = -7;
+pub const DB_RANGE_FAILED: i32 = -8;
+
+/// Maximum number of entries a single `db_range()` call can return
+pub const DB_RANGE_MAX_LIMIT: u32 = 1024;
 
 /// Only deploy() can call this. Creates a new database instance for this contract.
 ///
@@ -137,6 +141,78 @@ pub fn db_contains_key(db_handle: DbHandle, key: &[u8]) -> GenericResult<bool> {
     }
 }
 
+/// Everyone can call this. Will read up to `limit` entries from the key-value
+/// store with keys in the range from `start` (inclusive) to `end` (exclusive),
+/// ordered by key, or in reverse order if `reverse` is set. A `None` bound
+/// leaves that side of the range open. `limit` can't exceed `DB_RANGE_MAX_LIMIT`.
+///
+/// ```
+/// entries = db_range(db_handle, Some(start), None, false, 10);
+/// ```
+pub fn db_range(
+    db_handle: DbHandle,
+    start: Option<&[u8]>,
+    end: Option<&[u8]>,
+    reverse: bool,
+    limit: u32,
+) -> GenericResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    let mut len = 0;
+    let mut buf = vec![];
+    len += db_handle.encode(&mut buf)?;
+    len += start.map(|x| x.to_vec()).encode(&mut buf)?;
+    len += end.map(|x| x.to_vec()).encode(&mut buf)?;
+    len += reverse.encode(&mut buf)?;
+    len += limit.encode(&mut buf)?;
+
+    let ret = unsafe { db_range_(buf.as_ptr(), len as u32) };
+
+    if ret < 0 {
+        match ret as i32 {
+            CALLER_ACCESS_DENIED => return Err(ContractError::CallerAccessDenied),
+            DB_RANGE_FAILED => return Err(ContractError::DbRangeFailed),
+            _ => unimplemented!(),
+        }
+    }
+
+    let obj = ret as u32;
+    let obj_size = get_object_size(obj);
+    let mut buf = vec![0u8; obj_size as usize];
+    get_object_bytes(&mut buf, obj);
+
+    Ok(deserialize(&buf)?)
+}
+
+/// Everyone can call this. Will read up to `limit` entries from the key-value
+/// store with keys starting with `prefix`, with the same ordering as `db_range()`.
+///
+/// ```
+/// entries = db_scan_prefix(db_handle, prefix, true, 10);
+/// ```
+pub fn db_scan_prefix(
+    db_handle: DbHandle,
+    prefix: &[u8],
+    reverse: bool,
+    limit: u32,
+) -> GenericResult<Vec<(Vec<u8>, Vec<u8>)>> {
+    let end = prefix_end(prefix);
+    db_range(db_handle, Some(prefix), end.as_deref(), reverse, limit)
+}
+
+/// The smallest key greater than every key starting with `prefix`, or
+/// `None` if there is no such key because the prefix consists only of
+/// 0xff bytes.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(byte) = end.pop() {
+        if byte < u8::MAX {
+            end.push(byte + 1);
+            return Some(end)
+        }
+    }
+
+    None
+}
+
 /// Only update() can call this. Set a value within the transaction.
 ///
 /// ```
@@ -229,8 +305,24 @@ extern "C" {
     fn db_lookup_(ptr: *const u8, len: u32) -> i32;
     fn db_get_(ptr: *const u8, len: u32) -> i64;
     fn db_contains_key_(ptr: *const u8, len: u32) -> i32;
+    fn db_range_(ptr: *const u8, len: u32) -> i64;
     fn db_set_(ptr: *const u8, len: u32) -> i32;
     fn db_del_(ptr: *const u8, len: u32) -> i32;
 
     fn zkas_db_set_(ptr: *const u8, len: u32) -> i32;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prefix_end_bound() {
+        assert_eq!(prefix_end(&[0x01, 0x02]), Some(vec![0x01, 0x03]));
+        assert_eq!(prefix_end(&[0x01, 0xff]), Some(vec![0x02]));
+        assert_eq!(prefix_end(&[0x01, 0xff, 0xff]), Some(vec![0x02]));
+        assert_eq!(prefix_end(&[0xfe, 0xff]), Some(vec![0xff]));
+        assert_eq!(prefix_end(&[0xff, 0xff]), None);
+        assert_eq!(prefix_end(&[]), None);
+    }
+}
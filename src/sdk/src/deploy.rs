@@ -0,0 +1,64 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_serial::{SerialDecodable, SerialEncodable};
+
+use super::{crypto::PublicKey, error::ContractError};
+
+/// Functions available in the native deployment contract
+#[repr(u8)]
+pub enum DeployFunction {
+    DeployV1 = 0x00,
+    LockV1 = 0x01,
+}
+
+impl TryFrom<u8> for DeployFunction {
+    type Error = ContractError;
+
+    fn try_from(b: u8) -> core::result::Result<Self, Self::Error> {
+        match b {
+            0x00 => Ok(Self::DeployV1),
+            0x01 => Ok(Self::LockV1),
+            _ => Err(ContractError::InvalidFunction),
+        }
+    }
+}
+
+/// Parameters for `Deploy::Deploy`. The deployed contract ID is derived
+/// from the deploy public key, and the call must be signed by its secret
+/// key. Deploying an already deployed contract upgrades it, unless it has
+/// been locked.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct DeployParamsV1 {
+    /// Wasm bincode of the contract
+    pub wasm_bincode: Vec<u8>,
+    /// zkas bincodes of the contract circuits
+    pub zkas_bincodes: Vec<Vec<u8>>,
+    /// Public key of the deploy key
+    pub public_key: PublicKey,
+    /// Payload passed to the contract `__initialize` function
+    pub ix: Vec<u8>,
+}
+
+/// Parameters for `Deploy::Lock`. Once locked, a contract can't be upgraded.
+/// Only deployed contracts can be locked.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct LockParamsV1 {
+    /// Public key of the deploy key
+    pub public_key: PublicKey,
+}
@@ -72,6 +72,9 @@ pub enum ContractError {
     #[error("Db contains_key failed")]
     DbContainsKeyFailed,
 
+    #[error("Db range failed")]
+    DbRangeFailed,
+
     #[error("Invalid function call")]
     InvalidFunction,
 
@@ -80,6 +83,9 @@ pub enum ContractError {
 
     #[error("SMT: Path nodes are not consistent")]
     SmtInvalidPathNodes,
+
+    #[error("Contract invocation failed")]
+    InvokeFailed,
 }
 
 /// Builtin return values occupy the upper 32 bits
@@ -107,6 +113,8 @@ pub const INVALID_FUNCTION: i64 = to_builtin!(15);
 pub const DB_DEL_FAILED: i64 = to_builtin!(16);
 pub const SMT_INVALID_LEAF: i64 = to_builtin!(17);
 pub const SMT_INVALID_PATH_NODES: i64 = to_builtin!(18);
+pub const INVOKE_FAILED: i64 = to_builtin!(19);
+pub const DB_RANGE_FAILED: i64 = to_builtin!(20);
 
 impl From<ContractError> for i64 {
     fn from(err: ContractError) -> Self {
@@ -124,10 +132,12 @@ impl From<ContractError> for i64 {
             ContractError::DbLookupFailed => DB_LOOKUP_FAILED,
             ContractError::DbGetFailed => DB_GET_FAILED,
             ContractError::DbContainsKeyFailed => DB_CONTAINS_KEY_FAILED,
+            ContractError::DbRangeFailed => DB_RANGE_FAILED,
             ContractError::InvalidFunction => INVALID_FUNCTION,
             ContractError::DbDelFailed => DB_DEL_FAILED,
             ContractError::SmtInvalidLeaf => SMT_INVALID_LEAF,
             ContractError::SmtInvalidPathNodes => SMT_INVALID_PATH_NODES,
+            ContractError::InvokeFailed => INVOKE_FAILED,
             ContractError::Custom(error) => {
                 if error == 0 {
                     CUSTOM_ZERO
@@ -156,10 +166,12 @@ impl From<i64> for ContractError {
             DB_LOOKUP_FAILED => Self::DbLookupFailed,
             DB_GET_FAILED => Self::DbGetFailed,
             DB_CONTAINS_KEY_FAILED => Self::DbContainsKeyFailed,
+            DB_RANGE_FAILED => Self::DbRangeFailed,
             INVALID_FUNCTION => Self::InvalidFunction,
             DB_DEL_FAILED => Self::DbDelFailed,
             SMT_INVALID_LEAF => Self::SmtInvalidLeaf,
             SMT_INVALID_PATH_NODES => Self::SmtInvalidPathNodes,
+            INVOKE_FAILED => Self::InvokeFailed,
             _ => Self::Custom(error as u32),
         }
     }
@@ -0,0 +1,76 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_serial::{Encodable, SerialDecodable, SerialEncodable};
+
+use super::{
+    crypto::ContractId,
+    error::{ContractError, GenericResult},
+};
+
+/// Maximum number of topics of a single event
+pub const EVENT_MAX_TOPICS: usize = 4;
+
+/// Second topic of the events a contract emits for each nullifier it
+/// reveals, followed by the serialized nullifier as third topic. Nodes
+/// index the nullifiers reported by the money contract this way.
+pub const NULLIFIER_EVENT_TOPIC: &[u8] = b"nullifier";
+
+/// Second topic of the event the money contract emits for a paid fee,
+/// with the serialized minimum the fee is proven to reach as data. The
+/// fee value itself stays hidden.
+pub const FEE_EVENT_TOPIC: &[u8] = b"fee";
+
+/// An event emitted by a contract while applying a state update. Events of
+/// a transaction are stored in its receipt, so they can be followed without
+/// re-executing the contract.
+#[derive(Debug, Clone, Eq, PartialEq, SerialEncodable, SerialDecodable)]
+pub struct ContractEvent {
+    /// ID of the contract emitting the event
+    pub contract_id: ContractId,
+    /// Index of the contract call in its transaction
+    pub call_idx: u32,
+    /// Topics used to filter events, by convention the first one being
+    /// the contract function emitting it
+    pub topics: Vec<Vec<u8>>,
+    /// Serialized event data
+    pub data: Vec<u8>,
+}
+
+/// Only update() can call this. Emit an event with given topics and data,
+/// which gets stored in the transaction receipt. At most `EVENT_MAX_TOPICS`
+/// topics can be given.
+///
+/// ```
+/// emit_event(&[vec![MoneyFunction::FreezeV1 as u8], serialize(&token_id)], &[]);
+/// ```
+pub fn emit_event(topics: &[Vec<u8>], data: &[u8]) -> GenericResult<()> {
+    let mut buf = vec![];
+    let mut len = 0;
+    len += topics.to_vec().encode(&mut buf)?;
+    len += data.to_vec().encode(&mut buf)?;
+
+    match unsafe { emit_event_(buf.as_ptr(), len as u32) } {
+        0 => Ok(()),
+        errcode => Err(ContractError::from(errcode)),
+    }
+}
+
+extern "C" {
+    fn emit_event_(ptr: *const u8, len: u32) -> i64;
+}
@@ -0,0 +1,76 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_serial::Encodable;
+
+use super::{
+    crypto::ContractId,
+    db::{get_object_bytes, get_object_size},
+    error::{ContractError, GenericResult},
+};
+
+/// Sections of another contract that can be invoked. Invocations are
+/// read-only, so only sections that can't write contract state are
+/// available, and an invoked contract can't modify its state.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum InvokeSection {
+    /// Metadata function of the contract
+    Metadata = 0x00,
+    /// Entrypoint function of the contract, validating the payload. Its
+    /// state update is returned to the caller instead of being applied.
+    Exec = 0x01,
+}
+
+/// Only exec() and metadata() can call this. Synchronously invokes a section
+/// of another deployed contract with given payload, returning its return data.
+/// * `contract_id` is the ID of the invoked contract.
+/// * `section` is the invoked section of the contract.
+/// * `payload` is passed to the invoked section, like a contract call payload.
+///
+/// The invoked contract spends gas from the caller's remaining gas. Invoking
+/// a contract already being executed in the same call chain, including the
+/// caller itself, is denied, and so are call chains deeper than the limit
+/// set by the host.
+pub fn invoke_contract(
+    contract_id: ContractId,
+    section: InvokeSection,
+    payload: &[u8],
+) -> GenericResult<Vec<u8>> {
+    let mut buf = vec![];
+    let mut len = 0;
+    len += contract_id.encode(&mut buf)?;
+    len += (section as u8).encode(&mut buf)?;
+    len += payload.to_vec().encode(&mut buf)?;
+
+    let ret = unsafe { invoke_contract_(buf.as_ptr(), len as u32) };
+    if ret < 0 {
+        return Err(ContractError::from(ret))
+    }
+
+    let obj = ret as u32;
+    let obj_size = get_object_size(obj);
+    let mut buf = vec![0u8; obj_size as usize];
+    get_object_bytes(&mut buf, obj);
+
+    Ok(buf)
+}
+
+extern "C" {
+    fn invoke_contract_(ptr: *const u8, len: u32) -> i64;
+}
@@ -0,0 +1,49 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use darkfi_serial::{SerialDecodable, SerialEncodable};
+
+use super::{crypto::MerkleNode, pasta::pallas};
+
+/// Money contract info tree, holding the lead coins Merkle tree
+pub const MONEY_CONTRACT_INFO_TREE: &str = "info";
+/// Staked and derived lead coins commitment hashes, with their leaf position
+pub const MONEY_CONTRACT_LEAD_COINS_TREE: &str = "lead_coins";
+/// Merkle roots of the lead coins tree
+pub const MONEY_CONTRACT_LEAD_COIN_ROOTS_TREE: &str = "lead_coin_roots";
+/// Nullifiers of unstaked lead coins, and of lead coins that won a slot
+pub const MONEY_CONTRACT_LEAD_NULLIFIERS_TREE: &str = "lead_nullifiers";
+/// Key of the lead coins Merkle tree in the info tree
+pub const MONEY_CONTRACT_LEAD_COIN_MERKLE_TREE: &str = "lead_coin_tree";
+
+/// Function of the money contract minting the coin a block leader derived
+/// from its winning lead coin. It is executed and applied by consensus once
+/// the block gets finalized, and can't be called from transactions.
+pub const MONEY_DERIVE_LEAD_COIN_V1: u8 = 0x07;
+
+/// Parameters for `Money::DeriveLeadCoin`, taken from the public inputs of
+/// the block leader proof.
+#[derive(Clone, Debug, SerialEncodable, SerialDecodable)]
+pub struct DeriveLeadCoinParamsV1 {
+    /// Lead coins Merkle root the winning coin was proven in
+    pub merkle_root: MerkleNode,
+    /// Nullifier of the winning lead coin
+    pub nullifier: pallas::Base,
+    /// Commitment hash of the derived lead coin
+    pub lead_coin: pallas::Base,
+}
@@ -38,6 +38,20 @@ pub mod crypto;
 pub mod merkle;
 pub use merkle::merkle_add;
 
+/// Cross-contract invocation
+pub mod invoke;
+pub use invoke::invoke_contract;
+
+/// Contract events
+pub mod event;
+pub use event::emit_event;
+
 /// Transaction structure
 pub mod tx;
 pub use tx::ContractCall;
+
+/// Contract deployment definitions
+pub mod deploy;
+
+/// Lead coin definitions of the money contract, shared with consensus
+pub mod lead;
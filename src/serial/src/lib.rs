@@ -16,7 +16,10 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::io::{Cursor, Error, ErrorKind, Read, Write};
+use std::{
+    cmp,
+    io::{Cursor, Error, ErrorKind, Read, Write},
+};
 
 #[cfg(feature = "derive")]
 pub use darkfi_derive::{SerialDecodable, SerialEncodable};
@@ -27,6 +30,9 @@ mod async_serial;
 mod endian;
 mod types;
 
+/// Upper bound on the number of items preallocated when decoding a vector
+const MAX_VEC_PREALLOC: u64 = 4096;
+
 /// Data which can be encoded in a consensus-consistent way.
 pub trait Encodable {
     /// Encode an object with a well-defined format.
@@ -459,7 +465,9 @@ impl<T: Decodable> Decodable for Vec<T> {
     #[inline]
     fn decode<D: Read>(mut d: D) -> Result<Self, Error> {
         let len = VarInt::decode(&mut d)?.0;
-        let mut ret = Vec::with_capacity(len as usize);
+        // Don't trust the length prefix for preallocation, it can be
+        // arbitrarily large. The vector still grows as items get decoded.
+        let mut ret = Vec::with_capacity(cmp::min(len, MAX_VEC_PREALLOC) as usize);
         for _ in 0..len {
             ret.push(Decodable::decode(&mut d)?);
         }
@@ -785,6 +793,9 @@ mod tests {
     fn deserialize_vec_test() {
         assert_eq!(deserialize(&[3u8, 2, 3, 4]).ok(), Some(vec![2u8, 3, 4]));
         assert!((deserialize(&[4u8, 2, 3, 4, 5, 6]) as Result<Vec<u8>, Error>).is_err());
+        // A huge length prefix must not be trusted for allocation
+        let huge = [0xffu8, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 1, 2];
+        assert!((deserialize(&huge) as Result<Vec<u64>, Error>).is_err());
     }
 
     #[test]
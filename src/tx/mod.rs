@@ -142,6 +142,16 @@ impl Transaction {
         Ok(sigs)
     }
 
+    /// Check the transaction is structurally sound, meaning it has at least
+    /// one call, each call carries data, and there is a set of proofs and
+    /// signatures for every call. This doesn't depend on any state.
+    pub fn is_well_formed(&self) -> bool {
+        !self.calls.is_empty() &&
+            self.calls.iter().all(|call| !call.data.is_empty()) &&
+            self.proofs.len() == self.calls.len() &&
+            self.signatures.len() == self.calls.len()
+    }
+
     /// Encode the object into a byte vector for signing
     pub fn encode_without_sigs(&self) -> Result<Vec<u8>> {
         let mut buf = vec![];
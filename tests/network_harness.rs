@@ -0,0 +1,88 @@
+/* This file is part of DarkFi (https://dark.fi)
+ *
+ * Copyright (C) 2020-2023 Dyne.org foundation
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as
+ * published by the Free Software Foundation, either version 3 of the
+ * License, or (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program.  If not, see <https://www.gnu.org/licenses/>.
+ */
+
+use std::time::Duration;
+
+use async_std::sync::Arc;
+use smol::Executor;
+
+use darkfi::net::{harness::NetHarness, transport::LinkConditions};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn harness_full_mesh() {
+    let ex = Arc::new(Executor::new());
+
+    smol::block_on(ex.run(async {
+        let net = NetHarness::full_mesh(ex.clone(), 4).await;
+        net.start().await.unwrap();
+
+        assert!(net.wait_connected(3, TIMEOUT).await);
+
+        net.stop().await;
+    }));
+}
+
+#[test]
+fn harness_slow_link() {
+    let ex = Arc::new(Executor::new());
+
+    smol::block_on(ex.run(async {
+        let net = NetHarness::full_mesh(ex.clone(), 2).await;
+        let latency = Duration::from_millis(200);
+        net.set_link(0, 1, LinkConditions { latency, loss: 0.0 });
+        net.start().await.unwrap();
+
+        // The handshake takes a few round trips, but still goes through
+        assert!(net.wait_connected(1, TIMEOUT).await);
+
+        net.stop().await;
+    }));
+}
+
+#[test]
+fn harness_partition_heal() {
+    let ex = Arc::new(Executor::new());
+
+    smol::block_on(ex.run(async {
+        let net = NetHarness::full_mesh(ex.clone(), 4).await;
+        net.start().await.unwrap();
+        assert!(net.wait_connected(3, TIMEOUT).await);
+
+        // Split the network in two halves
+        net.partition(&[0, 1], &[2, 3]);
+        let split = net
+            .wait_until(TIMEOUT, || async {
+                for i in 0..net.len() {
+                    if net.channel_count(i).await != 1 {
+                        return false
+                    }
+                }
+                true
+            })
+            .await;
+        assert!(split);
+
+        // Manual peers redial once the partition is gone
+        net.heal(&[0, 1], &[2, 3]);
+        assert!(net.wait_connected(3, TIMEOUT).await);
+
+        net.stop().await;
+    }));
+}
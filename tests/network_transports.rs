@@ -16,17 +16,28 @@
  * along with this program.  If not, see <https://www.gnu.org/licenses/>.
  */
 
-use std::{env::var, fs};
+use std::{
+    env::var,
+    fs,
+    time::{Duration, Instant},
+};
 
 use async_std::{
     io,
     io::{ReadExt, WriteExt},
     stream::StreamExt,
+    sync::Arc,
     task,
 };
 use url::Url;
 
-use darkfi::net::transport::{NymTransport, TcpTransport, TorTransport, Transport, UnixTransport};
+use darkfi::{
+    net::transport::{
+        LinkConditions, MemNetwork, MemTransport, NoiseKeypair, NoiseUpgrade, NymTransport,
+        TcpTransport, TorTransport, Transport, TransportListener, UnixTransport,
+    },
+    Error,
+};
 
 #[async_std::test]
 async fn unix_transport() {
@@ -110,6 +121,173 @@ async fn tcp_tls_transport() {
     assert_eq!(buf, payload);
 }
 
+#[async_std::test]
+async fn tcp_noise_transport() {
+    let tcp = TcpTransport::new(None, 1024);
+    let url = Url::parse("tcp+noise://127.0.0.1:5434").unwrap();
+
+    let server_keys = Arc::new(NoiseKeypair::generate());
+    let client_keys = Arc::new(NoiseKeypair::generate());
+    let server_pubkey = server_keys.public();
+    let client_pubkey = client_keys.public();
+
+    let listener = tcp.listen_on(url.clone()).unwrap().await.unwrap();
+    let server = NoiseUpgrade::new(server_keys, None);
+
+    let _ = task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let stream = stream.unwrap();
+            let stream = server.clone().upgrade_listener_noise(stream).await.unwrap();
+            assert_eq!(stream.remote_pubkey(), client_pubkey);
+            let (mut reader, mut writer) = smol::io::split(stream);
+            io::copy(&mut reader, &mut writer).await.unwrap();
+        }
+    });
+
+    // Larger than a single Noise frame
+    let payload: Vec<u8> = (0..100_000).map(|i| i as u8).collect();
+
+    // Pin the server's key on the client side
+    let client = NoiseUpgrade::new(client_keys, Some(Arc::new(vec![server_pubkey])));
+    let stream = tcp.dial(url, None).unwrap().await.unwrap();
+    let mut stream = client.upgrade_dialer_noise(stream).await.unwrap();
+    assert_eq!(stream.remote_pubkey(), server_pubkey);
+
+    stream.write_all(&payload).await.unwrap();
+    stream.flush().await.unwrap();
+    let mut buf = vec![0_u8; payload.len()];
+    stream.read_exact(&mut buf).await.unwrap();
+
+    assert_eq!(buf, payload);
+}
+
+#[async_std::test]
+async fn tcp_noise_transport_untrusted_peer() {
+    let tcp = TcpTransport::new(None, 1024);
+    let url = Url::parse("tcp+noise://127.0.0.1:5435").unwrap();
+
+    let listener = tcp.listen_on(url.clone()).unwrap().await.unwrap();
+    let server = NoiseUpgrade::new(Arc::new(NoiseKeypair::generate()), None);
+
+    let _ = task::spawn(async move {
+        let mut incoming = listener.incoming();
+        while let Some(stream) = incoming.next().await {
+            let _ = server.clone().upgrade_listener_noise(stream.unwrap()).await;
+        }
+    });
+
+    // Only trust some other key
+    let trusted = Some(Arc::new(vec![NoiseKeypair::generate().public()]));
+    let client = NoiseUpgrade::new(Arc::new(NoiseKeypair::generate()), trusted);
+    let stream = tcp.dial(url, None).unwrap().await.unwrap();
+    let result = client.upgrade_dialer_noise(stream).await;
+
+    assert!(matches!(result, Err(Error::UntrustedPeerKey(_))));
+}
+
+/// Spawn an echo server on the `mem://` endpoint `name`.
+async fn mem_echo_server(name: &str) {
+    let url = Url::parse(&format!("mem://{}", name)).unwrap();
+    let listener = MemTransport::new(None).listen_on(url).unwrap().await.unwrap();
+
+    let _ = task::spawn(async move {
+        while let Ok((mut stream, _)) = listener.next().await {
+            let _ = task::spawn(async move {
+                let mut buf = vec![0_u8; 1024];
+                loop {
+                    let n = stream.read(&mut buf).await.unwrap();
+                    if n == 0 {
+                        break
+                    }
+                    stream.write_all(&buf[..n]).await.unwrap();
+                    stream.flush().await.unwrap();
+                }
+            });
+        }
+    });
+}
+
+#[async_std::test]
+async fn mem_transport() {
+    mem_echo_server("echo").await;
+
+    let mem = MemTransport::new(Some("client".to_string()));
+    let url = Url::parse("mem://echo").unwrap();
+
+    let payload = b"ohai mem";
+
+    let mut client = mem.clone().dial(url, None).unwrap().await.unwrap();
+    client.write_all(payload).await.unwrap();
+    client.flush().await.unwrap();
+    let mut buf = vec![0_u8; 8];
+    client.read_exact(&mut buf).await.unwrap();
+
+    assert_eq!(buf, payload);
+
+    // Nothing listens there
+    let url = Url::parse("mem://nowhere").unwrap();
+    assert!(mem.dial(url, None).unwrap().await.is_err());
+}
+
+#[async_std::test]
+async fn mem_transport_link_conditions() {
+    mem_echo_server("echo-slow").await;
+    mem_echo_server("echo-lossy").await;
+
+    let network = MemNetwork::global();
+    let latency = Duration::from_millis(100);
+    network.set_link("echo-slow", "slow", LinkConditions { latency, loss: 0.0 });
+    network.set_link("echo-lossy", "lossy", LinkConditions { latency: Duration::ZERO, loss: 1.0 });
+
+    // Both ways get delayed
+    let mem = MemTransport::new(Some("slow".to_string()));
+    let url = Url::parse("mem://echo-slow").unwrap();
+    let mut client = mem.dial(url, None).unwrap().await.unwrap();
+
+    let start = Instant::now();
+    client.write_all(b"ohai").await.unwrap();
+    client.flush().await.unwrap();
+    let mut buf = vec![0_u8; 4];
+    client.read_exact(&mut buf).await.unwrap();
+    assert!(start.elapsed() >= latency * 2);
+
+    // Everything gets lost
+    let mem = MemTransport::new(Some("lossy".to_string()));
+    let url = Url::parse("mem://echo-lossy").unwrap();
+    let mut client = mem.dial(url, None).unwrap().await.unwrap();
+
+    client.write_all(b"ohai").await.unwrap();
+    client.flush().await.unwrap();
+    let read = io::timeout(Duration::from_millis(200), client.read_exact(&mut buf)).await;
+    assert_eq!(read.unwrap_err().kind(), io::ErrorKind::TimedOut);
+}
+
+#[async_std::test]
+async fn mem_transport_partition() {
+    mem_echo_server("echo-split").await;
+
+    let network = MemNetwork::global();
+    let mem = MemTransport::new(Some("split".to_string()));
+    let url = Url::parse("mem://echo-split").unwrap();
+    let mut client = mem.clone().dial(url.clone(), None).unwrap().await.unwrap();
+
+    // Existing connections get closed, new ones refused
+    network.partition(&["split"], &["echo-split"]);
+    assert!(network.is_partitioned("echo-split", "split"));
+    let mut buf = vec![0_u8; 4];
+    assert_eq!(client.read(&mut buf).await.unwrap(), 0);
+    assert!(client.write_all(b"ohai").await.is_err());
+    assert!(mem.clone().dial(url.clone(), None).unwrap().await.is_err());
+
+    network.heal(&["split"], &["echo-split"]);
+    let mut client = mem.dial(url, None).unwrap().await.unwrap();
+    client.write_all(b"ohai").await.unwrap();
+    client.flush().await.unwrap();
+    client.read_exact(&mut buf).await.unwrap();
+    assert_eq!(buf, b"ohai");
+}
+
 #[async_std::test]
 #[ignore]
 async fn tor_transport_no_control() {